@@ -0,0 +1,170 @@
+//! Arena allocation of the transient `(Entity, C)` lists built for each component section
+//! on load. Each section is deserialized into a bump arena kept per thread, and the arena
+//! is reset once the section's components are inserted, so loading a save reuses the
+//! same memory for every section instead of allocating and freeing a list per section.
+//! An arena grown past [`MAX_RETAINED_BYTES`] by a huge section is freed rather than kept,
+//! so one big load doesn't pin its memory to the thread for good.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::marker::PhantomData;
+
+use bevy_ecs::prelude::*;
+use bumpalo::collections::Vec as BumpVec;
+use bumpalo::Bump;
+use serde::de::{DeserializeOwned, DeserializeSeed, Deserializer, SeqAccess, Visitor};
+use serde_json::Value;
+
+use crate::entity_map::EntityMap;
+use crate::registry::{insert_components, LoadHookFn};
+
+/// The most memory a thread's arena keeps between sections.
+const MAX_RETAINED_BYTES: usize = 1 << 20;
+
+thread_local! {
+    /// Kept across loads, so that its chunks are reused rather than allocated again.
+    static ARENA: RefCell<Bump> = RefCell::new(Bump::new());
+}
+
+/// Runs `f` with this thread's arena, resetting it afterwards, or replacing it with an empty
+/// one if `f` grew it past [`MAX_RETAINED_BYTES`]. Should `f` load a section itself, e.g.
+/// from a component's insert, the inner load gets an arena of its own.
+fn with_arena<R>(f: impl FnOnce(&Bump) -> R) -> R {
+    ARENA.with(|arena| match arena.try_borrow_mut() {
+        Ok(mut arena) => {
+            let result = f(&arena);
+            if arena.allocated_bytes() > MAX_RETAINED_BYTES {
+                *arena = Bump::new();
+            } else {
+                arena.reset();
+            }
+            result
+        }
+        Err(_) => f(&Bump::new()),
+    })
+}
+
+/// Deserializes a section's `[entity, component]` pairs into a vector in `arena`.
+struct SectionSeed<'a, C> {
+    arena: &'a Bump,
+    _component: PhantomData<fn() -> C>,
+}
+
+impl<'de, 'a, C: DeserializeOwned + 'a> DeserializeSeed<'de> for SectionSeed<'a, C> {
+    type Value = BumpVec<'a, (Entity, C)>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, 'a, C: DeserializeOwned + 'a> Visitor<'de> for SectionSeed<'a, C> {
+    type Value = BumpVec<'a, (Entity, C)>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a list of [entity, component] pairs")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut entries = BumpVec::with_capacity_in(seq.size_hint().unwrap_or(0), self.arena);
+        while let Some(entry) = seq.next_element::<(Entity, C)>()? {
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+}
+
+/// [`insert_section`](crate::registry::insert_section) with the section's components
+/// deserialized into the thread's arena.
+pub(crate) fn insert_section<C: Component + DeserializeOwned>(
+    world: &mut World,
+    entity_map: &mut EntityMap,
+    section: Value,
+    load_hooks: &[LoadHookFn],
+) -> Result<Vec<Entity>, serde_json::Error> {
+    with_arena(|arena| {
+        let entity_comps = SectionSeed::<C> {
+            arena,
+            _component: PhantomData,
+        }
+        .deserialize(section)?;
+        Ok(insert_components(
+            world,
+            entity_map,
+            entity_comps,
+            load_hooks,
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::load::LoadMode;
+    use crate::registry::SaveRegistry;
+    use crate::tests::{Component1, SerializeMe};
+
+    #[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Name(String);
+
+    #[derive(Component, Serialize, Deserialize)]
+    struct Chunk([u64; 32]);
+
+    fn arena_bytes() -> usize {
+        ARENA.with(|arena| arena.borrow().allocated_bytes())
+    }
+
+    #[test]
+    fn test_sections_reuse_the_arena() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Name>();
+        let mut world = World::default();
+        for index in 0..500 {
+            world.spawn((Component1, Name(format!("goblin {index}")), SerializeMe));
+        }
+        let doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+
+        let mut capacities = Vec::new();
+        for _ in 0..3 {
+            let mut loaded = World::default();
+            let report = registry
+                .load(&mut loaded, &mut doc.clone(), LoadMode::Merge, SerializeMe)
+                .unwrap();
+            assert_eq!(report.restored["Name"], 500);
+            let names: Vec<&Name> = loaded.query::<&Name>().iter(&loaded).collect();
+            assert!(names.contains(&&Name("goblin 499".to_string())));
+            capacities.push(arena_bytes());
+        }
+        assert!(capacities[0] > 0);
+        assert!(capacities.windows(2).all(|pair| pair[0] == pair[1]));
+
+        let mut corrupt = doc.clone();
+        corrupt.insert("Name".to_string(), serde_json::json!([[1, 2]]));
+        assert!(registry
+            .load(
+                &mut World::default(),
+                &mut corrupt,
+                LoadMode::Merge,
+                SerializeMe
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_huge_sections_release_the_arena() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Chunk>();
+        let mut world = World::default();
+        world.spawn_batch((0..8_000).map(|_| (Chunk([7; 32]), SerializeMe)));
+        let doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+
+        let mut loaded = World::default();
+        let report = registry
+            .load(&mut loaded, &mut doc.clone(), LoadMode::Merge, SerializeMe)
+            .unwrap();
+        assert_eq!(report.restored["Chunk"], 8_000);
+        assert!(arena_bytes() <= MAX_RETAINED_BYTES);
+    }
+}
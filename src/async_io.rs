@@ -0,0 +1,362 @@
+//! Saving and loading slots without blocking the frame: encoding and storage IO run on
+//! bevy's [`IoTaskPool`], and the game polls the returned task once per frame.
+//!
+//! Without bevy_tasks' `multi-threaded` feature, and on wasm, the pool runs tasks on the
+//! main thread when it's ticked, which bevy's `TaskPoolPlugin` does every frame.
+
+use std::future::Future;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use bevy_ecs::event::Events;
+use bevy_ecs::prelude::*;
+use bevy_tasks::{IoTaskPool, TaskPool};
+use bevy_utils::Instant;
+
+use crate::config::{SaveConfig, SaveError};
+use crate::load::LoadReport;
+use crate::lock;
+use crate::metrics::PersistenceMetrics;
+use crate::registry::SaveRegistry;
+use crate::storage::SaveStorage;
+use crate::SaveDocument;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IoOperation {
+    Save,
+    Load,
+    /// A [permanent deletion](crate::SaveStorage::delete_slot_permanently).
+    Delete,
+}
+
+/// Sent when a [`SaveTask`] or [`LoadTask`] is finished by polling it, so that systems
+/// other than the one polling, e.g. a "saving..." indicator, can react. Only sent if the
+/// world has `Events<IoCompleted>`.
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct IoCompleted {
+    pub slot: String,
+    pub operation: IoOperation,
+    /// The error the operation failed with, if it did.
+    pub error: Option<String>,
+}
+
+fn send_io_completed<T>(
+    world: &mut World,
+    slot: &str,
+    operation: IoOperation,
+    result: &Result<T, SaveError>,
+) {
+    if let Some(mut events) = world.get_resource_mut::<Events<IoCompleted>>() {
+        events.send(IoCompleted {
+            slot: slot.to_string(),
+            operation,
+            error: result.as_ref().err().map(ToString::to_string),
+        });
+    }
+}
+
+/// `Send` on native targets, where the [`IoTaskPool`] may run tasks on other threads, and
+/// implemented by every type on wasm, where it runs them on the main thread and browser
+/// handles, such as those of IndexedDB, aren't `Send`.
+#[cfg(not(target_arch = "wasm32"))]
+pub trait MaybeSend: Send {}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Send> MaybeSend for T {}
+
+/// `Send` on native targets, where the [`IoTaskPool`] may run tasks on other threads, and
+/// implemented by every type on wasm, where it runs them on the main thread and browser
+/// handles, such as those of IndexedDB, aren't `Send`.
+#[cfg(target_arch = "wasm32")]
+pub trait MaybeSend {}
+
+#[cfg(target_arch = "wasm32")]
+impl<T> MaybeSend for T {}
+
+/// A [`SaveStorage`] whose operations complete asynchronously, for backends such as
+/// IndexedDB that can't be waited on. Every `SaveStorage` is one, doing its IO when the
+/// task runs.
+pub trait AsyncSaveStorage {
+    /// The bytes stored in `slot`, or `None` if it's empty.
+    fn read_async(
+        &self,
+        slot: &str,
+    ) -> impl Future<Output = io::Result<Option<Vec<u8>>>> + MaybeSend;
+
+    /// Stores `bytes` in `slot`, replacing what was there.
+    fn write_async(
+        &mut self,
+        slot: &str,
+        bytes: &[u8],
+    ) -> impl Future<Output = io::Result<()>> + MaybeSend;
+
+    /// Empties `slot`. Emptying an empty slot is not an error.
+    fn remove_async(&mut self, slot: &str) -> impl Future<Output = io::Result<()>> + MaybeSend;
+
+    /// The names of the slots holding a save, in no particular order.
+    fn slots_async(&self) -> impl Future<Output = io::Result<Vec<String>>> + MaybeSend;
+}
+
+impl<S: SaveStorage> AsyncSaveStorage for S {
+    fn read_async(
+        &self,
+        slot: &str,
+    ) -> impl Future<Output = io::Result<Option<Vec<u8>>>> + MaybeSend {
+        std::future::ready(self.read(slot))
+    }
+
+    fn write_async(
+        &mut self,
+        slot: &str,
+        bytes: &[u8],
+    ) -> impl Future<Output = io::Result<()>> + MaybeSend {
+        std::future::ready(self.write(slot, bytes))
+    }
+
+    fn remove_async(&mut self, slot: &str) -> impl Future<Output = io::Result<()>> + MaybeSend {
+        std::future::ready(self.remove(slot))
+    }
+
+    fn slots_async(&self) -> impl Future<Output = io::Result<Vec<String>>> + MaybeSend {
+        std::future::ready(self.slots())
+    }
+}
+
+/// The result of a task on the [`IoTaskPool`], filled in when it finishes. The task
+/// handle itself is detached, as the single-threaded pool's handles don't return results.
+type Outcome<T> = Arc<Mutex<Option<T>>>;
+
+fn spawn_io<T: Send + 'static>(
+    future: impl Future<Output = T> + MaybeSend + 'static,
+) -> Outcome<T> {
+    let outcome = Outcome::default();
+    let slot = outcome.clone();
+    IoTaskPool::get_or_init(TaskPool::new)
+        .spawn(async move {
+            let result = future.await;
+            *slot.lock().unwrap() = Some(result);
+        })
+        .detach();
+    outcome
+}
+
+/// A save started by [`SaveRegistry::save_slot_async`].
+pub struct SaveTask {
+    slot: String,
+    start: Instant,
+    entities: usize,
+    outcome: Outcome<Result<usize, SaveError>>,
+    lock: Option<Arc<()>>,
+}
+
+impl SaveTask {
+    pub fn slot(&self) -> &str {
+        &self.slot
+    }
+
+    /// Returns the outcome once the save is written, and `None` while it's in progress or
+    /// after the outcome was returned.
+    pub fn poll(&mut self, world: &mut World) -> Option<Result<(), SaveError>> {
+        let result = self.outcome.lock().unwrap().take()?;
+        let (size, entities) = match &result {
+            Ok(size) => (*size, self.entities),
+            Err(_) => (0, 0),
+        };
+        let error = result.as_ref().err();
+        PersistenceMetrics::record_save(world, self.start.elapsed(), size, entities, error);
+        lock::release(world, &mut self.lock);
+        send_io_completed(world, &self.slot, IoOperation::Save, &result);
+        Some(result.map(|_| ()))
+    }
+}
+
+/// A decoded save and its size in bytes.
+type ReadSave = (SaveDocument, usize);
+
+/// A load started by [`SaveRegistry::load_slot_async`]. Reading and decoding happen in the
+/// background; the decoded save is loaded into the world by the poll that finds it ready.
+pub struct LoadTask {
+    slot: String,
+    start: Instant,
+    config: SaveConfig,
+    outcome: Outcome<Result<ReadSave, SaveError>>,
+    lock: Option<Arc<()>>,
+}
+
+impl LoadTask {
+    pub fn slot(&self) -> &str {
+        &self.slot
+    }
+
+    /// Loads the save as [`SaveRegistry::load_bytes`] does once it has been read, returning
+    /// the outcome. Returns `None` while reading is in progress or after the outcome was
+    /// returned.
+    pub fn poll<M: Component + Clone>(
+        &mut self,
+        world: &mut World,
+        registry: &SaveRegistry,
+        marker: M,
+    ) -> Option<Result<LoadReport, SaveError>> {
+        let result = self.outcome.lock().unwrap().take()?;
+        let size = result.as_ref().map_or(0, |(_, size)| *size);
+        let result = result.and_then(|(mut doc, _)| {
+            let config = &self.config;
+            registry.load_document(
+                world,
+                &mut doc,
+                config,
+                config.load_mode(),
+                |_| true,
+                marker,
+            )
+        });
+        let entities = result.as_ref().map_or(0, |report| report.entity_map.len());
+        let error = result.as_ref().err();
+        PersistenceMetrics::record_load(world, self.start.elapsed(), size, entities, error);
+        lock::release(world, &mut self.lock);
+        send_io_completed(world, &self.slot, IoOperation::Load, &result);
+        Some(result)
+    }
+}
+
+impl SaveRegistry {
+    /// Like [`save_slot`](Self::save_slot), but only [extracts](Self::extract_for_save)
+    /// the save before returning; serializing, encoding and writing to `storage` happen on
+    /// the [`IoTaskPool`], and the save reflects the world as it was at the call. The
+    /// task holds the world's [`PersistenceLock`](crate::PersistenceLock), if it has one.
+    pub fn save_slot_async<M, S>(
+        &self,
+        world: &mut World,
+        storage: &S,
+        slot: &str,
+        config: &SaveConfig,
+    ) -> Result<SaveTask, SaveError>
+    where
+        M: Component,
+        S: AsyncSaveStorage + Clone + MaybeSend + 'static,
+    {
+        let start = Instant::now();
+        let lock = lock::acquire(world, IoOperation::Save, slot)?;
+        let extracted = self.extract_for_save::<M>(world, config)?;
+        let entities = extracted.entities().len();
+        let mut storage = storage.clone();
+        let config = config.clone();
+        let owned_slot = slot.to_string();
+        let outcome = spawn_io(async move {
+            let bytes = extracted.encode(&config)?;
+            storage.write_async(&owned_slot, &bytes).await?;
+            Ok(bytes.len())
+        });
+        Ok(SaveTask {
+            slot: slot.to_string(),
+            start,
+            entities,
+            outcome,
+            lock,
+        })
+    }
+
+    /// Like [`load_slot`](Self::load_slot), but reads and decodes the slot on the
+    /// [`IoTaskPool`]; [`LoadTask::poll`] loads it into the world once it's ready. The
+    /// task holds the world's [`PersistenceLock`](crate::PersistenceLock), if it has one.
+    pub fn load_slot_async<S>(
+        &self,
+        world: &mut World,
+        storage: &S,
+        slot: &str,
+        config: &SaveConfig,
+    ) -> Result<LoadTask, SaveError>
+    where
+        S: AsyncSaveStorage + Clone + MaybeSend + 'static,
+    {
+        let lock = lock::acquire(world, IoOperation::Load, slot)?;
+        let storage = storage.clone();
+        let task_config = config.clone();
+        let owned_slot = slot.to_string();
+        let outcome = spawn_io(async move {
+            let Some(bytes) = storage.read_async(&owned_slot).await? else {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("slot {owned_slot} is empty"),
+                )
+                .into());
+            };
+            Ok((task_config.decode(&bytes)?, bytes.len()))
+        });
+        Ok(LoadTask {
+            slot: slot.to_string(),
+            start: Instant::now(),
+            config: config.clone(),
+            outcome,
+            lock,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::FileStorage;
+    use crate::tests::{Component1, SerializeMe};
+
+    #[test]
+    fn test_async_slots() {
+        let dir = std::env::temp_dir().join(format!("bevy_serde_async_{}", std::process::id()));
+        let storage = FileStorage::new(&dir);
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>();
+        let mut world = World::default();
+        world.init_resource::<Events<IoCompleted>>();
+        world.spawn_batch((0..10).map(|_| (Component1, SerializeMe)));
+
+        let config = SaveConfig::new();
+        let mut save = registry
+            .save_slot_async::<SerializeMe, _>(&mut world, &storage, "one", &config)
+            .unwrap();
+        let saved = loop {
+            if let Some(result) = save.poll(&mut world) {
+                break result;
+            }
+            std::thread::yield_now();
+        };
+        saved.unwrap();
+        assert!(save.poll(&mut world).is_none());
+
+        let mut loaded = World::default();
+        let mut load = registry
+            .load_slot_async(&mut loaded, &storage, "one", &config)
+            .unwrap();
+        let report = loop {
+            if let Some(result) = load.poll(&mut loaded, &registry, SerializeMe) {
+                break result;
+            }
+            std::thread::yield_now();
+        };
+        assert_eq!(report.unwrap().entity_map.len(), 10);
+
+        let mut capped = World::default();
+        let config = SaveConfig::new().with_max_entities(9);
+        let mut load = registry
+            .load_slot_async(&mut capped, &storage, "one", &config)
+            .unwrap();
+        let report = loop {
+            if let Some(result) = load.poll(&mut capped, &registry, SerializeMe) {
+                break result;
+            }
+            std::thread::yield_now();
+        };
+        assert!(matches!(
+            report,
+            Err(SaveError::TooManyEntities { found: 10, max: 9 })
+        ));
+        assert_eq!(capped.entities().len(), 0);
+
+        let events = world.resource::<Events<IoCompleted>>();
+        let mut reader = events.get_reader();
+        let sent: Vec<&IoCompleted> = reader.read(events).collect();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].operation, IoOperation::Save);
+        assert_eq!(sent[0].error, None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,213 @@
+//! Autosaves for slowly changing worlds: rather than a full save every time, most autosaves
+//! store only the [`SaveDelta`] against the last full save, the keyframe, and a fresh
+//! keyframe is written every few autosaves.
+
+use std::io;
+
+use bevy_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{SaveConfig, SaveError};
+use crate::delta::SaveDelta;
+use crate::hash::StableHasher;
+use crate::load::LoadReport;
+use crate::metrics::PersistenceMetrics;
+use crate::registry::SaveRegistry;
+use crate::storage::SaveStorage;
+use crate::SaveDocument;
+
+/// The delta slot's contents. Deltas name the keyframe they apply to, so that a delta left
+/// over from before a newer keyframe (after a crash between the two writes) is ignored.
+#[derive(Serialize, Deserialize)]
+struct DeltaRecord {
+    keyframe: String,
+    delta: SaveDelta,
+}
+
+fn keyframe_hash(bytes: &[u8]) -> String {
+    let mut hasher = StableHasher::new();
+    hasher.write_bytes(bytes);
+    format!("{:016x}", hasher.finish())
+}
+
+/// What an [`autosave`](SaveRegistry::autosave) wrote.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AutosaveWrite {
+    /// A full save of this many bytes.
+    Keyframe(usize),
+    /// A delta against the keyframe, of this many bytes.
+    Delta(usize),
+}
+
+/// An autosave slot of a [`SaveStorage`] kept as a keyframe, in the slot itself and
+/// encoded by the chain's config, and a delta against it in the `{slot}.delta` slot.
+pub struct AutosaveChain {
+    slot: String,
+    config: SaveConfig,
+    keyframe_every: usize,
+    since_keyframe: usize,
+    /// The last keyframe as saved, with the hash of its encoding.
+    keyframe: Option<(SaveDocument, String)>,
+}
+
+impl AutosaveChain {
+    pub fn new(slot: &str) -> Self {
+        AutosaveChain {
+            slot: slot.to_string(),
+            config: SaveConfig::new(),
+            keyframe_every: 10,
+            since_keyframe: 0,
+            keyframe: None,
+        }
+    }
+
+    /// Sets the config keyframes are encoded and loaded with.
+    pub fn with_config(mut self, config: SaveConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Sets how many autosaves write a delta between keyframes, 10 by default. The first
+    /// autosave of a chain always writes a keyframe.
+    pub fn with_keyframe_every(mut self, autosaves: usize) -> Self {
+        self.keyframe_every = autosaves;
+        self
+    }
+
+    pub fn slot(&self) -> &str {
+        &self.slot
+    }
+
+    pub fn delta_slot(&self) -> String {
+        format!("{}.delta", self.slot)
+    }
+
+    /// The latest document in `storage`: the keyframe with the delta applied to it.
+    pub fn read(&self, storage: &impl SaveStorage) -> Result<Option<SaveDocument>, SaveError> {
+        let Some(bytes) = storage.read(&self.slot)? else {
+            return Ok(None);
+        };
+        let mut doc = self.config.decode(&bytes)?;
+        if let Some(delta) = storage.read(&self.delta_slot())? {
+            let record: DeltaRecord = serde_json::from_slice(&delta)?;
+            if record.keyframe == keyframe_hash(&bytes) {
+                record.delta.apply_to_document(&mut doc)?;
+            }
+        }
+        Ok(Some(doc))
+    }
+}
+
+impl SaveRegistry {
+    /// Autosaves the entities marked with `M` into `chain`'s slot of `storage`, writing a
+    /// keyframe or a delta against the last one. Counts as an autosave in the world's
+    /// [`PersistenceMetrics`], if it has them.
+    pub fn autosave<M: Component>(
+        &self,
+        world: &mut World,
+        storage: &mut impl SaveStorage,
+        chain: &mut AutosaveChain,
+    ) -> Result<AutosaveWrite, SaveError> {
+        let doc = self.save_document::<M>(world, &chain.config)?;
+        if let Some(mut metrics) = world.get_resource_mut::<PersistenceMetrics>() {
+            metrics.record_autosave();
+        }
+        match &chain.keyframe {
+            Some((keyframe, hash)) if chain.since_keyframe < chain.keyframe_every => {
+                let record = DeltaRecord {
+                    keyframe: hash.clone(),
+                    delta: SaveDelta::between_documents(keyframe, &doc)?,
+                };
+                let bytes = serde_json::to_vec(&record)?;
+                storage.write(&chain.delta_slot(), &bytes)?;
+                chain.since_keyframe += 1;
+                Ok(AutosaveWrite::Delta(bytes.len()))
+            }
+            _ => {
+                let bytes = chain.config.encode(&doc)?;
+                storage.write(&chain.slot, &bytes)?;
+                storage.remove(&chain.delta_slot())?;
+                chain.keyframe = Some((doc, keyframe_hash(&bytes)));
+                chain.since_keyframe = 0;
+                Ok(AutosaveWrite::Keyframe(bytes.len()))
+            }
+        }
+    }
+
+    /// Loads the latest autosave of `chain` in the config's
+    /// [`LoadMode`](crate::LoadMode). Fails with [`io::ErrorKind::NotFound`] if the slot is
+    /// empty.
+    pub fn load_autosave<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        storage: &impl SaveStorage,
+        chain: &AutosaveChain,
+        marker: M,
+    ) -> Result<LoadReport, SaveError> {
+        let Some(mut doc) = chain.read(storage)? else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("slot {} is empty", chain.slot),
+            )
+            .into());
+        };
+        let config = &chain.config;
+        self.load_document(
+            world,
+            &mut doc,
+            config,
+            config.load_mode(),
+            |_| true,
+            marker,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::FileStorage;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_autosave_deltas_between_keyframes() {
+        let dir = std::env::temp_dir().join(format!("bevy_serde_autosave_{}", std::process::id()));
+        let mut storage = FileStorage::new(&dir);
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .register_mapped::<Component2>();
+        let mut world = World::default();
+        world.init_resource::<PersistenceMetrics>();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        for _ in 0..200 {
+            world.spawn((Component1, SerializeMe));
+        }
+
+        let mut chain = AutosaveChain::new("auto").with_keyframe_every(2);
+        let mut writes = Vec::new();
+        for _ in 0..4 {
+            world.spawn((Component2 { target: entity1 }, SerializeMe));
+            writes.push(registry.autosave::<SerializeMe>(&mut world, &mut storage, &mut chain));
+        }
+        let writes: Vec<AutosaveWrite> = writes.into_iter().map(Result::unwrap).collect();
+        let (AutosaveWrite::Keyframe(keyframe), AutosaveWrite::Delta(delta)) =
+            (writes[0], writes[1])
+        else {
+            panic!("unexpected writes {writes:?}");
+        };
+        assert!(delta * 10 < keyframe);
+        assert!(matches!(writes[3], AutosaveWrite::Keyframe(_)));
+        assert_eq!(world.resource::<PersistenceMetrics>().autosaves, 4);
+
+        world.spawn((Component1, SerializeMe));
+        registry
+            .autosave::<SerializeMe>(&mut world, &mut storage, &mut chain)
+            .unwrap();
+        let report = registry
+            .load_autosave(&mut World::default(), &storage, &chain, SerializeMe)
+            .unwrap();
+        assert_eq!(report.entity_map.len(), 206);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,154 @@
+//! Command line tool for looking inside saves without building the game.
+//!
+//! ```text
+//! bevy-saves inspect FILE
+//! bevy-saves convert FILE --to ron|json|pretty-json
+//! bevy-saves diff A B
+//! bevy-saves validate FILE --schema MANIFEST.json
+//! ```
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::process::ExitCode;
+
+use bevy_serde_macros::manifest::MANIFEST_KEY;
+use bevy_serde_macros::SaveDocument;
+use bevy_serde_macros::{diff_saves, CompatibilityReport, Manifest, SaveConfig, SaveStats};
+use serde_json::Value;
+
+const USAGE: &str = "usage:
+  bevy-saves inspect FILE
+  bevy-saves convert FILE --to ron|json|pretty-json
+  bevy-saves diff A B
+  bevy-saves validate FILE --schema MANIFEST.json";
+
+/// Reads a save written by the registry, gzipped or not.
+fn read_document(path: &str) -> Result<SaveDocument, Box<dyn Error>> {
+    let bytes = std::fs::read(path).map_err(|err| format!("{path}: {err}"))?;
+    let config = SaveConfig::new();
+    #[cfg(feature = "gzip")]
+    let config = if bytes.starts_with(&[0x1f, 0x8b]) {
+        config.with_compression(bevy_serde_macros::Compression::Gzip)
+    } else {
+        config
+    };
+    Ok(config
+        .decode(&bytes)
+        .map_err(|err| format!("{path}: {err}"))?)
+}
+
+fn read_manifest(doc: &SaveDocument) -> Result<Option<Manifest>, serde_json::Error> {
+    doc.get(MANIFEST_KEY)
+        .map(|manifest| serde_json::from_value(manifest.clone()))
+        .transpose()
+}
+
+fn inspect(path: &str) -> Result<ExitCode, Box<dyn Error>> {
+    let doc = read_document(path)?;
+    let manifest = read_manifest(&doc)?;
+    match &manifest {
+        Some(manifest) => {
+            println!("format version {}", manifest.format_version);
+            for (key, value) in &manifest.metadata {
+                println!("{key}: {value}");
+            }
+        }
+        None => println!("no manifest"),
+    }
+    let stats = SaveStats::from_document(&doc)?;
+    println!(
+        "{:<32} {:>10} {:>12}  schema",
+        "section", "entities", "bytes"
+    );
+    for (name, section) in &stats.sections {
+        let info = manifest
+            .as_ref()
+            .and_then(|manifest| manifest.components.get(name));
+        let schema = match info {
+            Some(info) => format!(
+                "v{} {}",
+                info.version,
+                info.schema_hash
+                    .map_or("-".to_string(), |hash| hash.to_string())
+            ),
+            None => "-".to_string(),
+        };
+        println!(
+            "{name:<32} {:>10} {:>12}  {schema}",
+            section.entities, section.bytes
+        );
+    }
+    println!("total {} bytes", stats.total_bytes());
+    Ok(ExitCode::SUCCESS)
+}
+
+fn convert(path: &str, to: &str) -> Result<ExitCode, Box<dyn Error>> {
+    let doc = read_document(path)?;
+    let sorted: BTreeMap<&String, &Value> = doc.iter().collect();
+    let output = match to {
+        "json" => serde_json::to_string(&sorted)?,
+        "pretty-json" => serde_json::to_string_pretty(&sorted)?,
+        "ron" => ron::ser::to_string_pretty(&sorted, ron::ser::PrettyConfig::default())?,
+        other => return Err(format!("unknown format {other}").into()),
+    };
+    println!("{output}");
+    Ok(ExitCode::SUCCESS)
+}
+
+fn diff(a: &str, b: &str) -> Result<ExitCode, Box<dyn Error>> {
+    let diff = diff_saves(&read_document(a)?, &read_document(b)?)?;
+    print!("{diff}");
+    Ok(if diff.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+fn validate(path: &str, schema: &str) -> Result<ExitCode, Box<dyn Error>> {
+    let doc = read_document(path)?;
+    let schema = std::fs::read(schema).map_err(|err| format!("{schema}: {err}"))?;
+    let current: Manifest = serde_json::from_slice(&schema)?;
+    let report = CompatibilityReport::compare(read_manifest(&doc)?.as_ref(), &current);
+    if !report.has_manifest {
+        println!("save has no manifest");
+    }
+    for (label, names) in [
+        ("mismatched", &report.mismatched),
+        ("outdated", &report.outdated),
+        ("unknown", &report.unknown),
+        ("missing", &report.missing),
+    ] {
+        for name in names {
+            println!("{label}: {name}");
+        }
+    }
+    Ok(if report.is_compatible() {
+        println!("compatible");
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+fn run(args: &[String]) -> Result<ExitCode, Box<dyn Error>> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["inspect", file] => inspect(file),
+        ["convert", file, "--to", to] => convert(file, to),
+        ["diff", a, b] => diff(a, b),
+        ["validate", file, "--schema", schema] => validate(file, schema),
+        _ => Err(USAGE.into()),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Loading straight from the bytes of a save. [`deserialize`](crate::deserialize) takes
+//! its sections from an owned `serde_json::Value` tree, which allocates every string and
+//! number of the document before any component is built. Here the document is only split
+//! into sections borrowing from the input buffer, and each section is deserialized from its
+//! own slice of it, so the tree is never built.
+//!
+//! Components are `'static`, so their fields can't borrow from the buffer themselves: a
+//! `Cow<'static, str>` still ends up owned. What is saved is the intermediate tree; the
+//! owning path stays the fallback for documents that are already parsed.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use bevy_ecs::prelude::*;
+use serde::Deserialize;
+use serde_json::value::RawValue;
+
+use crate::entity_map::EntityMap;
+use crate::revive_or_rejuv_entity;
+
+/// The sections of a save document, each still the unparsed JSON text borrowed from the
+/// buffer it was read from.
+pub type RawSections<'de> = BTreeMap<Cow<'de, str>, &'de RawValue>;
+
+/// Splits the document in `bytes` into its sections without parsing them.
+pub fn raw_sections(bytes: &[u8]) -> Result<RawSections<'_>, serde_json::Error> {
+    serde_json::from_slice(bytes)
+}
+
+/// Like [`deserialize`](crate::deserialize), but reads the section `component_name` from
+/// the buffer `sections` borrows from. A missing section restores nothing.
+pub fn deserialize_borrowed<'de, C: Component + Deserialize<'de>, M: Component + Clone>(
+    world: &mut World,
+    entity_map: &mut EntityMap,
+    sections: &RawSections<'de>,
+    component_name: &str,
+    marker: M,
+) -> Result<(), serde_json::Error> {
+    let Some(section) = sections.get(component_name) else {
+        return Ok(());
+    };
+    let entity_comps: Vec<(Entity, C)> = serde_json::from_str(section.get())?;
+    revive_or_rejuv_entity(entity_comps, marker)(world, entity_map);
+    Ok(())
+}
+
+/// The borrowing counterpart of `deserialize_individually!`, taking the [`RawSections`] of
+/// a save rather than its parsed map.
+///
+/// ```ignore
+/// let sections = raw_sections(&bytes)?;
+/// deserialize_borrowed_individually!(&mut world, &mut entity_map, &sections, SerializeMe, Position);
+/// ```
+#[macro_export]
+macro_rules! deserialize_borrowed_individually {
+  ($world:expr, $emap:expr, $sections:expr, $marker:expr, $( $(#[$attr:meta])* $comp_type:ty $(as $section:literal)?),*, $(,)?) => {
+  {
+      $crate::assert_unique_components!($($(#[$attr])* $comp_type),*);
+      $(
+          $(#[$attr])*
+          $crate::borrowed::deserialize_borrowed::<$comp_type, _>(
+              $world,
+              $emap,
+              $sections,
+              &$crate::__section_name!($comp_type $(, $section)?),
+              $marker,
+          )
+          .unwrap();
+      )*
+  }
+  };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_load_from_borrowed_sections() {
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        world.spawn((Component2 { target: entity1 }, SerializeMe));
+        let ecs = &mut world;
+        let saved = crate::serialize_individually!(ecs, SerializeMe, Component1, Component2,);
+        let bytes = serde_json::to_vec(&saved).unwrap();
+
+        let sections = raw_sections(&bytes).unwrap();
+        assert_eq!(sections.len(), saved.len());
+        let mut loaded = World::default();
+        let ecs = &mut loaded;
+        let mut entity_map = EntityMap::new();
+        crate::deserialize_borrowed_individually!(
+            ecs,
+            &mut entity_map,
+            &sections,
+            SerializeMe,
+            Component1,
+            Component2,
+        );
+        assert_eq!(entity_map.len(), 2);
+        assert_eq!(loaded.query::<&Component1>().iter(&loaded).count(), 1);
+        assert_eq!(loaded.query::<&Component2>().iter(&loaded).count(), 1);
+    }
+}
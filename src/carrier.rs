@@ -0,0 +1,130 @@
+//! Resources saved through the entity pipeline, the roguelike tutorial's
+//! `SerializationHelper` pattern: before saving, the resource is moved onto a marked helper
+//! entity as a [`ResourceCarrier`], which is saved like any other component, and on load
+//! the carrier is moved back into the resource and its helper despawned.
+
+use bevy_ecs::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::entity_map::EntityMap;
+use crate::registry::{extract_section, ComponentRegistration, LoadHookFn, SaveRegistry};
+use crate::schema::trace_or_opaque;
+
+/// A component carrying the resource `R` on a helper entity, so that it is saved with the
+/// marked entities. Works with the macros like any component; a
+/// [`register_resource`](SaveRegistry::register_resource)ed one is unpacked automatically.
+#[derive(Component, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ResourceCarrier<R>(pub R);
+
+impl<R: Resource> ResourceCarrier<R> {
+    /// Moves `R` out of the world onto a new helper entity marked with `marker`, returning
+    /// the helper, or `None` if the world has no `R`. Call [`unpack`](Self::unpack) once
+    /// the save is written, whether or not it succeeded.
+    pub fn pack<M: Component>(world: &mut World, marker: M) -> Option<Entity> {
+        let resource = world.remove_resource::<R>()?;
+        Some(world.spawn((ResourceCarrier(resource), marker)).id())
+    }
+
+    /// Moves every carried `R` back into the world's resource and despawns the helpers,
+    /// returning whether there was one. If there are several, the last one wins.
+    pub fn unpack(world: &mut World) -> bool {
+        let helpers: Vec<Entity> = world
+            .query_filtered::<Entity, With<ResourceCarrier<R>>>()
+            .iter(world)
+            .collect();
+        for helper in &helpers {
+            if let Some(ResourceCarrier(resource)) =
+                world.entity_mut(*helper).take::<ResourceCarrier<R>>()
+            {
+                world.insert_resource(resource);
+            }
+            world.despawn(*helper);
+        }
+        !helpers.is_empty()
+    }
+}
+
+fn insert_resource<R: Resource + DeserializeOwned>(
+    world: &mut World,
+    entity_map: &mut EntityMap,
+    section: Value,
+    load_hooks: &[LoadHookFn],
+) -> Result<Vec<Entity>, serde_json::Error> {
+    let carriers: Vec<(Entity, ResourceCarrier<R>)> = serde_json::from_value(section)?;
+    for (saved, mut carrier) in carriers {
+        for hook in load_hooks {
+            hook(&mut carrier, entity_map);
+        }
+        // the helper may have been spawned up front with the document's other entities
+        if let Some(helper) = entity_map.remove(&saved) {
+            world.despawn(helper);
+        }
+        world.insert_resource(carrier.0);
+    }
+    Ok(Vec::new())
+}
+
+impl SaveRegistry {
+    /// Registers [`ResourceCarrier<R>`], whose section is loaded straight into the resource
+    /// `R`: its helper entity is despawned rather than restored. Saving still goes through
+    /// [`ResourceCarrier::pack`] and [`ResourceCarrier::unpack`].
+    pub fn register_resource<R: Resource + Serialize + DeserializeOwned>(&mut self) -> &mut Self {
+        self.push_registration(ComponentRegistration::custom::<ResourceCarrier<R>>(
+            0,
+            self.naming(),
+            trace_or_opaque::<R>(),
+            extract_section::<ResourceCarrier<R>>,
+            Box::new(insert_resource::<R>),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load::LoadMode;
+    use crate::tests::SerializeMe;
+
+    #[derive(Component, Default, Serialize, Deserialize)]
+    struct Explored(bool);
+
+    #[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Map {
+        depth: u32,
+        revealed: Vec<bool>,
+    }
+
+    #[test]
+    fn test_resource_rides_on_helper_entity() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Explored>()
+            .register_resource::<Map>()
+            .fill_missing_with_default::<Explored>();
+        let mut world = World::default();
+        let map = Map {
+            depth: 3,
+            revealed: vec![true, false],
+        };
+        world.insert_resource(map.clone());
+        world.spawn((Explored(true), SerializeMe));
+
+        let helper = ResourceCarrier::<Map>::pack(&mut world, SerializeMe).unwrap();
+        let mut doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        assert!(ResourceCarrier::<Map>::unpack(&mut world));
+        assert!(world.get_entity(helper).is_none());
+        assert_eq!(world.resource::<Map>(), &map);
+        assert_eq!(doc["ResourceCarrier<Map>"][0][1]["depth"], 3);
+
+        let mut loaded = World::default();
+        let report = registry
+            .load(&mut loaded, &mut doc, LoadMode::Merge, SerializeMe)
+            .unwrap();
+        assert_eq!(loaded.resource::<Map>(), &map);
+        assert_eq!(report.entity_map.len(), 1);
+        assert_eq!(loaded.entities().len(), 1);
+    }
+}
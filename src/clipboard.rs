@@ -0,0 +1,90 @@
+//! Copy and paste of entity groups through strings, for editors.
+
+use bevy_ecs::prelude::*;
+use bevy_ecs::world::EntityWorldMut;
+
+use crate::manifest::MANIFEST_KEY;
+use crate::parse;
+use crate::registry::SaveRegistry;
+use crate::subtree::spawn_from_json;
+use crate::SaveDocument;
+
+/// Serializes `selection` and the descendants of its entities into a string, suitable for
+/// the system clipboard. The registry's manifest is included, so a paste in a later session
+/// can be checked with [`SaveRegistry::can_load`].
+pub fn copy_to_string(
+    world: &World,
+    registry: &SaveRegistry,
+    selection: &[Entity],
+) -> Result<String, serde_json::Error> {
+    let mut doc = registry.serialize_entities_with_descendants(world, selection)?;
+    doc.insert(
+        MANIFEST_KEY.to_string(),
+        serde_json::to_value(registry.manifest())?,
+    );
+    serde_json::to_string(&doc)
+}
+
+/// Spawns a copy of entities copied with [`copy_to_string`], with references between them
+/// remapped to the copies, and returns the pasted roots. `offset` is called on each root,
+/// e.g. to move the group under the cursor; children follow their root if positioned
+/// relative to it.
+pub fn paste_from_string<M: Component + Clone>(
+    world: &mut World,
+    registry: &SaveRegistry,
+    s: &str,
+    mut offset: impl FnMut(EntityWorldMut),
+    marker: M,
+) -> Result<Vec<Entity>, serde_json::Error> {
+    let doc: SaveDocument = parse::from_slice(s.as_bytes())?;
+    let roots = spawn_from_json(world, registry, &doc, marker)?;
+    for root in &roots {
+        offset(world.entity_mut(*root));
+    }
+    Ok(roots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_hierarchy::{BuildWorldChildren, Children};
+    use serde::{Deserialize, Serialize};
+
+    use crate::tests::{Component2, SerializeMe};
+
+    #[derive(Component, Serialize, Deserialize)]
+    struct Position(i32, i32);
+
+    #[test]
+    fn test_copy_paste() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Position>()
+            .register_mapped::<Component2>();
+        let mut world = World::default();
+        let root = world.spawn(Position(1, 2)).id();
+        let child = world.spawn(Component2 { target: root }).id();
+        world.entity_mut(root).add_child(child);
+
+        let copied = copy_to_string(&world, &registry, &[root]).unwrap();
+        assert!(registry
+            .can_load(copied.as_bytes())
+            .unwrap()
+            .is_compatible());
+        let pasted = paste_from_string(
+            &mut world,
+            &registry,
+            &copied,
+            |mut root| root.get_mut::<Position>().unwrap().0 += 10,
+            SerializeMe,
+        )
+        .unwrap();
+        assert_eq!(pasted.len(), 1);
+        assert_eq!(world.get::<Position>(pasted[0]).unwrap().0, 11);
+        let new_child = world.get::<Children>(pasted[0]).unwrap()[0];
+        assert_eq!(
+            world.get::<Component2>(new_child).unwrap().target,
+            pasted[0]
+        );
+    }
+}
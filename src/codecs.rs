@@ -0,0 +1,430 @@
+//! Compact encodings for grid-like component fields, such as tilemaps and fog of war: big
+//! arrays with long runs of equal values, or of values close to their neighbours. A
+//! field opts in by changing its type to one of the wrappers here, which dereference to
+//! the `Vec` they wrap and are encoded on serialize and decoded on deserialize. The same
+//! goes for binary data, see [`BinaryBlob`].
+
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use serde::de::{self, DeserializeOwned, SeqAccess, Visitor};
+use serde::ser::SerializeSeq;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A `Vec` written as runs of `[value, count]`, e.g. `[0, 0, 0, 1]` as `[[0, 3], [1, 1]]`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Rle<T>(pub Vec<T>);
+
+impl<T: Serialize + PartialEq> Serialize for Rle<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut runs: Vec<(&T, usize)> = Vec::new();
+        for value in &self.0 {
+            match runs.last_mut() {
+                Some((last, count)) if *last == value => *count += 1,
+                _ => runs.push((value, 1)),
+            }
+        }
+        runs.serialize(serializer)
+    }
+}
+
+impl<'de, T: DeserializeOwned + Clone> Deserialize<'de> for Rle<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let runs: Vec<(T, usize)> = Vec::deserialize(deserializer)?;
+        let mut values = Vec::with_capacity(runs.iter().map(|(_, count)| count).sum());
+        for (value, count) in runs {
+            values.extend(std::iter::repeat_n(value, count));
+        }
+        Ok(Rle(values))
+    }
+}
+
+/// A `Vec` of integers written as the difference of each value from the one before. Human
+/// readable formats such as JSON get a list of the differences, which are short numbers
+/// for smooth data; binary formats get them as zigzag varints in a byte string.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Delta<T>(pub Vec<T>);
+
+impl<T: Copy + Into<i64>> Serialize for Delta<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let deltas = deltas(&self.0);
+        if serializer.is_human_readable() {
+            let mut seq = serializer.serialize_seq(Some(deltas.len()))?;
+            for delta in deltas {
+                seq.serialize_element(&delta)?;
+            }
+            seq.end()
+        } else {
+            serializer.serialize_bytes(&encode_varints(&deltas))
+        }
+    }
+}
+
+impl<'de, T: TryFrom<i64>> Deserialize<'de> for Delta<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let deltas = if deserializer.is_human_readable() {
+            Vec::<i64>::deserialize(deserializer)?
+        } else {
+            let bytes = deserializer.deserialize_bytes(BytesVisitor)?;
+            decode_varints(&bytes).ok_or_else(|| de::Error::custom("truncated varint"))?
+        };
+        let mut value = 0i64;
+        let mut values = Vec::with_capacity(deltas.len());
+        for delta in deltas {
+            value = value.wrapping_add(delta);
+            values.push(T::try_from(value).map_err(|_| {
+                de::Error::custom(format!("{value} is out of range for the delta's type"))
+            })?);
+        }
+        Ok(Delta(values))
+    }
+}
+
+fn deltas<T: Copy + Into<i64>>(values: &[T]) -> Vec<i64> {
+    let mut previous = 0i64;
+    values
+        .iter()
+        .map(|value| {
+            let value = (*value).into();
+            let delta = value.wrapping_sub(previous);
+            previous = value;
+            delta
+        })
+        .collect()
+}
+
+pub(crate) fn encode_varints(values: &[i64]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(values.len());
+    for value in values {
+        let mut zigzag = ((value << 1) ^ (value >> 63)) as u64;
+        while zigzag >= 0x80 {
+            bytes.push(zigzag as u8 | 0x80);
+            zigzag >>= 7;
+        }
+        bytes.push(zigzag as u8);
+    }
+    bytes
+}
+
+pub(crate) fn decode_varints(bytes: &[u8]) -> Option<Vec<i64>> {
+    let mut values = Vec::new();
+    let (mut zigzag, mut shift) = (0u64, 0u32);
+    for byte in bytes {
+        zigzag |= u64::from(byte & 0x7f).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            values.push((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64));
+            (zigzag, shift) = (0, 0);
+        } else {
+            shift += 7;
+        }
+    }
+    (shift == 0).then_some(values)
+}
+
+/// Accepts both byte strings and sequences of bytes, as binary formats differ in which
+/// they hand back for `serialize_bytes`.
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("bytes")
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Vec<u8>, E> {
+        Ok(bytes.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, bytes: Vec<u8>) -> Result<Vec<u8>, E> {
+        Ok(bytes)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(bytes)
+    }
+}
+
+/// A `Vec` for tilemaps and similar grids, written as runs of equal values. When values
+/// recur across runs, as tile kinds do, each is written once in a palette and the runs
+/// refer to it by index, as varint `(index, count)` pairs in a base64 string (a byte string
+/// in binary formats):
+/// `{"palette": {"palette": ["Wall", "Floor"], "runs": "<base64>"}}`. Grids without
+/// recurring values are written as [`Rle`]'s runs, `{"runs": [["Floor", 96], ...]}`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CompressedGrid<T>(pub Vec<T>);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum GridEncoding<T> {
+    Runs(Vec<(T, u64)>),
+    Palette { palette: Vec<T>, runs: BinaryBlob },
+}
+
+impl<T: Serialize + PartialEq> Serialize for CompressedGrid<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut runs: Vec<(&T, u64)> = Vec::new();
+        for value in &self.0 {
+            match runs.last_mut() {
+                Some((last, count)) if *last == value => *count += 1,
+                _ => runs.push((value, 1)),
+            }
+        }
+        let mut palette: Vec<&T> = Vec::new();
+        let mut indexed = Vec::with_capacity(runs.len() * 2);
+        for (value, count) in &runs {
+            let index = match palette.iter().position(|known| known == value) {
+                Some(index) => index,
+                None => {
+                    palette.push(value);
+                    palette.len() - 1
+                }
+            };
+            indexed.extend([index as i64, *count as i64]);
+        }
+        let encoding = if palette.len() < runs.len() {
+            GridEncoding::Palette {
+                palette,
+                runs: BinaryBlob(encode_varints(&indexed)),
+            }
+        } else {
+            GridEncoding::Runs(runs)
+        };
+        encoding.serialize(serializer)
+    }
+}
+
+impl<'de, T: DeserializeOwned + Clone> Deserialize<'de> for CompressedGrid<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let runs = match GridEncoding::<T>::deserialize(deserializer)? {
+            GridEncoding::Runs(runs) => runs,
+            GridEncoding::Palette { palette, runs } => {
+                let indexed =
+                    decode_varints(&runs).ok_or_else(|| de::Error::custom("truncated varint"))?;
+                if indexed.len() % 2 != 0 {
+                    return Err(de::Error::custom("a grid run is missing its count"));
+                }
+                indexed
+                    .chunks_exact(2)
+                    .map(|run| {
+                        let value = usize::try_from(run[0])
+                            .ok()
+                            .and_then(|index| palette.get(index))
+                            .ok_or_else(|| {
+                                de::Error::custom(format!("{} is not in the palette", run[0]))
+                            })?;
+                        let count = u64::try_from(run[1])
+                            .map_err(|_| de::Error::custom("negative grid run"))?;
+                        Ok((value.clone(), count))
+                    })
+                    .collect::<Result<Vec<(T, u64)>, D::Error>>()?
+            }
+        };
+        let mut values = Vec::new();
+        for (value, count) in runs {
+            values.extend(std::iter::repeat_n(value, count as usize));
+        }
+        Ok(CompressedGrid(values))
+    }
+}
+
+/// Binary data, such as a thumbnail or a baked navmesh, written as a base64 string in
+/// human readable formats rather than as a list of numbers, and as a byte string in binary
+/// formats. For a `Vec<u8>` field that can't change type, use the same encoding through
+/// `#[serde(with = "bevy_serde_macros::codecs::blob")]`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BinaryBlob(pub Vec<u8>);
+
+impl Serialize for BinaryBlob {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        blob::serialize(&self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BinaryBlob {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        blob::deserialize(deserializer).map(BinaryBlob)
+    }
+}
+
+impl Deref for BinaryBlob {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.0
+    }
+}
+
+impl DerefMut for BinaryBlob {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.0
+    }
+}
+
+impl From<Vec<u8>> for BinaryBlob {
+    fn from(bytes: Vec<u8>) -> Self {
+        BinaryBlob(bytes)
+    }
+}
+
+/// The encoding of [`BinaryBlob`] as a serde `with` module for `Vec<u8>` fields.
+pub mod blob {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde::de::{self, Deserialize, Deserializer};
+    use serde::Serializer;
+
+    use super::BytesVisitor;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&STANDARD.encode(bytes))
+        } else {
+            serializer.serialize_bytes(bytes)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        if deserializer.is_human_readable() {
+            let encoded = String::deserialize(deserializer)?;
+            STANDARD.decode(encoded).map_err(de::Error::custom)
+        } else {
+            deserializer.deserialize_bytes(BytesVisitor)
+        }
+    }
+}
+
+macro_rules! impl_vec_wrapper {
+    ($wrapper:ident) => {
+        impl<T> Deref for $wrapper<T> {
+            type Target = Vec<T>;
+
+            fn deref(&self) -> &Vec<T> {
+                &self.0
+            }
+        }
+
+        impl<T> DerefMut for $wrapper<T> {
+            fn deref_mut(&mut self) -> &mut Vec<T> {
+                &mut self.0
+            }
+        }
+
+        impl<T> From<Vec<T>> for $wrapper<T> {
+            fn from(values: Vec<T>) -> Self {
+                $wrapper(values)
+            }
+        }
+    };
+}
+
+impl_vec_wrapper!(Rle);
+impl_vec_wrapper!(Delta);
+impl_vec_wrapper!(CompressedGrid);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ecs::prelude::*;
+
+    use crate::tests::SerializeMe;
+
+    #[derive(Component, Serialize, Deserialize, PartialEq, Debug)]
+    struct FogOfWar {
+        revealed: Rle<bool>,
+        heights: Delta<i32>,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Thumbnail {
+        png: BinaryBlob,
+        #[serde(with = "blob")]
+        palette: Vec<u8>,
+    }
+
+    #[test]
+    fn test_blobs_are_base64_in_json() {
+        let thumbnail = Thumbnail {
+            png: BinaryBlob(vec![0x89, b'P', b'N', b'G']),
+            palette: vec![255, 0, 0],
+        };
+        let text = serde_json::to_string(&thumbnail).unwrap();
+        assert_eq!(text, r#"{"png":"iVBORw==","palette":"/wAA"}"#);
+        assert_eq!(serde_json::from_str::<Thumbnail>(&text).unwrap(), thumbnail);
+        assert!(serde_json::from_str::<BinaryBlob>(r#""not base64!""#).is_err());
+    }
+
+    #[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+    enum Tile {
+        Floor,
+        Wall,
+        Door { locked: bool },
+    }
+
+    #[test]
+    fn test_compressed_grid_uses_a_palette() {
+        // a 64x64 map of rooms: walls around every 8x8 cell, with a door in each
+        let map: Vec<Tile> = (0..64 * 64)
+            .map(|i| match (i % 64 % 8, i / 64 % 8) {
+                (0, 4) => Tile::Door { locked: i % 3 == 0 },
+                (0, _) | (_, 0) => Tile::Wall,
+                _ => Tile::Floor,
+            })
+            .collect();
+        let plain = serde_json::to_string(&map).unwrap();
+        let grid = CompressedGrid(map);
+        let text = serde_json::to_string(&grid).unwrap();
+        assert!(text.starts_with(r#"{"palette":{"palette":["Wall","Floor",{"Door""#));
+        assert!(text.len() * 10 < plain.len());
+        assert_eq!(
+            serde_json::from_str::<CompressedGrid<Tile>>(&text).unwrap(),
+            grid
+        );
+
+        let unique = CompressedGrid(vec![1, 1, 2, 3]);
+        let text = serde_json::to_string(&unique).unwrap();
+        assert_eq!(text, r#"{"runs":[[1,2],[2,1],[3,1]]}"#);
+        assert_eq!(
+            serde_json::from_str::<CompressedGrid<i32>>(&text).unwrap(),
+            unique
+        );
+        let bad_index = serde_json::json!({"palette": {"palette": [1], "runs": "AgI="}});
+        assert!(serde_json::from_value::<CompressedGrid<i32>>(bad_index).is_err());
+    }
+
+    #[test]
+    fn test_grid_codecs_roundtrip() {
+        let fog = FogOfWar {
+            revealed: Rle([vec![false; 1000], vec![true; 24]].concat()),
+            heights: Delta((0..1024).map(|i| 1000 + i / 10).collect()),
+        };
+        let value = serde_json::to_value(&fog).unwrap();
+        assert_eq!(
+            value["revealed"],
+            serde_json::json!([[false, 1000], [true, 24]])
+        );
+        assert_eq!(value["heights"][0], 1000);
+        assert!(value["heights"].as_array().unwrap()[1..]
+            .iter()
+            .all(|delta| matches!(delta.as_i64(), Some(0 | 1))));
+
+        let mut world = World::default();
+        world.spawn((fog, SerializeMe));
+        let ecs = &mut world;
+        let mut saved = crate::serialize_individually!(ecs, SerializeMe, FogOfWar,);
+        let mut loaded = World::default();
+        let ecs = &mut loaded;
+        let mut entity_map = crate::EntityMap::new();
+        crate::deserialize_individually!(ecs, &mut entity_map, &mut saved, SerializeMe, FogOfWar,);
+        let loaded_fog = loaded.query::<&FogOfWar>().single(&loaded);
+        assert_eq!(loaded_fog, world.query::<&FogOfWar>().single(&world));
+
+        let values = [0, -1, 63, -64, 64, i64::MAX, i64::MIN];
+        assert_eq!(decode_varints(&encode_varints(&values)).unwrap(), values);
+        assert_eq!(decode_varints(&[0x80]), None);
+    }
+}
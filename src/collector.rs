@@ -0,0 +1,122 @@
+//! Saves assembled by ordinary systems. Serialization only reads the world, so each
+//! component section can be gathered from a regular [`Query`] into a [`SaveCollector`],
+//! and only the system writing the finished document needs exclusive access.
+
+use std::collections::BTreeMap;
+
+use bevy_ecs::prelude::*;
+use bevy_utils::tracing::warn;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::exempt::SaveQuery;
+use crate::registry::short_type_name;
+use crate::section_value;
+
+/// [`SerializeComponents`](crate::SerializeComponents) for a system's [`SaveQuery`], which
+/// needs no `World` access of its own.
+pub trait SerializeQuery {
+    /// The section of the queried components, or nothing if no entity matches.
+    fn serialize_query(&self) -> Result<Option<Value>, serde_json::Error>;
+
+    /// Like [`serialize_query`](Self::serialize_query), but only includes the entities for
+    /// which `filter` returns true.
+    fn serialize_query_filtered(
+        &self,
+        filter: impl Fn(Entity) -> bool,
+    ) -> Result<Option<Value>, serde_json::Error>;
+}
+
+impl<C, M> SerializeQuery for SaveQuery<'_, '_, C, M>
+where
+    C: Component + Serialize,
+    M: Component,
+{
+    fn serialize_query(&self) -> Result<Option<Value>, serde_json::Error> {
+        self.serialize_query_filtered(|_| true)
+    }
+
+    fn serialize_query_filtered(
+        &self,
+        filter: impl Fn(Entity) -> bool,
+    ) -> Result<Option<Value>, serde_json::Error> {
+        section_value(self.iter().filter(|(entity, _)| filter(*entity)).collect())
+    }
+}
+
+/// The sections of a save gathered by systems, keyed like the sections of
+/// `serialize_individually!`.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct SaveCollector {
+    sections: BTreeMap<String, Value>,
+}
+
+impl SaveCollector {
+    /// Adds the section of `query`'s components, named after `C` as the macros name it.
+    pub fn collect<C: Component + Serialize, M: Component>(
+        &mut self,
+        query: &SaveQuery<C, M>,
+    ) -> Result<(), serde_json::Error> {
+        self.collect_as(&short_type_name(std::any::type_name::<C>()), query)
+    }
+
+    /// Adds the section of `query`'s components under `name`, replacing any section of
+    /// that name.
+    pub fn collect_as<C: Component + Serialize, M: Component>(
+        &mut self,
+        name: &str,
+        query: &SaveQuery<C, M>,
+    ) -> Result<(), serde_json::Error> {
+        match query.serialize_query()? {
+            Some(section) => self.sections.insert(name.to_string(), section),
+            None => self.sections.remove(name),
+        };
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+
+    /// Takes the gathered sections, leaving the collector empty for the next save.
+    pub fn take(&mut self) -> BTreeMap<String, Value> {
+        std::mem::take(&mut self.sections)
+    }
+}
+
+/// A system adding the section of the `C` components of entities marked with `M` to the
+/// [`SaveCollector`], e.g. `collect_section::<Health, SaveMe>` scheduled before the system
+/// writing the save. Serialization errors are logged.
+pub fn collect_section<C: Component + Serialize, M: Component>(
+    query: SaveQuery<C, M>,
+    mut collector: ResMut<SaveCollector>,
+) {
+    if let Err(err) = collector.collect(&query) {
+        warn!("failed to serialize {}: {err}", std::any::type_name::<C>());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ecs::system::RunSystemOnce;
+
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_collect_from_systems() {
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        world.spawn((Component2 { target: entity1 }, SerializeMe));
+        world.spawn(Component1);
+        world.init_resource::<SaveCollector>();
+        world.run_system_once(collect_section::<Component1, SerializeMe>);
+        world.run_system_once(collect_section::<Component2, SerializeMe>);
+
+        let collected = world.resource_mut::<SaveCollector>().take();
+        let ecs = &mut world;
+        let expected = crate::serialize_individually!(ecs, SerializeMe, Component1, Component2,);
+        assert_eq!(collected, expected);
+        assert!(world.resource::<SaveCollector>().is_empty());
+    }
+}
@@ -0,0 +1,108 @@
+//! Dropping the sections of component types the game no longer has. Loading leaves
+//! sections it doesn't know in the document, and tools that patch or layer documents carry
+//! them along, so without compaction they would be written into every save from then on.
+
+use crate::layout::is_reserved;
+use crate::mods::{flatten_mod_sections, nest_mod_sections, split_mod_section, MODS_KEY};
+use crate::registry::SaveRegistry;
+use crate::SaveDocument;
+
+/// A section dropped by [`SaveRegistry::compact`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiscardedSection {
+    pub name: String,
+    pub entries: usize,
+    /// The length of the section as compact JSON.
+    pub bytes: usize,
+}
+
+/// The outcome of [`SaveRegistry::compact`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompactionReport {
+    /// The dropped sections, in name order.
+    pub discarded: Vec<DiscardedSection>,
+}
+
+impl CompactionReport {
+    pub fn discarded_bytes(&self) -> usize {
+        self.discarded.iter().map(|section| section.bytes).sum()
+    }
+}
+
+impl SaveRegistry {
+    /// Drops every component section of `doc` that this registry doesn't register, except
+    /// those of the mods in `active_mods`, which are kept for the mod to read whether or not
+    /// its components are registered yet. Reserved sections such as the manifest are kept;
+    /// mod sections are compacted whether they are nested under [`MODS_KEY`] or not.
+    pub fn compact(
+        &self,
+        doc: &mut SaveDocument,
+        active_mods: &[&str],
+    ) -> Result<CompactionReport, serde_json::Error> {
+        let nested = doc.contains_key(MODS_KEY);
+        flatten_mod_sections(doc)?;
+        let mut orphans: Vec<String> = doc
+            .keys()
+            .filter(|name| {
+                !is_reserved(name)
+                    && self.get(name).is_none()
+                    && split_mod_section(name)
+                        .is_none_or(|(mod_name, _)| !active_mods.contains(&mod_name))
+            })
+            .cloned()
+            .collect();
+        orphans.sort();
+        let mut report = CompactionReport::default();
+        for name in orphans {
+            let section = doc.remove(&name).unwrap_or_default();
+            report.discarded.push(DiscardedSection {
+                entries: section.as_array().map_or(0, Vec::len),
+                bytes: serde_json::to_string(&section)?.len(),
+                name,
+            });
+        }
+        if nested {
+            nest_mod_sections(doc)?;
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ecs::prelude::*;
+
+    use crate::manifest::MANIFEST_KEY;
+    use crate::tests::{Component1, SerializeMe};
+
+    #[test]
+    fn test_compaction_drops_orphans() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>();
+        let mut world = World::default();
+        world.spawn((Component1, SerializeMe));
+        let mut doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        let orphan = serde_json::json!([[0, {"hp": 3}], [1, {"hp": 4}]]);
+        doc.insert("OldHealth".to_string(), orphan.clone());
+        doc.insert(
+            MODS_KEY.to_string(),
+            serde_json::json!({"kept_mod": {"Turret": []}, "removed_mod": {"Laser": [[0, 1]]}}),
+        );
+
+        let report = registry.compact(&mut doc, &["kept_mod"]).unwrap();
+        let names: Vec<&str> = report
+            .discarded
+            .iter()
+            .map(|section| section.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["OldHealth", "removed_mod:Laser"]);
+        assert_eq!(report.discarded[0].entries, 2);
+        assert_eq!(report.discarded[0].bytes, orphan.to_string().len());
+        assert!(doc.contains_key("Component1") && doc.contains_key(MANIFEST_KEY));
+        assert_eq!(
+            doc[MODS_KEY],
+            serde_json::json!({"kept_mod": {"Turret": []}})
+        );
+    }
+}
@@ -0,0 +1,767 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+
+use bevy_ecs::prelude::*;
+use bevy_utils::tracing::{info_span, warn};
+use bevy_utils::Instant;
+use serde_json::Value;
+
+use crate::async_io::IoOperation;
+use crate::layout::{
+    from_entity_layout, is_entity_layout, is_reserved, to_entity_layout, SaveLayout,
+};
+use crate::load::{saved_entities, LoadMode, LoadReport};
+use crate::lock::HeldOperation;
+use crate::manifest::{Manifest, MANIFEST_KEY};
+use crate::metrics::PersistenceMetrics;
+use crate::mods::{flatten_mod_sections, mods_in, nest_mod_sections, MODS_KEY};
+use crate::parse;
+use crate::quantize::quantize_floats;
+use crate::registry::SaveRegistry;
+use crate::stats::{BudgetWarning, SaveStats};
+use crate::subtree::HIERARCHY_KEY;
+use crate::SaveDocument;
+
+/// The serialization format of the save body.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SaveFormat {
+    #[default]
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    #[cfg(feature = "gzip")]
+    Gzip,
+}
+
+/// How entity ids are written in component sections.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EntityEncoding {
+    /// The `u64` of [`Entity::to_bits`], as written by `serialize_individually!`.
+    #[default]
+    Bits,
+    /// The same number as a decimal string, for readers such as JavaScript's `JSON.parse`
+    /// that lose precision on integers above 2^53. Covers the entity column of every
+    /// section and the hierarchy; references inside components are written by their own
+    /// `Serialize` impl, see [`entity_str`](crate::entity_str). Saves are read in either
+    /// encoding whatever this is set to.
+    String,
+}
+
+/// Applies `f` to every entity id written by the crate itself, rather than by a
+/// component: the first element of each section entry, mods' sections included, and both
+/// elements of hierarchy entries.
+fn map_entity_ids(doc: &mut SaveDocument, f: fn(&mut Value)) {
+    for (name, section) in doc.iter_mut() {
+        if name == MODS_KEY {
+            let mod_sections = section
+                .as_object_mut()
+                .into_iter()
+                .flat_map(|mods| mods.values_mut());
+            for (name, section) in mod_sections.filter_map(Value::as_object_mut).flatten() {
+                map_section_ids(name, section, f);
+            }
+        } else if name != MANIFEST_KEY {
+            map_section_ids(name, section, f);
+        }
+    }
+}
+
+fn map_section_ids(name: &str, section: &mut Value, f: fn(&mut Value)) {
+    let Value::Array(entries) = section else {
+        return;
+    };
+    for entry in entries {
+        if let Some([entity, rest]) = entry.as_array_mut().map(Vec::as_mut_slice) {
+            f(entity);
+            if name == HIERARCHY_KEY {
+                f(rest);
+            }
+        }
+    }
+}
+
+fn bits_to_string(id: &mut Value) {
+    if let Some(bits) = id.as_u64() {
+        *id = Value::String(bits.to_string());
+    }
+}
+
+fn string_to_bits(id: &mut Value) {
+    if let Some(bits) = id.as_str().and_then(|bits| bits.parse::<u64>().ok()) {
+        *id = Value::from(bits);
+    }
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// The save exceeds [`SaveConfig::with_max_size`].
+    TooLarge {
+        len: usize,
+        max: usize,
+    },
+    /// The body of a [`SplitSave`](crate::SplitSave) doesn't match its header.
+    BodyMismatch,
+    /// The save holds more entities than [`SaveConfig::with_max_entities`] allows.
+    TooManyEntities {
+        found: usize,
+        max: usize,
+    },
+    /// A [`SaveRequest`](crate::SaveRequest) names a profile missing from the world's
+    /// [`Profiles`](crate::Profiles).
+    UnknownProfile(String),
+    /// The world's [`PersistenceLock`](crate::PersistenceLock) is held by another save or
+    /// load.
+    Busy(HeldOperation),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "{err}"),
+            SaveError::Json(err) => write!(f, "{err}"),
+            SaveError::TooLarge { len, max } => {
+                write!(f, "save of {len} bytes exceeds the limit of {max} bytes")
+            }
+            SaveError::TooManyEntities { found, max } => {
+                write!(
+                    f,
+                    "save holds {found} entities, more than the limit of {max}"
+                )
+            }
+            SaveError::BodyMismatch => write!(f, "save body doesn't match its header"),
+            SaveError::UnknownProfile(name) => write!(f, "no save profile named {name:?}"),
+            SaveError::Busy(held) => {
+                let operation = match held.operation {
+                    IoOperation::Save => "save",
+                    IoOperation::Load => "load",
+                    IoOperation::Delete => "deletion",
+                };
+                write!(f, "a {operation} of {:?} is in flight", held.name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(err: io::Error) -> Self {
+        SaveError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(err: serde_json::Error) -> Self {
+        SaveError::Json(err)
+    }
+}
+
+/// Settings for the whole save/load pipeline, passed to [`SaveRegistry::save_bytes`] and
+/// [`SaveRegistry::load_bytes`]. The default writes compact, uncompressed JSON with sorted
+/// sections and no size limit, and loads in [`LoadMode::Merge`].
+#[derive(Clone, Debug, Default)]
+pub struct SaveConfig {
+    format: SaveFormat,
+    compression: Compression,
+    pretty: bool,
+    entity_encoding: EntityEncoding,
+    layout: SaveLayout,
+    metadata: BTreeMap<String, Value>,
+    max_size: Option<usize>,
+    section_budgets: BTreeMap<String, usize>,
+    total_budget: Option<usize>,
+    float_precision: Option<u32>,
+    max_entities: Option<usize>,
+    load_mode: LoadMode,
+}
+
+impl SaveConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_format(mut self, format: SaveFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Indents the output for readability. Ignored by binary formats.
+    pub fn with_pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+
+    /// Has no effect: a [`SaveDocument`] keeps its sections in name order, so they're
+    /// always written in it.
+    #[deprecated(note = "sections are always written in name order")]
+    pub fn with_sort_keys(self, _sort_keys: bool) -> Self {
+        self
+    }
+
+    pub fn with_entity_encoding(mut self, entity_encoding: EntityEncoding) -> Self {
+        self.entity_encoding = entity_encoding;
+        self
+    }
+
+    /// Writes saves in `layout`. Saves are read in either layout whatever this is set to.
+    pub fn with_layout(mut self, layout: SaveLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Adds a field to the [`Manifest::metadata`] of saves written with this config.
+    pub fn with_metadata(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.metadata.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// Rejects saves larger than `max_size` bytes, both when writing and when reading.
+    /// For compressed saves the limit applies to the compressed and decompressed sizes.
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Warns when the section `name` is larger than `bytes` as compact JSON, to catch
+    /// components that start serializing far more than intended. Budgets are checked by
+    /// [`encode`](Self::encode), which logs a warning for each section over budget, and
+    /// by [`check_budgets`](Self::check_budgets).
+    pub fn with_section_budget(mut self, name: &str, bytes: usize) -> Self {
+        self.section_budgets.insert(name.to_string(), bytes);
+        self
+    }
+
+    /// Warns when all sections together are larger than `bytes` as compact JSON, as with
+    /// [`with_section_budget`](Self::with_section_budget).
+    pub fn with_total_budget(mut self, bytes: usize) -> Self {
+        self.total_budget = Some(bytes);
+        self
+    }
+
+    /// Rounds every float in the component sections to `decimals` decimal places, see
+    /// [`quantize`](crate::quantize).
+    pub fn with_float_precision(mut self, decimals: u32) -> Self {
+        self.float_precision = Some(decimals);
+        self
+    }
+
+    /// Rejects saves holding more than `max_entities` entities when loading, before any of
+    /// them is spawned, so that a corrupt or malicious file can't exhaust memory.
+    pub fn with_max_entities(mut self, max_entities: usize) -> Self {
+        self.max_entities = Some(max_entities);
+        self
+    }
+
+    pub fn with_load_mode(mut self, load_mode: LoadMode) -> Self {
+        self.load_mode = load_mode;
+        self
+    }
+
+    pub fn format(&self) -> SaveFormat {
+        self.format
+    }
+
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    pub fn entity_encoding(&self) -> EntityEncoding {
+        self.entity_encoding
+    }
+
+    pub fn load_mode(&self) -> LoadMode {
+        self.load_mode
+    }
+
+    pub fn layout(&self) -> SaveLayout {
+        self.layout
+    }
+
+    pub fn metadata(&self) -> &BTreeMap<String, Value> {
+        &self.metadata
+    }
+
+    /// The sections of `doc`, and the document as a whole, that are over their budgets.
+    pub fn check_budgets(
+        &self,
+        doc: &SaveDocument,
+    ) -> Result<Vec<BudgetWarning>, serde_json::Error> {
+        if self.section_budgets.is_empty() && self.total_budget.is_none() {
+            return Ok(Vec::new());
+        }
+        Ok(self.budget_warnings(&SaveStats::from_document(doc)?))
+    }
+
+    /// The sections of `stats`, and its total, that are over their budgets.
+    pub(crate) fn budget_warnings(&self, stats: &SaveStats) -> Vec<BudgetWarning> {
+        let mut warnings: Vec<BudgetWarning> = self
+            .section_budgets
+            .iter()
+            .filter_map(|(name, budget)| {
+                let bytes = stats.sections.get(name)?.bytes;
+                (bytes > *budget).then(|| BudgetWarning {
+                    section: Some(name.clone()),
+                    bytes,
+                    budget: *budget,
+                })
+            })
+            .collect();
+        if let Some(budget) = self.total_budget {
+            let bytes = stats.total_bytes();
+            if bytes > budget {
+                warnings.push(BudgetWarning {
+                    section: None,
+                    bytes,
+                    budget,
+                });
+            }
+        }
+        warnings
+    }
+
+    /// Fails if `doc` holds more entities than [`with_max_entities`](Self::with_max_entities)
+    /// allows.
+    pub fn check_entity_count(&self, doc: &SaveDocument) -> Result<(), SaveError> {
+        let Some(max) = self.max_entities else {
+            return Ok(());
+        };
+        // the longest section is a lower bound that rejects most oversized saves without
+        // collecting every entity id
+        let longest = doc
+            .iter()
+            .filter(|(name, _)| !is_reserved(name))
+            .filter_map(|(_, section)| section.as_array().map(Vec::len))
+            .max()
+            .unwrap_or_default();
+        let found = if longest > max {
+            longest
+        } else {
+            saved_entities(doc)?.len()
+        };
+        match found > max {
+            true => Err(SaveError::TooManyEntities { found, max }),
+            false => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_size(&self, len: usize) -> Result<(), SaveError> {
+        match self.max_size {
+            Some(max) if len > max => Err(SaveError::TooLarge { len, max }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Decompresses gzipped `bytes`, stopping as soon as they exceed the max size, so that
+    /// a small file can't expand into all of memory.
+    #[cfg(feature = "gzip")]
+    pub(crate) fn gunzip(&self, bytes: &[u8]) -> Result<Vec<u8>, SaveError> {
+        use std::io::Read;
+        let limit = self.max_size.map_or(u64::MAX, |max| max as u64 + 1);
+        let mut decompressed = Vec::new();
+        flate2::read::GzDecoder::new(bytes)
+            .take(limit)
+            .read_to_end(&mut decompressed)?;
+        self.check_size(decompressed.len())?;
+        Ok(decompressed)
+    }
+
+    pub(crate) fn is_pretty(&self) -> bool {
+        self.pretty
+    }
+
+    /// Applies the parts of [`encode`](Self::encode) that work section by section, float
+    /// rounding and the entity encoding, to `sections`, whose mod sections must be nested.
+    pub(crate) fn encode_sections(&self, sections: &mut SaveDocument) {
+        if let Some(decimals) = self.float_precision {
+            for (_, section) in sections
+                .iter_mut()
+                .filter(|(name, _)| *name != MANIFEST_KEY)
+            {
+                quantize_floats(section, decimals);
+            }
+        }
+        if self.entity_encoding == EntityEncoding::String {
+            map_entity_ids(sections, bits_to_string);
+        }
+    }
+
+    /// Encodes a save document into bytes according to this config.
+    pub fn encode(&self, doc: &SaveDocument) -> Result<Vec<u8>, SaveError> {
+        let _span = info_span!("write").entered();
+        for warning in self.check_budgets(doc)? {
+            warn!("{warning}");
+        }
+        let mut regrouped = None;
+        if let Some(decimals) = self.float_precision {
+            let mut quantized = doc.clone();
+            for (_, section) in quantized
+                .iter_mut()
+                .filter(|(name, _)| *name != MANIFEST_KEY)
+            {
+                quantize_floats(section, decimals);
+            }
+            regrouped = Some(quantized);
+        }
+        if !mods_in(doc).is_empty() {
+            let mut nested = regrouped.take().unwrap_or_else(|| doc.clone());
+            nest_mod_sections(&mut nested)?;
+            regrouped = Some(nested);
+        }
+        if self.layout == SaveLayout::ByEntity {
+            regrouped = Some(to_entity_layout(regrouped.as_ref().unwrap_or(doc))?);
+        }
+        if self.entity_encoding == EntityEncoding::String {
+            let regrouped = regrouped.get_or_insert_with(|| doc.clone());
+            map_entity_ids(regrouped, bits_to_string);
+        }
+        let doc = regrouped.as_ref().unwrap_or(doc);
+        let bytes = if self.pretty {
+            serde_json::to_vec_pretty(doc)
+        } else {
+            serde_json::to_vec(doc)
+        }?;
+        self.check_size(bytes.len())?;
+        let bytes = match self.compression {
+            Compression::None => bytes,
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                use std::io::Write;
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(&bytes)?;
+                encoder.finish()?
+            }
+        };
+        self.check_size(bytes.len())?;
+        Ok(bytes)
+    }
+
+    /// Decodes bytes written with [`encode`](Self::encode) under the same config, into the
+    /// [`SaveLayout::ByComponent`] layout the loaders expect.
+    pub fn decode(&self, bytes: &[u8]) -> Result<SaveDocument, SaveError> {
+        let mut doc = self.decode_as_written(bytes)?;
+        map_entity_ids(&mut doc, string_to_bits);
+        if is_entity_layout(&doc) {
+            doc = from_entity_layout(&doc)?;
+        }
+        flatten_mod_sections(&mut doc)?;
+        Ok(doc)
+    }
+
+    fn decode_as_written(&self, bytes: &[u8]) -> Result<SaveDocument, SaveError> {
+        let _span = info_span!("parse", len = bytes.len()).entered();
+        self.check_size(bytes.len())?;
+        match self.compression {
+            Compression::None => Ok(parse::from_slice(bytes)?),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Ok(parse::from_slice(&self.gunzip(bytes)?)?),
+        }
+    }
+}
+
+impl SaveRegistry {
+    /// The document [`save_bytes`](Self::save_bytes) encodes: the serialized entities and,
+    /// if the config has metadata, a manifest carrying it.
+    pub(crate) fn save_document<M: Component>(
+        &self,
+        world: &mut World,
+        config: &SaveConfig,
+    ) -> Result<SaveDocument, SaveError> {
+        let mut doc = self.serialize::<M>(world)?;
+        if !config.metadata.is_empty() {
+            doc.insert(
+                MANIFEST_KEY.to_string(),
+                serde_json::to_value(self.manifest_for(config))?,
+            );
+        }
+        Ok(doc)
+    }
+
+    /// The registry's manifest, carrying the config's metadata.
+    pub(crate) fn manifest_for(&self, config: &SaveConfig) -> Manifest {
+        Manifest {
+            metadata: config.metadata.clone(),
+            ..self.manifest()
+        }
+    }
+
+    /// Serializes the entities marked with `M` as [`serialize`](Self::serialize) does and
+    /// encodes them according to `config`. Updates the world's [`PersistenceMetrics`] and
+    /// sends [`PersistenceTelemetry`](crate::PersistenceTelemetry), if it has them.
+    pub fn save_bytes<M: Component>(
+        &self,
+        world: &mut World,
+        config: &SaveConfig,
+    ) -> Result<Vec<u8>, SaveError> {
+        self.save_sections::<M>(world, config, |_| true)
+    }
+
+    /// [`save_bytes`](Self::save_bytes) restricted to the component sections passing
+    /// `filter`.
+    pub(crate) fn save_sections<M: Component>(
+        &self,
+        world: &mut World,
+        config: &SaveConfig,
+        filter: impl Fn(&str) -> bool,
+    ) -> Result<Vec<u8>, SaveError> {
+        let start = Instant::now();
+        let result = self.save_document::<M>(world, config).and_then(|mut doc| {
+            doc.retain(|name, _| is_reserved(name) || filter(name));
+            config.encode(&doc)
+        });
+        if PersistenceMetrics::is_observed(world) {
+            let (size, entities) = match &result {
+                Ok(bytes) => (
+                    bytes.len(),
+                    world.query_filtered::<(), With<M>>().iter(world).count(),
+                ),
+                Err(_) => (0, 0),
+            };
+            let error = result.as_ref().err();
+            PersistenceMetrics::record_save(world, start.elapsed(), size, entities, error);
+        }
+        result
+    }
+
+    /// Decodes a save written by [`save_bytes`](Self::save_bytes) and loads it in the
+    /// config's [`LoadMode`]. Updates the world's [`PersistenceMetrics`] and sends
+    /// [`PersistenceTelemetry`](crate::PersistenceTelemetry), if it has them.
+    pub fn load_bytes<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        bytes: &[u8],
+        config: &SaveConfig,
+        marker: M,
+    ) -> Result<LoadReport, SaveError> {
+        self.load_sections(world, bytes, config, |_| true, marker)
+    }
+
+    /// [`load_bytes`](Self::load_bytes) restricted to the component sections passing
+    /// `filter`.
+    pub(crate) fn load_sections<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        bytes: &[u8],
+        config: &SaveConfig,
+        filter: impl Fn(&str) -> bool,
+        marker: M,
+    ) -> Result<LoadReport, SaveError> {
+        let start = Instant::now();
+        let result = config.decode(bytes).and_then(|mut doc| {
+            self.load_document(world, &mut doc, config, config.load_mode, filter, marker)
+        });
+        let entities = result.as_ref().map_or(0, |report| report.entity_map.len());
+        let error = result.as_ref().err();
+        PersistenceMetrics::record_load(world, start.elapsed(), bytes.len(), entities, error);
+        result
+    }
+
+    /// Loads a decoded save in `mode` once it passes the config's limits, such as
+    /// [`with_max_entities`](SaveConfig::with_max_entities). Every load of a save read with a
+    /// config goes through here.
+    pub(crate) fn load_document<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        doc: &mut SaveDocument,
+        config: &SaveConfig,
+        mode: LoadMode,
+        filter: impl Fn(&str) -> bool,
+        marker: M,
+    ) -> Result<LoadReport, SaveError> {
+        doc.remove(MANIFEST_KEY);
+        config.check_entity_count(doc)?;
+        Ok(self.load_filtered(world, doc, mode, filter, marker)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_config_roundtrip() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Component2>();
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        world.spawn((Component2 { target: entity1 }, SerializeMe));
+
+        let config = SaveConfig::new()
+            .with_pretty(true)
+            .with_metadata("slot", "autosave")
+            .with_load_mode(LoadMode::Replace);
+        let bytes = registry
+            .save_bytes::<SerializeMe>(&mut world, &config)
+            .unwrap();
+        let text = String::from_utf8(bytes.clone()).unwrap();
+        assert!(text.find("Component1") < text.find("Component2"));
+        let manifest = Manifest::read(&bytes).unwrap().unwrap();
+        assert_eq!(manifest.metadata["slot"], "autosave");
+
+        let report = registry
+            .load_bytes(&mut world, &bytes, &config, SerializeMe)
+            .unwrap();
+        assert_eq!(report.entity_map.len(), 2);
+        assert!(matches!(
+            registry.load_bytes(
+                &mut world,
+                &bytes,
+                &config.clone().with_max_size(8),
+                SerializeMe
+            ),
+            Err(SaveError::TooLarge { max: 8, .. })
+        ));
+    }
+
+    #[test]
+    fn test_entity_layout_roundtrip() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Component2>();
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        world.spawn((Component2 { target: entity1 }, SerializeMe));
+
+        let config = SaveConfig::new().with_layout(SaveLayout::ByEntity);
+        let bytes = registry
+            .save_bytes::<SerializeMe>(&mut world, &config)
+            .unwrap();
+        let written: Value = serde_json::from_slice(&bytes).unwrap();
+        assert!(written.get("Component1").is_none());
+        assert!(written.get(crate::layout::entity_key(entity1)).is_some());
+        let report = registry
+            .load_bytes(
+                &mut World::default(),
+                &bytes,
+                &SaveConfig::new(),
+                SerializeMe,
+            )
+            .unwrap();
+        assert_eq!(report.entity_map.len(), 2);
+    }
+
+    #[test]
+    fn test_string_entity_encoding() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .register_mapped::<Component2>();
+        let mut world = World::default();
+        let entity1 = world.spawn_empty().id();
+        world.despawn(entity1);
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        assert!(entity1.to_bits() >= 1 << 32);
+        world.spawn((Component2 { target: entity1 }, SerializeMe));
+
+        let config = SaveConfig::new().with_entity_encoding(EntityEncoding::String);
+        let bytes = registry
+            .save_bytes::<SerializeMe>(&mut world, &config)
+            .unwrap();
+        let written: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            written["Component1"][0][0],
+            Value::String(entity1.to_bits().to_string())
+        );
+        let mut loaded = World::default();
+        let report = registry
+            .load_bytes(&mut loaded, &bytes, &SaveConfig::new(), SerializeMe)
+            .unwrap();
+        let target = loaded.query::<&Component2>().single(&loaded).target;
+        assert_eq!(target, report.entity_map[&entity1]);
+    }
+
+    #[test]
+    fn test_budgets() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Component2>();
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        world.spawn((Component2 { target: entity1 }, SerializeMe));
+        let doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+
+        let config = SaveConfig::new()
+            .with_section_budget("Component1", 1024)
+            .with_section_budget("Component2", 4)
+            .with_total_budget(8);
+        let warnings = config.check_budgets(&doc).unwrap();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].section.as_deref(), Some("Component2"));
+        assert_eq!(warnings[1].section, None);
+        assert!(warnings[0]
+            .to_string()
+            .contains("over its budget of 4 bytes"));
+    }
+
+    #[test]
+    fn test_entity_cap_rejects_before_spawning() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Component2>();
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        world.spawn((Component2 { target: entity1 }, SerializeMe));
+        let bytes = registry
+            .save_bytes::<SerializeMe>(&mut world, &SaveConfig::new())
+            .unwrap();
+
+        let mut loaded = World::default();
+        let err = registry
+            .load_bytes(
+                &mut loaded,
+                &bytes,
+                &SaveConfig::new().with_max_entities(1),
+                SerializeMe,
+            )
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            SaveError::TooManyEntities { found: 2, max: 1 }
+        ));
+        assert_eq!(loaded.entities().len(), 0);
+        registry
+            .load_bytes(
+                &mut loaded,
+                &bytes,
+                &SaveConfig::new().with_max_entities(2),
+                SerializeMe,
+            )
+            .unwrap();
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip_roundtrip() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>();
+        let mut world = World::default();
+        world.spawn_batch((0..100).map(|_| (Component1, SerializeMe)));
+
+        let plain = registry
+            .save_bytes::<SerializeMe>(&mut world, &SaveConfig::new())
+            .unwrap();
+        let config = SaveConfig::new().with_compression(Compression::Gzip);
+        let compressed = registry
+            .save_bytes::<SerializeMe>(&mut world, &config)
+            .unwrap();
+        assert!(compressed.len() < plain.len());
+        let report = registry
+            .load_bytes(&mut World::default(), &compressed, &config, SerializeMe)
+            .unwrap();
+        assert_eq!(report.entity_map.len(), 100);
+        assert!(matches!(
+            config.with_max_size(plain.len() - 1).decode(&compressed),
+            Err(SaveError::TooLarge { .. })
+        ));
+    }
+}
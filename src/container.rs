@@ -0,0 +1,420 @@
+//! A save file in two parts: a JSON header that can be read, grepped and listed in save
+//! menus without touching the rest, followed by the component sections in a compact
+//! binary encoding, compressed as the [`SaveConfig`] says. Bulk data doesn't pay for JSON's
+//! text numbers and quoted keys, while the metadata stays human-inspectable.
+//!
+//! Layout: the magic bytes `BSAVE\0`, the header's length as a little-endian `u32`, the
+//! header as JSON, then the payload.
+
+use std::collections::BTreeMap;
+use std::io;
+
+use bevy_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+
+use crate::config::{Compression, SaveConfig, SaveError};
+use crate::load::LoadReport;
+use crate::manifest::{Manifest, MANIFEST_KEY};
+use crate::mods::{flatten_mod_sections, nest_mod_sections};
+use crate::registry::SaveRegistry;
+use crate::SaveDocument;
+
+const MAGIC: &[u8] = b"BSAVE\0";
+
+/// The JSON part of a container save.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ContainerHeader {
+    pub manifest: Option<Manifest>,
+    /// The config's [`metadata`](SaveConfig::with_metadata).
+    pub metadata: BTreeMap<String, Value>,
+    /// Whether the payload is gzip compressed.
+    pub compressed: bool,
+    /// The number of payload bytes after compression.
+    pub payload_len: usize,
+}
+
+impl ContainerHeader {
+    /// Reads the header of a container save, leaving the payload untouched.
+    pub fn read(bytes: &[u8]) -> Result<Self, SaveError> {
+        let (header, _) = split_container(bytes)?;
+        Ok(serde_json::from_slice(header)?)
+    }
+}
+
+fn invalid(message: &str) -> SaveError {
+    SaveError::Io(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+fn split_container(bytes: &[u8]) -> Result<(&[u8], &[u8]), SaveError> {
+    let rest = bytes
+        .strip_prefix(MAGIC)
+        .ok_or_else(|| invalid("not a container save"))?;
+    let (len, rest) = rest
+        .split_first_chunk::<4>()
+        .ok_or_else(|| invalid("truncated container header"))?;
+    let len = u32::from_le_bytes(*len) as usize;
+    if rest.len() < len {
+        return Err(invalid("truncated container header"));
+    }
+    Ok(rest.split_at(len))
+}
+
+impl SaveRegistry {
+    /// Saves the entities marked with `M` as a container. The config's compression applies
+    /// to the payload and its metadata goes in the header; the JSON formatting options
+    /// don't apply.
+    pub fn save_container<M: Component>(
+        &self,
+        world: &mut World,
+        config: &SaveConfig,
+    ) -> Result<Vec<u8>, SaveError> {
+        let mut doc = self.serialize::<M>(world)?;
+        let manifest = doc
+            .remove(MANIFEST_KEY)
+            .map(serde_json::from_value)
+            .transpose()?;
+        nest_mod_sections(&mut doc)?;
+        let mut payload = Vec::new();
+        let sections: BTreeMap<&String, &Value> = doc.iter().collect();
+        write_len(&mut payload, sections.len());
+        for (name, section) in sections {
+            write_str(&mut payload, name);
+            write_value(&mut payload, section);
+        }
+        let compressed = config.compression() != Compression::None;
+        let payload = compress(payload, config.compression())?;
+        let header = serde_json::to_vec(&ContainerHeader {
+            manifest,
+            metadata: config.metadata().clone(),
+            compressed,
+            payload_len: payload.len(),
+        })?;
+        let header_len = u32::try_from(header.len()).map_err(|_| invalid("header too large"))?;
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(header_len.to_le_bytes());
+        bytes.extend(header);
+        bytes.extend(payload);
+        Ok(bytes)
+    }
+
+    /// Loads a save written by [`save_container`](Self::save_container) in the config's
+    /// [`LoadMode`](crate::LoadMode).
+    pub fn load_container<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        bytes: &[u8],
+        config: &SaveConfig,
+        marker: M,
+    ) -> Result<LoadReport, SaveError> {
+        let mut doc = read_container(bytes, config)?;
+        self.load_document(
+            world,
+            &mut doc,
+            config,
+            config.load_mode(),
+            |_| true,
+            marker,
+        )
+    }
+}
+
+/// The component sections of a container save, in the layout the loaders expect. The
+/// config's [`max_size`](SaveConfig::with_max_size) bounds the save both as read and once
+/// its payload is decompressed.
+pub fn read_container(bytes: &[u8], config: &SaveConfig) -> Result<SaveDocument, SaveError> {
+    config.check_size(bytes.len())?;
+    let (header, payload) = split_container(bytes)?;
+    let header: ContainerHeader = serde_json::from_slice(header)?;
+    if payload.len() != header.payload_len {
+        return Err(invalid("container payload length doesn't match its header"));
+    }
+    let payload = decompress(payload, header.compressed, config)?;
+    let mut reader = payload.as_slice();
+    let mut doc = SaveDocument::new();
+    for _ in 0..read_len(&mut reader)? {
+        let name = read_str(&mut reader)?;
+        doc.insert(name, read_value(&mut reader, 0)?);
+    }
+    flatten_mod_sections(&mut doc)?;
+    Ok(doc)
+}
+
+fn compress(payload: Vec<u8>, compression: Compression) -> Result<Vec<u8>, SaveError> {
+    match compression {
+        Compression::None => Ok(payload),
+        #[cfg(feature = "gzip")]
+        Compression::Gzip => {
+            use std::io::Write;
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&payload)?;
+            Ok(encoder.finish()?)
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "gzip"), allow(unused_variables))]
+fn decompress(payload: &[u8], compressed: bool, config: &SaveConfig) -> Result<Vec<u8>, SaveError> {
+    if !compressed {
+        return Ok(payload.to_vec());
+    }
+    #[cfg(feature = "gzip")]
+    return config.gunzip(payload);
+    #[cfg(not(feature = "gzip"))]
+    Err(invalid("compressed container saves need the gzip feature"))
+}
+
+// The payload encodes each JSON value as a tag byte followed by its data. Lengths and
+// integers are LEB128 varints, negative integers zigzag encoded.
+const NULL: u8 = 0;
+const FALSE: u8 = 1;
+const TRUE: u8 = 2;
+const UINT: u8 = 3;
+const NEG_INT: u8 = 4;
+const FLOAT: u8 = 5;
+const STRING: u8 = 6;
+const ARRAY: u8 = 7;
+const OBJECT: u8 = 8;
+
+/// How deeply arrays and objects may nest, as in serde_json, so that corrupt input can't
+/// overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_len(out: &mut Vec<u8>, len: usize) {
+    write_varint(out, len as u64);
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_len(out, s.len());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_value(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => out.push(NULL),
+        Value::Bool(false) => out.push(FALSE),
+        Value::Bool(true) => out.push(TRUE),
+        Value::Number(number) => {
+            if let Some(uint) = number.as_u64() {
+                out.push(UINT);
+                write_varint(out, uint);
+            } else if let Some(int) = number.as_i64() {
+                out.push(NEG_INT);
+                write_varint(out, ((int << 1) ^ (int >> 63)) as u64);
+            } else {
+                out.push(FLOAT);
+                out.extend(number.as_f64().unwrap_or_default().to_le_bytes());
+            }
+        }
+        Value::String(s) => {
+            out.push(STRING);
+            write_str(out, s);
+        }
+        Value::Array(values) => {
+            out.push(ARRAY);
+            write_len(out, values.len());
+            for value in values {
+                write_value(out, value);
+            }
+        }
+        Value::Object(fields) => {
+            out.push(OBJECT);
+            write_len(out, fields.len());
+            for (key, value) in fields {
+                write_str(out, key);
+                write_value(out, value);
+            }
+        }
+    }
+}
+
+fn truncated() -> SaveError {
+    invalid("truncated container payload")
+}
+
+fn read_bytes<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], SaveError> {
+    if input.len() < len {
+        return Err(truncated());
+    }
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes)
+}
+
+fn read_varint(input: &mut &[u8]) -> Result<u64, SaveError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = read_bytes(input, 1)?[0];
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid("varint too long in container payload"))
+}
+
+fn read_len(input: &mut &[u8]) -> Result<usize, SaveError> {
+    let len = read_varint(input)? as usize;
+    // every element takes at least a byte, so longer lengths can only be corrupt, and
+    // must not be allowed to reserve memory
+    match len <= input.len() {
+        true => Ok(len),
+        false => Err(truncated()),
+    }
+}
+
+fn read_str(input: &mut &[u8]) -> Result<String, SaveError> {
+    let len = read_len(input)?;
+    String::from_utf8(read_bytes(input, len)?.to_vec())
+        .map_err(|_| invalid("invalid UTF-8 in container payload"))
+}
+
+fn read_value(input: &mut &[u8], depth: usize) -> Result<Value, SaveError> {
+    if depth > MAX_DEPTH {
+        return Err(invalid("container payload nested too deeply"));
+    }
+    Ok(match read_bytes(input, 1)?[0] {
+        NULL => Value::Null,
+        FALSE => Value::Bool(false),
+        TRUE => Value::Bool(true),
+        UINT => Value::from(read_varint(input)?),
+        NEG_INT => {
+            let zigzag = read_varint(input)?;
+            Value::from((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64))
+        }
+        FLOAT => {
+            let bytes = read_bytes(input, 8)?.try_into().map_err(|_| truncated())?;
+            Number::from_f64(f64::from_le_bytes(bytes)).map_or(Value::Null, Value::Number)
+        }
+        STRING => Value::String(read_str(input)?),
+        ARRAY => {
+            let len = read_len(input)?;
+            let mut values = Vec::with_capacity(len);
+            for _ in 0..len {
+                values.push(read_value(input, depth + 1)?);
+            }
+            Value::Array(values)
+        }
+        OBJECT => {
+            let len = read_len(input)?;
+            let mut fields = Map::new();
+            for _ in 0..len {
+                let key = read_str(input)?;
+                fields.insert(key, read_value(input, depth + 1)?);
+            }
+            Value::Object(fields)
+        }
+        _ => return Err(invalid("unknown value tag in container payload")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Component1, Component2, Component3, SerializeMe, TestEnum};
+
+    #[test]
+    fn test_container_roundtrip() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .register_mapped::<Component2>()
+            .register::<Component3>();
+        let mut world = World::default();
+        for i in 0..50 {
+            let entity = world.spawn((Component1, SerializeMe)).id();
+            world.spawn((
+                Component2 { target: entity },
+                Component3 {
+                    target: entity,
+                    test_enum: TestEnum::ATest(format!("entity {i}")),
+                },
+                SerializeMe,
+            ));
+        }
+        let config = SaveConfig::new().with_metadata("slot", "quicksave");
+        let json = registry
+            .save_bytes::<SerializeMe>(&mut world, &config)
+            .unwrap();
+        let bytes = registry
+            .save_container::<SerializeMe>(&mut world, &config)
+            .unwrap();
+        assert!(bytes.len() < json.len());
+
+        let header = ContainerHeader::read(&bytes).unwrap();
+        assert_eq!(header.metadata["slot"], "quicksave");
+        assert!(header
+            .manifest
+            .unwrap()
+            .components
+            .contains_key("Component3"));
+        let header_text = String::from_utf8_lossy(&bytes[..bytes.len() - header.payload_len]);
+        assert!(header_text.contains(r#""slot":"quicksave""#));
+
+        let mut doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        doc.remove(MANIFEST_KEY);
+        assert_eq!(read_container(&bytes, &config).unwrap(), doc);
+        let report = registry
+            .load_container(&mut World::default(), &bytes, &config, SerializeMe)
+            .unwrap();
+        assert_eq!(report.entity_map.len(), 100);
+        assert!(read_container(&bytes[..bytes.len() - 1], &config).is_err());
+        assert!(read_container(&json, &config).is_err());
+    }
+
+    /// A container around a hand-built payload of one section, `Section`.
+    fn container(section: &[u8], compression: Compression) -> Vec<u8> {
+        let mut payload = Vec::new();
+        write_len(&mut payload, 1);
+        write_str(&mut payload, "Section");
+        payload.extend_from_slice(section);
+        let payload = compress(payload, compression).unwrap();
+        let header = serde_json::to_vec(&ContainerHeader {
+            manifest: None,
+            metadata: BTreeMap::new(),
+            compressed: compression != Compression::None,
+            payload_len: payload.len(),
+        })
+        .unwrap();
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend((header.len() as u32).to_le_bytes());
+        bytes.extend(header);
+        bytes.extend(payload);
+        bytes
+    }
+
+    #[test]
+    fn test_hostile_payloads_are_rejected() {
+        let mut nested = [ARRAY, 1].repeat(100_000);
+        nested.push(NULL);
+        let bytes = container(&nested, Compression::None);
+        assert!(read_container(&bytes, &SaveConfig::new()).is_err());
+        let shallow = [[ARRAY, 1].repeat(MAX_DEPTH), vec![NULL]].concat();
+        let bytes = container(&shallow, Compression::None);
+        assert!(read_container(&bytes, &SaveConfig::new()).is_ok());
+
+        #[cfg(feature = "gzip")]
+        {
+            let mut zeros = vec![STRING];
+            write_len(&mut zeros, 1 << 20);
+            zeros.resize(zeros.len() + (1 << 20), 0);
+            let bomb = container(&zeros, Compression::Gzip);
+            let config = SaveConfig::new().with_max_size(64 * 1024);
+            assert!(bomb.len() < 64 * 1024);
+            assert!(matches!(
+                read_container(&bomb, &config),
+                Err(SaveError::TooLarge { .. })
+            ));
+            assert!(read_container(&bomb, &SaveConfig::new()).is_ok());
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use bevy_ecs::prelude::*;
+use serde::de::Error;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::entity_map::{get_or_insert, EntityMap};
+use crate::manifest::MANIFEST_KEY;
+use crate::registry::SaveRegistry;
+use crate::snapshot::WorldSnapshot;
+use crate::SaveDocument;
+
+/// The difference between two saves of the same world: entities that appeared or
+/// disappeared, and per component section the values that were set or removed.
+///
+/// Entities are identified by their ids in the saved world, so a delta only applies
+/// on top of the save (or world) it was computed against.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SaveDelta {
+    pub added_entities: Vec<Entity>,
+    pub removed_entities: Vec<Entity>,
+    /// Per section, components that are new or whose value changed.
+    pub changed: BTreeMap<String, Vec<(Entity, Value)>>,
+    /// Per section, components removed from entities that still exist.
+    pub removed: BTreeMap<String, Vec<Entity>>,
+}
+
+/// Reads a component section as `(entity, component)` pairs.
+pub(crate) fn section_entries<'a>(
+    name: &str,
+    section: &'a Value,
+) -> Result<Vec<(Entity, &'a Value)>, serde_json::Error> {
+    let malformed = || {
+        serde_json::Error::custom(format!(
+            "section {name} is not a list of [entity, component] pairs"
+        ))
+    };
+    section
+        .as_array()
+        .ok_or_else(malformed)?
+        .iter()
+        .map(|entry| match entry.as_array().map(Vec::as_slice) {
+            Some([entity, comp]) => entity
+                .as_u64()
+                .map(|bits| (Entity::from_bits(bits), comp))
+                .ok_or_else(malformed),
+            _ => Err(malformed()),
+        })
+        .collect()
+}
+
+pub(crate) fn document_entities(
+    doc: &SaveDocument,
+) -> Result<BTreeMap<String, BTreeMap<Entity, &Value>>, serde_json::Error> {
+    doc.iter()
+        .filter(|(name, _)| name.as_str() != MANIFEST_KEY)
+        .map(|(name, section)| {
+            section_entries(name, section)
+                .map(|entries| (name.clone(), entries.into_iter().collect()))
+        })
+        .collect()
+}
+
+impl SaveDelta {
+    pub fn is_empty(&self) -> bool {
+        self.added_entities.is_empty()
+            && self.removed_entities.is_empty()
+            && self.changed.is_empty()
+            && self.removed.is_empty()
+    }
+
+    /// Computes the delta taking the save document `before` to `after`. Entities are
+    /// considered present if they appear in any component section.
+    pub fn between_documents(
+        before: &SaveDocument,
+        after: &SaveDocument,
+    ) -> Result<Self, serde_json::Error> {
+        let before = document_entities(before)?;
+        let after = document_entities(after)?;
+        let all_entities = |sections: &BTreeMap<String, BTreeMap<Entity, &Value>>| {
+            sections
+                .values()
+                .flat_map(|section| section.keys().copied())
+                .collect::<BTreeSet<Entity>>()
+        };
+        Ok(Self::between(
+            &before,
+            &after,
+            &all_entities(&before),
+            &all_entities(&after),
+        ))
+    }
+
+    /// Computes the delta taking the snapshot `before` to `after`.
+    pub fn between_snapshots(
+        before: &WorldSnapshot,
+        after: &WorldSnapshot,
+    ) -> Result<Self, serde_json::Error> {
+        let before_doc = before.to_document()?;
+        let after_doc = after.to_document()?;
+        Ok(Self::between(
+            &document_entities(&before_doc)?,
+            &document_entities(&after_doc)?,
+            &before.entities().iter().copied().collect(),
+            &after.entities().iter().copied().collect(),
+        ))
+    }
+
+    fn between(
+        before: &BTreeMap<String, BTreeMap<Entity, &Value>>,
+        after: &BTreeMap<String, BTreeMap<Entity, &Value>>,
+        before_entities: &BTreeSet<Entity>,
+        after_entities: &BTreeSet<Entity>,
+    ) -> Self {
+        let empty = BTreeMap::new();
+        let mut delta = SaveDelta {
+            added_entities: after_entities
+                .difference(before_entities)
+                .copied()
+                .collect(),
+            removed_entities: before_entities
+                .difference(after_entities)
+                .copied()
+                .collect(),
+            ..Default::default()
+        };
+        let names: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+        for name in names {
+            let before_section = before.get(name).unwrap_or(&empty);
+            let after_section = after.get(name).unwrap_or(&empty);
+            let changed: Vec<(Entity, Value)> = after_section
+                .iter()
+                .filter(|(entity, value)| before_section.get(*entity) != Some(*value))
+                .map(|(entity, value)| (*entity, (*value).clone()))
+                .collect();
+            let removed: Vec<Entity> = before_section
+                .keys()
+                .filter(|entity| {
+                    !after_section.contains_key(*entity) && after_entities.contains(*entity)
+                })
+                .copied()
+                .collect();
+            if !changed.is_empty() {
+                delta.changed.insert(name.clone(), changed);
+            }
+            if !removed.is_empty() {
+                delta.removed.insert(name.clone(), removed);
+            }
+        }
+        delta
+    }
+
+    /// Applies the delta to the save document it was computed against, turning it into
+    /// the later document.
+    pub fn apply_to_document(&self, doc: &mut SaveDocument) -> Result<(), serde_json::Error> {
+        let removed_entities: BTreeSet<Entity> = self.removed_entities.iter().copied().collect();
+        let mut sections = BTreeMap::new();
+        for (name, section) in document_entities(doc)? {
+            let removed = self.removed.get(&name);
+            let section: BTreeMap<Entity, Value> = section
+                .into_iter()
+                .filter(|(entity, _)| {
+                    !removed_entities.contains(entity)
+                        && !removed.is_some_and(|removed| removed.contains(entity))
+                })
+                .map(|(entity, value)| (entity, value.clone()))
+                .collect();
+            sections.insert(name, section);
+        }
+        for (name, changed) in &self.changed {
+            sections
+                .entry(name.clone())
+                .or_default()
+                .extend(changed.iter().cloned());
+        }
+        for (name, section) in sections {
+            if section.is_empty() {
+                doc.remove(&name);
+            } else {
+                doc.insert(
+                    name,
+                    serde_json::to_value(section.into_iter().collect::<Vec<_>>())?,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the delta to a live world. `entity_map` maps the delta's entities to
+    /// entities of `world` as in [`deserialize`](crate::deserialize); entities new to the
+    /// map are spawned and given `marker`, removed entities are despawned.
+    pub fn apply_to_world<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        registry: &SaveRegistry,
+        entity_map: &mut EntityMap,
+        marker: M,
+    ) -> Result<(), serde_json::Error> {
+        for entity in &self.removed_entities {
+            if let Some(mapped) = entity_map.remove(entity) {
+                world.despawn(mapped);
+            }
+        }
+        for entity in &self.added_entities {
+            let new_entity = get_or_insert(world, entity_map, *entity);
+            world.entity_mut(new_entity).insert(marker.clone());
+        }
+        for (name, entities) in &self.removed {
+            let Some(reg) = registry.get(name) else {
+                continue;
+            };
+            for entity in entities {
+                if let Some(mapped) = entity_map.get(entity) {
+                    (reg.remove)(world, *mapped);
+                }
+            }
+        }
+        let mut updated = Vec::new();
+        for (name, changed) in &self.changed {
+            let entities = registry.deserialize_section(
+                world,
+                entity_map,
+                name,
+                serde_json::to_value(changed)?,
+                marker.clone(),
+            )?;
+            updated.push((name, entities));
+        }
+        for (name, entities) in updated {
+            registry.map_section_references(world, name, &entities, entity_map);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::take_snapshot;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    fn registry() -> SaveRegistry {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register_cloneable::<Component1>()
+            .register_cloneable::<Component2>();
+        registry
+    }
+
+    #[test]
+    fn test_delta_between_documents() {
+        let registry = registry();
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        let entity2 = world.spawn((Component1, SerializeMe)).id();
+        let before = registry.serialize::<SerializeMe>(&mut world).unwrap();
+
+        world.despawn(entity1);
+        world.entity_mut(entity2).remove::<Component1>();
+        world
+            .entity_mut(entity2)
+            .insert(Component2 { target: entity2 });
+        let entity3 = world.spawn((Component1, SerializeMe)).id();
+        let after = registry.serialize::<SerializeMe>(&mut world).unwrap();
+
+        let delta = SaveDelta::between_documents(&before, &after).unwrap();
+        assert_eq!(delta.removed_entities, vec![entity1]);
+        assert_eq!(delta.added_entities, vec![entity3]);
+        assert_eq!(delta.removed["Component1"], vec![entity2]);
+        assert_eq!(delta.changed["Component2"].len(), 1);
+
+        let mut patched = before.clone();
+        delta.apply_to_document(&mut patched).unwrap();
+        assert_eq!(
+            SaveDelta::between_documents(&patched, &after).unwrap(),
+            SaveDelta::default()
+        );
+    }
+
+    #[test]
+    fn test_delta_between_snapshots_applies_to_world() {
+        let registry = registry();
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        let before = take_snapshot::<SerializeMe>(&mut world, &registry);
+        let entity2 = world
+            .spawn((Component2 { target: entity1 }, SerializeMe))
+            .id();
+        world.entity_mut(entity1).remove::<Component1>();
+        let after = take_snapshot::<SerializeMe>(&mut world, &registry);
+
+        let delta = SaveDelta::between_snapshots(&before, &after).unwrap();
+        assert!(!delta.is_empty());
+
+        let mut replica = World::default();
+        let mut entity_map = EntityMap::new();
+        crate::snapshot::restore_snapshot(&mut replica, &before, &mut entity_map, SerializeMe);
+        delta
+            .apply_to_world(&mut replica, &registry, &mut entity_map, SerializeMe)
+            .unwrap();
+        assert!(replica.get::<Component1>(entity_map[&entity1]).is_none());
+        assert!(replica.get::<Component2>(entity_map[&entity2]).is_some());
+    }
+}
@@ -0,0 +1,103 @@
+use std::any::TypeId;
+
+use bevy_ecs::prelude::*;
+
+use crate::registry::{ComponentRegistration, SaveRegistry};
+
+impl SaveRegistry {
+    /// Loads `Dep`'s section before `C`'s, so that code run while `C` is inserted, such as
+    /// [`LoadFromSave::from_saved`](crate::LoadFromSave::from_saved), finds `Dep` already
+    /// in the world, e.g. `Equipment` checking slots against the `Inventory`. Without
+    /// dependencies, sections are loaded in registration order.
+    ///
+    /// # Panics
+    /// If either component hasn't been registered, or if the dependency closes a cycle.
+    pub fn add_dependency<C: Component, Dep: Component>(&mut self) -> &mut Self {
+        if self.get_by_type::<Dep>().is_none() {
+            panic!(
+                "{} must be registered before depending on it",
+                std::any::type_name::<Dep>()
+            );
+        }
+        let Some(reg) = self.get_by_type_mut::<C>() else {
+            panic!(
+                "{} must be registered before adding dependencies",
+                std::any::type_name::<C>()
+            );
+        };
+        reg.dependencies.push(TypeId::of::<Dep>());
+        if self.load_order().len() < self.iter().count() {
+            panic!(
+                "{} and {} depend on each other",
+                std::any::type_name::<C>(),
+                std::any::type_name::<Dep>()
+            );
+        }
+        self
+    }
+
+    /// The registrations in the order their sections are loaded: every component after
+    /// those it depends on, and otherwise in registration order. Registrations caught in a
+    /// dependency cycle are left out, which [`add_dependency`](Self::add_dependency)
+    /// prevents.
+    pub fn load_order(&self) -> Vec<&ComponentRegistration> {
+        let mut pending: Vec<&ComponentRegistration> = self.iter().collect();
+        let mut order: Vec<&ComponentRegistration> = Vec::with_capacity(pending.len());
+        while let Some(index) = pending.iter().position(|reg| {
+            reg.dependencies.iter().all(|dep| {
+                order.iter().any(|done| done.type_id == *dep)
+                    || !pending.iter().any(|other| other.type_id == *dep)
+            })
+        }) {
+            order.push(pending.remove(index));
+        }
+        order
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity_map::EntityMap;
+    use serde::{Deserialize, Serialize};
+
+    use crate::tests::SerializeMe;
+
+    #[derive(Component, Serialize, Deserialize)]
+    struct Equipment;
+
+    #[derive(Component, Serialize, Deserialize)]
+    struct Inventory;
+
+    #[test]
+    fn test_dependencies_load_first() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Equipment>()
+            .register::<Inventory>()
+            .add_dependency::<Equipment, Inventory>();
+        let names: Vec<&str> = registry.load_order().iter().map(|reg| reg.name()).collect();
+        assert_eq!(names, vec!["Inventory", "Equipment"]);
+
+        let mut world = World::default();
+        world.spawn((Equipment, Inventory, SerializeMe));
+        let mut doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        let mut loaded = World::default();
+        registry
+            .deserialize(&mut loaded, &mut EntityMap::new(), &mut doc, SerializeMe)
+            .unwrap();
+        let mut query = loaded.query::<(&Equipment, &Inventory)>();
+        assert_eq!(query.iter(&loaded).count(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "depend on each other")]
+    fn test_cycles_are_rejected() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Equipment>()
+            .register::<Inventory>()
+            .add_dependency::<Equipment, Inventory>()
+            .add_dependency::<Inventory, Equipment>();
+    }
+}
@@ -0,0 +1,119 @@
+//! Reports [`PersistenceMetrics`] through `bevy_diagnostic`, so that they show up next to
+//! FPS in diagnostic overlays and the `LogDiagnosticsPlugin`.
+
+use bevy_app::prelude::*;
+use bevy_diagnostic::{Diagnostic, DiagnosticId, Diagnostics, RegisterDiagnostic};
+use bevy_ecs::prelude::*;
+
+use crate::metrics::PersistenceMetrics;
+
+/// Registers the persistence diagnostics and a [`PersistenceMetrics`] resource, and records
+/// a measurement after every save or load made through the registry's byte API.
+#[derive(Default)]
+pub struct PersistenceDiagnosticsPlugin;
+
+impl Plugin for PersistenceDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PersistenceMetrics>()
+            .register_diagnostic(
+                Diagnostic::new(Self::SAVE_DURATION, "save_duration", 20).with_suffix("ms"),
+            )
+            .register_diagnostic(Diagnostic::new(Self::SAVE_SIZE, "save_size", 20).with_suffix("B"))
+            .register_diagnostic(
+                Diagnostic::new(Self::LOAD_DURATION, "load_duration", 20).with_suffix("ms"),
+            )
+            .register_diagnostic(Diagnostic::new(Self::ENTITIES_SAVED, "entities_saved", 20))
+            .register_diagnostic(Diagnostic::new(
+                Self::ENTITIES_LOADED,
+                "entities_loaded",
+                20,
+            ))
+            .register_diagnostic(
+                Diagnostic::new(Self::AUTOSAVE_COUNT, "autosave_count", 1)
+                    .with_smoothing_factor(0.0),
+            )
+            .add_systems(Last, Self::diagnostic_system);
+    }
+}
+
+impl PersistenceDiagnosticsPlugin {
+    pub const SAVE_DURATION: DiagnosticId =
+        DiagnosticId::from_u128(163528453906542093627416233418309417217);
+    pub const SAVE_SIZE: DiagnosticId =
+        DiagnosticId::from_u128(263981127419580326474318842617302265870);
+    pub const LOAD_DURATION: DiagnosticId =
+        DiagnosticId::from_u128(112087341532650975264125734839127390091);
+    pub const ENTITIES_SAVED: DiagnosticId =
+        DiagnosticId::from_u128(301420975528147601872365291039845667412);
+    pub const ENTITIES_LOADED: DiagnosticId =
+        DiagnosticId::from_u128(49208661973920547816237418720394652239);
+    pub const AUTOSAVE_COUNT: DiagnosticId =
+        DiagnosticId::from_u128(228746159301874520931287364510298743165);
+
+    pub fn diagnostic_system(
+        mut diagnostics: Diagnostics,
+        metrics: Res<PersistenceMetrics>,
+        mut last: Local<PersistenceMetrics>,
+    ) {
+        if metrics.saves != last.saves {
+            diagnostics.add_measurement(Self::SAVE_DURATION, || {
+                metrics.last_save_duration.as_secs_f64() * 1000.0
+            });
+            diagnostics.add_measurement(Self::SAVE_SIZE, || metrics.last_save_size as f64);
+            diagnostics.add_measurement(Self::ENTITIES_SAVED, || metrics.entities_saved as f64);
+        }
+        if metrics.loads != last.loads {
+            diagnostics.add_measurement(Self::LOAD_DURATION, || {
+                metrics.last_load_duration.as_secs_f64() * 1000.0
+            });
+            diagnostics.add_measurement(Self::ENTITIES_LOADED, || metrics.entities_loaded as f64);
+        }
+        diagnostics.add_measurement(Self::AUTOSAVE_COUNT, || metrics.autosaves as f64);
+        *last = metrics.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_diagnostic::DiagnosticsStore;
+
+    use crate::tests::{Component1, SerializeMe};
+    use crate::{SaveConfig, SaveRegistry};
+
+    #[test]
+    fn test_save_is_measured() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>();
+        let mut app = App::new();
+        app.add_plugins(PersistenceDiagnosticsPlugin);
+        app.world.spawn((Component1, SerializeMe));
+
+        let bytes = registry
+            .save_bytes::<SerializeMe>(&mut app.world, &SaveConfig::new())
+            .unwrap();
+        app.world
+            .resource_mut::<PersistenceMetrics>()
+            .record_autosave();
+        app.update();
+        let store = app.world.resource::<DiagnosticsStore>();
+        let value = |id| {
+            store
+                .get_measurement(id)
+                .map(|measurement| measurement.value)
+        };
+        assert_eq!(
+            value(PersistenceDiagnosticsPlugin::SAVE_SIZE),
+            Some(bytes.len() as f64)
+        );
+        assert_eq!(
+            value(PersistenceDiagnosticsPlugin::ENTITIES_SAVED),
+            Some(1.0)
+        );
+        assert_eq!(
+            value(PersistenceDiagnosticsPlugin::AUTOSAVE_COUNT),
+            Some(1.0)
+        );
+        assert_eq!(value(PersistenceDiagnosticsPlugin::LOAD_DURATION), None);
+    }
+}
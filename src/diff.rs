@@ -0,0 +1,161 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use bevy_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::delta::document_entities;
+use crate::SaveDocument;
+
+/// A component that differs between two saves. `before` is `None` for a component that was
+/// added, `after` for one that was removed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ComponentChange {
+    pub entity: Entity,
+    /// The section name of the component.
+    pub component: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+/// A report of how two saves differ, for people rather than for patching: unlike
+/// [`SaveDelta`](crate::SaveDelta) it keeps the old value of every change. Its `Display`
+/// impl prints one line per difference.
+///
+/// Entities are matched by the ids they were saved with, so comparing a save with a save
+/// of the world it was loaded into only lines up if the load kept the saved ids, as
+/// [`testing::world_with_saved_ids`](crate::testing::world_with_saved_ids) does.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SaveDiff {
+    pub added_entities: Vec<Entity>,
+    pub removed_entities: Vec<Entity>,
+    /// Every component that differs, including those of added and removed entities, in
+    /// entity and then section order.
+    pub changes: Vec<ComponentChange>,
+}
+
+impl SaveDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_entities.is_empty()
+            && self.removed_entities.is_empty()
+            && self.changes.is_empty()
+    }
+}
+
+fn by_entity(
+    sections: BTreeMap<String, BTreeMap<Entity, &Value>>,
+) -> BTreeMap<Entity, BTreeMap<String, &Value>> {
+    let mut entities: BTreeMap<Entity, BTreeMap<String, &Value>> = BTreeMap::new();
+    for (name, section) in sections {
+        for (entity, value) in section {
+            entities
+                .entry(entity)
+                .or_default()
+                .insert(name.clone(), value);
+        }
+    }
+    entities
+}
+
+/// Compares the save documents `a` and `b`, ignoring their manifests.
+pub fn diff_saves(a: &SaveDocument, b: &SaveDocument) -> Result<SaveDiff, serde_json::Error> {
+    let before = by_entity(document_entities(a)?);
+    let after = by_entity(document_entities(b)?);
+    let empty = BTreeMap::new();
+    let mut diff = SaveDiff {
+        added_entities: after
+            .keys()
+            .filter(|e| !before.contains_key(*e))
+            .copied()
+            .collect(),
+        removed_entities: before
+            .keys()
+            .filter(|e| !after.contains_key(*e))
+            .copied()
+            .collect(),
+        changes: Vec::new(),
+    };
+    let entities: BTreeSet<&Entity> = before.keys().chain(after.keys()).collect();
+    for entity in entities {
+        let before = before.get(entity).unwrap_or(&empty);
+        let after = after.get(entity).unwrap_or(&empty);
+        let names: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+        for name in names {
+            let (old, new) = (before.get(name), after.get(name));
+            if old != new {
+                diff.changes.push(ComponentChange {
+                    entity: *entity,
+                    component: name.clone(),
+                    before: old.map(|value| (*value).clone()),
+                    after: new.map(|value| (*value).clone()),
+                });
+            }
+        }
+    }
+    Ok(diff)
+}
+
+impl fmt::Display for SaveDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entity in &self.added_entities {
+            writeln!(f, "+ {entity:?}")?;
+        }
+        for entity in &self.removed_entities {
+            writeln!(f, "- {entity:?}")?;
+        }
+        for change in &self.changes {
+            let (entity, name) = (change.entity, &change.component);
+            match (&change.before, &change.after) {
+                (Some(before), Some(after)) => {
+                    writeln!(f, "~ {entity:?} {name}: {before} -> {after}")?
+                }
+                (None, Some(after)) => writeln!(f, "+ {entity:?} {name}: {after}")?,
+                (Some(before), None) => writeln!(f, "- {entity:?} {name}: {before}")?,
+                (None, None) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::registry::SaveRegistry;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_diff_report() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Component2>();
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        let entity2 = world
+            .spawn((Component2 { target: entity1 }, SerializeMe))
+            .id();
+        let before = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        assert!(diff_saves(&before, &before).unwrap().is_empty());
+
+        world
+            .entity_mut(entity2)
+            .insert(Component2 { target: entity2 });
+        world.despawn(entity1);
+        let entity3 = world.spawn((Component1, SerializeMe)).id();
+        let after = registry.serialize::<SerializeMe>(&mut world).unwrap();
+
+        let diff = diff_saves(&before, &after).unwrap();
+        assert_eq!(diff.added_entities, vec![entity3]);
+        assert_eq!(diff.removed_entities, vec![entity1]);
+        assert_eq!(diff.changes.len(), 3);
+        assert_eq!(diff.changes[1].before, Some(json!({ "target": entity1 })));
+        let report = diff.to_string();
+        assert!(report.contains(&format!(
+            "~ {entity2:?} Component2: {} -> {}",
+            json!({ "target": entity1 }),
+            json!({ "target": entity2 })
+        )));
+    }
+}
@@ -0,0 +1,288 @@
+//! Components defined at runtime, e.g. by a scripting layer that keeps its components in a
+//! storage of its own keyed by name rather than as Rust types. A
+//! [`DynamicComponentStore`] registered with the [`SaveRegistry`] saves each of its
+//! components as a section of the same document as the typed ones, and loads it back.
+
+use bevy_ecs::prelude::*;
+use bevy_utils::tracing::info_span;
+use bevy_utils::Instant;
+use serde_json::Value;
+
+use crate::delta::section_entries;
+use crate::entity_map::{get_or_insert, EntityMap};
+use crate::load::LoadReport;
+use crate::registry::SaveRegistry;
+use crate::stats::SaveStats;
+use crate::SaveDocument;
+
+/// A storage of runtime-defined components, each saved as a section named after it.
+/// Typically a thin adapter over a resource holding the script data.
+///
+/// Components registered by type take precedence over a dynamic one of the same name. In
+/// [`LoadMode::Sync`](crate::LoadMode::Sync), dynamic components of entities the save
+/// doesn't have are left to the store.
+pub trait DynamicComponentStore: Send + Sync + 'static {
+    /// The names of the components the store currently defines. Loading restores only
+    /// sections of these, so a mod's scripts must define theirs before its saves load;
+    /// the sections of the others are left in the document.
+    fn component_names(&self, world: &World) -> Vec<String>;
+
+    /// The `name` components of those of `entities` that have one.
+    fn save(
+        &self,
+        world: &World,
+        name: &str,
+        entities: &[Entity],
+    ) -> Result<Vec<(Entity, Value)>, serde_json::Error>;
+
+    /// Restores a saved `name` component onto `entity`. Entity references in `value` are
+    /// those of the save; `entity_map` maps them to the loaded entities, and holds every
+    /// entity of the document by the time this is called.
+    fn load(
+        &self,
+        world: &mut World,
+        name: &str,
+        entity: Entity,
+        value: Value,
+        entity_map: &EntityMap,
+    ) -> Result<(), serde_json::Error>;
+}
+
+impl SaveRegistry {
+    /// Saves and loads the components of `store` alongside the registered ones.
+    pub fn register_dynamic_store(&mut self, store: impl DynamicComponentStore) -> &mut Self {
+        self.dynamic_stores.push(Box::new(store));
+        self
+    }
+
+    /// The sections of the dynamic stores, with the store each belongs to.
+    fn dynamic_sections(&self, world: &World) -> Vec<(&dyn DynamicComponentStore, String)> {
+        self.dynamic_stores
+            .iter()
+            .flat_map(|store| {
+                store
+                    .component_names(world)
+                    .into_iter()
+                    .map(|name| (store.as_ref(), name))
+            })
+            .filter(|(_, name)| self.get(name).is_none())
+            .collect()
+    }
+
+    pub(crate) fn dynamic_section_names(&self, world: &World) -> Vec<String> {
+        self.dynamic_sections(world)
+            .into_iter()
+            .map(|(_, name)| name)
+            .collect()
+    }
+
+    /// The section `name` of a dynamic store for `entities`, or `None` if no store defines
+    /// it or none of them has the component.
+    pub(crate) fn dynamic_section(
+        &self,
+        world: &World,
+        entities: &[Entity],
+        name: &str,
+    ) -> Result<Option<Value>, serde_json::Error> {
+        let Some((store, _)) = self
+            .dynamic_sections(world)
+            .into_iter()
+            .find(|(_, section)| section == name)
+        else {
+            return Ok(None);
+        };
+        dynamic_section_value(store, world, entities, name)
+    }
+
+    /// Adds the dynamic stores' sections for `entities` to `data_map`.
+    pub(crate) fn write_dynamic_sections(
+        &self,
+        world: &World,
+        entities: &[Entity],
+        data_map: &mut SaveDocument,
+        mut stats: Option<&mut SaveStats>,
+    ) -> Result<(), serde_json::Error> {
+        for (store, name) in self.dynamic_sections(world) {
+            let _span = info_span!("section", name = name.as_str()).entered();
+            let start = Instant::now();
+            if let Some(section) = dynamic_section_value(store, world, entities, &name)? {
+                if let Some(stats) = stats.as_deref_mut() {
+                    stats.record(&name, &section, start.elapsed())?;
+                }
+                data_map.insert(name, section);
+            }
+        }
+        Ok(())
+    }
+
+    /// Spawns the entities of the dynamic sections of `doc` passing `filter`.
+    pub(crate) fn spawn_dynamic(
+        &self,
+        world: &mut World,
+        entity_map: &mut EntityMap,
+        doc: &SaveDocument,
+        filter: &impl Fn(&str) -> bool,
+    ) -> Result<Vec<Entity>, serde_json::Error> {
+        let mut spawned = Vec::new();
+        for name in self.dynamic_section_names(world) {
+            let Some(section) = doc.get(&name).filter(|_| filter(&name)) else {
+                continue;
+            };
+            for (entity, _) in section_entries(&name, section)? {
+                spawned.push(get_or_insert(world, entity_map, entity));
+            }
+        }
+        Ok(spawned)
+    }
+
+    /// Restores the dynamic sections of `doc` passing `filter`, removing them from `doc`,
+    /// and returns the restored entities. Counts them by section in `report`, if given.
+    pub(crate) fn insert_dynamic<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        entity_map: &mut EntityMap,
+        doc: &mut SaveDocument,
+        filter: &impl Fn(&str) -> bool,
+        marker: &M,
+        mut report: Option<&mut LoadReport>,
+    ) -> Result<Vec<Entity>, serde_json::Error> {
+        let mut restored = Vec::new();
+        for (store, name) in self.dynamic_sections(world) {
+            if !filter(&name) {
+                continue;
+            }
+            let Some(section) = doc.remove(&name) else {
+                continue;
+            };
+            let _span = info_span!("section", name = name.as_str()).entered();
+            let entries: Vec<(Entity, Value)> = section_entries(&name, &section)?
+                .into_iter()
+                .map(|(entity, value)| (entity, value.clone()))
+                .collect();
+            for (saved, value) in &entries {
+                let entity = get_or_insert(world, entity_map, *saved);
+                store.load(world, &name, entity, value.clone(), entity_map)?;
+                world.entity_mut(entity).insert(marker.clone());
+                restored.push(entity);
+            }
+            if let Some(report) = report.as_mut().filter(|_| !entries.is_empty()) {
+                report.restored.insert(name, entries.len());
+            }
+        }
+        Ok(restored)
+    }
+}
+
+fn dynamic_section_value(
+    store: &dyn DynamicComponentStore,
+    world: &World,
+    entities: &[Entity],
+    name: &str,
+) -> Result<Option<Value>, serde_json::Error> {
+    let mut entries = store.save(world, name, entities)?;
+    if entries.is_empty() {
+        return Ok(None);
+    }
+    entries.sort_by_key(|(entity, _)| *entity);
+    serde_json::to_value(entries).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    use crate::load::LoadMode;
+    use crate::tests::{Component1, SerializeMe};
+
+    /// A scripting layer's component storage: component name to the values by entity.
+    #[derive(Resource, Default)]
+    struct ScriptComponents(BTreeMap<String, BTreeMap<Entity, Value>>);
+
+    struct ScriptStore;
+
+    impl DynamicComponentStore for ScriptStore {
+        fn component_names(&self, world: &World) -> Vec<String> {
+            world
+                .get_resource::<ScriptComponents>()
+                .map_or(Vec::new(), |scripts| scripts.0.keys().cloned().collect())
+        }
+
+        fn save(
+            &self,
+            world: &World,
+            name: &str,
+            entities: &[Entity],
+        ) -> Result<Vec<(Entity, Value)>, serde_json::Error> {
+            let values = &world.resource::<ScriptComponents>().0[name];
+            Ok(entities
+                .iter()
+                .filter_map(|entity| Some((*entity, values.get(entity)?.clone())))
+                .collect())
+        }
+
+        fn load(
+            &self,
+            world: &mut World,
+            name: &str,
+            entity: Entity,
+            value: Value,
+            _entity_map: &EntityMap,
+        ) -> Result<(), serde_json::Error> {
+            let mut scripts = world.resource_mut::<ScriptComponents>();
+            scripts
+                .0
+                .entry(name.to_string())
+                .or_default()
+                .insert(entity, value);
+            Ok(())
+        }
+    }
+
+    fn define(world: &mut World, name: &str) {
+        world
+            .resource_mut::<ScriptComponents>()
+            .0
+            .insert(name.to_string(), BTreeMap::new());
+    }
+
+    #[test]
+    fn test_script_components_ride_along() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .register_dynamic_store(ScriptStore);
+        let mut world = World::default();
+        world.init_resource::<ScriptComponents>();
+        define(&mut world, "Mana");
+        define(&mut world, "Unused");
+        let wizard = world.spawn((Component1, SerializeMe)).id();
+        let familiar = world.spawn(SerializeMe).id();
+        let unsaved = world.spawn_empty().id();
+        let mana = &mut world.resource_mut::<ScriptComponents>().0;
+        let mana = mana.get_mut("Mana").unwrap();
+        mana.insert(wizard, serde_json::json!({"current": 12, "max": 40}));
+        mana.insert(familiar, serde_json::json!({"current": 3, "max": 5}));
+        mana.insert(unsaved, serde_json::json!({"current": 0, "max": 0}));
+
+        let mut doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        assert_eq!(doc["Mana"].as_array().unwrap().len(), 2);
+        assert!(!doc.contains_key("Unused"));
+
+        let mut loaded = World::default();
+        loaded.init_resource::<ScriptComponents>();
+        define(&mut loaded, "Mana");
+        let report = registry
+            .load(&mut loaded, &mut doc, LoadMode::Merge, SerializeMe)
+            .unwrap();
+        assert_eq!(report.restored["Mana"], 2);
+        let new_wizard = report.entity_map[&wizard];
+        let new_familiar = report.entity_map[&familiar];
+        assert!(loaded.get::<Component1>(new_wizard).is_some());
+        assert!(loaded.get::<SerializeMe>(new_familiar).is_some());
+        let mana = &loaded.resource::<ScriptComponents>().0["Mana"];
+        assert_eq!(mana[&new_wizard]["max"], 40);
+        assert_eq!(mana[&new_familiar]["current"], 3);
+        assert_eq!(mana.len(), 2);
+    }
+}
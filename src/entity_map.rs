@@ -0,0 +1,269 @@
+//! The entity-mapping machinery behind loading, usable on its own to move entities
+//! between worlds.
+
+use bevy_ecs::entity::{EntityMapper, MapEntities};
+use bevy_ecs::prelude::*;
+use bevy_utils::hashbrown::HashMap;
+
+use crate::exempt::SaveExempt;
+use crate::registry::SaveRegistry;
+
+/// Some entities may exist in the World prior to deserialization, however we assume
+/// these are mutually exclusive from the entities we are restoring. As such, we
+/// don't need to worry about them, as the table below shows (unmapped entities
+/// are those that are pre-existing and exclusive from those we are restoring):
+///  
+/// Entity exists in unmapped | Entity is in entity_map | Result
+///              0            |             0           | create new entity; add to map
+///              0            |             1           | reuse entity in map
+///              1            |             0           | create new entity; add to map
+///              1            |             1           | reuse entity in entity map
+pub fn get_or_insert(world: &mut World, entity_map: &mut EntityMap, entity: Entity) -> Entity {
+    match entity_map.get(&entity) {
+        Some(new_entity) => *new_entity,
+        None => {
+            let new_entity = world.spawn_empty().id();
+            entity_map.insert(entity, new_entity);
+            new_entity
+        }
+    }
+}
+
+/// Maps saved entities to the entities they were restored into, and back. Every saved
+/// entity maps to one restored entity and vice versa: inserting a pair replaces any pair
+/// sharing either side.
+///
+/// As a resource, it's the long-lived map [`SaveRegistry::load_additive`] loads through.
+#[derive(Resource, Clone, Debug, Default, PartialEq, Eq)]
+pub struct EntityMap {
+    forward: HashMap<Entity, Entity>,
+    inverse: HashMap<Entity, Entity>,
+}
+
+impl EntityMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maps the saved entity `saved` to `loaded`, returning the entity `saved` was mapped
+    /// to before.
+    pub fn insert(&mut self, saved: Entity, loaded: Entity) -> Option<Entity> {
+        if let Some(previous_saved) = self.inverse.insert(loaded, saved) {
+            if previous_saved != saved {
+                self.forward.remove(&previous_saved);
+            }
+        }
+        let previous = self.forward.insert(saved, loaded);
+        if let Some(previous) = previous.filter(|previous| *previous != loaded) {
+            self.inverse.remove(&previous);
+        }
+        previous
+    }
+
+    /// Removes the mapping of the saved entity `saved`, returning the entity it was mapped
+    /// to.
+    pub fn remove(&mut self, saved: &Entity) -> Option<Entity> {
+        let loaded = self.forward.remove(saved)?;
+        self.inverse.remove(&loaded);
+        Some(loaded)
+    }
+
+    /// The entity the saved entity `saved` was restored into.
+    pub fn get(&self, saved: &Entity) -> Option<&Entity> {
+        self.forward.get(saved)
+    }
+
+    /// The entity the saved entity `saved` was restored into, e.g. to fix up references
+    /// held outside of components after a load.
+    pub fn translate(&self, saved: Entity) -> Option<Entity> {
+        self.forward.get(&saved).copied()
+    }
+
+    /// The saved entity that `loaded` was restored from.
+    pub fn inverse(&self, loaded: Entity) -> Option<Entity> {
+        self.inverse.get(&loaded).copied()
+    }
+
+    pub fn contains_key(&self, saved: &Entity) -> bool {
+        self.forward.contains_key(saved)
+    }
+
+    pub fn len(&self) -> usize {
+        self.forward.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.forward.is_empty()
+    }
+
+    /// The pairs of saved and restored entities, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Entity, &Entity)> {
+        self.forward.iter()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Entity> {
+        self.forward.keys()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Entity> {
+        self.forward.values()
+    }
+
+    /// Keeps only the pairs of saved and restored entities for which `keep` returns true.
+    pub fn retain(&mut self, mut keep: impl FnMut(&Entity, &Entity) -> bool) {
+        let inverse = &mut self.inverse;
+        self.forward.retain(|saved, loaded| {
+            let kept = keep(saved, loaded);
+            if !kept {
+                inverse.remove(loaded);
+            }
+            kept
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.forward.clear();
+        self.inverse.clear();
+    }
+}
+
+impl std::ops::Index<&Entity> for EntityMap {
+    type Output = Entity;
+
+    fn index(&self, saved: &Entity) -> &Entity {
+        &self.forward[saved]
+    }
+}
+
+impl FromIterator<(Entity, Entity)> for EntityMap {
+    fn from_iter<I: IntoIterator<Item = (Entity, Entity)>>(iter: I) -> Self {
+        let mut entity_map = EntityMap::new();
+        entity_map.extend(iter);
+        entity_map
+    }
+}
+
+impl Extend<(Entity, Entity)> for EntityMap {
+    fn extend<I: IntoIterator<Item = (Entity, Entity)>>(&mut self, iter: I) {
+        for (saved, loaded) in iter {
+            self.insert(saved, loaded);
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a EntityMap {
+    type Item = (&'a Entity, &'a Entity);
+    type IntoIter = bevy_utils::hashbrown::hash_map::Iter<'a, Entity, Entity>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.forward.iter()
+    }
+}
+
+/// Rewrites the `Entity` fields of the `C` components on `entities` through `entity_map`.
+/// References to entities outside the map are pointed at reserved ids that will never be
+/// alive in `world`, rather than at whatever happens to live there.
+pub(crate) fn map_component_entities<C: Component + MapEntities>(
+    world: &mut World,
+    entities: &[Entity],
+    entity_map: &EntityMap,
+) -> Vec<Entity> {
+    // bevy's mapper wants its own map type, and records the reserved ids in it; those must
+    // not leak into the caller's map, where get_or_insert would later hand them out
+    let mut mapper_map: bevy_utils::HashMap<Entity, Entity> =
+        entity_map.iter().map(|(old, new)| (*old, *new)).collect();
+    EntityMapper::world_scope(&mut mapper_map, world, |world, mapper| {
+        for entity in entities {
+            if let Some(mut comp) = world.get_mut::<C>(*entity) {
+                comp.map_entities(mapper);
+            }
+        }
+    });
+    // the reserved ids are those of references to entities missing from the map
+    let mut dangling: Vec<Entity> = mapper_map
+        .into_keys()
+        .filter(|old| !entity_map.contains_key(old))
+        .collect();
+    dangling.sort();
+    dangling
+}
+
+/// Copies the registered components of the entities marked with `M` from `src` into new
+/// entities of `dst`, remapping entity references between the copies. Returns the map from
+/// `src` entities to their copies.
+pub fn copy_entities<M: Component + Clone>(
+    src: &mut World,
+    dst: &mut World,
+    registry: &SaveRegistry,
+    marker: M,
+) -> Result<EntityMap, serde_json::Error> {
+    let mut entity_map = EntityMap::new();
+    let marked: Vec<Entity> = src
+        .query_filtered::<Entity, (With<M>, Without<SaveExempt>)>()
+        .iter(src)
+        .collect();
+    for entity in marked {
+        let new_entity = get_or_insert(dst, &mut entity_map, entity);
+        dst.entity_mut(new_entity).insert(marker.clone());
+    }
+    let mut doc = registry.serialize::<M>(src)?;
+    registry.deserialize(dst, &mut entity_map, &mut doc, marker)?;
+    Ok(entity_map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_copy_entities_remaps_references() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .register_mapped::<Component2>();
+
+        let mut dst = World::default();
+        dst.spawn_batch((0..5).map(|_| Component1));
+
+        let mut src = World::default();
+        let outside = src.spawn(Component1).id();
+        let entity1 = src.spawn((Component1, SerializeMe)).id();
+        let entity2 = src
+            .spawn((Component2 { target: entity1 }, SerializeMe))
+            .id();
+        let entity3 = src
+            .spawn((Component2 { target: outside }, SerializeMe))
+            .id();
+        src.spawn(SerializeMe);
+
+        let entity_map = copy_entities(&mut src, &mut dst, &registry, SerializeMe).unwrap();
+        assert_eq!(entity_map.len(), 4);
+        assert_eq!(
+            dst.get::<Component2>(entity_map[&entity2]).unwrap().target,
+            entity_map[&entity1]
+        );
+        let dangling = dst.get::<Component2>(entity_map[&entity3]).unwrap().target;
+        assert!(dst.get_entity(dangling).is_none());
+        assert!(!entity_map.contains_key(&outside));
+    }
+
+    #[test]
+    fn test_entity_map_inverse() {
+        let mut world = World::default();
+        let [saved1, saved2, loaded1, loaded2] = [(); 4].map(|_| world.spawn_empty().id());
+        let mut entity_map: EntityMap = [(saved1, loaded1)].into_iter().collect();
+        assert_eq!(entity_map.translate(saved1), Some(loaded1));
+        assert_eq!(entity_map.inverse(loaded1), Some(saved1));
+
+        assert_eq!(entity_map.insert(saved1, loaded2), Some(loaded1));
+        assert_eq!(entity_map.inverse(loaded1), None);
+        entity_map.insert(saved2, loaded2);
+        assert_eq!(entity_map.translate(saved1), None);
+        assert_eq!(entity_map.inverse(loaded2), Some(saved2));
+        assert_eq!(entity_map.len(), 1);
+
+        entity_map.retain(|saved, _| *saved != saved2);
+        assert!(entity_map.is_empty() && entity_map.inverse(loaded2).is_none());
+    }
+}
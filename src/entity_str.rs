@@ -0,0 +1,56 @@
+//! Serde helpers for writing entity references inside components as decimal strings,
+//! to go with [`EntityEncoding::String`](crate::EntityEncoding::String):
+//!
+//! ```ignore
+//! #[derive(Component, Serialize, Deserialize)]
+//! struct Target {
+//!     #[serde(with = "bevy_serde_macros::entity_str")]
+//!     entity: Entity,
+//! }
+//! ```
+//!
+//! Both the string and the plain `u64` form are accepted when reading.
+
+use bevy_ecs::prelude::*;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serializer};
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Bits {
+    Number(u64),
+    String(String),
+}
+
+pub fn serialize<S: Serializer>(entity: &Entity, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&entity.to_bits())
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Entity, D::Error> {
+    let bits = match Bits::deserialize(deserializer)? {
+        Bits::Number(bits) => bits,
+        Bits::String(bits) => bits
+            .parse()
+            .map_err(|_| D::Error::custom(format!("{bits} is not an entity id")))?,
+    };
+    Ok(Entity::from_bits(bits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Target(#[serde(with = "super")] Entity);
+
+    #[test]
+    fn test_reads_both_forms() {
+        let entity = Entity::from_bits((7 << 32) | 3);
+        let json = serde_json::to_value(Target(entity)).unwrap();
+        assert_eq!(json, serde_json::json!(entity.to_bits().to_string()));
+        assert_eq!(serde_json::from_value::<Target>(json).unwrap().0, entity);
+        let bits = serde_json::json!(entity.to_bits());
+        assert_eq!(serde_json::from_value::<Target>(bits).unwrap().0, entity);
+    }
+}
@@ -0,0 +1,121 @@
+//! What loading a save will cost, worked out from the sizes of its sections without
+//! deserializing any component, so that low-memory platforms can warn or refuse first.
+
+use std::collections::BTreeMap;
+
+use serde::de::IgnoredAny;
+use serde_json::value::RawValue;
+
+use crate::borrowed::raw_sections;
+use crate::layout::{is_reserved, parse_entity_key};
+use crate::mods::{mod_section_name, MODS_KEY};
+
+/// Loading parses a save into a `serde_json::Value` tree before building components from
+/// it. Small JSON tokens such as `1,` grow into 32-byte nodes, so the tree is taken to be
+/// this many times the size of the text it was parsed from.
+const TREE_BYTES_PER_JSON_BYTE: usize = 4;
+/// The memory an entity takes in the world beyond its components.
+const BYTES_PER_ENTITY: usize = 64;
+
+/// The size of one component section of a save.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SectionEstimate {
+    pub entries: usize,
+    /// The length of the section's JSON text.
+    pub bytes: usize,
+}
+
+/// The result of [`estimate_load_cost`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LoadEstimate {
+    /// The number of entities the save holds. For saves in the component-major layout
+    /// this is the length of the longest section, a lower bound that is exact whenever one
+    /// component is on every entity.
+    pub entities: usize,
+    /// The component sections by name, in the component-major layout only.
+    pub sections: BTreeMap<String, SectionEstimate>,
+    /// A rough prediction of the most memory the load will take at once, in bytes: the
+    /// save itself, the parsed document and the spawned entities.
+    pub peak_memory: usize,
+}
+
+impl LoadEstimate {
+    pub fn section_bytes(&self) -> usize {
+        self.sections.values().map(|section| section.bytes).sum()
+    }
+}
+
+/// Estimates the cost of loading the uncompressed JSON save `save_data`. Each section is
+/// only scanned, never built in memory.
+pub fn estimate_load_cost(save_data: &[u8]) -> Result<LoadEstimate, serde_json::Error> {
+    let mut estimate = LoadEstimate::default();
+    let mut entity_records = 0;
+    for (key, raw) in raw_sections(save_data)? {
+        if key == MODS_KEY {
+            let mods: BTreeMap<String, BTreeMap<String, &RawValue>> =
+                serde_json::from_str(raw.get())?;
+            for (mod_name, sections) in mods {
+                for (name, raw) in sections {
+                    let name = mod_section_name(&mod_name, &name);
+                    estimate.sections.insert(name, section_estimate(raw)?);
+                }
+            }
+        } else if parse_entity_key(&key).is_some() {
+            entity_records += 1;
+        } else if !is_reserved(&key) {
+            estimate
+                .sections
+                .insert(key.into_owned(), section_estimate(raw)?);
+        }
+    }
+    let longest = estimate.sections.values().map(|section| section.entries);
+    estimate.entities = longest.max().unwrap_or_default().max(entity_records);
+    estimate.peak_memory = save_data.len()
+        + save_data.len() * TREE_BYTES_PER_JSON_BYTE
+        + estimate.entities * BYTES_PER_ENTITY;
+    Ok(estimate)
+}
+
+fn section_estimate(raw: &RawValue) -> Result<SectionEstimate, serde_json::Error> {
+    Ok(SectionEstimate {
+        entries: serde_json::from_str::<Vec<IgnoredAny>>(raw.get())?.len(),
+        bytes: raw.get().len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ecs::prelude::*;
+
+    use crate::config::SaveConfig;
+    use crate::layout::SaveLayout;
+    use crate::registry::SaveRegistry;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_estimate_from_section_sizes() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Component2>();
+        let mut world = World::default();
+        for _ in 0..3 {
+            let entity = world.spawn((Component1, SerializeMe)).id();
+            world.spawn((Component2 { target: entity }, SerializeMe));
+        }
+        let bytes = registry
+            .save_bytes::<SerializeMe>(&mut world, &SaveConfig::new())
+            .unwrap();
+
+        let estimate = estimate_load_cost(&bytes).unwrap();
+        assert_eq!(estimate.entities, 3);
+        assert_eq!(estimate.sections["Component2"].entries, 3);
+        assert!(estimate.section_bytes() < bytes.len());
+        assert!(estimate.peak_memory > bytes.len());
+
+        let config = SaveConfig::new().with_layout(SaveLayout::ByEntity);
+        let bytes = registry
+            .save_bytes::<SerializeMe>(&mut world, &config)
+            .unwrap();
+        assert_eq!(estimate_load_cost(&bytes).unwrap().entities, 6);
+    }
+}
@@ -0,0 +1,54 @@
+use bevy_ecs::event::Events;
+use bevy_ecs::prelude::*;
+
+/// Sent once per load by [`SaveRegistry::deserialize`](crate::SaveRegistry::deserialize)
+/// and the loads built on it, after every section is in and references are mapped, so that
+/// systems can react to a restore as a whole instead of to each inserted component. Only
+/// sent if the world has `Events<LoadCompleted>`, e.g. through `App::add_event`.
+///
+/// bevy_ecs 0.12 has no component lifecycle hooks or observers, so inserting components
+/// during a load runs no user code; this event is the single notification of it.
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct LoadCompleted {
+    /// Every entity a component was loaded onto, in ascending order.
+    pub entities: Vec<Entity>,
+}
+
+pub(crate) fn send_load_completed(world: &mut World, mut entities: Vec<Entity>) {
+    if let Some(mut events) = world.get_resource_mut::<Events<LoadCompleted>>() {
+        entities.sort();
+        entities.dedup();
+        events.send(LoadCompleted { entities });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load::LoadMode;
+    use crate::registry::SaveRegistry;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_one_event_per_load() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Component2>();
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        world.spawn((Component1, Component2 { target: entity1 }, SerializeMe));
+        let mut doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+
+        let mut loaded = World::default();
+        loaded.init_resource::<Events<LoadCompleted>>();
+        let report = registry
+            .load(&mut loaded, &mut doc, LoadMode::Merge, SerializeMe)
+            .unwrap();
+        let events = loaded.resource::<Events<LoadCompleted>>();
+        let mut reader = events.get_reader();
+        let sent: Vec<&LoadCompleted> = reader.read(events).collect();
+        assert_eq!(sent.len(), 1);
+        let mut expected: Vec<Entity> = report.entity_map.values().copied().collect();
+        expected.sort();
+        assert_eq!(sent[0].entities, expected);
+    }
+}
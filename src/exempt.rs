@@ -0,0 +1,115 @@
+//! Opting single entities and fields out of saves without touching markers.
+
+use std::ops::{Deref, DerefMut};
+
+use bevy_ecs::prelude::*;
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Keeps an entity out of every save, even if it carries the save marker, e.g. a preview
+/// spawned by code from a marked template. Honoured by the macros, the registry and the
+/// [`SaveCollector`](crate::SaveCollector).
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SaveExempt;
+
+/// The query a system serializes the `C` components of the entities marked with `M`
+/// through, see [`SerializeQuery`](crate::SerializeQuery). Leaves out [`SaveExempt`]
+/// entities.
+pub type SaveQuery<'w, 's, C, M> =
+    Query<'w, 's, (Entity, &'static C), (With<M>, Without<SaveExempt>)>;
+
+/// A component field that isn't saved, such as a cache or a handle: it is written as
+/// `null` and comes back as `T::default()` on load. Serde's
+/// `#[serde(skip_serializing, default)]` omits the field altogether where that's an option.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Transient<T>(pub T);
+
+impl<T> Serialize for Transient<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit()
+    }
+}
+
+impl<'de, T: Default> Deserialize<'de> for Transient<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        IgnoredAny::deserialize(deserializer)?;
+        Ok(Transient(T::default()))
+    }
+}
+
+impl<T> Deref for Transient<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Transient<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_hierarchy::BuildWorldChildren;
+
+    use crate::registry::SaveRegistry;
+    use crate::subtree::HIERARCHY_KEY;
+    use crate::tests::{Component1, SerializeMe};
+
+    #[derive(Component, Serialize, Deserialize)]
+    struct Sprite {
+        path: String,
+        handle: Transient<u32>,
+    }
+
+    #[test]
+    fn test_exempt_entities_and_transient_fields() {
+        let mut world = World::default();
+        world.spawn((Component1, SerializeMe));
+        let preview = world.spawn((Component1, SerializeMe, SaveExempt)).id();
+        world.spawn((
+            Sprite {
+                path: "hero.png".to_string(),
+                handle: Transient(42),
+            },
+            SerializeMe,
+        ));
+
+        let ecs = &mut world;
+        let saved = crate::serialize_individually!(ecs, SerializeMe, Component1, Sprite,);
+        assert_eq!(saved["Component1"].as_array().unwrap().len(), 1);
+        assert_eq!(
+            saved["Sprite"][0][1],
+            serde_json::json!({"path": "hero.png", "handle": null})
+        );
+        let grouped = crate::serialize_grouped!(ecs, SerializeMe, Component1,);
+        assert_eq!(grouped.len(), 1);
+
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Sprite>();
+        let doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        assert_eq!(doc["Component1"], saved["Component1"]);
+        let fragment = registry.serialize_entities(&world, &[preview]).unwrap();
+        assert!(fragment.is_empty());
+
+        // an exempt child is left out of the hierarchy, along with its own children
+        let root = world.spawn(Component1).id();
+        let kept = world.spawn(Component1).id();
+        let grandchild = world.spawn(Component1).id();
+        world.entity_mut(root).add_child(kept).add_child(preview);
+        world.entity_mut(preview).add_child(grandchild);
+        let fragment = registry
+            .serialize_entities_with_descendants(&world, &[root])
+            .unwrap();
+        let hierarchy: Vec<(Entity, Option<Entity>)> =
+            serde_json::from_value(fragment[HIERARCHY_KEY].clone()).unwrap();
+        assert_eq!(hierarchy, vec![(root, None), (kept, Some(root))]);
+        assert_eq!(fragment["Component1"].as_array().unwrap().len(), 2);
+        let sprite: Sprite = serde_json::from_value(doc["Sprite"][0][1].clone()).unwrap();
+        assert_eq!(*sprite.handle, 0);
+    }
+}
@@ -0,0 +1,147 @@
+//! The extraction boundary of a save: everything a save needs is copied out of the world in
+//! one synchronous step, so that every section reflects the same tick however long
+//! serializing, encoding and writing take afterwards, on whichever thread they run.
+
+use bevy_ecs::component::Tick;
+use bevy_ecs::prelude::*;
+use bevy_utils::tracing::info_span;
+
+use crate::config::{SaveConfig, SaveError};
+use crate::manifest::MANIFEST_KEY;
+use crate::quantize::quantize_floats;
+use crate::registry::SaveRegistry;
+use crate::snapshot::SnapshotColumn;
+use crate::unknown_variants::write_stashed;
+use crate::SaveDocument;
+
+/// A save copied out of the world by [`SaveRegistry::extract_for_save`], owning its data
+/// and `Send`, to be turned into a document or bytes away from the world.
+///
+/// Components registered with
+/// [`register_cloneable`](SaveRegistry::register_cloneable) are cloned, which is cheaper
+/// than serializing them and leaves that work to [`to_document`](Self::to_document); the
+/// others are serialized during extraction.
+pub struct ExtractedSave {
+    tick: Tick,
+    entities: Vec<Entity>,
+    /// Cloned sections, with the float precision to write them at.
+    columns: Vec<(String, Option<u32>, Box<dyn SnapshotColumn>)>,
+    serialized: SaveDocument,
+}
+
+impl ExtractedSave {
+    /// The world's change tick when the save was extracted.
+    pub fn tick(&self) -> Tick {
+        self.tick
+    }
+
+    /// The entities saved, in ascending order.
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    /// The save document, as [`SaveRegistry::save_bytes`] would have encoded it at the
+    /// time of extraction.
+    pub fn to_document(&self) -> Result<SaveDocument, serde_json::Error> {
+        let mut doc = self.serialized.clone();
+        for (name, float_precision, column) in &self.columns {
+            let mut section = column.to_value()?;
+            if let Some(decimals) = float_precision {
+                quantize_floats(&mut section, *decimals);
+            }
+            doc.insert(name.clone(), section);
+        }
+        Ok(doc)
+    }
+
+    /// Serializes and encodes the save according to `config`.
+    pub fn encode(&self, config: &SaveConfig) -> Result<Vec<u8>, SaveError> {
+        config.encode(&self.to_document()?)
+    }
+}
+
+impl SaveRegistry {
+    /// Copies the entities marked with `M` out of `world` for saving, see
+    /// [`ExtractedSave`]. The manifest carries `config`'s metadata.
+    pub fn extract_for_save<M: Component>(
+        &self,
+        world: &mut World,
+        config: &SaveConfig,
+    ) -> Result<ExtractedSave, serde_json::Error> {
+        let _span = info_span!("extract").entered();
+        let entities = self.entities_to_save::<M>(world, |_, _| true);
+        let mut columns = Vec::new();
+        let mut serialized = SaveDocument::new();
+        for reg in self.iter() {
+            let kept = reg.kept_entities(world, &entities);
+            if let Some(capture) = reg.capture {
+                if let Some(column) = capture(world, &kept) {
+                    columns.push((reg.name().to_string(), reg.float_precision, column));
+                }
+            } else if let Some(mut section) = (reg.extract)(world, &kept)? {
+                if let Some(decimals) = reg.float_precision {
+                    quantize_floats(&mut section, decimals);
+                }
+                serialized.insert(reg.name().to_string(), section);
+            }
+        }
+        write_stashed(world, &entities, &mut serialized);
+        serialized.insert(
+            MANIFEST_KEY.to_string(),
+            serde_json::to_value(self.manifest_for(config))?,
+        );
+        Ok(ExtractedSave {
+            tick: world.read_change_tick(),
+            entities,
+            columns,
+            serialized,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Component1, Component2, Component3, SerializeMe, TestEnum};
+
+    #[test]
+    fn test_extracted_save_is_point_in_time() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register_cloneable::<Component1>()
+            .register_cloneable::<Component2>()
+            .register::<Component3>();
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        let entity2 = world
+            .spawn((Component2 { target: entity1 }, SerializeMe))
+            .id();
+        world.spawn((
+            Component3 {
+                target: entity1,
+                test_enum: TestEnum::CTest,
+            },
+            SerializeMe,
+        ));
+        let config = SaveConfig::new();
+        let expected = config
+            .decode(
+                &registry
+                    .save_bytes::<SerializeMe>(&mut world, &config)
+                    .unwrap(),
+            )
+            .unwrap();
+
+        let extracted = registry
+            .extract_for_save::<SerializeMe>(&mut world, &config)
+            .unwrap();
+        world.despawn(entity1);
+        world.get_mut::<Component2>(entity2).unwrap().target = entity2;
+
+        // serialized on another thread, after the world moved on
+        let doc = std::thread::spawn(move || extracted.to_document().unwrap())
+            .join()
+            .unwrap();
+        assert_eq!(doc, expected);
+    }
+}
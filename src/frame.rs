@@ -0,0 +1,321 @@
+use std::fmt;
+use std::io::{self, Write};
+
+use bevy_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::delta::SaveDelta;
+use crate::entity_map::EntityMap;
+use crate::manifest::{Manifest, MANIFEST_KEY};
+use crate::parse;
+use crate::registry::SaveRegistry;
+use crate::SaveDocument;
+
+/// Frames larger than this are rejected by [`FrameDecoder`] rather than buffered.
+pub const DEFAULT_MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+
+/// The unit of the framed encoding. On the wire each frame is its JSON encoding preceded by
+/// its length as a big-endian `u32`, so a stream can be split back into frames without
+/// parsing it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Frame {
+    Manifest(Manifest),
+    Section {
+        name: String,
+        data: Value,
+    },
+    Delta(SaveDelta),
+    /// Marks the end of a document, letting receivers finish a load without closing the
+    /// connection.
+    End,
+}
+
+#[derive(Debug)]
+pub enum FrameError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    TooLarge { len: usize, max: usize },
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::Io(err) => write!(f, "{err}"),
+            FrameError::Json(err) => write!(f, "{err}"),
+            FrameError::TooLarge { len, max } => {
+                write!(f, "frame of {len} bytes exceeds the limit of {max} bytes")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+impl From<io::Error> for FrameError {
+    fn from(err: io::Error) -> Self {
+        FrameError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for FrameError {
+    fn from(err: serde_json::Error) -> Self {
+        FrameError::Json(err)
+    }
+}
+
+pub fn write_frame<W: Write>(writer: &mut W, frame: &Frame) -> Result<(), FrameError> {
+    let payload = serde_json::to_vec(frame)?;
+    let len = u32::try_from(payload.len()).map_err(|_| FrameError::TooLarge {
+        len: payload.len(),
+        max: u32::MAX as usize,
+    })?;
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(&payload)?;
+    Ok(())
+}
+
+/// Writes a save document as one frame per component section, preceded by its manifest (if
+/// any) and followed by [`Frame::End`].
+pub fn write_document_frames<W: Write>(
+    writer: &mut W,
+    doc: &SaveDocument,
+) -> Result<(), FrameError> {
+    if let Some(manifest) = doc.get(MANIFEST_KEY) {
+        write_frame(
+            writer,
+            &Frame::Manifest(serde_json::from_value(manifest.clone())?),
+        )?;
+    }
+    for (name, data) in doc.iter().filter(|(name, _)| *name != MANIFEST_KEY) {
+        write_frame(
+            writer,
+            &Frame::Section {
+                name: name.clone(),
+                data: data.clone(),
+            },
+        )?;
+    }
+    write_frame(writer, &Frame::End)
+}
+
+/// Splits an incoming byte stream into [`Frame`]s. Bytes can be pushed in arbitrary chunks
+/// as they arrive; complete frames are returned by [`next_frame`](FrameDecoder::next_frame).
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+    /// The start of the unread bytes of `buffer`; those before it belong to frames returned
+    /// already, and are only dropped once there are enough of them to be worth moving the
+    /// rest for.
+    read: usize,
+    max_frame_len: usize,
+}
+
+/// How many bytes of returned frames [`FrameDecoder`] keeps before compacting its buffer.
+const COMPACT_THRESHOLD: usize = 64 * 1024;
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        FrameDecoder {
+            buffer: Vec::new(),
+            read: 0,
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
+        }
+    }
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_frame_len(mut self, max_frame_len: usize) -> Self {
+        self.max_frame_len = max_frame_len;
+        self
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        if self.read == self.buffer.len() {
+            self.buffer.clear();
+            self.read = 0;
+        } else if self.read >= COMPACT_THRESHOLD && self.read * 2 >= self.buffer.len() {
+            self.buffer.drain(..self.read);
+            self.read = 0;
+        }
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Returns the next complete frame, or `None` if more bytes are needed.
+    pub fn next_frame(&mut self) -> Result<Option<Frame>, FrameError> {
+        let unread = &self.buffer[self.read..];
+        let Some(header) = unread.get(..4) else {
+            return Ok(None);
+        };
+        let len = u32::from_be_bytes(header.try_into().expect("4 byte header")) as usize;
+        if len > self.max_frame_len {
+            return Err(FrameError::TooLarge {
+                len,
+                max: self.max_frame_len,
+            });
+        }
+        if unread.len() < 4 + len {
+            return Ok(None);
+        }
+        let frame = parse::from_slice(&unread[4..4 + len])?;
+        self.read += 4 + len;
+        Ok(Some(frame))
+    }
+}
+
+/// Feeds frames into a world as they arrive: sections are deserialized through the
+/// registry and deltas applied, each as soon as its frame is complete. Entity references
+/// in sections are remapped when [`Frame::End`] arrives.
+pub struct FrameLoader<M> {
+    decoder: FrameDecoder,
+    entity_map: EntityMap,
+    /// Sections loaded since the last [`Frame::End`], whose references are still unmapped.
+    unmapped: Vec<(String, Vec<Entity>)>,
+    manifest: Option<Manifest>,
+    finished: bool,
+    marker: M,
+}
+
+impl<M: Component + Clone> FrameLoader<M> {
+    pub fn new(marker: M) -> Self {
+        FrameLoader {
+            decoder: FrameDecoder::new(),
+            entity_map: EntityMap::new(),
+            unmapped: Vec::new(),
+            manifest: None,
+            finished: false,
+            marker,
+        }
+    }
+
+    pub fn with_decoder(mut self, decoder: FrameDecoder) -> Self {
+        self.decoder = decoder;
+        self
+    }
+
+    /// Buffers `bytes` and applies every frame they complete. Returns the number of frames
+    /// applied.
+    pub fn feed(
+        &mut self,
+        world: &mut World,
+        registry: &SaveRegistry,
+        bytes: &[u8],
+    ) -> Result<usize, FrameError> {
+        self.decoder.push(bytes);
+        let mut applied = 0;
+        while let Some(frame) = self.decoder.next_frame()? {
+            match frame {
+                Frame::Manifest(manifest) => self.manifest = Some(manifest),
+                Frame::Section { name, data } => {
+                    let entities = registry.deserialize_section(
+                        world,
+                        &mut self.entity_map,
+                        &name,
+                        data,
+                        self.marker.clone(),
+                    )?;
+                    self.unmapped.push((name, entities));
+                }
+                Frame::Delta(delta) => delta.apply_to_world(
+                    world,
+                    registry,
+                    &mut self.entity_map,
+                    self.marker.clone(),
+                )?,
+                Frame::End => {
+                    for (name, entities) in self.unmapped.drain(..) {
+                        registry.map_section_references(world, &name, &entities, &self.entity_map);
+                    }
+                    self.finished = true;
+                }
+            }
+            applied += 1;
+        }
+        Ok(applied)
+    }
+
+    /// The manifest of the document being received, once its frame has arrived.
+    pub fn manifest(&self) -> Option<&Manifest> {
+        self.manifest.as_ref()
+    }
+
+    /// True once a [`Frame::End`] has been received.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Maps entities of the sending world to the entities spawned for them.
+    pub fn entity_map(&self) -> &EntityMap {
+        &self.entity_map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_frames_load_incrementally() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Component2>();
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        world.spawn((Component2 { target: entity1 }, SerializeMe));
+        let doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+
+        let mut wire = Vec::new();
+        write_document_frames(&mut wire, &doc).unwrap();
+        let delta = SaveDelta {
+            added_entities: vec![Entity::from_raw(40)],
+            ..Default::default()
+        };
+        write_frame(&mut wire, &Frame::Delta(delta)).unwrap();
+
+        let mut restored = World::default();
+        let mut loader = FrameLoader::new(SerializeMe);
+        let mut frames = 0;
+        for chunk in wire.chunks(7) {
+            frames += loader.feed(&mut restored, &registry, chunk).unwrap();
+        }
+        assert_eq!(frames, 5);
+        assert!(loader.is_finished());
+        assert_eq!(loader.manifest(), Some(&registry.manifest()));
+        assert_eq!(loader.entity_map().len(), 3);
+        assert_eq!(
+            restored
+                .query_filtered::<Entity, With<SerializeMe>>()
+                .iter(&restored)
+                .count(),
+            3
+        );
+
+        let mut decoder = FrameDecoder::new().with_max_frame_len(8);
+        decoder.push(&wire);
+        assert!(matches!(
+            decoder.next_frame(),
+            Err(FrameError::TooLarge { max: 8, .. })
+        ));
+
+        // a long stream of small frames only ever buffers a bounded tail of it
+        let mut stream = Vec::new();
+        for _ in 0..20_000 {
+            write_frame(&mut stream, &Frame::End).unwrap();
+        }
+        let mut decoder = FrameDecoder::new();
+        let mut ends = 0;
+        for chunk in stream.chunks(1000) {
+            decoder.push(chunk);
+            while let Some(frame) = decoder.next_frame().unwrap() {
+                assert!(matches!(frame, Frame::End));
+                ends += 1;
+            }
+            assert!(decoder.buffer.len() <= 2 * COMPACT_THRESHOLD + 1000);
+        }
+        assert_eq!(ends, 20_000);
+        assert!(decoder.next_frame().unwrap().is_none());
+    }
+}
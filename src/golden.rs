@@ -0,0 +1,146 @@
+//! Golden-save tests: a save of a known world is compared to a fixture checked into the
+//! repository, so that any change to the save format shows up as a failing test and a
+//! reviewable diff of the fixture.
+//!
+//! Fixtures live in `tests/golden/{name}.json` under the crate being tested, or under
+//! `$GOLDEN_DIR` if it's set. Running the tests with `UPDATE_GOLDEN=1` writes the current
+//! saves as the new fixtures instead of comparing.
+
+use std::path::PathBuf;
+
+use bevy_ecs::prelude::*;
+use serde_json::Value;
+
+use crate::manifest::MANIFEST_KEY;
+use crate::registry::SaveRegistry;
+use crate::testing::describe_differences;
+use crate::SaveDocument;
+
+/// Set to anything but `0` to update fixtures instead of checking them.
+pub const UPDATE_VAR: &str = "UPDATE_GOLDEN";
+
+/// Overrides the directory fixtures are kept in.
+pub const DIR_VAR: &str = "GOLDEN_DIR";
+
+pub fn fixture_path(name: &str) -> PathBuf {
+    let dir = match std::env::var_os(DIR_VAR) {
+        Some(dir) => PathBuf::from(dir),
+        None => std::env::var_os("CARGO_MANIFEST_DIR")
+            .map_or_else(PathBuf::new, PathBuf::from)
+            .join("tests")
+            .join("golden"),
+    };
+    dir.join(format!("{name}.json"))
+}
+
+fn updating() -> bool {
+    std::env::var(UPDATE_VAR).is_ok_and(|update| update != "0")
+}
+
+/// `doc` with the entries of every component section in entity order, so that the
+/// fixture doesn't depend on the order queries visit entities in.
+fn canonical(doc: &SaveDocument) -> SaveDocument {
+    let mut doc = doc.clone();
+    for (name, section) in doc.iter_mut() {
+        if let (false, Value::Array(entries)) = (name == MANIFEST_KEY, section) {
+            entries.sort_by_key(|entry| entry.get(0).and_then(Value::as_u64));
+        }
+    }
+    doc
+}
+
+/// Compares `doc` to the fixture `name`, or writes it as the fixture in update mode.
+///
+/// # Panics
+/// If the fixture is missing or differs from `doc`, naming the sections that differ.
+pub fn check(name: &str, doc: &SaveDocument) {
+    let path = fixture_path(name);
+    let doc = canonical(doc);
+    if updating() {
+        let mut json = serde_json::to_string_pretty(&doc).unwrap();
+        json.push('\n');
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        std::fs::write(&path, json).unwrap();
+        return;
+    }
+    let fixture = match std::fs::read(&path) {
+        Ok(fixture) => fixture,
+        Err(err) => panic!(
+            "can't read golden save {}: {err}; run with {UPDATE_VAR}=1 to create it",
+            path.display()
+        ),
+    };
+    let fixture: SaveDocument = serde_json::from_slice(&fixture).unwrap();
+    let mut message = format!(
+        "save differs from golden save {}; run with {UPDATE_VAR}=1 to accept the change",
+        path.display()
+    );
+    if describe_differences(&mut message, &fixture, &doc, ("golden", "current")) {
+        panic!("{message}");
+    }
+}
+
+/// Saves the entities marked with `M` through `registry`, manifest included, and
+/// [`check`]s the save against the fixture `name`.
+pub fn check_registry<M: Component>(name: &str, world: &mut World, registry: &SaveRegistry) {
+    check(name, &registry.serialize::<M>(world).unwrap());
+}
+
+/// Saves the listed component types of the entities marked with `$marker` with
+/// `serialize_individually!` and [`check`](crate::golden::check)s the save against the fixture
+/// `$name`.
+///
+/// ```ignore
+/// assert_golden!("level_one", &mut world, SerializeMe, Position, Health);
+/// ```
+#[macro_export]
+macro_rules! assert_golden {
+  ($name:expr, $world:expr, $marker:ty, $( $(#[$attr:meta])* $comp_type:ty $(as $section:literal)?),+ $(,)?) => {{
+      let world = $world;
+      let doc: $crate::SaveDocument =
+          $crate::serialize_individually!(world, $marker, $($(#[$attr])* $comp_type $(as $section)?),*,);
+      $crate::golden::check($name, &doc);
+  }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Component1, Component2, Component3, SerializeMe, TestEnum};
+
+    fn build_world() -> World {
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        world.spawn((
+            Component2 { target: entity1 },
+            Component3 {
+                target: entity1,
+                test_enum: TestEnum::BTest(3),
+            },
+            SerializeMe,
+        ));
+        world
+    }
+
+    #[test]
+    fn test_save_format_is_unchanged() {
+        let world = &mut build_world();
+        assert_golden!(
+            "components",
+            world,
+            SerializeMe,
+            Component1,
+            Component2,
+            Component3
+        );
+
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .register_mapped::<Component2>()
+            .register::<Component3>();
+        check_registry::<SerializeMe>("registry", &mut build_world(), &registry);
+    }
+}
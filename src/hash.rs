@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+use std::hash::Hasher;
+
+use bevy_ecs::prelude::*;
+
+use crate::delta::section_entries;
+use crate::manifest::MANIFEST_KEY;
+use crate::registry::SaveRegistry;
+use crate::SaveDocument;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// A 64-bit FNV-1a hasher. Unlike `std`'s `DefaultHasher`, its output is fixed across
+/// Rust versions and platforms, so the hashes it produces may be persisted.
+#[derive(Clone, Debug)]
+pub struct StableHasher(u64);
+
+impl StableHasher {
+    pub fn new() -> Self {
+        StableHasher(FNV_OFFSET_BASIS)
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+macro_rules! write_le {
+    ($($method:ident: $ty:ty),*) => {
+        $(
+            fn $method(&mut self, i: $ty) {
+                self.write_bytes(&i.to_le_bytes());
+            }
+        )*
+    };
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.write_bytes(bytes);
+    }
+
+    // Integers are hashed little-endian so that big-endian targets agree.
+    write_le!(
+        write_u16: u16, write_u32: u32, write_u64: u64, write_u128: u128,
+        write_i16: i16, write_i32: i32, write_i64: i64, write_i128: i128
+    );
+
+    // Pointer-sized integers are widened so that hashes agree between 32 and 64 bit targets.
+    fn write_usize(&mut self, i: usize) {
+        self.write_bytes(&(i as u64).to_le_bytes());
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_bytes(&(i as i64).to_le_bytes());
+    }
+}
+
+/// Hashes the component sections of a save document independently of section order,
+/// entity order within sections, and the manifest. Two documents hash equal exactly when
+/// they hold the same components for the same entities.
+pub fn hash_document(doc: &SaveDocument) -> Result<u64, serde_json::Error> {
+    let mut sections = BTreeMap::new();
+    for (name, section) in doc.iter().filter(|(name, _)| *name != MANIFEST_KEY) {
+        let mut entries = section_entries(name, section)?;
+        entries.sort_by_key(|(entity, _)| *entity);
+        sections.insert(name.as_str(), entries);
+    }
+    // serde_json objects serialize with sorted keys, so this is canonical
+    let canonical = serde_json::to_vec(&sections)?;
+    let mut hasher = StableHasher::new();
+    hasher.write_bytes(&canonical);
+    Ok(hasher.finish())
+}
+
+/// A deterministic hash of the registered components of the entities marked with `M`,
+/// for desync detection in lockstep multiplayer or as a cheap "did anything change since
+/// the last autosave?" check.
+///
+/// Entity ids are part of the hash, so peers only agree if they spawn entities in the same
+/// order. The hash is stable across platforms and builds for as long as the serialized
+/// form of the components is.
+pub fn hash_world<M: Component>(
+    world: &mut World,
+    registry: &SaveRegistry,
+) -> Result<u64, serde_json::Error> {
+    hash_document(&registry.serialize::<M>(world)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_hash_world() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Component2>();
+
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        let entity2 = world.spawn((Component1, SerializeMe)).id();
+        let hash = hash_world::<SerializeMe>(&mut world, &registry).unwrap();
+        assert_eq!(
+            hash,
+            hash_world::<SerializeMe>(&mut world, &registry).unwrap()
+        );
+
+        world.spawn(Component2 { target: entity1 });
+        assert_eq!(
+            hash,
+            hash_world::<SerializeMe>(&mut world, &registry).unwrap()
+        );
+
+        world
+            .entity_mut(entity2)
+            .insert(Component2 { target: entity1 });
+        let changed = hash_world::<SerializeMe>(&mut world, &registry).unwrap();
+        assert_ne!(hash, changed);
+
+        let mut other_registry = SaveRegistry::new();
+        other_registry
+            .register::<Component2>()
+            .register::<Component1>();
+        assert_eq!(
+            changed,
+            hash_world::<SerializeMe>(&mut world, &other_registry).unwrap()
+        );
+    }
+}
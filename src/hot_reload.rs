@@ -0,0 +1,195 @@
+//! Development hot-reload of save files: a designer edits a save on disk, as JSON or as RON
+//! written by `bevy-saves convert --to ron`, and the running world picks up the change.
+//!
+//! Changes are reported by the platform's file watcher, through `notify`, on the file's
+//! directory rather than the file itself, so editors that save by replacing the file are
+//! followed too. Reported changes that leave the contents as they were are ignored.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use bevy_ecs::prelude::*;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::config::{SaveConfig, SaveError};
+use crate::load::{LoadMode, LoadReport};
+use crate::registry::SaveRegistry;
+use crate::SaveDocument;
+
+/// Watches a save file and re-applies it to the world whenever it changes. Files ending in
+/// `.ron` are read as RON, anything else is decoded by the watcher's [`SaveConfig`].
+#[derive(Resource, Debug)]
+pub struct HotReload {
+    path: PathBuf,
+    config: SaveConfig,
+    mode: LoadMode,
+    /// Set by the watcher whenever the file's directory reports a change to it.
+    changed: Arc<AtomicBool>,
+    /// A hash of the contents last loaded, or considered loaded.
+    loaded: Option<u64>,
+    _watcher: Mutex<RecommendedWatcher>,
+}
+
+fn contents_hash(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl HotReload {
+    /// Watches `path`. The file as it is now is considered loaded already; only later
+    /// changes are applied. Fails if the file's directory can't be watched.
+    pub fn new(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let changed = Arc::new(AtomicBool::new(false));
+        let file_name = path.file_name().map(ToOwned::to_owned);
+        let flag = changed.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<_>| {
+            let Ok(notify::Event { kind, paths, .. }) = event else {
+                return;
+            };
+            if !matches!(kind, EventKind::Access(_))
+                && paths
+                    .iter()
+                    .any(|path| path.file_name() == file_name.as_deref())
+            {
+                flag.store(true, Ordering::Release);
+            }
+        })
+        .map_err(io::Error::other)?;
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(io::Error::other)?;
+        Ok(HotReload {
+            loaded: std::fs::read(&path).ok().map(|bytes| contents_hash(&bytes)),
+            path,
+            config: SaveConfig::new(),
+            mode: LoadMode::Sync,
+            changed,
+            _watcher: Mutex::new(watcher),
+        })
+    }
+
+    pub fn with_config(mut self, config: SaveConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Sets how changes are applied, [`LoadMode::Sync`] by default, which updates the
+    /// marked entities in place and suits a save of the running world. Use
+    /// [`LoadMode::Merge`] to spawn the file's entities alongside the world's.
+    pub fn with_mode(mut self, mode: LoadMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn parse(&self, bytes: &[u8]) -> Result<SaveDocument, SaveError> {
+        if self.path.extension().is_some_and(|ext| ext == "ron") {
+            ron::de::from_bytes(bytes)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err).into())
+        } else {
+            self.config.decode(bytes)
+        }
+    }
+
+    /// Reloads the file into `world` if its contents changed since the last poll, returning
+    /// the load's outcome, or `None` if they didn't. Call it from an exclusive system.
+    ///
+    /// A file that fails to load, e.g. because it was saved halfway through an edit, is
+    /// retried only once it changes again.
+    pub fn poll<M: Component + Clone>(
+        &mut self,
+        world: &mut World,
+        registry: &SaveRegistry,
+        marker: M,
+    ) -> Option<Result<LoadReport, SaveError>> {
+        if !self.changed.swap(false, Ordering::Acquire) {
+            return None;
+        }
+        // a file replaced by an editor may be missing for a moment; its creation is
+        // reported as another change
+        let bytes = std::fs::read(&self.path).ok()?;
+        let hash = contents_hash(&bytes);
+        if self.loaded == Some(hash) {
+            return None;
+        }
+        self.loaded = Some(hash);
+        Some(self.parse(&bytes).and_then(|mut doc| {
+            registry.load_document(world, &mut doc, &self.config, self.mode, |_| true, marker)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    use crate::tests::{Component3, SerializeMe, TestEnum};
+
+    /// Polls until the watcher picks up a change, for at most a few seconds.
+    fn poll_until_loaded(
+        watcher: &mut HotReload,
+        world: &mut World,
+        registry: &SaveRegistry,
+    ) -> LoadReport {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            if let Some(result) = watcher.poll(world, registry, SerializeMe) {
+                return result.unwrap();
+            }
+            assert!(Instant::now() < deadline, "the edit was never picked up");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn text(world: &World, entity: Entity) -> &str {
+        match &world.get::<Component3>(entity).unwrap().test_enum {
+            TestEnum::ATest(text) => text,
+            _ => panic!("expected TestEnum::ATest"),
+        }
+    }
+
+    #[test]
+    fn test_hot_reload_applies_edits() {
+        let dir = std::env::temp_dir().join(format!("bevy_serde_hot_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("level.ron");
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component3>();
+        let mut world = World::default();
+        let entity = world.spawn(SerializeMe).id();
+        world.entity_mut(entity).insert(Component3 {
+            target: entity,
+            test_enum: TestEnum::ATest("before".to_string()),
+        });
+        let doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        let ron = ron::ser::to_string(&doc).unwrap();
+        std::fs::write(&path, &ron).unwrap();
+
+        let mut watcher = HotReload::new(&path).unwrap();
+        assert!(watcher.poll(&mut world, &registry, SerializeMe).is_none());
+        // both edits land within the same second, as quick saves from an editor do
+        for edit in ["after", "again"] {
+            std::fs::write(&path, ron.replace("before", edit)).unwrap();
+            poll_until_loaded(&mut watcher, &mut world, &registry);
+            assert_eq!(text(&world, entity), edit);
+        }
+        std::fs::write(&path, ron.replace("before", "again")).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(watcher.poll(&mut world, &registry, SerializeMe).is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
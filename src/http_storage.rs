@@ -0,0 +1,261 @@
+//! Save slots on a plain HTTP endpoint, for cloud saves without a platform SDK.
+
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::{Arc, Mutex};
+
+use bevy_utils::hashbrown::HashMap;
+
+use crate::storage::SaveStorage;
+
+/// Returned, wrapped in an [`io::Error`], when a slot was changed on the server since this
+/// client last read or wrote it, e.g. by the same player on another device. Reading the slot
+/// again picks up the server's version and allows overwriting it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyncConflict {
+    pub slot: String,
+}
+
+impl fmt::Display for SyncConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "slot {} was changed on the server", self.slot)
+    }
+}
+
+impl std::error::Error for SyncConflict {}
+
+/// Slots stored at `{base_url}/{slot}`: read with `GET`, written with `PUT` and removed with
+/// `DELETE`. Listing slots is a `GET` of `{base_url}` answered with a JSON array of slot
+/// names.
+///
+/// Conflicts are detected with ETags: writes and removals send the ETag the slot had when
+/// this client last read or wrote it in `If-Match`, or `If-None-Match: *` for slots it
+/// hasn't seen, and a `412 Precondition Failed` answer fails with a [`SyncConflict`].
+/// Clones share the ETags they have seen, so the copies taken by
+/// [`save_slot_async`](crate::SaveRegistry::save_slot_async) keep them up to date.
+#[derive(Clone)]
+pub struct HttpStorage {
+    base_url: String,
+    headers: Vec<(String, String)>,
+    agent: ureq::Agent,
+    etags: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl HttpStorage {
+    pub fn new(base_url: &str) -> Self {
+        HttpStorage {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            headers: Vec::new(),
+            agent: ureq::Agent::new(),
+            etags: Arc::default(),
+        }
+    }
+
+    /// Adds a header to every request, e.g. `Authorization`.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// The URL of `slot`, with the slot name percent-encoded as a single path segment, so
+    /// that names holding `/`, `?` or `#` can't address another resource. Fails for the
+    /// empty name and for `.` and `..`, which URLs resolve to other paths.
+    pub fn url(&self, slot: &str) -> io::Result<String> {
+        if matches!(slot, "" | "." | "..") {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{slot:?} can't be used as a slot name"),
+            ));
+        }
+        let mut url = format!("{}/", self.base_url);
+        for byte in slot.bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                    url.push(byte as char)
+                }
+                _ => url.push_str(&format!("%{byte:02X}")),
+            }
+        }
+        Ok(url)
+    }
+
+    /// The ETag of `slot` as last seen by this client.
+    pub fn etag(&self, slot: &str) -> Option<String> {
+        self.etags.lock().unwrap().get(slot).cloned()
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        self.headers
+            .iter()
+            .fold(self.agent.request(method, url), |request, (name, value)| {
+                request.set(name, value)
+            })
+    }
+
+    /// `request` made conditional on the slot being as this client last saw it.
+    fn conditional(&self, request: ureq::Request, slot: &str) -> ureq::Request {
+        match self.etag(slot) {
+            Some(etag) => request.set("If-Match", &etag),
+            None => request.set("If-None-Match", "*"),
+        }
+    }
+
+    fn record_etag(&self, slot: &str, response: &ureq::Response) {
+        let mut etags = self.etags.lock().unwrap();
+        match response.header("ETag") {
+            Some(etag) => etags.insert(slot.to_string(), etag.to_string()),
+            None => etags.remove(slot),
+        };
+    }
+
+    fn error(&self, slot: &str, err: ureq::Error) -> io::Error {
+        match err {
+            ureq::Error::Status(412, _) => io::Error::other(SyncConflict {
+                slot: slot.to_string(),
+            }),
+            ureq::Error::Status(status, _) => {
+                let url = self.url(slot).unwrap_or_else(|_| self.base_url.clone());
+                io::Error::other(format!("{url} answered {status}"))
+            }
+            ureq::Error::Transport(transport) => io::Error::other(transport),
+        }
+    }
+}
+
+impl SaveStorage for HttpStorage {
+    fn read(&self, slot: &str) -> io::Result<Option<Vec<u8>>> {
+        let response = match self.request("GET", &self.url(slot)?).call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(404, _)) => {
+                self.etags.lock().unwrap().remove(slot);
+                return Ok(None);
+            }
+            Err(err) => return Err(self.error(slot, err)),
+        };
+        self.record_etag(slot, &response);
+        let mut bytes = Vec::new();
+        response.into_reader().read_to_end(&mut bytes)?;
+        Ok(Some(bytes))
+    }
+
+    fn write(&mut self, slot: &str, bytes: &[u8]) -> io::Result<()> {
+        let request = self.request("PUT", &self.url(slot)?);
+        let response = self
+            .conditional(request, slot)
+            .set("Content-Type", "application/octet-stream")
+            .send_bytes(bytes)
+            .map_err(|err| self.error(slot, err))?;
+        self.record_etag(slot, &response);
+        Ok(())
+    }
+
+    fn remove(&mut self, slot: &str) -> io::Result<()> {
+        let request = self.request("DELETE", &self.url(slot)?);
+        match self.conditional(request, slot).call() {
+            Ok(_) | Err(ureq::Error::Status(404, _)) => {
+                self.etags.lock().unwrap().remove(slot);
+                Ok(())
+            }
+            Err(err) => Err(self.error(slot, err)),
+        }
+    }
+
+    fn slots(&self) -> io::Result<Vec<String>> {
+        let response = self
+            .request("GET", &self.base_url)
+            .call()
+            .map_err(|err| self.error("", err))?;
+        Ok(serde_json::from_reader(response.into_reader())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// Answers one request per entry of `answers`, with its status line and ETag, and returns
+    /// the heads of the requests received.
+    fn serve(
+        answers: Vec<(&'static str, Option<&'static str>)>,
+    ) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/saves", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for (status, etag) in answers {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut head = String::new();
+                let mut len = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        len = value.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                    head.push_str(&line);
+                }
+                let mut body = vec![0; len];
+                reader.read_exact(&mut body).unwrap();
+                requests.push(head);
+                let etag = etag.map_or(String::new(), |etag| format!("ETag: {etag}\r\n"));
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 {status}\r\n{etag}Content-Length: 4\r\nConnection: close\r\n\r\nsave"
+                )
+                .unwrap();
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn test_etag_conflicts() {
+        let (url, server) = serve(vec![
+            ("200 OK", Some("\"1\"")),
+            ("200 OK", Some("\"2\"")),
+            ("412 Precondition Failed", None),
+        ]);
+        let mut storage = HttpStorage::new(&url).with_header("Authorization", "Bearer token");
+        assert_eq!(storage.read("one").unwrap().unwrap(), b"save");
+        assert_eq!(storage.etag("one").as_deref(), Some("\"1\""));
+        storage.write("one", b"new save").unwrap();
+        let err = storage.write("one", b"newer save").unwrap_err();
+        let conflict = err.get_ref().unwrap().downcast_ref::<SyncConflict>();
+        assert_eq!(conflict.unwrap().slot, "one");
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /saves/one"));
+        assert!(requests[0].contains("Authorization: Bearer token"));
+        assert!(requests[1].starts_with("PUT /saves/one"));
+        assert!(requests[1].contains("If-Match: \"1\""));
+        assert!(requests[2].contains("If-Match: \"2\""));
+    }
+
+    #[test]
+    fn test_slot_names_are_one_path_segment() {
+        let storage = HttpStorage::new("http://localhost/saves/");
+        assert_eq!(
+            storage.url("slot_1.json").unwrap(),
+            "http://localhost/saves/slot_1.json"
+        );
+        assert_eq!(
+            storage.url("../admin?x=1#top a").unwrap(),
+            "http://localhost/saves/..%2Fadmin%3Fx%3D1%23top%20a"
+        );
+        assert_eq!(
+            storage.url("café").unwrap(),
+            "http://localhost/saves/caf%C3%A9"
+        );
+        for slot in ["", ".", ".."] {
+            let err = storage.read(slot).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+}
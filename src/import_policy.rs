@@ -0,0 +1,211 @@
+//! Loading saves that can't be trusted, e.g. imported from another player in an online
+//! mode: only whitelisted component types are restored, and their values go through
+//! validators that can clamp or reject them.
+
+use std::fmt;
+
+use bevy_ecs::prelude::*;
+
+use crate::layout::is_reserved;
+use crate::load::{LoadMode, LoadReport};
+use crate::registry::SaveRegistry;
+use crate::SaveDocument;
+
+type SanitizeFn = Box<dyn Fn(&mut World, Entity) -> Option<Result<(), String>> + Send + Sync>;
+
+struct AllowedComponent {
+    type_path: &'static str,
+    sanitize: Option<SanitizeFn>,
+}
+
+/// The component types an untrusted save may restore, see
+/// [`SaveRegistry::load_untrusted`]. Sections of any other type are dropped unread.
+#[derive(Default)]
+pub struct ImportPolicy {
+    allowed: Vec<AllowedComponent>,
+}
+
+impl ImportPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accepts the `C` components of untrusted saves as they are.
+    pub fn allow<C: Component>(mut self) -> Self {
+        self.allowed.push(AllowedComponent {
+            type_path: std::any::type_name::<C>(),
+            sanitize: None,
+        });
+        self
+    }
+
+    /// Accepts the `C` components of untrusted saves that pass `validator`, which may
+    /// also clamp the value in place. Components it rejects are removed again.
+    pub fn allow_validated<C: Component>(
+        mut self,
+        validator: impl Fn(&mut C) -> Result<(), String> + Send + Sync + 'static,
+    ) -> Self {
+        self.allowed.push(AllowedComponent {
+            type_path: std::any::type_name::<C>(),
+            sanitize: Some(Box::new(move |world: &mut World, entity: Entity| {
+                let result = validator(world.get_mut::<C>(entity)?.as_mut());
+                if result.is_err() {
+                    world.entity_mut(entity).remove::<C>();
+                }
+                Some(result)
+            })),
+        });
+        self
+    }
+}
+
+/// A component of an untrusted save that its validator rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RejectedValue {
+    /// The entity the component was restored into and removed from.
+    pub entity: Entity,
+    /// The section name of the component.
+    pub component: String,
+    pub message: String,
+}
+
+impl fmt::Display for RejectedValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "rejected {} on {:?}: {}",
+            self.component, self.entity, self.message
+        )
+    }
+}
+
+/// The outcome of [`SaveRegistry::load_untrusted`].
+#[derive(Clone, Debug, Default)]
+pub struct ImportReport {
+    pub load: LoadReport,
+    /// Sections of the save that the policy doesn't allow, dropped without being read.
+    pub rejected_sections: Vec<String>,
+    pub rejected_values: Vec<RejectedValue>,
+}
+
+impl SaveRegistry {
+    /// Loads a save from an untrusted source, restoring only the component types `policy`
+    /// allows and running its validators on them. Reserved sections, such as the
+    /// hierarchy, are kept.
+    pub fn load_untrusted<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        doc: &mut SaveDocument,
+        policy: &ImportPolicy,
+        mode: LoadMode,
+        marker: M,
+    ) -> Result<ImportReport, serde_json::Error> {
+        let allowed_names: Vec<&str> = policy
+            .allowed
+            .iter()
+            .filter_map(|allowed| self.registration_name(allowed.type_path))
+            .collect();
+        let mut rejected_sections: Vec<String> = doc
+            .keys()
+            .filter(|name| !is_reserved(name) && !allowed_names.contains(&name.as_str()))
+            .cloned()
+            .collect();
+        rejected_sections.sort();
+        for name in &rejected_sections {
+            doc.remove(name);
+        }
+
+        let load = self.load(world, doc, mode, marker)?;
+        let mut rejected_values = Vec::new();
+        for allowed in &policy.allowed {
+            let (Some(sanitize), Some(name)) =
+                (&allowed.sanitize, self.registration_name(allowed.type_path))
+            else {
+                continue;
+            };
+            for entity in load.entity_map.values() {
+                if let Some(Err(message)) = sanitize(world, *entity) {
+                    rejected_values.push(RejectedValue {
+                        entity: *entity,
+                        component: name.to_string(),
+                        message,
+                    });
+                }
+            }
+        }
+        Ok(ImportReport {
+            load,
+            rejected_sections,
+            rejected_values,
+        })
+    }
+
+    fn registration_name(&self, type_path: &str) -> Option<&str> {
+        self.iter()
+            .find(|reg| reg.type_path() == type_path)
+            .map(|reg| reg.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::tests::SerializeMe;
+
+    #[derive(Component, Serialize, Deserialize, PartialEq, Debug)]
+    struct Hat(String);
+
+    #[derive(Component, Serialize, Deserialize, PartialEq, Debug)]
+    struct Tint(u8);
+
+    #[derive(Component, Serialize, Deserialize)]
+    struct Strength(u32);
+
+    #[test]
+    fn test_untrusted_load_keeps_whitelisted_components() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Hat>()
+            .register::<Tint>()
+            .register::<Strength>();
+        let mut world = World::default();
+        world.spawn((Hat("crown".into()), Tint(200), Strength(9999), SerializeMe));
+        world.spawn((Hat("".into()), SerializeMe));
+        let mut doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+
+        let policy = ImportPolicy::new()
+            .allow_validated::<Hat>(|hat| match hat.0.is_empty() {
+                true => Err("no hat name".to_string()),
+                false => Ok(()),
+            })
+            .allow_validated::<Tint>(|tint| {
+                tint.0 = tint.0.min(100);
+                Ok(())
+            });
+        let mut imported = World::default();
+        let report = registry
+            .load_untrusted(
+                &mut imported,
+                &mut doc,
+                &policy,
+                LoadMode::Merge,
+                SerializeMe,
+            )
+            .unwrap();
+
+        assert_eq!(report.rejected_sections, vec!["Strength"]);
+        assert_eq!(imported.query::<&Strength>().iter(&imported).count(), 0);
+        let hats: Vec<&Hat> = imported.query::<&Hat>().iter(&imported).collect();
+        assert_eq!(hats, vec![&Hat("crown".into())]);
+        let tints: Vec<&Tint> = imported.query::<&Tint>().iter(&imported).collect();
+        assert_eq!(tints, vec![&Tint(100)]);
+        let messages: Vec<String> = report
+            .rejected_values
+            .iter()
+            .map(|rejected| format!("{}: {}", rejected.component, rejected.message))
+            .collect();
+        assert_eq!(messages, vec!["Hat: no hat name"]);
+    }
+}
@@ -0,0 +1,296 @@
+//! An append-only persistence mode: rather than rewriting the whole save, each save appends
+//! the [`SaveDelta`] since the previous one to a journal, and every so often a full
+//! snapshot replaces the journal. Loading replays the journal onto the latest snapshot.
+//!
+//! Records are single JSON lines synced to disk as they are written, and snapshots replace
+//! their file atomically, so a crash at any point loses at most the record being written.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::config::SaveError;
+use crate::delta::SaveDelta;
+use crate::load::{LoadMode, LoadReport};
+use crate::manifest::MANIFEST_KEY;
+use crate::registry::SaveRegistry;
+use crate::SaveDocument;
+
+/// One journal line: the changes made by a save.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JournalRecord {
+    /// Counts saves, snapshots included, since the journal was created.
+    pub sequence: u64,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub delta: SaveDelta,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JournalSnapshot {
+    sequence: u64,
+    timestamp: u64,
+    document: SaveDocument,
+}
+
+/// What a [`journal_save`](SaveRegistry::journal_save) wrote.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JournalWrite {
+    /// A full snapshot, replacing the journal.
+    Snapshot,
+    /// A journal record of this many bytes.
+    Record(usize),
+    /// Nothing, as nothing changed since the previous save.
+    Unchanged,
+}
+
+/// A journal in a directory, holding `snapshot.json` and `journal.jsonl`.
+pub struct Journal {
+    dir: PathBuf,
+    snapshot_every: usize,
+    records_since_snapshot: usize,
+    sequence: u64,
+    /// The document as of the last save, which the next record is a delta against.
+    document: Option<SaveDocument>,
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+impl Journal {
+    /// Opens the journal in `dir`, replaying it so that saves continue where it left off. A
+    /// record torn by a crash at the end of the journal is discarded.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, SaveError> {
+        let mut journal = Journal {
+            dir: dir.into(),
+            snapshot_every: 100,
+            records_since_snapshot: 0,
+            sequence: 0,
+            document: None,
+        };
+        journal.replay()?;
+        Ok(journal)
+    }
+
+    /// Sets how many records are appended between full snapshots, 100 by default.
+    pub fn with_snapshot_every(mut self, records: usize) -> Self {
+        self.snapshot_every = records;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn snapshot_path(&self) -> PathBuf {
+        self.dir.join("snapshot.json")
+    }
+
+    pub fn journal_path(&self) -> PathBuf {
+        self.dir.join("journal.jsonl")
+    }
+
+    /// The saved document: the latest snapshot with the journal replayed onto it, or `None`
+    /// if nothing was saved yet.
+    pub fn document(&self) -> Option<&SaveDocument> {
+        self.document.as_ref()
+    }
+
+    /// Reads the snapshot and the journal's records after it, truncating the journal after
+    /// its last complete record.
+    fn replay(&mut self) -> Result<(), SaveError> {
+        let snapshot: JournalSnapshot = match std::fs::read(self.snapshot_path()) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut document = snapshot.document;
+        self.sequence = snapshot.sequence;
+        let journal = match std::fs::read(self.journal_path()) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        let mut valid_len = 0;
+        for line in journal.split_inclusive(|byte| *byte == b'\n') {
+            // an unterminated or unreadable line can only be the torn last record
+            let Some(record) = line
+                .strip_suffix(b"\n")
+                .and_then(|line| serde_json::from_slice::<JournalRecord>(line).ok())
+            else {
+                break;
+            };
+            valid_len += line.len();
+            // records at or before the snapshot are left over from a crash between writing
+            // the snapshot and emptying the journal
+            if record.sequence > self.sequence {
+                record.delta.apply_to_document(&mut document)?;
+                self.sequence = record.sequence;
+                self.records_since_snapshot += 1;
+            }
+        }
+        if valid_len < journal.len() {
+            OpenOptions::new()
+                .write(true)
+                .open(self.journal_path())?
+                .set_len(valid_len as u64)?;
+        }
+        self.document = Some(document);
+        Ok(())
+    }
+
+    fn write_snapshot(&mut self, document: SaveDocument) -> Result<(), SaveError> {
+        std::fs::create_dir_all(&self.dir)?;
+        self.sequence += 1;
+        let snapshot = JournalSnapshot {
+            sequence: self.sequence,
+            timestamp: now_millis(),
+            document,
+        };
+        let path = self.snapshot_path();
+        let tmp = path.with_extension("json.tmp");
+        let mut file = File::create(&tmp)?;
+        serde_json::to_writer(&mut file, &snapshot)?;
+        file.sync_all()?;
+        std::fs::rename(tmp, path)?;
+        File::create(self.journal_path())?.sync_all()?;
+        self.records_since_snapshot = 0;
+        self.document = Some(snapshot.document);
+        Ok(())
+    }
+
+    fn append(&mut self, delta: SaveDelta) -> Result<usize, SaveError> {
+        let record = JournalRecord {
+            sequence: self.sequence + 1,
+            timestamp: now_millis(),
+            delta,
+        };
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.journal_path())?;
+        file.write_all(&line)?;
+        file.sync_data()?;
+        self.sequence = record.sequence;
+        self.records_since_snapshot += 1;
+        if let Some(document) = &mut self.document {
+            record.delta.apply_to_document(document)?;
+        }
+        Ok(line.len())
+    }
+}
+
+impl SaveRegistry {
+    /// Saves the entities marked with `M` to `journal`: a full snapshot if the journal is new
+    /// or has reached its [snapshot interval](Journal::with_snapshot_every), otherwise a
+    /// record of the changes since the previous save.
+    pub fn journal_save<M: Component>(
+        &self,
+        world: &mut World,
+        journal: &mut Journal,
+    ) -> Result<JournalWrite, SaveError> {
+        let mut document = self.serialize::<M>(world)?;
+        document.remove(MANIFEST_KEY);
+        match &journal.document {
+            Some(previous) if journal.records_since_snapshot < journal.snapshot_every => {
+                let delta = SaveDelta::between_documents(previous, &document)?;
+                if delta.is_empty() {
+                    Ok(JournalWrite::Unchanged)
+                } else {
+                    Ok(JournalWrite::Record(journal.append(delta)?))
+                }
+            }
+            _ => {
+                journal.write_snapshot(document)?;
+                Ok(JournalWrite::Snapshot)
+            }
+        }
+    }
+
+    /// Loads the latest state of `journal` in `mode`. Fails with [`io::ErrorKind::NotFound`]
+    /// if nothing was saved to it yet.
+    pub fn load_journal<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        journal: &Journal,
+        mode: LoadMode,
+        marker: M,
+    ) -> Result<LoadReport, SaveError> {
+        let Some(document) = journal.document() else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no snapshot in {}", journal.dir.display()),
+            )
+            .into());
+        };
+        Ok(self.load_filtered(world, &mut document.clone(), mode, |_| true, marker)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_journal_replays_onto_snapshot() {
+        let dir = std::env::temp_dir().join(format!("bevy_serde_journal_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .register_mapped::<Component2>();
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+
+        let mut journal = Journal::open(&dir).unwrap().with_snapshot_every(2);
+        let save = |world: &mut World, journal: &mut Journal| {
+            registry
+                .journal_save::<SerializeMe>(world, journal)
+                .unwrap()
+        };
+        assert_eq!(save(&mut world, &mut journal), JournalWrite::Snapshot);
+        assert_eq!(save(&mut world, &mut journal), JournalWrite::Unchanged);
+        world.spawn((Component2 { target: entity1 }, SerializeMe));
+        assert!(matches!(
+            save(&mut world, &mut journal),
+            JournalWrite::Record(_)
+        ));
+        world.spawn((Component1, SerializeMe));
+        assert!(matches!(
+            save(&mut world, &mut journal),
+            JournalWrite::Record(_)
+        ));
+
+        // a crash midway through the next record
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(journal.journal_path())
+            .unwrap();
+        file.write_all(br#"{"sequence":5,"timest"#).unwrap();
+
+        let mut journal = Journal::open(&dir).unwrap().with_snapshot_every(2);
+        let report = registry
+            .load_journal(
+                &mut World::default(),
+                &journal,
+                LoadMode::Merge,
+                SerializeMe,
+            )
+            .unwrap();
+        assert_eq!(report.entity_map.len(), 3);
+        world.spawn((Component1, SerializeMe));
+        assert_eq!(save(&mut world, &mut journal), JournalWrite::Snapshot);
+        assert_eq!(std::fs::read(journal.journal_path()).unwrap(), b"");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
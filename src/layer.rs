@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+
+use bevy_ecs::prelude::*;
+use serde_json::Value;
+
+use crate::delta::section_entries;
+use crate::load::{LoadMode, LoadReport};
+use crate::manifest::{Manifest, MANIFEST_KEY};
+use crate::registry::SaveRegistry;
+use crate::SaveDocument;
+
+/// Applies an override document on top of `doc`. The layer has the same layout as a save
+/// but may be partial: each component it contains replaces the same component of the same
+/// entity in `doc`, and everything it doesn't mention is kept. Entities are identified by
+/// their ids in the base save, so layers must be written against it.
+///
+/// Manifest entries of the layer replace those of `doc` per component.
+pub fn apply_layer(doc: &mut SaveDocument, layer: &SaveDocument) -> Result<(), serde_json::Error> {
+    for (name, section) in layer.iter().filter(|(name, _)| *name != MANIFEST_KEY) {
+        let mut merged: BTreeMap<Entity, Value> = match doc.get(name) {
+            Some(base) => section_entries(name, base)?
+                .into_iter()
+                .map(|(entity, comp)| (entity, comp.clone()))
+                .collect(),
+            None => BTreeMap::new(),
+        };
+        for (entity, comp) in section_entries(name, section)? {
+            merged.insert(entity, comp.clone());
+        }
+        doc.insert(
+            name.clone(),
+            serde_json::to_value(merged.into_iter().collect::<Vec<_>>())?,
+        );
+    }
+    if let Some(layer_manifest) = layer.get(MANIFEST_KEY) {
+        let layer_manifest: Manifest = serde_json::from_value(layer_manifest.clone())?;
+        let mut manifest: Manifest = match doc.get(MANIFEST_KEY) {
+            Some(manifest) => serde_json::from_value(manifest.clone())?,
+            None => layer_manifest.clone(),
+        };
+        manifest.components.extend(layer_manifest.components);
+        doc.insert(MANIFEST_KEY.to_string(), serde_json::to_value(manifest)?);
+    }
+    Ok(())
+}
+
+impl SaveRegistry {
+    /// Loads `base` with each of `layers` applied on top in order, later layers winning,
+    /// e.g. a shipped world with a difficulty variant and mod tweaks over it.
+    pub fn load_layered<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        base: &SaveDocument,
+        layers: &[SaveDocument],
+        mode: LoadMode,
+        marker: M,
+    ) -> Result<LoadReport, serde_json::Error> {
+        let mut doc = base.clone();
+        for layer in layers {
+            apply_layer(&mut doc, layer)?;
+        }
+        self.load(world, &mut doc, mode, marker)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_later_layers_win() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Component2>();
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        let entity2 = world
+            .spawn((Component2 { target: entity1 }, SerializeMe))
+            .id();
+        let base = registry.serialize::<SerializeMe>(&mut world).unwrap();
+
+        let layer = |comps: Value| -> SaveDocument {
+            [("Component2".to_string(), comps)].into_iter().collect()
+        };
+        let layers = [
+            layer(json!([[entity1, { "target": entity2 }], [entity2, { "target": entity2 }]])),
+            layer(json!([[entity1, { "target": entity1 }]])),
+        ];
+        let report = registry
+            .load_layered(&mut world, &base, &layers, LoadMode::Replace, SerializeMe)
+            .unwrap();
+        let map = &report.entity_map;
+        assert!(world.get::<Component1>(map[&entity1]).is_some());
+        assert_eq!(
+            world.get::<Component2>(map[&entity1]).unwrap().target,
+            entity1
+        );
+        assert_eq!(
+            world.get::<Component2>(map[&entity2]).unwrap().target,
+            entity2
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! Conversion between the component-major layout the crate writes and an entity-major
+//! layout that is easier to read and edit by hand.
+
+use std::collections::BTreeMap;
+
+use bevy_ecs::prelude::*;
+use serde::de::Error;
+use serde_json::{Map, Value};
+
+use crate::delta::section_entries;
+use crate::SaveDocument;
+
+/// The shape of a save document.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SaveLayout {
+    /// `{"Health": [[entity, {...}], ...], ...}`, as written by the macros and the registry.
+    #[default]
+    ByComponent,
+    /// `{"12v1": {"Health": {...}, "Position": {...}}, ...}`, keyed by
+    /// [`entity_key`].
+    ByEntity,
+}
+
+/// The key of `entity` in a [`SaveLayout::ByEntity`] document: its index and generation,
+/// as in bevy's debug output.
+pub fn entity_key(entity: Entity) -> String {
+    format!("{}v{}", entity.index(), entity.generation())
+}
+
+pub fn parse_entity_key(key: &str) -> Option<Entity> {
+    let (index, generation) = key.split_once('v')?;
+    let index: u32 = index.parse().ok()?;
+    let generation: u32 = generation.parse().ok()?;
+    Some(Entity::from_bits(
+        (u64::from(generation) << 32) | u64::from(index),
+    ))
+}
+
+/// Whether `key` is reserved for document-level data such as the manifest, rather than a
+/// component section or entity.
+pub(crate) fn is_reserved(key: &str) -> bool {
+    key.starts_with("__")
+}
+
+/// True if `doc` is in the [`SaveLayout::ByEntity`] layout. Documents with no entities are
+/// the same in both layouts and count as [`SaveLayout::ByComponent`].
+pub fn is_entity_layout(doc: &SaveDocument) -> bool {
+    let mut keys = doc.keys().filter(|key| !is_reserved(key)).peekable();
+    keys.peek().is_some() && keys.all(|key| parse_entity_key(key).is_some())
+}
+
+/// Regroups a component-major document by entity. Reserved entries such as the manifest
+/// are kept as they are.
+pub fn to_entity_layout(doc: &SaveDocument) -> Result<SaveDocument, serde_json::Error> {
+    let mut entities: BTreeMap<Entity, Map<String, Value>> = BTreeMap::new();
+    let mut out = SaveDocument::new();
+    for (name, section) in doc {
+        if is_reserved(name) {
+            out.insert(name.clone(), section.clone());
+            continue;
+        }
+        for (entity, comp) in section_entries(name, section)? {
+            entities
+                .entry(entity)
+                .or_default()
+                .insert(name.clone(), comp.clone());
+        }
+    }
+    out.extend(
+        entities
+            .into_iter()
+            .map(|(entity, comps)| (entity_key(entity), Value::Object(comps))),
+    );
+    Ok(out)
+}
+
+/// Turns an entity-major document back into the component-major layout the loaders
+/// expect, with each section in ascending entity order.
+pub fn from_entity_layout(doc: &SaveDocument) -> Result<SaveDocument, serde_json::Error> {
+    let mut sections: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+    let mut out = SaveDocument::new();
+    let mut entities: Vec<(Entity, &Value)> = Vec::new();
+    for (key, comps) in doc {
+        if is_reserved(key) {
+            out.insert(key.clone(), comps.clone());
+            continue;
+        }
+        let entity = parse_entity_key(key)
+            .ok_or_else(|| serde_json::Error::custom(format!("{key} is not an entity key")))?;
+        entities.push((entity, comps));
+    }
+    entities.sort_by_key(|(entity, _)| *entity);
+    for (entity, comps) in entities {
+        let Value::Object(comps) = comps else {
+            return Err(serde_json::Error::custom(format!(
+                "components of {} must be an object",
+                entity_key(entity)
+            )));
+        };
+        for (name, comp) in comps {
+            sections
+                .entry(name.clone())
+                .or_default()
+                .push(serde_json::to_value((entity, comp))?);
+        }
+    }
+    out.extend(
+        sections
+            .into_iter()
+            .map(|(name, entries)| (name, Value::Array(entries))),
+    );
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::registry::SaveRegistry;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_layouts_convert_both_ways() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Component2>();
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        let entity2 = world
+            .spawn((Component1, Component2 { target: entity1 }, SerializeMe))
+            .id();
+        let doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+
+        let by_entity = to_entity_layout(&doc).unwrap();
+        assert!(is_entity_layout(&by_entity));
+        assert!(!is_entity_layout(&doc));
+        assert_eq!(
+            by_entity[&entity_key(entity2)],
+            json!({ "Component1": null, "Component2": { "target": entity1 } })
+        );
+        assert_eq!(from_entity_layout(&by_entity).unwrap(), doc);
+        assert_eq!(parse_entity_key("12v1").unwrap().generation(), 1);
+        assert!(parse_entity_key("Health").is_none());
+    }
+}
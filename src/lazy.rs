@@ -0,0 +1,225 @@
+//! Components loaded without being deserialized, for huge worlds that should boot before
+//! every heavy component has been parsed. A component registered with
+//! [`SaveRegistry::register_deferred`] is restored as a [`Deferred`] holding its saved
+//! value, and only deserialized the first time a system asks for it. Components holding
+//! `Entity` ids are registered with [`SaveRegistry::register_deferred_mapped`] instead,
+//! which deserializes them on load, as their ids can only be mapped while the world loads.
+
+use std::marker::PhantomData;
+
+use bevy_ecs::component::TableStorage;
+use bevy_ecs::entity::{EntityMapper, MapEntities};
+use bevy_ecs::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::entity_map::map_component_entities;
+use crate::registry::{extract_section, insert_section, ComponentRegistration, SaveRegistry};
+use crate::schema::trace_or_opaque;
+
+enum DeferredState<C> {
+    Saved(Value),
+    Loaded(C),
+}
+
+/// A `C` that is deserialized from its saved value on first access. Query it mutably and
+/// call [`get`](Self::get) or [`get_mut`](Self::get_mut); the value is kept once
+/// deserialized. Saving writes the saved value back as is while it hasn't been touched.
+pub struct Deferred<C> {
+    state: DeferredState<C>,
+    _marker: PhantomData<fn() -> C>,
+}
+
+impl<C: Send + Sync + 'static> Component for Deferred<C> {
+    type Storage = TableStorage;
+}
+
+impl<C: DeserializeOwned> Deferred<C> {
+    pub fn new(comp: C) -> Self {
+        Self::from_state(DeferredState::Loaded(comp))
+    }
+
+    /// A `C` still to be deserialized from `saved`.
+    pub fn from_saved(saved: Value) -> Self {
+        Self::from_state(DeferredState::Saved(saved))
+    }
+
+    fn from_state(state: DeferredState<C>) -> Self {
+        Deferred {
+            state,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Whether the value has been deserialized yet.
+    pub fn is_loaded(&self) -> bool {
+        matches!(self.state, DeferredState::Loaded(_))
+    }
+
+    pub fn get(&mut self) -> Result<&C, serde_json::Error> {
+        self.get_mut().map(|comp| &*comp)
+    }
+
+    /// The value, deserializing it first if this is the first access. A value that fails
+    /// to deserialize stays saved, so the error is returned again on the next access.
+    pub fn get_mut(&mut self) -> Result<&mut C, serde_json::Error> {
+        if let DeferredState::Saved(saved) = &self.state {
+            self.state = DeferredState::Loaded(C::deserialize(saved)?);
+        }
+        match &mut self.state {
+            DeferredState::Loaded(comp) => Ok(comp),
+            DeferredState::Saved(_) => unreachable!(),
+        }
+    }
+
+    pub fn into_inner(self) -> Result<C, serde_json::Error> {
+        match self.state {
+            DeferredState::Saved(saved) => serde_json::from_value(saved),
+            DeferredState::Loaded(comp) => Ok(comp),
+        }
+    }
+}
+
+impl<C: Serialize> Serialize for Deferred<C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match &self.state {
+            DeferredState::Saved(saved) => saved.serialize(serializer),
+            DeferredState::Loaded(comp) => comp.serialize(serializer),
+        }
+    }
+}
+
+/// Deserializes the value to map it, so a value that fails to deserialize is left as saved.
+impl<C: DeserializeOwned + MapEntities> MapEntities for Deferred<C> {
+    fn map_entities(&mut self, entity_mapper: &mut EntityMapper) {
+        if let Ok(comp) = self.get_mut() {
+            comp.map_entities(entity_mapper);
+        }
+    }
+}
+
+impl<'de, C> Deserialize<'de> for Deferred<C> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Deferred {
+            state: DeferredState::Saved(Value::deserialize(deserializer)?),
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl SaveRegistry {
+    /// Registers `C` to be loaded as a [`Deferred<C>`], under the section name and schema
+    /// `C` would have, so that saves of either registration load with the other. Entities
+    /// saved with a `Deferred<C>` write it to that section too. `Entity` ids inside `C` are
+    /// left as saved; register such components with
+    /// [`register_deferred_mapped`](Self::register_deferred_mapped).
+    pub fn register_deferred<C: Component + Serialize + DeserializeOwned>(&mut self) -> &mut Self {
+        let naming = self.naming();
+        self.push_registration(
+            ComponentRegistration::custom::<Deferred<C>>(
+                0,
+                naming,
+                trace_or_opaque::<C>(),
+                extract_section::<Deferred<C>>,
+                Box::new(insert_section::<Deferred<C>>),
+            )
+            .named_after::<C>(naming),
+        )
+    }
+
+    /// Registers `C` like [`register_deferred`](Self::register_deferred), but maps the
+    /// `Entity` ids inside it to the loaded entities. This deserializes each `Deferred<C>`
+    /// as it is loaded, so only its type, not its parsing, is deferred.
+    pub fn register_deferred_mapped<C: Component + Serialize + DeserializeOwned + MapEntities>(
+        &mut self,
+    ) -> &mut Self {
+        self.register_deferred::<C>();
+        if let Some(reg) = self.get_by_type_mut::<Deferred<C>>() {
+            reg.map_entities = Some(map_component_entities::<Deferred<C>>);
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ecs::system::RunSystemOnce;
+
+    use crate::load::LoadMode;
+    use crate::tests::{Component1, SerializeMe};
+
+    #[derive(Component, Serialize, Deserialize, Clone, PartialEq, Debug)]
+    struct Terrain(Vec<u8>);
+
+    #[test]
+    fn test_deferred_component_loads_on_access() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Terrain>();
+        let mut world = World::default();
+        world.spawn((Terrain(vec![1, 2, 3]), SerializeMe));
+        let saved = registry.serialize::<SerializeMe>(&mut world).unwrap();
+
+        let mut lazy = SaveRegistry::new();
+        lazy.register_deferred::<Terrain>();
+        assert_eq!(
+            lazy.get("Terrain").unwrap().schema_hash(),
+            registry.get("Terrain").unwrap().schema_hash()
+        );
+        let mut world = World::default();
+        lazy.load(&mut world, &mut saved.clone(), LoadMode::Merge, SerializeMe)
+            .unwrap();
+        let mut query = world.query::<&Deferred<Terrain>>();
+        assert!(!query.single(&world).is_loaded());
+        let resaved = lazy.serialize::<SerializeMe>(&mut world).unwrap();
+        assert_eq!(resaved["Terrain"], saved["Terrain"]);
+
+        world.run_system_once(|mut terrain: Query<&mut Deferred<Terrain>>| {
+            terrain.single_mut().get_mut().unwrap().0.push(4);
+        });
+        assert!(query.single(&world).is_loaded());
+        let resaved = lazy.serialize::<SerializeMe>(&mut world).unwrap();
+        assert_eq!(resaved["Terrain"][0][1], serde_json::json!([1, 2, 3, 4]));
+    }
+
+    #[derive(Component, Serialize, Deserialize)]
+    struct Orders {
+        target: Entity,
+    }
+
+    impl MapEntities for Orders {
+        fn map_entities(&mut self, entity_mapper: &mut EntityMapper) {
+            self.target = entity_mapper.get_or_reserve(self.target);
+        }
+    }
+
+    #[test]
+    fn test_deferred_entities_are_mapped() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .register_deferred_mapped::<Orders>();
+        let mut world = World::default();
+        let target = world.spawn((Component1, SerializeMe)).id();
+        world.spawn((Deferred::new(Orders { target }), SerializeMe));
+        let saved = registry.serialize::<SerializeMe>(&mut world).unwrap();
+
+        let mut loaded = World::default();
+        loaded.spawn_batch((0..10).map(|_| Component1));
+        let report = registry
+            .load(
+                &mut loaded,
+                &mut saved.clone(),
+                LoadMode::Merge,
+                SerializeMe,
+            )
+            .unwrap();
+        let mut orders = loaded.query::<&mut Deferred<Orders>>();
+        let mut orders = orders.single_mut(&mut loaded);
+        assert!(orders.is_loaded());
+        let loaded_target = orders.get().unwrap().target;
+        assert_eq!(report.entity_map.translate(target), Some(loaded_target));
+        assert_ne!(loaded_target, target);
+    }
+}
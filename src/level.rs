@@ -0,0 +1,197 @@
+use std::io;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use bevy_ecs::prelude::*;
+use serde::Serialize;
+
+use crate::config::{SaveConfig, SaveError};
+use crate::entity_map::EntityMap;
+use crate::region::file_stem;
+use crate::registry::SaveRegistry;
+
+type FileNameFn<L> = Box<dyn Fn(&L) -> String + Send + Sync>;
+
+/// Per-level persistence for games built of separate levels or maps, such as a roguelike's
+/// dungeon floors: the entities marked with `M` belong to the current level and are saved
+/// to the level's own file on exit and restored from it on entry. Entities without `M`,
+/// like the player and their followers, stay in the world across levels.
+///
+/// Levels are loaded through [`SaveRegistry::load_additive`], with the cross-level entities
+/// in the world's persistent [`EntityMap`], so a level's references to them, e.g. a
+/// monster's target, point at them again when the level is re-entered.
+#[derive(Resource)]
+pub struct LevelStore<L, M> {
+    dir: PathBuf,
+    file_name: FileNameFn<L>,
+    config: SaveConfig,
+    current: Option<L>,
+    _marker: PhantomData<fn(M)>,
+}
+
+/// The default level file name, as for regions: `level_3.json` for level `3`.
+fn default_file_name<L: Serialize>(level: &L) -> String {
+    format!("level_{}.json", file_stem(level))
+}
+
+impl<L, M> LevelStore<L, M>
+where
+    L: Clone + PartialEq + Serialize + Send + Sync + 'static,
+    M: Component + Clone,
+{
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        LevelStore {
+            dir: dir.into(),
+            file_name: Box::new(default_file_name::<L>),
+            config: SaveConfig::new(),
+            current: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Overrides how level ids are turned into file names within the store's directory.
+    pub fn with_file_names(
+        mut self,
+        file_name: impl Fn(&L) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.file_name = Box::new(file_name);
+        self
+    }
+
+    /// Sets the config level files are encoded and decoded with.
+    pub fn with_config(mut self, config: SaveConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn path(&self, level: &L) -> PathBuf {
+        self.dir.join((self.file_name)(level))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The level entered last, unless it was exited since.
+    pub fn current(&self) -> Option<&L> {
+        self.current.as_ref()
+    }
+
+    /// Whether `level` has been saved before, i.e. entering it restores its entities.
+    pub fn is_stored(&self, level: &L) -> bool {
+        self.path(level).exists()
+    }
+
+    /// Writes the entities marked with `M` to `level`'s file, replacing what was stored.
+    pub fn save_level(
+        &self,
+        world: &mut World,
+        registry: &SaveRegistry,
+        level: &L,
+    ) -> Result<(), SaveError> {
+        let bytes = registry.save_bytes::<M>(world, &self.config)?;
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(self.path(level), bytes)?;
+        Ok(())
+    }
+
+    /// Saves the current level, if there is one, and despawns its entities.
+    pub fn exit_level(
+        &mut self,
+        world: &mut World,
+        registry: &SaveRegistry,
+    ) -> Result<(), SaveError> {
+        let Some(level) = self.current.take() else {
+            return Ok(());
+        };
+        if let Err(err) = self.save_level(world, registry, &level) {
+            self.current = Some(level);
+            return Err(err);
+        }
+        let entities: Vec<Entity> = world
+            .query_filtered::<Entity, With<M>>()
+            .iter(world)
+            .collect();
+        for entity in entities {
+            world.despawn(entity);
+        }
+        Ok(())
+    }
+
+    /// Exits the current level and makes `level` current, spawning its stored entities
+    /// with `marker`. Returns `false` if `level` has never been saved, e.g. because it is
+    /// yet to be generated; it is current all the same.
+    pub fn enter_level(
+        &mut self,
+        world: &mut World,
+        registry: &SaveRegistry,
+        level: L,
+        marker: M,
+    ) -> Result<bool, SaveError> {
+        self.exit_level(world, registry)?;
+        let bytes = match std::fs::read(self.path(&level)) {
+            Ok(bytes) => Some(bytes),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err.into()),
+        };
+        self.current = Some(level);
+        let Some(bytes) = bytes else {
+            return Ok(false);
+        };
+        let mut doc = self.config.decode(&bytes)?;
+        self.config.check_entity_count(&doc)?;
+        // every entity left in the world lives across levels, and keeps its id
+        let persistent: Vec<Entity> = world.iter_entities().map(|entity| entity.id()).collect();
+        let mut entity_map = world.get_resource_or_insert_with(EntityMap::new);
+        for entity in persistent {
+            entity_map.insert(entity, entity);
+        }
+        registry.load_additive(world, &mut doc, marker)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_levels_keep_references_to_the_player() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .register_mapped::<Component2>();
+        let dir = std::env::temp_dir().join(format!("bevy_serde_levels_{}", std::process::id()));
+        let mut store = LevelStore::<u32, SerializeMe>::new(&dir);
+        assert!(store.path(&3).ends_with("level_3.json"));
+
+        let mut world = World::default();
+        let player = world.spawn(Component1).id();
+        assert!(!store
+            .enter_level(&mut world, &registry, 1, SerializeMe)
+            .unwrap());
+        world.spawn((Component2 { target: player }, SerializeMe));
+        world.spawn((Component1, SerializeMe));
+
+        assert!(!store
+            .enter_level(&mut world, &registry, 2, SerializeMe)
+            .unwrap());
+        assert!(store.is_stored(&1));
+        assert_eq!(world.entities().len(), 1);
+        world.spawn((Component1, SerializeMe));
+
+        assert!(store
+            .enter_level(&mut world, &registry, 1, SerializeMe)
+            .unwrap());
+        assert_eq!(store.current(), Some(&1));
+        let targets: Vec<Entity> = world
+            .query::<&Component2>()
+            .iter(&world)
+            .map(|monster| monster.target)
+            .collect();
+        assert_eq!(targets, vec![player]);
+        assert_eq!(world.entities().len(), 3);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,14 +2,198 @@
 // Copyright 2019 Herbert Wolverson (DBA Bracket Productions)
 // (Copyright (c) 2017 The Specs Project Developers)
 
+use std::collections::BTreeMap;
+
 use bevy_ecs::prelude::*;
-use bevy_utils::hashbrown::HashMap;
 use serde::de::{Deserialize, DeserializeOwned};
 use serde::ser::Serialize;
 use serde_json::Value;
 
-const EMPTY_JS_ARRAY: Value = serde_json::json!([]);
-type EntityMapperDynFn = dyn FnOnce(&mut World, &mut HashMap<Entity, Entity>);
+#[cfg(feature = "arena")]
+pub mod arena;
+pub mod async_io;
+pub mod autosave;
+pub mod borrowed;
+pub mod carrier;
+pub mod clipboard;
+pub mod codecs;
+pub mod collector;
+pub mod compact;
+pub mod config;
+pub mod container;
+pub mod delta;
+pub mod dependencies;
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+pub mod diff;
+pub mod dynamic;
+pub mod entity_map;
+pub mod entity_str;
+pub mod estimate;
+pub mod events;
+pub mod exempt;
+pub mod extract;
+pub mod frame;
+pub mod golden;
+pub mod hash;
+#[cfg(feature = "hot-reload")]
+pub mod hot_reload;
+#[cfg(feature = "http")]
+pub mod http_storage;
+pub mod import_policy;
+pub mod journal;
+pub mod layer;
+pub mod layout;
+pub mod lazy;
+pub mod level;
+pub mod load;
+pub mod load_from_save;
+pub mod lock;
+pub mod manifest;
+pub mod metrics;
+pub mod migration;
+pub mod mods;
+pub mod namespace;
+pub mod omit;
+pub mod parse;
+pub mod patch;
+#[cfg(feature = "app")]
+pub mod plugin;
+pub mod prefab;
+pub mod profiles;
+pub mod quantize;
+pub mod redact;
+pub mod region;
+pub mod registry;
+pub mod replication;
+pub mod rollback;
+pub mod schema;
+pub mod section_map;
+pub mod sections;
+pub mod seed;
+pub mod snapshot;
+pub mod spec;
+pub mod split;
+pub mod stats;
+pub mod storage;
+pub mod streaming;
+pub mod subtree;
+pub mod support;
+#[cfg(feature = "lifecycle")]
+pub mod suspend;
+pub mod testing;
+pub mod ticks;
+pub mod undo;
+pub mod unknown_variants;
+pub mod validate;
+#[cfg(all(feature = "web", target_arch = "wasm32"))]
+pub mod web_storage;
+pub mod world_ext;
+
+pub use async_io::{AsyncSaveStorage, IoCompleted, IoOperation, LoadTask, MaybeSend, SaveTask};
+pub use autosave::{AutosaveChain, AutosaveWrite};
+pub use borrowed::{deserialize_borrowed, raw_sections, RawSections};
+pub use carrier::ResourceCarrier;
+pub use clipboard::{copy_to_string, paste_from_string};
+pub use codecs::{BinaryBlob, CompressedGrid, Delta, Rle};
+pub use collector::{collect_section, SaveCollector, SerializeQuery};
+pub use compact::{CompactionReport, DiscardedSection};
+pub use config::{Compression, EntityEncoding, SaveConfig, SaveError, SaveFormat};
+pub use container::{read_container, ContainerHeader};
+pub use delta::SaveDelta;
+#[cfg(feature = "diagnostics")]
+pub use diagnostics::PersistenceDiagnosticsPlugin;
+pub use diff::{diff_saves, ComponentChange, SaveDiff};
+pub use dynamic::DynamicComponentStore;
+pub use entity_map::{copy_entities, get_or_insert, EntityMap};
+pub use estimate::{estimate_load_cost, LoadEstimate};
+pub use events::LoadCompleted;
+pub use exempt::{SaveExempt, SaveQuery, Transient};
+pub use extract::ExtractedSave;
+pub use frame::{Frame, FrameDecoder, FrameLoader};
+pub use hash::hash_world;
+#[cfg(feature = "hot-reload")]
+pub use hot_reload::HotReload;
+#[cfg(feature = "http")]
+pub use http_storage::{HttpStorage, SyncConflict};
+pub use import_policy::{ImportPolicy, ImportReport, RejectedValue};
+pub use journal::{Journal, JournalRecord, JournalWrite};
+pub use layer::apply_layer;
+pub use layout::SaveLayout;
+pub use lazy::Deferred;
+pub use level::LevelStore;
+pub use load::{
+    prepare_world_for_load, DanglingReference, EntityGenerations, LoadMode, LoadReport,
+    SkippedEntry,
+};
+pub use load_from_save::LoadFromSave;
+pub use lock::{HeldOperation, LockPolicy, PersistenceBlocked, PersistenceLock};
+pub use manifest::{CompatibilityReport, Manifest};
+pub use metrics::{PersistenceMetrics, PersistenceTelemetry};
+pub use migration::{upgrade_save, Migrations};
+pub use patch::{JsonPatch, PatchError, PatchOperation};
+#[cfg(feature = "app")]
+pub use plugin::{Permadeath, PermadeathPlugin, PersistencePlugin, PlayerDied};
+pub use prefab::{
+    export_prefab, prefab_document, read_prefab, spawn_prefab, spawn_prefab_with, PrefabOverrides,
+};
+pub use profiles::{Profiles, SaveProfile, SaveRequest};
+pub use redact::{RedactAction, Redaction};
+pub use region::RegionStore;
+pub use registry::{NamingScheme, SaveRegistry};
+pub use replication::{ReplicationUpdate, Replicator};
+pub use rollback::RollbackBuffer;
+pub use section_map::SectionMap;
+pub use sections::SaveSections;
+pub use snapshot::{restore_snapshot, take_snapshot, WorldSnapshot};
+pub use spec::{ComponentSpec, FormatSpec};
+pub use split::{SaveHeader, SplitSave};
+pub use stats::{BudgetWarning, SaveStats, SectionStats};
+pub use storage::{FileStorage, SaveStorage};
+pub use subtree::{load_subtree, serialize_subtree, spawn_from_json};
+pub use support::{create_support_bundle, read_support_bundle, SupportBundleOptions};
+#[cfg(feature = "lifecycle")]
+pub use suspend::{SaveOnSuspend, SaveOnSuspendPlugin};
+pub use ticks::{LastLoad, LoadChangeDetection};
+pub use undo::UndoStack;
+pub use unknown_variants::{StashedComponents, VariantFallback};
+pub use validate::{ValidateOnLoad, ValidationError};
+#[cfg(all(feature = "web", target_arch = "wasm32"))]
+pub use web_storage::{BrowserStorage, IndexedDbStorage, LocalStorage};
+pub use world_ext::WorldSaveExt;
+
+/// Paths used by the exported macros, so that they expand without any imports at the
+/// call site.
+#[doc(hidden)]
+pub mod __private {
+    pub use bevy_ecs::prelude::{Entity, EntityRef, With};
+    pub use serde;
+    pub use serde_json;
+    pub use std::collections::BTreeMap;
+
+    /// Records a list entry's section name, panicking if an earlier entry of the list
+    /// already claimed it, as happens with types sharing a name in different modules.
+    pub fn claim_section(
+        claimed: &mut BTreeMap<String, &'static str>,
+        name: String,
+        ty: &'static str,
+    ) {
+        if let Some(earlier) = claimed.insert(name.clone(), ty) {
+            panic!(
+                "`{earlier}` and `{ty}` would both be saved in section {name:?}; \
+                 name one of them with `as`, e.g. `{ty} as \"...\"`"
+            );
+        }
+    }
+}
+
+pub(crate) const EMPTY_JS_ARRAY: Value = serde_json::json!([]);
+
+/// A save document: its sections by name, kept in name order so that identical worlds are
+/// written byte for byte identically.
+pub type SaveDocument = BTreeMap<String, Value>;
+
+type EntityMapperDynFn = dyn FnOnce(&mut World, &mut EntityMap);
 
 /// A trait which allows to serialize entities and their components. Loosely based on the component
 /// of the same name from the specs ECS library.
@@ -30,7 +214,8 @@ where
     ///   might be represented by just a couple of integers, there might be more efficient ways to handle
     ///   this in Bevy. For instance, creating a query for an option of all serializable components could
     ///   be considered, but this approach would introduce the complexity of handling an option wrapper for
-    ///   each component, potentially increasing the data size.
+    ///   each component, potentially increasing the data size. `serialize_grouped!` takes that approach,
+    ///   writing one record per entity instead of one section per component.
     ///
     /// # Type Parameters
     /// - `C`: The type of the component to be serialized. Must implement `Component` and `Serialize`.
@@ -44,6 +229,23 @@ where
     /// A result containing either a `serde_json::Value` representing the serialized data or an error
     /// (`serde_json::Error`).
     fn serialize(self, world: &World) -> Result<Option<Value>, serde_json::Error>;
+
+    /// Like [`serialize`](SerializeComponents::serialize), but only includes the entities for
+    /// which `filter` returns true. This allows interest management at serialization time, e.g.
+    /// only saving or sending the entities within some radius of the player or camera:
+    ///
+    /// ```ignore
+    /// query.serialize_filtered(world, |entity, world| {
+    ///     world.get::<Position>(entity).is_some_and(|pos| pos.distance(player) < 100.0)
+    /// })
+    /// ```
+    fn serialize_filtered<F>(
+        self,
+        world: &World,
+        filter: F,
+    ) -> Result<Option<Value>, serde_json::Error>
+    where
+        F: Fn(Entity, &World) -> bool;
 }
 
 impl<C, M> SerializeComponents<C, M> for QueryState<(Entity, &C), With<M>>
@@ -51,94 +253,259 @@ where
     M: Component,
     C: Component + Serialize,
 {
-    fn serialize(mut self, world: &World) -> Result<Option<Value>, serde_json::Error> {
-        let comp_data: Vec<(Entity, &C)> = self.iter(world).collect();
-        if comp_data.is_empty() {
-            Ok(None)
-        } else {
-            let comp_values = comp_data
-                .into_iter()
-                .map(serde_json::to_value)
-                .collect::<Result<Vec<Value>, serde_json::Error>>()?;
-            Ok(Some(Value::Array(comp_values)))
-        }
+    fn serialize(self, world: &World) -> Result<Option<Value>, serde_json::Error> {
+        self.serialize_filtered(world, |_, _| true)
+    }
+
+    fn serialize_filtered<F>(
+        mut self,
+        world: &World,
+        filter: F,
+    ) -> Result<Option<Value>, serde_json::Error>
+    where
+        F: Fn(Entity, &World) -> bool,
+    {
+        section_value(
+            self.iter(world)
+                .filter(|(entity, _)| {
+                    world.get::<SaveExempt>(*entity).is_none() && filter(*entity, world)
+                })
+                .collect(),
+        )
     }
 }
 
+/// The `[[entity, component], ...]` section of `comp_data` in ascending entity order, or
+/// nothing if it's empty.
+pub(crate) fn section_value<C: Serialize>(
+    mut comp_data: Vec<(Entity, &C)>,
+) -> Result<Option<Value>, serde_json::Error> {
+    comp_data.sort_by_key(|(entity, _)| *entity);
+    if comp_data.is_empty() {
+        Ok(None)
+    } else {
+        let comp_values = comp_data
+            .into_iter()
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<Value>, serde_json::Error>>()?;
+        Ok(Some(Value::Array(comp_values)))
+    }
+}
+
+/// The section key of a macro list entry: the `as` name if given, otherwise the type name
+/// without module paths, generic arguments included, e.g. `Stat<Strength>`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __section_name {
+    ($comp_type:ty) => {
+        $crate::registry::short_type_name(stringify!($comp_type))
+    };
+    ($comp_type:ty, $section:literal) => {
+        ::std::string::String::from($section)
+    };
+}
+
+/// Fails to compile if a component type appears more than once in a list, with an error
+/// naming the type ("conflicting implementations of trait `DuplicateComponentInList` for
+/// type `Health`"). The list macros check their lists with it, as a repeated type would
+/// silently overwrite its own section; it can also check a shared type list on its own.
+///
+/// ```
+/// # use bevy_ecs::prelude::*;
+/// # #[derive(Component)] struct Health;
+/// # #[derive(Component)] struct Mana;
+/// bevy_serde_macros::assert_unique_components!(Health, Mana);
+/// ```
+///
+/// ```compile_fail
+/// # use bevy_ecs::prelude::*;
+/// # #[derive(Component)] struct Health;
+/// bevy_serde_macros::assert_unique_components!(Health, Health);
+/// ```
+///
+/// The check declares an item, so lists inside generic functions can't name the function's
+/// type parameters.
+#[macro_export]
+macro_rules! assert_unique_components {
+    ($($(#[$attr:meta])* $comp_type:ty),* $(,)?) => {
+        const _: () = {
+            #[allow(dead_code)]
+            trait DuplicateComponentInList {}
+            $($(#[$attr])* impl DuplicateComponentInList for $comp_type {})*
+        };
+    };
+}
+
+/// Serializes the components of the listed types on every entity marked with `$marker`,
+/// evaluating to a `BTreeMap<String, serde_json::Value>` keyed by component name, with the
+/// entities of each section in ascending order. The map can be post-processed (e.g. to add
+/// metadata or merge sections) before it is written with any serde serializer.
+///
+/// Sections are named after the type without module paths, so generic components such as
+/// `Stat<Strength>` and `Stat<Agility>` get sections of their own. An entry can pick its
+/// section name instead, e.g. `combat::Health as "Health"`; the same entry must then be
+/// passed to `deserialize_individually!`. Listing a type twice is a compile error, see
+/// [`assert_unique_components!`], and two entries ending up with the same section name, such
+/// as `combat::Health` and `ui::Health`, panic until one of them is renamed with `as`.
+///
+/// Entries may carry `cfg` attributes, so one list serves every build configuration:
+/// `#[cfg(feature = "devtools")] DevMarkerData,` is left out of builds without the feature,
+/// and its type need not exist in them.
 #[macro_export]
 macro_rules! serialize_individually {
-  ($world:expr, $ser:expr, $marker:ty, $( $comp_type:ty),*, $(,)?) => {
-      use serde_json::Value;
-      let mut data_map: HashMap<String, Value> = HashMap::new();
+  ($world:expr, $marker:ty, $( $(#[$attr:meta])* $comp_type:ty $(as $section:literal)?),*, $(,)?) => {{
+      $crate::assert_unique_components!($($(#[$attr])* $comp_type),*);
+      let mut claimed = $crate::__private::BTreeMap::new();
       $(
-        let comp_name_fq = stringify!($comp_type);
-        let comp_name = comp_name_fq.rsplit("::").next().unwrap_or(&comp_name_fq);
-        let comp_data_res = SerializeComponents::<$comp_type, $marker>::serialize(
-            $world.query_filtered::<(Entity, &$comp_type), With<$marker>>(),
-            $world,
+        $(#[$attr])*
+        $crate::__private::claim_section(
+            &mut claimed,
+            $crate::__section_name!($comp_type $(, $section)?),
+            stringify!($comp_type),
         );
-        match comp_data_res.unwrap() {
-            Some(comp_data) => data_map.insert(comp_name.to_string(), comp_data),
-            None => None,
-        };
       )*
-      data_map.serialize(&mut $ser).unwrap();
-  };
+      let mut data_map: $crate::__private::BTreeMap<
+          ::std::string::String,
+          $crate::__private::serde_json::Value,
+      > = $crate::__private::BTreeMap::new();
+      $(
+        $(#[$attr])*
+        {
+          let comp_name = $crate::__section_name!($comp_type $(, $section)?);
+          let comp_data_res = $crate::SerializeComponents::<$comp_type, $marker>::serialize(
+              $world.query_filtered::<
+                  ($crate::__private::Entity, &$comp_type),
+                  $crate::__private::With<$marker>,
+              >(),
+              $world,
+          );
+          if let Some(comp_data) = comp_data_res.unwrap() {
+              data_map.insert(comp_name, comp_data);
+          }
+        }
+      )*
+      data_map
+  }};
 }
 
-/// Some entities may exist in the World prior to deserialization, however we assume
-/// these are mutually exclusive from the entities we are restoring. As such, we
-/// don't need to worry about them, as the table below shows (unmapped entities
-/// are those that are pre-existing and exclusive from those we are restoring):
-///  
-/// Entity exists in unmapped | Entity is in entity_map | Result
-///              0            |             0           | create new entity; add to map
-///              0            |             1           | reuse entity in map
-///              1            |             0           | create new entity; add to map
-///              1            |             1           | reuse entity in entity map
-fn get_or_insert(
-    world: &mut World,
-    entity_map: &mut HashMap<Entity, Entity>,
-    entity: Entity,
-) -> Entity {
-    match entity_map.get(&entity) {
-        Some(new_entity) => *new_entity,
-        None => {
-            let new_entity = world.spawn_empty().id();
-            entity_map.insert(entity, new_entity);
-            new_entity
+/// Like `serialize_individually!`, but only serializes the marked entities for which the
+/// `filter` closure (`Fn(Entity, &World) -> bool`) returns true.
+#[macro_export]
+macro_rules! serialize_individually_filtered {
+  ($world:expr, $marker:ty, $filter:expr, $( $(#[$attr:meta])* $comp_type:ty $(as $section:literal)?),*, $(,)?) => {{
+      $crate::assert_unique_components!($($(#[$attr])* $comp_type),*);
+      let mut claimed = $crate::__private::BTreeMap::new();
+      $(
+        $(#[$attr])*
+        $crate::__private::claim_section(
+            &mut claimed,
+            $crate::__section_name!($comp_type $(, $section)?),
+            stringify!($comp_type),
+        );
+      )*
+      let filter = $filter;
+      let mut data_map: $crate::__private::BTreeMap<
+          ::std::string::String,
+          $crate::__private::serde_json::Value,
+      > = $crate::__private::BTreeMap::new();
+      $(
+        $(#[$attr])*
+        {
+          let comp_name = $crate::__section_name!($comp_type $(, $section)?);
+          let comp_data_res = $crate::SerializeComponents::<$comp_type, $marker>::serialize_filtered(
+              $world.query_filtered::<
+                  ($crate::__private::Entity, &$comp_type),
+                  $crate::__private::With<$marker>,
+              >(),
+              $world,
+              &filter,
+          );
+          if let Some(comp_data) = comp_data_res.unwrap() {
+              data_map.insert(comp_name, comp_data);
+          }
         }
-    }
+      )*
+      data_map
+  }};
+}
+
+/// Like `serialize_individually!`, but visits each marked entity once and writes a single
+/// record per entity holding whichever of the listed components it has, keyed by component
+/// name. Evaluates to a map from [`layout::entity_key`] to record, i.e. a document in the
+/// [`SaveLayout::ByEntity`] layout; [`layout::from_entity_layout`] turns it back into the
+/// sections `deserialize_individually!` reads. Marked entities with none of the components
+/// are left out.
+#[macro_export]
+macro_rules! serialize_grouped {
+  ($world:expr, $marker:ty, $( $(#[$attr:meta])* $comp_type:ty $(as $section:literal)?),*, $(,)?) => {{
+      $crate::assert_unique_components!($($(#[$attr])* $comp_type),*);
+      let mut claimed = $crate::__private::BTreeMap::new();
+      $(
+        $(#[$attr])*
+        $crate::__private::claim_section(
+            &mut claimed,
+            $crate::__section_name!($comp_type $(, $section)?),
+            stringify!($comp_type),
+        );
+      )*
+      let mut data_map: $crate::__private::BTreeMap<
+          ::std::string::String,
+          $crate::__private::serde_json::Value,
+      > = $crate::__private::BTreeMap::new();
+      let mut query = $world.query_filtered::<
+          ($crate::__private::Entity, $crate::__private::EntityRef),
+          $crate::__private::With<$marker>,
+      >();
+      for (entity, entity_ref) in query.iter($world) {
+          if entity_ref.contains::<$crate::SaveExempt>() {
+              continue;
+          }
+          let mut record = $crate::__private::serde_json::Map::new();
+          $(
+            $(#[$attr])*
+            if let Some(comp) = entity_ref.get::<$comp_type>() {
+                record.insert(
+                    $crate::__section_name!($comp_type $(, $section)?),
+                    $crate::__private::serde_json::to_value(comp).unwrap(),
+                );
+            }
+          )*
+          if !record.is_empty() {
+              data_map.insert(
+                  $crate::layout::entity_key(entity),
+                  $crate::__private::serde_json::Value::Object(record),
+              );
+          }
+      }
+      data_map
+  }};
 }
 
-fn revive_or_rejuv_entity<'de, C: Component + Deserialize<'de>, M: Component + Clone>(
+pub(crate) fn revive_or_rejuv_entity<'de, C: Component + Deserialize<'de>, M: Component + Clone>(
     entity_comps: Vec<(Entity, C)>,
     marker: M,
 ) -> Box<EntityMapperDynFn> {
-    Box::new(
-        move |world: &mut World, mapper: &mut HashMap<Entity, Entity>| {
-            entity_comps.into_iter().for_each(|(entity, comp)| {
-                let new_entity = get_or_insert(world, mapper, entity);
-                world.entity_mut(new_entity).insert((comp, marker.clone()));
-            });
-        },
-    )
+    Box::new(move |world: &mut World, mapper: &mut EntityMap| {
+        entity_comps.into_iter().for_each(|(entity, comp)| {
+            let new_entity = get_or_insert(world, mapper, entity);
+            world.entity_mut(new_entity).insert((comp, marker.clone()));
+        });
+    })
 }
 
 #[allow(dead_code)]
 pub fn deserialize<C: Component + DeserializeOwned, M: Component + Clone>(
     world: &mut World,
-    entity_map: &mut HashMap<Entity, Entity>,
-    component_json_obj: &mut HashMap<String, Value>,
+    entity_map: &mut EntityMap,
+    component_json_obj: &mut impl SectionMap,
     component_name: &str,
     marker: M,
 ) -> Result<(), serde_json::Error> {
     // to avoid memory duplication, we remove the component vec from the map,
     // allowing the deserializer to take ownership
     let comp_vec_value = component_json_obj
-        .remove(component_name)
+        .take_section(component_name)
         .unwrap_or(EMPTY_JS_ARRAY);
-    component_json_obj.shrink_to_fit();
 
     let entity_comps: Vec<(Entity, C)> = serde_json::from_value(comp_vec_value)?;
 
@@ -148,16 +515,16 @@ pub fn deserialize<C: Component + DeserializeOwned, M: Component + Clone>(
 
 #[macro_export]
 macro_rules! deserialize_individually {
-  ($world:expr, $emap:expr, $json_map:expr, $marker:expr, $( $comp_type:ty),*, $(,)?) => {
+  ($world:expr, $emap:expr, $json_map:expr, $marker:expr, $( $(#[$attr:meta])* $comp_type:ty $(as $section:literal)?),*, $(,)?) => {
   {
+      $crate::assert_unique_components!($($(#[$attr])* $comp_type),*);
       $(
-          let comp_name_fq = stringify!($comp_type);
-          let comp_name = comp_name_fq.rsplit("::").next().unwrap_or(&comp_name_fq);
-          deserialize::<$comp_type, _>(
+          $(#[$attr])*
+          $crate::deserialize::<$comp_type, _>(
               $world,
               $emap,
               $json_map,
-              &comp_name,
+              &$crate::__section_name!($comp_type $(, $section)?),
               $marker,
           )
           .unwrap();
@@ -170,30 +537,38 @@ macro_rules! deserialize_individually {
 mod tests {
 
     use super::*;
+    use bevy_ecs::entity::{EntityMapper, MapEntities};
     use serde::{Deserialize, Serialize};
 
+    #[allow(clippy::enum_variant_names)]
     #[derive(Serialize, Deserialize)]
-    enum TestEnum {
+    pub enum TestEnum {
         ATest(String),
         BTest(u32),
         CTest,
     }
 
-    #[derive(Clone, Component)]
+    #[derive(Clone, Component, Default)]
     pub struct SerializeMe;
 
-    #[derive(Component, Serialize, Deserialize)]
+    #[derive(Clone, Component, Serialize, Deserialize)]
     pub struct Component1;
 
-    #[derive(Component, Serialize, Deserialize)]
+    #[derive(Clone, Component, Serialize, Deserialize)]
     pub struct Component2 {
-        target: Entity,
+        pub target: Entity,
+    }
+
+    impl MapEntities for Component2 {
+        fn map_entities(&mut self, entity_mapper: &mut EntityMapper) {
+            self.target = entity_mapper.get_or_reserve(self.target);
+        }
     }
 
     #[derive(Component, Serialize, Deserialize)]
     pub struct Component3 {
-        target: Entity,
-        test_enum: TestEnum,
+        pub target: Entity,
+        pub test_enum: TestEnum,
     }
 
     // We dont want to have any entities for this for testing purposes
@@ -215,16 +590,138 @@ mod tests {
     pub fn save_game(ecs: &mut World) -> Vec<u8> {
         let writer = Vec::new();
         let mut serializer = serde_json::Serializer::new(writer);
-        execute_with_type_list!(serialize_individually!(ecs, serializer, SerializeMe));
+        let data_map = execute_with_type_list!(serialize_individually!(ecs, SerializeMe));
+        data_map.serialize(&mut serializer).unwrap();
         serializer.into_inner()
     }
 
+    #[test]
+    fn test_output_is_deterministic() {
+        let build = || {
+            let mut world = World::default();
+            for _ in 0..20 {
+                let entity = world.spawn((Component1, SerializeMe)).id();
+                world.spawn((Component2 { target: entity }, SerializeMe));
+            }
+            world
+        };
+        let saves: Vec<Vec<u8>> = (0..4).map(|_| save_game(&mut build())).collect();
+        assert!(saves.windows(2).all(|pair| pair[0] == pair[1]));
+        let save_json: Value = serde_json::from_slice(&saves[0]).unwrap();
+        let entities: Vec<u64> = save_json["Component1"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry[0].as_u64().unwrap())
+            .collect();
+        assert!(entities.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_filtered_serialization() {
+        let mut world = World::default();
+        let near = world.spawn((Component1, SerializeMe)).id();
+        let far = world.spawn((Component1, SerializeMe)).id();
+        world.spawn((Component2 { target: near }, SerializeMe));
+
+        let ecs = &mut world;
+        let save_json = execute_with_type_list!(serialize_individually_filtered!(
+            ecs,
+            SerializeMe,
+            |entity: Entity, world: &World| entity != far
+                && world.get::<Component1>(entity).is_some()
+        ));
+        assert_eq!(save_json.len(), 1);
+        assert_eq!(
+            save_json["Component1"],
+            serde_json::json!([[near.to_bits(), null]])
+        );
+    }
+
+    #[test]
+    fn test_grouped_serialization() {
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        let entity2 = world
+            .spawn((Component1, Component2 { target: entity1 }, SerializeMe))
+            .id();
+        world.spawn(SerializeMe);
+
+        let ecs = &mut world;
+        let grouped = execute_with_type_list!(serialize_grouped!(ecs, SerializeMe));
+        assert_eq!(grouped.len(), 2);
+        assert_eq!(
+            grouped[&layout::entity_key(entity2)],
+            serde_json::json!({ "Component1": null, "Component2": { "target": entity1 } })
+        );
+
+        let by_component = execute_with_type_list!(serialize_individually!(ecs, SerializeMe));
+        let regrouped = layout::from_entity_layout(&grouped.into_iter().collect()).unwrap();
+        assert_eq!(regrouped, by_component.into_iter().collect());
+    }
+
+    #[derive(Component, Serialize, Deserialize, Debug, PartialEq)]
+    struct Stat<T: Send + Sync + 'static> {
+        value: u8,
+        #[serde(skip)]
+        kind: std::marker::PhantomData<T>,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Strength;
+
+    #[derive(Debug, PartialEq)]
+    struct Agility;
+
+    fn stat<T: Send + Sync + 'static>(value: u8) -> Stat<T> {
+        Stat {
+            value,
+            kind: std::marker::PhantomData,
+        }
+    }
+
+    #[test]
+    fn test_generic_and_renamed_entries() {
+        let mut world = World::default();
+        world.spawn((
+            stat::<Strength>(3),
+            stat::<Agility>(5),
+            Component1,
+            SerializeMe,
+        ));
+        let ecs = &mut world;
+        let saved = serialize_individually!(
+            ecs,
+            SerializeMe,
+            Stat<Strength>,
+            self::Stat<self::Agility>,
+            Component1 as "Marker1",
+        );
+        let names: Vec<&str> = saved.keys().map(String::as_str).collect();
+        assert_eq!(names, vec!["Marker1", "Stat<Agility>", "Stat<Strength>"]);
+
+        let mut loaded = World::default();
+        let ecs = &mut loaded;
+        let mut entity_map = EntityMap::new();
+        deserialize_individually!(
+            ecs,
+            &mut entity_map,
+            &mut saved.clone(),
+            SerializeMe,
+            Stat<Strength>,
+            Stat<Agility>,
+            Component1 as "Marker1",
+        );
+        let mut query = loaded.query::<(&Stat<Strength>, &Stat<Agility>, &Component1)>();
+        let (strength, agility, _) = query.single(&loaded);
+        assert_eq!((strength.value, agility.value), (3, 5));
+    }
+
     #[allow(dead_code)]
-    pub fn load_game(ecs: &mut World, save_data: Vec<u8>) -> () {
-        ecs.clear_entities();
-        let mut entity_map = HashMap::new();
-        let mut component_value_map: HashMap<String, Value> =
-            serde_json::from_slice(&save_data).unwrap();
+    pub fn load_game(ecs: &mut World, save_data: Vec<u8>) {
+        prepare_world_for_load::<SerializeMe>(ecs);
+        let mut entity_map = EntityMap::new();
+        let mut component_value_map: SaveDocument = serde_json::from_slice(&save_data).unwrap();
         execute_with_type_list!(deserialize_individually!(
             ecs,
             &mut entity_map,
@@ -249,16 +746,16 @@ mod tests {
             .id();
 
         let save_data = save_game(&mut world);
-        let save_json: HashMap<String, Value> = serde_json::from_slice(&save_data).unwrap();
-        let expected_json: HashMap<String, Value> = serde_json::from_str("{}").unwrap();
+        let save_json: SaveDocument = serde_json::from_slice(&save_data).unwrap();
+        let expected_json: SaveDocument = serde_json::from_str("{}").unwrap();
         assert_eq!(save_json, expected_json);
 
         world.get_entity_mut(entity1).unwrap().insert(SerializeMe);
         world.get_entity_mut(entity2).unwrap().insert(SerializeMe);
 
         let save_data = save_game(&mut world); // Normally you would save this to a file
-        let save_json: HashMap<String, Value> = serde_json::from_slice(&save_data).unwrap();
-        let expected_json: HashMap<String, Value> = serde_json::from_str(
+        let save_json: SaveDocument = serde_json::from_slice(&save_data).unwrap();
+        let expected_json: SaveDocument = serde_json::from_str(
             r#"{"Component3": [[1, {"target": 0, "test_enum": {"ATest": "test"}}]], "Component2": [[1, {"target": 0}]], "Component1": [[0, null], [1, null]]}"#,
         ).unwrap();
         assert_eq!(save_json, expected_json);
@@ -266,8 +763,8 @@ mod tests {
         world.clear_all();
         let cleared_save_data = save_game(&mut world);
         assert_eq!(
-            serde_json::from_slice::<HashMap<String, Value>>(&cleared_save_data).unwrap(),
-            serde_json::from_str::<HashMap<String, Value>>("{}").unwrap()
+            serde_json::from_slice::<SaveDocument>(&cleared_save_data).unwrap(),
+            serde_json::from_str::<SaveDocument>("{}").unwrap()
         );
         load_game(&mut world, save_data.clone());
 
@@ -275,3 +772,114 @@ mod tests {
         assert_eq!(save_data2, save_data);
     }
 }
+
+#[cfg(test)]
+mod macro_hygiene_tests {
+    // deliberately no imports: the macros must expand using paths of their own, and must
+    // not clash with local items sharing names with what they use
+    #[allow(dead_code)]
+    struct Value;
+    #[allow(dead_code)]
+    struct HashMap;
+
+    #[derive(Clone, bevy_ecs::component::Component)]
+    struct Marker;
+
+    #[derive(bevy_ecs::component::Component, serde::Serialize, serde::Deserialize)]
+    struct Health(u32);
+
+    #[test]
+    fn test_macros_expand_without_imports() {
+        let mut world = bevy_ecs::world::World::default();
+        world.spawn((Health(3), Marker));
+        let saved = crate::serialize_individually!(&mut world, Marker, Health,);
+        let filtered = crate::serialize_individually_filtered!(
+            &mut world,
+            Marker,
+            |_: bevy_ecs::entity::Entity, _: &bevy_ecs::world::World| true,
+            Health,
+        );
+        assert_eq!(saved, filtered);
+
+        let mut json_map = saved;
+
+        let mut entity_map = crate::EntityMap::new();
+        let mut restored = bevy_ecs::world::World::default();
+        crate::deserialize_individually!(
+            &mut restored,
+            &mut entity_map,
+            &mut json_map,
+            Marker,
+            Health,
+        );
+        assert_eq!(restored.query::<&Health>().single(&restored).0, 3);
+    }
+
+    mod ui {
+        #[derive(bevy_ecs::component::Component, serde::Serialize)]
+        pub struct Health;
+    }
+
+    #[test]
+    #[should_panic(expected = "would both be saved in section \"Health\"")]
+    fn test_section_name_collision_panics() {
+        let mut world = bevy_ecs::world::World::default();
+        crate::serialize_individually!(&mut world, Marker, Health, ui::Health,);
+    }
+
+    #[test]
+    fn test_as_disambiguates_section_names() {
+        let mut world = bevy_ecs::world::World::default();
+        world.spawn((Health(3), ui::Health, Marker));
+        let saved =
+            crate::serialize_grouped!(&mut world, Marker, Health, ui::Health as "HealthBar",);
+        let record = saved.values().next().unwrap();
+        assert_eq!(record["Health"], 3);
+        assert!(record["HealthBar"].is_null());
+    }
+
+    #[derive(bevy_ecs::component::Component, serde::Serialize, serde::Deserialize)]
+    struct Mana(u32);
+
+    #[test]
+    fn test_cfg_gated_entries() {
+        let mut world = bevy_ecs::world::World::default();
+        world.spawn((Health(3), Mana(5), Marker));
+        // `DevOnly` doesn't exist, which is fine as long as its entry is configured out
+        let saved = crate::serialize_individually!(
+            &mut world,
+            Marker,
+            Health,
+            #[cfg(any())]
+            DevOnly,
+            #[cfg(all())]
+            Mana as "Magic",
+        );
+        assert_eq!(saved.keys().collect::<Vec<_>>(), vec!["Health", "Magic"]);
+        let grouped = crate::serialize_grouped!(
+            &mut world,
+            Marker,
+            #[cfg(any())]
+            Mana,
+            Health,
+        );
+        assert_eq!(
+            grouped.values().next().unwrap().as_object().unwrap().len(),
+            1
+        );
+
+        let mut json_map = saved;
+        let mut restored = bevy_ecs::world::World::default();
+        crate::deserialize_individually!(
+            &mut restored,
+            &mut crate::EntityMap::new(),
+            &mut json_map,
+            Marker,
+            #[cfg(any())]
+            DevOnly,
+            #[cfg(all())]
+            Mana as "Magic",
+        );
+        assert_eq!(restored.query::<&Mana>().single(&restored).0, 5);
+    }
+}
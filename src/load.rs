@@ -0,0 +1,559 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::time::Duration;
+
+use bevy_ecs::prelude::*;
+use bevy_hierarchy::DespawnRecursiveExt;
+use bevy_utils::tracing::info_span;
+use bevy_utils::Instant;
+use serde::de::Error;
+
+use crate::delta::section_entries;
+use crate::entity_map::EntityMap;
+use crate::layout::is_reserved;
+use crate::manifest::MANIFEST_KEY;
+use crate::registry::SaveRegistry;
+use crate::validate::ValidationError;
+use crate::SaveDocument;
+
+/// What happens to the entities already in the world when a save is loaded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LoadMode {
+    /// Restored entities are spawned alongside the existing ones, which are left untouched.
+    #[default]
+    Merge,
+    /// Every entity in the world is despawned before loading.
+    Replace,
+    /// The marked entities and their descendants are despawned before loading, as by
+    /// [`prepare_world_for_load`]; UI, cameras and other unmarked entities survive.
+    ReplaceMarked,
+    /// "Load checkpoint" semantics for a save of this same world: marked entities that are
+    /// in the save are restored in place, keeping their ids, and marked entities missing
+    /// from it are despawned. Unmarked entities survive.
+    Sync,
+}
+
+/// How the entities restored by a load are numbered, see
+/// [`SaveRegistry::set_entity_generations`].
+///
+/// An [`Entity`] is an index plus a generation, bumped each time the index is reused.
+/// Keeping saved ids makes loaded worlds line up with the saved ones, which tools diffing
+/// a live world against a save need, but only works where those ids are free: in an empty
+/// world, or one holding only the entities being restored in place.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EntityGenerations {
+    /// Entities are spawned afresh, taking whatever ids the world hands out, and the
+    /// [`LoadReport::entity_map`] relates them to the saved ids. Loads never conflict with
+    /// the entities already in the world.
+    #[default]
+    Remap,
+    /// Entities are restored with their saved index and generation, so loaded ids equal
+    /// saved ids.
+    Preserve,
+    /// Entities are restored with their saved index and generation 0, so ids are
+    /// predictable without carrying over the generations of the world that was saved, e.g.
+    /// when comparing saves of separate runs.
+    Reset,
+}
+
+/// An entry of a save that failed to deserialize and was left out of the load, see
+/// [`SaveRegistry::set_skip_corrupt_entries`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SkippedEntry {
+    /// The section name of the component.
+    pub component: String,
+    /// The entry's position in its section.
+    pub index: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SkippedEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "skipped {} entry {}: {}",
+            self.component, self.index, self.message
+        )
+    }
+}
+
+/// A restored component referring to a saved entity that wasn't restored. The reference
+/// points at an id that is never alive in the world.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DanglingReference {
+    /// The section name of the referring component.
+    pub component: String,
+    /// The saved id of the missing entity.
+    pub target: Entity,
+}
+
+impl fmt::Display for DanglingReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} refers to missing entity {:?}",
+            self.component, self.target
+        )
+    }
+}
+
+/// The outcome of [`SaveRegistry::load`].
+#[derive(Clone, Debug, Default)]
+pub struct LoadReport {
+    /// Maps saved entities to the entities they were restored into.
+    pub entity_map: EntityMap,
+    /// The marked entities despawned before loading: in [`LoadMode::Sync`] those the save
+    /// doesn't contain, and in [`LoadMode::ReplaceMarked`] all of them.
+    pub despawned: Vec<Entity>,
+    /// Loaded components that failed their [`ValidateOnLoad`](crate::ValidateOnLoad)
+    /// check. They are loaded regardless; it's up to the caller to reject the save.
+    pub validation_errors: Vec<ValidationError>,
+    /// The number of components restored, by section name.
+    pub restored: BTreeMap<String, usize>,
+    /// The number of entities spawned for the save.
+    pub created: usize,
+    /// The number of existing entities the save was restored into, in
+    /// [`LoadMode::Sync`].
+    pub reused: usize,
+    /// Sections of the save that no registered component claims.
+    pub unknown_sections: Vec<String>,
+    pub skipped_entries: Vec<SkippedEntry>,
+    /// Entries naming an enum variant this build doesn't know, handled by the fallback set
+    /// with [`on_unknown_variant`](SaveRegistry::on_unknown_variant).
+    pub unknown_variants: Vec<SkippedEntry>,
+    pub dangling_references: Vec<DanglingReference>,
+    /// The sections brought up to date by [`load_migrated`](SaveRegistry::load_migrated).
+    pub migrations_applied: Vec<String>,
+    pub duration: Duration,
+}
+
+/// Despawns the entities marked with `M`, along with their descendants, so that a save
+/// can be loaded without wiping the rest of the world the way `World::clear_entities`
+/// does. Returns the despawned marked entities.
+pub fn prepare_world_for_load<M: Component>(world: &mut World) -> Vec<Entity> {
+    let marked: Vec<Entity> = world
+        .query_filtered::<Entity, With<M>>()
+        .iter(world)
+        .collect();
+    for entity in &marked {
+        // a marked entity may already be gone as the descendant of another
+        if let Some(entity_mut) = world.get_entity_mut(*entity) {
+            entity_mut.despawn_recursive();
+        }
+    }
+    marked
+}
+
+/// Every entity that appears in a component section of `doc`, leaving out reserved entries
+/// such as the manifest and namespaces.
+pub(crate) fn saved_entities(doc: &SaveDocument) -> Result<BTreeSet<Entity>, serde_json::Error> {
+    let mut entities = BTreeSet::new();
+    for (name, section) in doc.iter().filter(|(name, _)| !is_reserved(name)) {
+        entities.extend(
+            section_entries(name, section)?
+                .into_iter()
+                .map(|(entity, _)| entity),
+        );
+    }
+    Ok(entities)
+}
+
+impl SaveRegistry {
+    /// Loads a save document written by [`serialize`](Self::serialize), adding `marker` to
+    /// every restored entity.
+    pub fn load<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        doc: &mut SaveDocument,
+        mode: LoadMode,
+        marker: M,
+    ) -> Result<LoadReport, serde_json::Error> {
+        doc.remove(MANIFEST_KEY);
+        self.load_filtered(world, doc, mode, |_| true, marker)
+    }
+
+    /// Like [`load`](Self::load), but only restores the components whose section name
+    /// passes `filter`, leaving the other sections in `doc`. In [`LoadMode::Sync`] only
+    /// those components are reset on the entities restored in place.
+    pub fn load_filtered<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        doc: &mut SaveDocument,
+        mode: LoadMode,
+        filter: impl Fn(&str) -> bool,
+        marker: M,
+    ) -> Result<LoadReport, serde_json::Error> {
+        self.load_seeded(world, doc, mode, filter, marker, &mut EntityMap::new())
+    }
+
+    /// Loads `doc` additively, in [`LoadMode::Merge`], through the world's long-lived
+    /// [`EntityMap`] resource, which is created if missing and updated with the restored
+    /// entities. Streaming several region files into one world this way keeps references
+    /// between files consistent:
+    /// - a saved entity already in the map is restored into the entity it was mapped to
+    ///   before, gaining the components of this file and overwriting any it had;
+    /// - references to entities restored by earlier loads resolve to them, and references
+    ///   to entities that aren't loaded yet stay dangling, as in a single load;
+    /// - entries whose entity has since been despawned are dropped before loading, so
+    ///   such saved entities are spawned afresh.
+    ///
+    /// The report's entity map only holds the entities of `doc`.
+    pub fn load_additive<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        doc: &mut SaveDocument,
+        marker: M,
+    ) -> Result<LoadReport, serde_json::Error> {
+        let mut entity_map = world.remove_resource::<EntityMap>().unwrap_or_default();
+        entity_map.retain(|_, entity| world.get_entity(*entity).is_some());
+        doc.remove(MANIFEST_KEY);
+        let result = self.load_seeded(
+            world,
+            doc,
+            LoadMode::Merge,
+            |_| true,
+            marker,
+            &mut entity_map,
+        );
+        world.insert_resource(entity_map);
+        result
+    }
+
+    /// Spawns the saved entities missing from `entity_map` under the ids the registry's
+    /// [`EntityGenerations`] asks for. Fails without spawning anything if one of those ids
+    /// is taken.
+    fn spawn_with_saved_ids(
+        &self,
+        world: &mut World,
+        entity_map: &mut EntityMap,
+        saved: &BTreeSet<Entity>,
+    ) -> Result<(), serde_json::Error> {
+        let targets: Vec<(Entity, Entity)> = saved
+            .iter()
+            .filter(|entity| !entity_map.contains_key(entity))
+            .filter_map(|entity| match self.entity_generations() {
+                EntityGenerations::Remap => None,
+                EntityGenerations::Preserve => Some((*entity, *entity)),
+                EntityGenerations::Reset => Some((*entity, Entity::from_raw(entity.index()))),
+            })
+            .collect();
+        let taken = targets.iter().find_map(|(_, target)| {
+            world
+                .entities()
+                .resolve_from_id(target.index())
+                .filter(|live| world.get_entity(*live).is_some())
+        });
+        if let Some(taken) = taken {
+            return Err(serde_json::Error::custom(format!(
+                "can't restore saved entity ids, as {taken:?} is already in use; \
+                 load into an empty world or use EntityGenerations::Remap"
+            )));
+        }
+        for (entity, target) in targets {
+            world.get_or_spawn(target);
+            entity_map.insert(entity, target);
+        }
+        Ok(())
+    }
+
+    /// [`load_filtered`](Self::load_filtered), starting from the mappings already in
+    /// `entity_map`.
+    fn load_seeded<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        doc: &mut SaveDocument,
+        mode: LoadMode,
+        filter: impl Fn(&str) -> bool,
+        marker: M,
+        entity_map: &mut EntityMap,
+    ) -> Result<LoadReport, serde_json::Error> {
+        let start = Instant::now();
+        let mut report = LoadReport::default();
+        let saved = saved_entities(doc)?;
+        let prepare_span = info_span!("prepare", ?mode).entered();
+        match mode {
+            LoadMode::Merge => {}
+            LoadMode::Replace => world.clear_entities(),
+            LoadMode::ReplaceMarked => report.despawned = prepare_world_for_load::<M>(world),
+            LoadMode::Sync => {
+                let marked: Vec<Entity> = world
+                    .query_filtered::<Entity, With<M>>()
+                    .iter(world)
+                    .collect();
+                for entity in marked {
+                    if saved.contains(&entity) {
+                        for reg in self.iter().filter(|reg| filter(reg.name())) {
+                            (reg.remove)(world, entity);
+                        }
+                        entity_map.insert(entity, entity);
+                    } else {
+                        world.despawn(entity);
+                        report.despawned.push(entity);
+                    }
+                }
+            }
+        }
+        prepare_span.exit();
+        let reused: BTreeSet<Entity> = saved
+            .iter()
+            .filter(|entity| entity_map.contains_key(entity))
+            .copied()
+            .collect();
+        report.reused = reused.len();
+        self.spawn_with_saved_ids(world, entity_map, &saved)?;
+        self.deserialize_marked(
+            world,
+            entity_map,
+            doc,
+            filter,
+            marker,
+            &reused,
+            Some(&mut report),
+        )?;
+        report.entity_map = saved
+            .iter()
+            .filter_map(|old| entity_map.get(old).map(|new| (*old, *new)))
+            .collect();
+        report.created = report
+            .entity_map
+            .keys()
+            .filter(|old| !reused.contains(old))
+            .count();
+        let mut loaded: Vec<Entity> = report.entity_map.values().copied().collect();
+        loaded.sort();
+        report.validation_errors = self.validate(world, &loaded);
+        report.duration = start.elapsed();
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_hierarchy::BuildWorldChildren;
+    use serde_json::Value;
+
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_entity_generations() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .register_mapped::<Component2>();
+        let mut world = World::default();
+        // bump the generation of the entities' indices
+        for _ in 0..3 {
+            let entities = [world.spawn_empty().id(), world.spawn_empty().id()];
+            for entity in entities {
+                world.despawn(entity);
+            }
+        }
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        let entity2 = world
+            .spawn((Component2 { target: entity1 }, SerializeMe))
+            .id();
+        assert_ne!(entity1.generation(), 0);
+        let doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        let load = |registry: &SaveRegistry, world: &mut World| {
+            registry.load(world, &mut doc.clone(), LoadMode::Merge, SerializeMe)
+        };
+
+        let mut preserved = World::default();
+        registry.set_entity_generations(EntityGenerations::Preserve);
+        let report = load(&registry, &mut preserved).unwrap();
+        assert_eq!(report.entity_map[&entity1], entity1);
+        assert_eq!(
+            preserved.get::<Component2>(entity2).unwrap().target,
+            entity1
+        );
+        // the ids are taken now
+        assert!(load(&registry, &mut preserved).is_err());
+
+        registry.set_entity_generations(EntityGenerations::Reset);
+        let report = load(&registry, &mut World::default()).unwrap();
+        assert_eq!(
+            report.entity_map[&entity2],
+            Entity::from_raw(entity2.index())
+        );
+
+        registry.set_entity_generations(EntityGenerations::Remap);
+        let report = load(&registry, &mut preserved).unwrap();
+        assert_eq!(report.created, 2);
+    }
+
+    #[test]
+    fn test_load_modes() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Component2>();
+        let mut world = World::default();
+        world.spawn((Component1, SerializeMe));
+        let unmarked = world.spawn(Component1).id();
+        let doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+
+        let report = registry
+            .load(&mut world, &mut doc.clone(), LoadMode::Merge, SerializeMe)
+            .unwrap();
+        assert_eq!(report.entity_map.len(), 1);
+        assert!(world.get_entity(unmarked).is_some());
+        assert_eq!(world.query::<&Component1>().iter(&world).count(), 3);
+
+        registry
+            .load(&mut world, &mut doc.clone(), LoadMode::Replace, SerializeMe)
+            .unwrap();
+        assert!(world.get_entity(unmarked).is_none());
+        assert_eq!(world.query::<&Component1>().iter(&world).count(), 1);
+    }
+
+    #[test]
+    fn test_replace_marked_keeps_unmarked() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>();
+        let mut world = World::default();
+        world.spawn((Component1, SerializeMe));
+        let camera = world.spawn(Component1).id();
+        let doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        let parent = world.spawn((Component1, SerializeMe)).id();
+        let child = world.spawn(Component1).set_parent(parent).id();
+
+        let report = registry
+            .load(
+                &mut world,
+                &mut doc.clone(),
+                LoadMode::ReplaceMarked,
+                SerializeMe,
+            )
+            .unwrap();
+        assert_eq!(report.despawned.len(), 2);
+        assert!(world.get_entity(camera).is_some());
+        assert!(world.get_entity(child).is_none());
+        assert_eq!(world.query::<&SerializeMe>().iter(&world).count(), 1);
+    }
+
+    #[test]
+    fn test_additive_loads_share_entity_map() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .register_mapped::<Component2>();
+        let mut world = World::default();
+        let door = world.spawn((Component1, SerializeMe)).id();
+        let key = world.spawn((Component2 { target: door }, SerializeMe)).id();
+        let region_a = registry
+            .serialize_filtered::<SerializeMe>(&mut world, |entity, _| entity == door)
+            .unwrap();
+        let region_b = registry
+            .serialize_filtered::<SerializeMe>(&mut world, |entity, _| entity == key)
+            .unwrap();
+
+        let mut loaded = World::default();
+        registry
+            .load_additive(&mut loaded, &mut region_a.clone(), SerializeMe)
+            .unwrap();
+        let report = registry
+            .load_additive(&mut loaded, &mut region_b.clone(), SerializeMe)
+            .unwrap();
+        assert_eq!(report.entity_map.len(), 1);
+        let entity_map = loaded.resource::<EntityMap>();
+        assert_eq!(
+            loaded.get::<Component2>(entity_map[&key]).unwrap().target,
+            entity_map[&door]
+        );
+
+        let report = registry
+            .load_additive(&mut loaded, &mut region_b.clone(), SerializeMe)
+            .unwrap();
+        assert_eq!((report.created, report.reused), (0, 1));
+        assert_eq!(loaded.query::<&Component2>().iter(&loaded).count(), 1);
+    }
+
+    #[test]
+    fn test_sync_load_despawns_missing() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Component2>();
+        let mut world = World::default();
+        let kept = world.spawn((Component1, SerializeMe)).id();
+        let unmarked = world.spawn(Component1).id();
+        let doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+
+        world.entity_mut(kept).insert(Component2 { target: kept });
+        let spawned = world.spawn((Component1, SerializeMe)).id();
+        let report = registry
+            .load(&mut world, &mut doc.clone(), LoadMode::Sync, SerializeMe)
+            .unwrap();
+        assert_eq!(report.despawned, vec![spawned]);
+        assert_eq!(report.entity_map[&kept], kept);
+        assert!(world.get::<Component1>(kept).is_some());
+        assert!(world.get::<Component2>(kept).is_none());
+        assert!(world.get_entity(unmarked).is_some());
+        assert!(world.get_entity(spawned).is_none());
+    }
+
+    #[test]
+    fn test_selective_load() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Component2>();
+        let mut world = World::default();
+        let entity = world
+            .spawn((Component1, SerializeMe))
+            .insert(Component2 {
+                target: Entity::PLACEHOLDER,
+            })
+            .id();
+        let mut doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+
+        world
+            .entity_mut(entity)
+            .remove::<(Component1, Component2)>();
+        let report = registry
+            .load_filtered(
+                &mut world,
+                &mut doc,
+                LoadMode::Sync,
+                |name| name == "Component1",
+                SerializeMe,
+            )
+            .unwrap();
+        assert!(report.despawned.is_empty());
+        assert!(world.get::<Component1>(entity).is_some());
+        assert!(world.get::<Component2>(entity).is_none());
+        assert!(doc.contains_key("Component2") && doc.contains_key(MANIFEST_KEY));
+        assert!(!doc.contains_key("Component1"));
+    }
+
+    #[test]
+    fn test_load_report() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .register_mapped::<Component2>()
+            .set_skip_corrupt_entries(true);
+        let mut world = World::default();
+        let kept = world.spawn((Component1, SerializeMe)).id();
+        let outside = world.spawn(Component1).id();
+        world.spawn((Component2 { target: outside }, SerializeMe));
+        let mut doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        doc.insert("Unknown".to_string(), Value::Array(Vec::new()));
+        let Value::Array(entries) = doc.get_mut("Component2").unwrap() else {
+            panic!("Component2 section is not an array");
+        };
+        entries.push(serde_json::json!([kept, { "target": "corrupt" }]));
+
+        let report = registry
+            .load(&mut world, &mut doc, LoadMode::Sync, SerializeMe)
+            .unwrap();
+        assert_eq!((report.created, report.reused), (0, 2));
+        assert_eq!(report.restored["Component1"], 1);
+        assert_eq!(report.restored["Component2"], 1);
+        assert_eq!(report.unknown_sections, vec!["Unknown"]);
+        assert_eq!(report.skipped_entries.len(), 1);
+        assert_eq!(report.skipped_entries[0].index, 1);
+        assert_eq!(
+            report.dangling_references,
+            vec![DanglingReference {
+                component: "Component2".to_string(),
+                target: outside,
+            }]
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use bevy_ecs::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use serde_json::Value;
+
+use crate::entity_map::EntityMap;
+use crate::registry::{insert_components, ComponentRegistration, LoadHookFn, SaveRegistry};
+use crate::schema::trace_or_opaque;
+
+/// A component of which only part is saved, the rest being rebuilt from the world on load,
+/// e.g. a sprite whose texture handle is looked up again from its saved path. Register it
+/// with [`SaveRegistry::register_from_save`].
+pub trait LoadFromSave: Component + Sized {
+    /// The persisted part of the component.
+    type Saved: Serialize + DeserializeOwned;
+
+    fn to_saved(&self) -> Self::Saved;
+
+    /// Rebuilds the component from its saved part. Called once per loaded component,
+    /// before it is inserted, so the world doesn't contain it yet.
+    fn from_saved(saved: Self::Saved, world: &mut World) -> Self;
+}
+
+fn extract_saved<C: LoadFromSave>(
+    world: &World,
+    entities: &[Entity],
+) -> Result<Option<Value>, serde_json::Error> {
+    let comp_values = entities
+        .iter()
+        .filter_map(|entity| {
+            world
+                .get::<C>(*entity)
+                .map(|comp| (*entity, comp.to_saved()))
+        })
+        .map(|entry| serde_json::to_value(&entry))
+        .collect::<Result<Vec<Value>, serde_json::Error>>()?;
+    if comp_values.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(Value::Array(comp_values)))
+    }
+}
+
+fn insert_saved<C: LoadFromSave>(
+    world: &mut World,
+    entity_map: &mut EntityMap,
+    section: Value,
+    load_hooks: &[LoadHookFn],
+) -> Result<Vec<Entity>, serde_json::Error> {
+    let saved: Vec<(Entity, C::Saved)> = serde_json::from_value(section)?;
+    let entity_comps: Vec<_> = saved
+        .into_iter()
+        .map(|(entity, saved)| (entity, C::from_saved(saved, world)))
+        .collect();
+    Ok(insert_components(
+        world,
+        entity_map,
+        entity_comps,
+        load_hooks,
+    ))
+}
+
+impl SaveRegistry {
+    /// Registers `C`, saving only its [`LoadFromSave::Saved`] part and rebuilding the rest
+    /// with [`LoadFromSave::from_saved`] on load. The manifest describes the saved part.
+    pub fn register_from_save<C: LoadFromSave>(&mut self) -> &mut Self {
+        self.push_registration(ComponentRegistration::custom::<C>(
+            0,
+            self.naming(),
+            trace_or_opaque::<C::Saved>(),
+            extract_saved::<C>,
+            Box::new(insert_saved::<C>),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::SerializeMe;
+
+    #[derive(Resource, Default)]
+    struct Textures(Vec<String>);
+
+    #[derive(Component)]
+    struct Sprite {
+        path: String,
+        handle: usize,
+    }
+
+    impl LoadFromSave for Sprite {
+        type Saved = String;
+
+        fn to_saved(&self) -> String {
+            self.path.clone()
+        }
+
+        fn from_saved(path: String, world: &mut World) -> Self {
+            let mut textures = world.get_resource_or_insert_with(Textures::default);
+            textures.0.push(path.clone());
+            Sprite {
+                path,
+                handle: textures.0.len() - 1,
+            }
+        }
+    }
+
+    #[test]
+    fn test_rebuilds_from_world() {
+        let mut registry = SaveRegistry::new();
+        registry.register_from_save::<Sprite>();
+        let mut world = World::default();
+        world.spawn((
+            Sprite {
+                path: "hero.png".to_string(),
+                handle: 7,
+            },
+            SerializeMe,
+        ));
+        let doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        assert_eq!(doc["Sprite"][0][1], "hero.png");
+
+        let mut loaded = World::default();
+        loaded.insert_resource(Textures(vec!["tree.png".to_string()]));
+        registry
+            .deserialize(
+                &mut loaded,
+                &mut EntityMap::new(),
+                &mut doc.clone(),
+                SerializeMe,
+            )
+            .unwrap();
+        let sprite = loaded.query::<&Sprite>().single(&loaded);
+        assert_eq!((sprite.path.as_str(), sprite.handle), ("hero.png", 1));
+    }
+}
@@ -0,0 +1,240 @@
+//! Keeping saves and loads from overlapping. While an async save or load is in flight,
+//! another operation started against the same world would see it half done: an autosave
+//! firing during a load writes a half-empty save. With a [`PersistenceLock`] in the world,
+//! such operations are turned away with [`SaveError::Busy`] and a [`PersistenceBlocked`]
+//! event instead.
+
+use std::sync::{Arc, Weak};
+
+use bevy_ecs::event::Events;
+use bevy_ecs::prelude::*;
+
+use crate::async_io::IoOperation;
+use crate::config::SaveError;
+use crate::profiles::SaveRequest;
+
+/// What happens to a [`SaveRequest`] that arrives while the lock is held.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LockPolicy {
+    /// The request fails with [`SaveError::Busy`].
+    #[default]
+    Reject,
+    /// The request fails with [`SaveError::Busy`] too, but is kept in the lock until
+    /// [`PersistenceLock::take_queued`] hands it back, e.g. to re-issue it once the
+    /// operation in flight is done. Loads are always rejected.
+    Queue,
+}
+
+/// The operation holding a [`PersistenceLock`]: a slot for async saves and loads, or a
+/// profile for requests.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeldOperation {
+    pub operation: IoOperation,
+    pub name: String,
+}
+
+/// Sent when the lock turns an operation away. Only sent if the world has
+/// `Events<PersistenceBlocked>`.
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct PersistenceBlocked {
+    pub operation: IoOperation,
+    /// The slot or profile of the operation that was turned away.
+    pub name: String,
+    pub in_flight: HeldOperation,
+    /// Whether the request was kept for [`PersistenceLock::take_queued`].
+    pub queued: bool,
+}
+
+/// Serializes the world's saves and loads. [`SaveTask`](crate::SaveTask)s and
+/// [`LoadTask`](crate::LoadTask)s hold it from their start until the poll that finishes
+/// them, or until they're dropped; save and load requests only check it.
+#[derive(Resource, Debug, Default)]
+pub struct PersistenceLock {
+    policy: LockPolicy,
+    held: Option<(HeldOperation, Weak<()>)>,
+    queued: Vec<SaveRequest>,
+}
+
+impl PersistenceLock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_policy(mut self, policy: LockPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn policy(&self) -> LockPolicy {
+        self.policy
+    }
+
+    /// The operation in flight, if any.
+    pub fn held(&self) -> Option<&HeldOperation> {
+        self.held
+            .as_ref()
+            .filter(|(_, guard)| guard.strong_count() > 0)
+            .map(|(held, _)| held)
+    }
+
+    /// Takes the requests queued under [`LockPolicy::Queue`], oldest first. A request
+    /// repeated while queued, like a timer's autosave, is kept once.
+    pub fn take_queued(&mut self) -> Vec<SaveRequest> {
+        std::mem::take(&mut self.queued)
+    }
+
+    fn block(
+        &mut self,
+        operation: IoOperation,
+        name: &str,
+        request: Option<&SaveRequest>,
+    ) -> Option<PersistenceBlocked> {
+        let in_flight = self.held()?.clone();
+        let queued = match request {
+            Some(request) if self.policy == LockPolicy::Queue => {
+                if !self.queued.contains(request) {
+                    self.queued.push(request.clone());
+                }
+                true
+            }
+            _ => false,
+        };
+        Some(PersistenceBlocked {
+            operation,
+            name: name.to_string(),
+            in_flight,
+            queued,
+        })
+    }
+}
+
+/// Fails with [`SaveError::Busy`] if `world`'s lock is held, sending a
+/// [`PersistenceBlocked`] event.
+pub(crate) fn check(
+    world: &mut World,
+    operation: IoOperation,
+    name: &str,
+    request: Option<&SaveRequest>,
+) -> Result<(), SaveError> {
+    let Some(blocked) = world
+        .get_resource_mut::<PersistenceLock>()
+        .and_then(|mut lock| lock.block(operation, name, request))
+    else {
+        return Ok(());
+    };
+    let err = SaveError::Busy(blocked.in_flight.clone());
+    if let Some(mut events) = world.get_resource_mut::<Events<PersistenceBlocked>>() {
+        events.send(blocked);
+    }
+    Err(err)
+}
+
+/// Takes `world`'s lock, if it has one, for an operation that outlives the call. The lock
+/// is held as long as the returned guard is alive.
+pub(crate) fn acquire(
+    world: &mut World,
+    operation: IoOperation,
+    name: &str,
+) -> Result<Option<Arc<()>>, SaveError> {
+    check(world, operation, name, None)?;
+    let Some(mut lock) = world.get_resource_mut::<PersistenceLock>() else {
+        return Ok(None);
+    };
+    let guard = Arc::new(());
+    let held = HeldOperation {
+        operation,
+        name: name.to_string(),
+    };
+    lock.held = Some((held, Arc::downgrade(&guard)));
+    Ok(Some(guard))
+}
+
+pub(crate) fn release(world: &mut World, guard: &mut Option<Arc<()>>) {
+    let Some(guard) = guard.take() else {
+        return;
+    };
+    if let Some(mut lock) = world.get_resource_mut::<PersistenceLock>() {
+        if lock
+            .held
+            .as_ref()
+            .is_some_and(|(_, held)| held.ptr_eq(&Arc::downgrade(&guard)))
+        {
+            lock.held = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SaveConfig;
+    use crate::profiles::{Profiles, SaveProfile};
+    use crate::registry::SaveRegistry;
+    use crate::storage::FileStorage;
+    use crate::tests::{Component1, SerializeMe};
+
+    #[test]
+    fn test_lock_blocks_saves_during_loads() {
+        let dir = std::env::temp_dir().join(format!("bevy_serde_lock_{}", std::process::id()));
+        let mut storage = FileStorage::new(&dir);
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>();
+        let config = SaveConfig::new();
+        let mut world = World::default();
+        world.insert_resource(PersistenceLock::new().with_policy(LockPolicy::Queue));
+        world.init_resource::<Events<PersistenceBlocked>>();
+        let mut profiles = Profiles::new();
+        profiles.insert("autosave", SaveProfile::new(config.clone()));
+        world.insert_resource(profiles);
+        world.spawn_batch((0..10).map(|_| (Component1, SerializeMe)));
+        registry
+            .save_slot::<SerializeMe>(&mut world, &mut storage, "one", &config)
+            .unwrap();
+
+        let mut load = registry
+            .load_slot_async(&mut world, &storage, "one", &config)
+            .unwrap();
+        let autosave = SaveRequest::new("autosave");
+        for _ in 0..2 {
+            let err = registry
+                .save_request::<SerializeMe>(&mut world, &autosave)
+                .unwrap_err();
+            assert!(matches!(
+                err,
+                SaveError::Busy(HeldOperation {
+                    operation: IoOperation::Load,
+                    ..
+                })
+            ));
+        }
+        assert!(registry
+            .save_slot_async::<SerializeMe, _>(&mut world, &storage, "two", &config)
+            .is_err());
+        let events = world.resource::<Events<PersistenceBlocked>>();
+        let blocked: Vec<bool> = events.get_reader().read(events).map(|e| e.queued).collect();
+        assert_eq!(blocked, vec![true, true, false]);
+
+        let report = loop {
+            if let Some(result) = load.poll(&mut world, &registry, SerializeMe) {
+                break result;
+            }
+            std::thread::yield_now();
+        };
+        assert_eq!(report.unwrap().entity_map.len(), 10);
+        let mut lock = world.resource_mut::<PersistenceLock>();
+        assert_eq!(lock.held(), None);
+        assert_eq!(lock.take_queued(), vec![autosave.clone()]);
+        registry
+            .save_request::<SerializeMe>(&mut world, &autosave)
+            .unwrap();
+
+        // a task dropped unfinished releases the lock too
+        let task = registry
+            .save_slot_async::<SerializeMe, _>(&mut world, &storage, "two", &config)
+            .unwrap();
+        assert!(world.resource::<PersistenceLock>().held().is_some());
+        drop(task);
+        assert_eq!(world.resource::<PersistenceLock>().held(), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
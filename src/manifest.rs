@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::de::{self, Deserializer, Visitor};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::registry::SaveRegistry;
+
+/// The key under which a [`Manifest`] is stored in a save document. It cannot collide
+/// with a section name, as those are Rust type names.
+pub const MANIFEST_KEY: &str = "__manifest__";
+
+/// The version of the document layout written by this build.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// A [`Format::stable_hash`](crate::schema::Format::stable_hash), stored as a hex string
+/// so that it survives JSON consumers limited to 53-bit integers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SchemaHash(pub u64);
+
+impl fmt::Display for SchemaHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl Serialize for SchemaHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SchemaHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct HexVisitor;
+
+        impl<'de> Visitor<'de> for HexVisitor {
+            type Value = SchemaHash;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a hexadecimal schema hash")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<SchemaHash, E> {
+                u64::from_str_radix(v, 16)
+                    .map(SchemaHash)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+            }
+        }
+
+        deserializer.deserialize_str(HexVisitor)
+    }
+}
+
+/// Per-component information recorded in a [`Manifest`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComponentInfo {
+    /// The data version of the component, advanced by [`Migrations`](crate::migration::Migrations).
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub version: u32,
+    /// Absent once a migration has rewritten the section offline, as the structure of the
+    /// migrated data is then only described by `version`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_hash: Option<SchemaHash>,
+}
+
+fn is_zero(version: &u32) -> bool {
+    *version == 0
+}
+
+/// Describes the build that wrote a save: the document layout version and the serde
+/// structure of every registered component.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format_version: u32,
+    pub components: BTreeMap<String, ComponentInfo>,
+    /// Free-form fields set through [`SaveConfig::with_metadata`](crate::SaveConfig::with_metadata),
+    /// e.g. a save name or play time.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, Value>,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Manifest {
+            format_version: FORMAT_VERSION,
+            components: BTreeMap::new(),
+            metadata: BTreeMap::new(),
+        }
+    }
+}
+
+impl Manifest {
+    /// Reads only the manifest of a JSON save document, skipping over the component
+    /// sections without building them in memory. Returns `None` for documents written
+    /// without a manifest (e.g. by `serialize_individually!`).
+    pub fn read(save_data: &[u8]) -> Result<Option<Manifest>, serde_json::Error> {
+        #[derive(Deserialize)]
+        struct ManifestOnly {
+            #[serde(rename = "__manifest__")]
+            manifest: Option<Manifest>,
+        }
+        serde_json::from_slice::<ManifestOnly>(save_data).map(|doc| doc.manifest)
+    }
+}
+
+/// The result of comparing a save's manifest against the running build.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompatibilityReport {
+    /// Whether the save carried a manifest at all. Saves without one can't be checked.
+    pub has_manifest: bool,
+    /// Components whose structure is unchanged.
+    pub compatible: Vec<String>,
+    /// Components present in both, but whose serde structure has changed.
+    pub mismatched: Vec<String>,
+    /// Components saved at an older version; they need migrating before they can be loaded.
+    pub outdated: Vec<String>,
+    /// Components in the save that this build doesn't register; their data would be lost.
+    pub unknown: Vec<String>,
+    /// Components this build registers that the save doesn't mention; they load as absent.
+    pub missing: Vec<String>,
+}
+
+impl CompatibilityReport {
+    /// True if every component in the save can be loaded by this build.
+    pub fn is_compatible(&self) -> bool {
+        self.has_manifest
+            && self.mismatched.is_empty()
+            && self.outdated.is_empty()
+            && self.unknown.is_empty()
+    }
+
+    pub fn compare(saved: Option<&Manifest>, current: &Manifest) -> Self {
+        let Some(saved) = saved else {
+            return CompatibilityReport::default();
+        };
+        let mut report = CompatibilityReport {
+            has_manifest: true,
+            ..Default::default()
+        };
+        for (name, info) in &saved.components {
+            match current.components.get(name) {
+                None => report.unknown.push(name.clone()),
+                Some(current_info) if info.version < current_info.version => {
+                    report.outdated.push(name.clone())
+                }
+                Some(current_info)
+                    if info.version > current_info.version
+                        || info.schema_hash.is_some_and(|hash| {
+                            current_info
+                                .schema_hash
+                                .is_some_and(|current| current != hash)
+                        }) =>
+                {
+                    report.mismatched.push(name.clone())
+                }
+                Some(_) => report.compatible.push(name.clone()),
+            }
+        }
+        report.missing = current
+            .components
+            .keys()
+            .filter(|name| !saved.components.contains_key(*name))
+            .cloned()
+            .collect();
+        report
+    }
+}
+
+impl SaveRegistry {
+    /// Checks whether a JSON save can be loaded by the running build by comparing the
+    /// schema hashes in its manifest, without deserializing any components.
+    pub fn can_load(&self, save_data: &[u8]) -> Result<CompatibilityReport, serde_json::Error> {
+        // compare under this registry's naming, whichever scheme the save was written with
+        let saved = Manifest::read(save_data)?.map(|mut saved| {
+            saved.components = std::mem::take(&mut saved.components)
+                .into_iter()
+                .map(|(name, info)| match self.get(&name) {
+                    Some(reg) => (reg.name().to_string(), info),
+                    None => (name, info),
+                })
+                .collect();
+            saved
+        });
+        Ok(CompatibilityReport::compare(
+            saved.as_ref(),
+            &self.manifest(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{save_game, Component1, Component2, Component3, SerializeMe};
+    use bevy_ecs::prelude::*;
+
+    #[test]
+    fn test_can_load() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Component2>();
+
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        world.spawn((Component2 { target: entity1 }, SerializeMe));
+        let save_data =
+            serde_json::to_vec(&registry.serialize::<SerializeMe>(&mut world).unwrap()).unwrap();
+
+        let report = registry.can_load(&save_data).unwrap();
+        assert!(report.is_compatible());
+        assert_eq!(report.compatible, vec!["Component1", "Component2"]);
+
+        let mut newer = SaveRegistry::new();
+        newer.register::<Component1>().register::<Component3>();
+        let report = newer.can_load(&save_data).unwrap();
+        assert!(!report.is_compatible());
+        assert_eq!(report.unknown, vec!["Component2"]);
+        assert_eq!(report.missing, vec!["Component3"]);
+
+        let mut tampered: serde_json::Value = serde_json::from_slice(&save_data).unwrap();
+        tampered[MANIFEST_KEY]["components"]["Component1"]["schema_hash"] = "00ff".into();
+        let report = registry
+            .can_load(&serde_json::to_vec(&tampered).unwrap())
+            .unwrap();
+        assert_eq!(report.mismatched, vec!["Component1"]);
+
+        let report = registry.can_load(&save_game(&mut world)).unwrap();
+        assert!(!report.has_manifest);
+        assert!(!report.is_compatible());
+    }
+}
@@ -0,0 +1,170 @@
+use std::time::Duration;
+
+use bevy_ecs::event::Events;
+use bevy_ecs::prelude::*;
+
+use crate::async_io::IoOperation;
+use crate::config::SaveError;
+
+/// Running totals of the persistence work done on a world. When present as a resource,
+/// [`SaveRegistry::save_bytes`](crate::SaveRegistry::save_bytes) and
+/// [`SaveRegistry::load_bytes`](crate::SaveRegistry::load_bytes) keep it up to date; with
+/// the `diagnostics` feature, `PersistenceDiagnosticsPlugin` reports it through
+/// `bevy_diagnostic`.
+#[derive(Resource, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PersistenceMetrics {
+    pub saves: u64,
+    pub loads: u64,
+    /// Incremented by [`record_autosave`](Self::record_autosave), as the registry can't
+    /// tell an autosave from a manual one.
+    pub autosaves: u64,
+    pub last_save_duration: Duration,
+    /// In bytes, after compression.
+    pub last_save_size: usize,
+    pub last_load_duration: Duration,
+    pub entities_saved: usize,
+    pub entities_loaded: usize,
+}
+
+/// Sent after every save and load through [`SaveRegistry::save_bytes`](crate::SaveRegistry::save_bytes),
+/// [`SaveRegistry::load_bytes`](crate::SaveRegistry::load_bytes) and the slot functions
+/// built on them, failed ones included, so that a single system can forward persistence
+/// health to analytics. Only sent if the world has `Events<PersistenceTelemetry>`.
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct PersistenceTelemetry {
+    pub operation: IoOperation,
+    pub duration: Duration,
+    /// The size of the save in bytes, after compression. Zero for failed saves.
+    pub bytes: usize,
+    /// The entities saved or loaded. Zero if the operation failed.
+    pub entities: usize,
+    pub error: Option<String>,
+}
+
+impl PersistenceMetrics {
+    pub fn record_autosave(&mut self) {
+        self.autosaves += 1;
+    }
+
+    /// Whether anything in `world` listens to the figures passed to
+    /// [`record_save`](Self::record_save) and [`record_load`](Self::record_load), so that
+    /// callers can skip working them out.
+    pub(crate) fn is_observed(world: &World) -> bool {
+        world.contains_resource::<PersistenceMetrics>()
+            || world.contains_resource::<Events<PersistenceTelemetry>>()
+    }
+
+    pub(crate) fn record_save(
+        world: &mut World,
+        duration: Duration,
+        size: usize,
+        entities: usize,
+        error: Option<&SaveError>,
+    ) {
+        if let (Some(mut metrics), None) = (world.get_resource_mut::<PersistenceMetrics>(), error) {
+            metrics.saves += 1;
+            metrics.last_save_duration = duration;
+            metrics.last_save_size = size;
+            metrics.entities_saved = entities;
+        }
+        send_telemetry(world, IoOperation::Save, duration, size, entities, error);
+    }
+
+    pub(crate) fn record_load(
+        world: &mut World,
+        duration: Duration,
+        size: usize,
+        entities: usize,
+        error: Option<&SaveError>,
+    ) {
+        if let (Some(mut metrics), None) = (world.get_resource_mut::<PersistenceMetrics>(), error) {
+            metrics.loads += 1;
+            metrics.last_load_duration = duration;
+            metrics.entities_loaded = entities;
+        }
+        send_telemetry(world, IoOperation::Load, duration, size, entities, error);
+    }
+}
+
+fn send_telemetry(
+    world: &mut World,
+    operation: IoOperation,
+    duration: Duration,
+    bytes: usize,
+    entities: usize,
+    error: Option<&SaveError>,
+) {
+    if let Some(mut events) = world.get_resource_mut::<Events<PersistenceTelemetry>>() {
+        events.send(PersistenceTelemetry {
+            operation,
+            duration,
+            bytes,
+            entities,
+            error: error.map(ToString::to_string),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Component1, SerializeMe};
+    use crate::{SaveConfig, SaveRegistry};
+
+    #[test]
+    fn test_save_and_load_update_metrics() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>();
+        let mut world = World::default();
+        world.init_resource::<PersistenceMetrics>();
+        world.spawn_batch((0..3).map(|_| (Component1, SerializeMe)));
+
+        let config = SaveConfig::new();
+        let bytes = registry
+            .save_bytes::<SerializeMe>(&mut world, &config)
+            .unwrap();
+        registry
+            .load_bytes(&mut world, &bytes, &config, SerializeMe)
+            .unwrap();
+        let metrics = world.resource::<PersistenceMetrics>();
+        assert_eq!((metrics.saves, metrics.loads), (1, 1));
+        assert_eq!(metrics.last_save_size, bytes.len());
+        assert_eq!(metrics.entities_saved, 3);
+        assert_eq!(metrics.entities_loaded, 3);
+    }
+
+    #[test]
+    fn test_telemetry_includes_failures() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>();
+        let mut world = World::default();
+        world.init_resource::<Events<PersistenceTelemetry>>();
+        world.spawn_batch((0..3).map(|_| (Component1, SerializeMe)));
+
+        let config = SaveConfig::new();
+        let bytes = registry
+            .save_bytes::<SerializeMe>(&mut world, &config)
+            .unwrap();
+        assert!(registry
+            .load_bytes(&mut World::default(), &bytes[1..], &config, SerializeMe)
+            .is_err());
+        assert!(registry
+            .save_bytes::<SerializeMe>(&mut world, &config.clone().with_max_size(1))
+            .is_err());
+
+        let events = world.resource::<Events<PersistenceTelemetry>>();
+        let mut reader = events.get_reader();
+        let sent: Vec<&PersistenceTelemetry> = reader.read(events).collect();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(
+            (sent[0].operation, sent[0].bytes, sent[0].entities),
+            (IoOperation::Save, bytes.len(), 3)
+        );
+        assert_eq!(sent[0].error, None);
+        assert!(sent[1]
+            .error
+            .as_ref()
+            .unwrap()
+            .contains("exceeds the limit"));
+    }
+}
@@ -0,0 +1,323 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use bevy_ecs::prelude::*;
+use serde_json::{Map, Value};
+
+use crate::load::{LoadMode, LoadReport};
+use crate::manifest::{ComponentInfo, Manifest, FORMAT_VERSION, MANIFEST_KEY};
+use crate::registry::SaveRegistry;
+use crate::SaveDocument;
+
+type ComponentMigrationFn = Box<dyn Fn(Value) -> Result<Value, String> + Send + Sync>;
+type FormatUpgradeFn = fn(&mut Map<String, Value>) -> Result<(), MigrationError>;
+
+/// Upgrades of the document layout itself, indexed by the version they upgrade from.
+const FORMAT_UPGRADES: [FormatUpgradeFn; FORMAT_VERSION as usize] = [add_manifest];
+
+/// Format 0 is the manifest-less layout written by `serialize_individually!`.
+fn add_manifest(doc: &mut Map<String, Value>) -> Result<(), MigrationError> {
+    let manifest = Manifest {
+        format_version: 1,
+        components: doc
+            .keys()
+            .map(|name| {
+                (
+                    name.clone(),
+                    ComponentInfo {
+                        version: 0,
+                        schema_hash: None,
+                    },
+                )
+            })
+            .collect(),
+        ..Manifest::default()
+    };
+    doc.insert(MANIFEST_KEY.to_string(), serde_json::to_value(manifest)?);
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    Json(serde_json::Error),
+    /// The save isn't a JSON object of component sections.
+    NotADocument,
+    /// The save was written by a newer build than this one.
+    NewerFormat {
+        found: u32,
+        supported: u32,
+    },
+    /// A component section isn't a list of `[entity, component]` pairs.
+    MalformedSection(String),
+    /// A registered component migration returned an error.
+    Component {
+        name: String,
+        from_version: u32,
+        message: String,
+    },
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::Json(err) => write!(f, "{err}"),
+            MigrationError::NotADocument => write!(f, "save data is not a JSON object"),
+            MigrationError::NewerFormat { found, supported } => write!(
+                f,
+                "save format version {found} is newer than the supported version {supported}"
+            ),
+            MigrationError::MalformedSection(name) => {
+                write!(
+                    f,
+                    "section {name} is not a list of [entity, component] pairs"
+                )
+            }
+            MigrationError::Component {
+                name,
+                from_version,
+                message,
+            } => write!(
+                f,
+                "migrating {name} from version {from_version} failed: {message}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+impl From<serde_json::Error> for MigrationError {
+    fn from(err: serde_json::Error) -> Self {
+        MigrationError::Json(err)
+    }
+}
+
+/// A set of per-component data migrations. Each migration upgrades a single component
+/// value by one version; chains of them bring old saves up to the latest version.
+#[derive(Default)]
+pub struct Migrations {
+    component: BTreeMap<String, BTreeMap<u32, ComponentMigrationFn>>,
+}
+
+impl Migrations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a migration for the section `name`, taking a component value saved at
+    /// `from_version` to `from_version + 1`.
+    pub fn add(
+        &mut self,
+        name: &str,
+        from_version: u32,
+        migrate: impl Fn(Value) -> Result<Value, String> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.component
+            .entry(name.to_string())
+            .or_default()
+            .insert(from_version, Box::new(migrate));
+        self
+    }
+
+    /// The version the component `name` reaches once every applicable migration has run.
+    pub fn latest_version(&self, name: &str) -> u32 {
+        self.component
+            .get(name)
+            .and_then(|migrations| migrations.keys().next_back())
+            .map_or(0, |from_version| from_version + 1)
+    }
+
+    /// Brings a parsed save document up to the current format version and runs the
+    /// component migrations, updating the manifest to match. Returns the names of the
+    /// sections that were migrated.
+    pub fn upgrade_document(
+        &self,
+        doc: &mut Map<String, Value>,
+    ) -> Result<Vec<String>, MigrationError> {
+        let mut format_version = match doc.get(MANIFEST_KEY) {
+            Some(manifest) => format_version_of(manifest)?,
+            None => 0,
+        };
+        if format_version > FORMAT_VERSION {
+            return Err(MigrationError::NewerFormat {
+                found: format_version,
+                supported: FORMAT_VERSION,
+            });
+        }
+        while format_version < FORMAT_VERSION {
+            FORMAT_UPGRADES[format_version as usize](doc)?;
+            format_version += 1;
+        }
+
+        let mut manifest: Manifest = match doc.remove(MANIFEST_KEY) {
+            Some(manifest) => serde_json::from_value(manifest)?,
+            None => Manifest::default(),
+        };
+        manifest.format_version = FORMAT_VERSION;
+
+        let mut migrated = Vec::new();
+        for (name, migrations) in &self.component {
+            let Some(section) = doc.get_mut(name) else {
+                continue;
+            };
+            let info = manifest
+                .components
+                .entry(name.clone())
+                .or_insert(ComponentInfo {
+                    version: 0,
+                    schema_hash: None,
+                });
+            let start_version = info.version;
+            while let Some(migrate) = migrations.get(&info.version) {
+                let Value::Array(entries) = section else {
+                    return Err(MigrationError::MalformedSection(name.clone()));
+                };
+                for entry in entries.iter_mut() {
+                    let comp = match entry {
+                        Value::Array(pair) if pair.len() == 2 => &mut pair[1],
+                        _ => return Err(MigrationError::MalformedSection(name.clone())),
+                    };
+                    *comp = migrate(comp.take()).map_err(|message| MigrationError::Component {
+                        name: name.clone(),
+                        from_version: info.version,
+                        message,
+                    })?;
+                }
+                info.version += 1;
+            }
+            if info.version != start_version {
+                info.schema_hash = None;
+                migrated.push(name.clone());
+            }
+        }
+
+        doc.insert(MANIFEST_KEY.to_string(), serde_json::to_value(manifest)?);
+        Ok(migrated)
+    }
+}
+
+fn format_version_of(manifest: &Value) -> Result<u32, MigrationError> {
+    manifest
+        .get("format_version")
+        .and_then(Value::as_u64)
+        .map(|version| version as u32)
+        .ok_or(MigrationError::NotADocument)
+}
+
+/// Upgrades a JSON save to the current document format and applies `migrations` to its
+/// component sections, without constructing a `World`. Intended for batch conversion of
+/// stored saves, e.g. server-side after a release that changes component structure.
+pub fn upgrade_save(save_data: &[u8], migrations: &Migrations) -> Result<Vec<u8>, MigrationError> {
+    let Value::Object(mut doc) = serde_json::from_slice(save_data)? else {
+        return Err(MigrationError::NotADocument);
+    };
+    migrations.upgrade_document(&mut doc)?;
+    Ok(serde_json::to_vec(&doc)?)
+}
+
+impl SaveRegistry {
+    /// Upgrades `doc` with `migrations` and loads it as [`load`](Self::load) does, listing
+    /// the migrated sections in the report.
+    pub fn load_migrated<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        doc: SaveDocument,
+        migrations: &Migrations,
+        mode: LoadMode,
+        marker: M,
+    ) -> Result<LoadReport, MigrationError> {
+        let mut doc: Map<String, Value> = doc.into_iter().collect();
+        let migrated = migrations.upgrade_document(&mut doc)?;
+        let mut doc: SaveDocument = doc.into_iter().collect();
+        let mut report = self.load(world, &mut doc, mode, marker)?;
+        report.migrations_applied = migrated;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{save_game, Component1, SerializeMe};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Component, Serialize, Deserialize)]
+    struct Component2 {
+        target: Entity,
+        #[serde(default)]
+        weight: u32,
+    }
+
+    fn migrations() -> Migrations {
+        let mut migrations = Migrations::new();
+        migrations.add("Component2", 0, |mut comp| {
+            comp["weight"] = 1.into();
+            Ok(comp)
+        });
+        migrations.add("Component2", 1, |mut comp| {
+            let weight = comp["weight"].as_u64().ok_or("weight is not a number")?;
+            comp["weight"] = (weight * 10).into();
+            Ok(comp)
+        });
+        migrations
+    }
+
+    #[test]
+    fn test_upgrade_legacy_save() {
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        world.spawn((crate::tests::Component2 { target: entity1 }, SerializeMe));
+        let legacy = save_game(&mut world);
+
+        let upgraded = upgrade_save(&legacy, &migrations()).unwrap();
+        let doc: Value = serde_json::from_slice(&upgraded).unwrap();
+        assert_eq!(doc["Component2"][0][1]["weight"], 10);
+        assert_eq!(doc["Component1"], serde_json::json!([[0, null]]));
+
+        let manifest = Manifest::read(&upgraded).unwrap().unwrap();
+        assert_eq!(manifest.format_version, FORMAT_VERSION);
+        assert_eq!(manifest.components["Component2"].version, 2);
+        assert_eq!(manifest.components["Component1"].version, 0);
+
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .register_versioned::<Component2>(migrations().latest_version("Component2"));
+        let report = registry.can_load(&upgraded).unwrap();
+        assert!(report.is_compatible(), "{report:?}");
+
+        // upgrading is idempotent once everything is at the latest version
+        assert_eq!(upgrade_save(&upgraded, &migrations()).unwrap(), upgraded);
+
+        let legacy_doc = serde_json::from_slice(&legacy).unwrap();
+        let report = registry
+            .load_migrated(
+                &mut World::default(),
+                legacy_doc,
+                &migrations(),
+                LoadMode::Merge,
+                SerializeMe,
+            )
+            .unwrap();
+        assert_eq!(report.migrations_applied, vec!["Component2"]);
+    }
+
+    #[test]
+    fn test_migration_error_names_component() {
+        let mut migrations = Migrations::new();
+        migrations.add("Component1", 0, |_| Err("no".to_string()));
+        let save_data = br#"{"Component1": [[0, null]]}"#;
+        let err = upgrade_save(save_data, &migrations).unwrap_err();
+        assert!(matches!(
+            err,
+            MigrationError::Component { ref name, from_version: 0, .. } if name == "Component1"
+        ));
+        assert!(matches!(
+            upgrade_save(
+                br#"{"__manifest__": {"format_version": 99, "components": {}}}"#,
+                &migrations
+            ),
+            Err(MigrationError::NewerFormat { found: 99, .. })
+        ));
+    }
+}
@@ -0,0 +1,171 @@
+//! Component sections belonging to mods. A mod's components are keyed `my_mod:Turret`
+//! (see [`SaveRegistry::set_mod`](crate::SaveRegistry::set_mod)), which keeps them apart
+//! from the base game's and other mods' components of the same name. In memory they are
+//! ordinary sections; saved files group them per mod under [`MODS_KEY`]:
+//!
+//! ```json
+//! { "Health": [...], "__mods__": { "my_mod": { "Turret": [...] } } }
+//! ```
+//!
+//! so that the data of a removed mod can be dropped, or kept for later, in one piece.
+
+use std::collections::BTreeSet;
+
+use serde::de::Error;
+use serde_json::{Map, Value};
+
+use crate::SaveDocument;
+
+/// The reserved key of the section holding the sections of every mod, by mod name.
+pub const MODS_KEY: &str = "__mods__";
+
+/// The key of the section `name` of the mod `mod_name`.
+pub fn mod_section_name(mod_name: &str, name: &str) -> String {
+    format!("{mod_name}:{name}")
+}
+
+/// Splits a mod's section key into the mod name and the section name within the mod.
+/// Type paths such as `my_game::Health` are not mod sections.
+pub fn split_mod_section(key: &str) -> Option<(&str, &str)> {
+    let (mod_name, name) = key.split_once(':')?;
+    (!mod_name.is_empty() && !name.is_empty() && !name.starts_with(':')).then_some((mod_name, name))
+}
+
+/// Moves every mod section of `doc` into its mod's entry of the [`MODS_KEY`] section.
+pub fn nest_mod_sections(doc: &mut SaveDocument) -> Result<(), serde_json::Error> {
+    let keys: Vec<String> = doc
+        .keys()
+        .filter(|key| split_mod_section(key).is_some())
+        .cloned()
+        .collect();
+    if keys.is_empty() {
+        return Ok(());
+    }
+    let mut mods = match doc.remove(MODS_KEY) {
+        Some(Value::Object(mods)) => mods,
+        Some(_) => return Err(malformed()),
+        None => Map::new(),
+    };
+    for key in keys {
+        let section = doc.remove(&key).unwrap_or_default();
+        let (mod_name, name) = split_mod_section(&key).unwrap_or_default();
+        let Value::Object(sections) = mods
+            .entry(mod_name)
+            .or_insert_with(|| Value::Object(Map::new()))
+        else {
+            return Err(malformed());
+        };
+        sections.insert(name.to_string(), section);
+    }
+    doc.insert(MODS_KEY.to_string(), Value::Object(mods));
+    Ok(())
+}
+
+/// Turns the [`MODS_KEY`] section of `doc` back into `my_mod:Turret` sections, as
+/// loading expects.
+pub fn flatten_mod_sections(doc: &mut SaveDocument) -> Result<(), serde_json::Error> {
+    let mods = match doc.remove(MODS_KEY) {
+        Some(Value::Object(mods)) => mods,
+        Some(_) => return Err(malformed()),
+        None => return Ok(()),
+    };
+    for (mod_name, sections) in mods {
+        let Value::Object(sections) = sections else {
+            return Err(malformed());
+        };
+        for (name, section) in sections {
+            doc.insert(mod_section_name(&mod_name, &name), section);
+        }
+    }
+    Ok(())
+}
+
+/// The mods with data in `doc`, whether its mod sections are nested or not.
+pub fn mods_in(doc: &SaveDocument) -> BTreeSet<String> {
+    let nested = doc
+        .get(MODS_KEY)
+        .and_then(Value::as_object)
+        .into_iter()
+        .flat_map(|mods| mods.keys().cloned());
+    let flat = doc
+        .keys()
+        .filter_map(|key| split_mod_section(key))
+        .map(|(mod_name, _)| mod_name.to_string());
+    nested.chain(flat).collect()
+}
+
+/// Drops the data of every mod for which `keep` returns false, e.g. of the mods that are
+/// no longer installed, from `doc`, whether its mod sections are nested or not.
+pub fn retain_mods(doc: &mut SaveDocument, keep: impl Fn(&str) -> bool) {
+    doc.retain(|key, _| split_mod_section(key).is_none_or(|(mod_name, _)| keep(mod_name)));
+    if let Some(Value::Object(mods)) = doc.get_mut(MODS_KEY) {
+        mods.retain(|mod_name, _| keep(mod_name));
+        if mods.is_empty() {
+            doc.remove(MODS_KEY);
+        }
+    }
+}
+
+/// Drops all data of the mod `mod_name` from `doc`.
+pub fn strip_mod(doc: &mut SaveDocument, mod_name: &str) {
+    retain_mods(doc, |name| name != mod_name);
+}
+
+fn malformed() -> serde_json::Error {
+    serde_json::Error::custom(format!(
+        "{MODS_KEY} must map mod names to objects of sections"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ecs::prelude::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::config::SaveConfig;
+    use crate::registry::SaveRegistry;
+    use crate::tests::{Component1, SerializeMe};
+
+    mod my_mod {
+        use super::*;
+
+        #[derive(Component, Serialize, Deserialize)]
+        pub struct Component1(pub u32);
+    }
+
+    #[test]
+    fn test_mod_sections_are_namespaced_and_strippable() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .set_mod::<my_mod::Component1>("my_mod")
+            .register::<my_mod::Component1>();
+        assert!(registry.get("my_mod:Component1").is_some());
+        let mut world = World::default();
+        world.spawn((Component1, SerializeMe));
+        world.spawn((my_mod::Component1(7), SerializeMe));
+
+        let config = SaveConfig::default();
+        let bytes = registry
+            .save_bytes::<SerializeMe>(&mut world, &config)
+            .unwrap();
+        let mut written: SaveDocument = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(written[MODS_KEY]["my_mod"]["Component1"][0][1], 7);
+        assert!(written.contains_key("Component1"));
+        assert_eq!(mods_in(&written), BTreeSet::from(["my_mod".to_string()]));
+
+        let mut loaded = World::default();
+        registry
+            .load_bytes(&mut loaded, &bytes, &config, SerializeMe)
+            .unwrap();
+        assert_eq!(
+            loaded.query::<&my_mod::Component1>().iter(&loaded).count(),
+            1
+        );
+        assert_eq!(loaded.query::<&Component1>().iter(&loaded).count(), 1);
+
+        strip_mod(&mut written, "my_mod");
+        assert!(!written.contains_key(MODS_KEY) && written.contains_key("Component1"));
+    }
+}
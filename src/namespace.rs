@@ -0,0 +1,157 @@
+//! Several worlds in one save document, e.g. the simulation world and a SubApp's world,
+//! each under its own namespace and restored independently. The registry works on any
+//! `&mut World`, so a SubApp's world is saved like the main one.
+
+use bevy_ecs::prelude::*;
+use serde::de::Error;
+
+use crate::load::{LoadMode, LoadReport};
+use crate::registry::SaveRegistry;
+use crate::SaveDocument;
+
+/// The key of the namespace `name` in a save document. Namespaces are reserved keys, like
+/// the manifest's, so they never collide with component sections, and are prefixed `ns:`,
+/// so that a namespace named e.g. `manifest` doesn't collide with the manifest either.
+pub fn namespace_key(name: &str) -> String {
+    format!("__ns:{name}__")
+}
+
+/// Takes the document stored under the namespace `name` out of `doc`.
+pub fn take_namespace(
+    doc: &mut SaveDocument,
+    name: &str,
+) -> Result<Option<SaveDocument>, serde_json::Error> {
+    doc.remove(&namespace_key(name))
+        .map(serde_json::from_value)
+        .transpose()
+}
+
+impl SaveRegistry {
+    /// Serializes the entities of `world` marked with `M`, manifest included, into `doc`
+    /// under the namespace `name`, replacing what was there.
+    pub fn serialize_namespaced<M: Component>(
+        &self,
+        world: &mut World,
+        doc: &mut SaveDocument,
+        name: &str,
+    ) -> Result<(), serde_json::Error> {
+        let nested = self.serialize::<M>(world)?;
+        doc.insert(namespace_key(name), serde_json::to_value(nested)?);
+        Ok(())
+    }
+
+    /// Loads the namespace `name` of `doc` into `world` as [`load`](Self::load) does,
+    /// leaving the other namespaces in place. Fails if `doc` has no such namespace.
+    pub fn load_namespaced<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        doc: &mut SaveDocument,
+        name: &str,
+        mode: LoadMode,
+        marker: M,
+    ) -> Result<LoadReport, serde_json::Error> {
+        let Some(mut nested) = take_namespace(doc, name)? else {
+            return Err(serde_json::Error::custom(format!(
+                "document has no {} namespace",
+                namespace_key(name)
+            )));
+        };
+        self.load(world, &mut nested, mode, marker)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SaveConfig;
+    use crate::manifest::MANIFEST_KEY;
+    use crate::mods::MODS_KEY;
+    use crate::subtree::HIERARCHY_KEY;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_worlds_restore_independently() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .register_mapped::<Component2>();
+        let mut simulation = World::default();
+        let entity1 = simulation.spawn((Component1, SerializeMe)).id();
+        simulation.spawn((Component2 { target: entity1 }, SerializeMe));
+        let mut render = World::default();
+        render.spawn((Component1, SerializeMe));
+
+        let mut doc = SaveDocument::new();
+        registry
+            .serialize_namespaced::<SerializeMe>(&mut simulation, &mut doc, "simulation")
+            .unwrap();
+        registry
+            .serialize_namespaced::<SerializeMe>(&mut render, &mut doc, "render")
+            .unwrap();
+        let text = serde_json::to_string(&doc).unwrap();
+        let mut doc: SaveDocument = serde_json::from_str(&text).unwrap();
+
+        let mut loaded = World::default();
+        let report = registry
+            .load_namespaced(
+                &mut loaded,
+                &mut doc,
+                "render",
+                LoadMode::Merge,
+                SerializeMe,
+            )
+            .unwrap();
+        assert_eq!(report.entity_map.len(), 1);
+        assert!(doc.contains_key("__ns:simulation__"));
+        let report = registry
+            .load_namespaced(
+                &mut loaded,
+                &mut doc,
+                "simulation",
+                LoadMode::Merge,
+                SerializeMe,
+            )
+            .unwrap();
+        assert_eq!(report.entity_map.len(), 2);
+        assert!(registry
+            .load_namespaced(
+                &mut loaded,
+                &mut doc,
+                "render",
+                LoadMode::Merge,
+                SerializeMe
+            )
+            .is_err());
+
+        // namespaces named after reserved sections leave those alone
+        let mut doc = registry.serialize::<SerializeMe>(&mut simulation).unwrap();
+        let manifest = doc[MANIFEST_KEY].clone();
+        for name in ["manifest", "hierarchy", "mods"] {
+            registry
+                .serialize_namespaced::<SerializeMe>(&mut render, &mut doc, name)
+                .unwrap();
+        }
+        assert_eq!(doc[MANIFEST_KEY], manifest);
+        assert!(!doc.contains_key(HIERARCHY_KEY) && !doc.contains_key(MODS_KEY));
+
+        // the top-level sections load around the namespaces, also under an entity cap
+        let config = SaveConfig::new().with_max_entities(2);
+        let mut loaded = World::default();
+        let report = registry
+            .load_document(
+                &mut loaded,
+                &mut doc.clone(),
+                &config,
+                LoadMode::Merge,
+                |_| true,
+                SerializeMe,
+            )
+            .unwrap();
+        assert_eq!(report.entity_map.len(), 2);
+        assert!(report.unknown_sections.is_empty());
+        let report = registry
+            .load_namespaced(&mut loaded, &mut doc, "mods", LoadMode::Merge, SerializeMe)
+            .unwrap();
+        assert_eq!(report.entity_map.len(), 1);
+    }
+}
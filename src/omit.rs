@@ -0,0 +1,113 @@
+//! Leaving component instances out of saves, the registry's counterpart to serde's
+//! `skip_serializing_if`: a `Velocity` that is zero, or any component at its default,
+//! needn't take up space in every save.
+
+use bevy_ecs::prelude::*;
+
+use crate::registry::SaveRegistry;
+
+impl SaveRegistry {
+    /// Leaves every `C` for which `skip` returns true out of saves. Loading restores such
+    /// entities without a `C`, unless [`fill_missing_with_default`](Self::fill_missing_with_default)
+    /// is set as well.
+    ///
+    /// # Panics
+    /// If `C` hasn't been registered.
+    pub fn skip_saving_if<C: Component>(
+        &mut self,
+        skip: impl Fn(&C) -> bool + Send + Sync + 'static,
+    ) -> &mut Self {
+        let Some(reg) = self.get_by_type_mut::<C>() else {
+            panic!(
+                "{} must be registered before adding a skip predicate",
+                std::any::type_name::<C>()
+            );
+        };
+        reg.skip_saving_if = Some(Box::new(move |world: &World, entity: Entity| {
+            world.get::<C>(entity).is_some_and(&skip)
+        }));
+        self
+    }
+
+    /// Inserts `C::default()` on every entity of a loaded document that has no `C` after
+    /// its section was restored, for components that every saved entity carries and that
+    /// are only left out when at their default.
+    ///
+    /// # Panics
+    /// If `C` hasn't been registered.
+    pub fn fill_missing_with_default<C: Component + Default>(&mut self) -> &mut Self {
+        let Some(reg) = self.get_by_type_mut::<C>() else {
+            panic!(
+                "{} must be registered before filling it in on load",
+                std::any::type_name::<C>()
+            );
+        };
+        reg.fill_missing = Some(Box::new(|world: &mut World, entities: &[Entity]| {
+            let mut filled = Vec::new();
+            for entity in entities {
+                // e.g. a resource carrier's helper, despawned once unpacked
+                let Some(mut entity_mut) = world.get_entity_mut(*entity) else {
+                    continue;
+                };
+                if !entity_mut.contains::<C>() {
+                    entity_mut.insert(C::default());
+                    filled.push(*entity);
+                }
+            }
+            filled
+        }));
+        self
+    }
+
+    /// Leaves every `C` equal to its default out of saves, and fills it back in on load.
+    pub fn skip_saving_default<C: Component + Default + PartialEq>(&mut self) -> &mut Self {
+        self.skip_saving_if::<C>(|comp| *comp == C::default())
+            .fill_missing_with_default::<C>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::load::LoadMode;
+    use crate::tests::{Component1, SerializeMe};
+
+    #[derive(Component, Serialize, Deserialize, Default, PartialEq, Debug)]
+    struct Velocity(i32);
+
+    #[derive(Component, Serialize, Deserialize, Default, PartialEq, Debug)]
+    struct Armor(u32);
+
+    #[test]
+    fn test_skipped_components_are_omitted_and_filled() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .register::<Velocity>()
+            .register::<Armor>()
+            .skip_saving_if::<Velocity>(|velocity| velocity.0 == 0)
+            .skip_saving_default::<Armor>();
+        let mut world = World::default();
+        world.spawn((Component1, Velocity(0), Armor(0), SerializeMe));
+        world.spawn((Component1, Velocity(3), Armor(5), SerializeMe));
+        let mut doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        assert_eq!(doc["Velocity"].as_array().unwrap().len(), 1);
+        assert_eq!(doc["Armor"].as_array().unwrap().len(), 1);
+
+        let mut loaded = World::default();
+        registry
+            .load(&mut loaded, &mut doc, LoadMode::Merge, SerializeMe)
+            .unwrap();
+        let velocities: Vec<&Velocity> = loaded.query::<&Velocity>().iter(&loaded).collect();
+        assert_eq!(velocities, vec![&Velocity(3)]);
+        let mut armor: Vec<u32> = loaded
+            .query::<&Armor>()
+            .iter(&loaded)
+            .map(|armor| armor.0)
+            .collect();
+        armor.sort();
+        assert_eq!(armor, vec![0, 5]);
+    }
+}
@@ -0,0 +1,45 @@
+//! JSON parsing on the load path. With the `simd-json` feature, documents and frames are
+//! parsed with simd-json, which picks the fastest implementation the CPU supports at
+//! runtime, into the same `serde_json::Value` trees. Input simd-json rejects is parsed
+//! again with serde_json, so errors, and anything only serde_json accepts, are as without
+//! the feature.
+//!
+//! [`raw_sections`](crate::raw_sections) keeps using serde_json, as its sections are
+//! serde_json's `RawValue`s borrowing from the input.
+
+use serde::de::DeserializeOwned;
+
+/// Parses `bytes` as JSON into a `T`.
+pub fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, serde_json::Error> {
+    #[cfg(feature = "simd-json")]
+    {
+        // simd-json parses in place, leaving `bytes` intact for the fallback
+        let mut buffer = bytes.to_vec();
+        if let Ok(parsed) = simd_json::serde::from_slice(&mut buffer) {
+            return Ok(parsed);
+        }
+    }
+    serde_json::from_slice(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SaveDocument;
+    use serde_json::Value;
+
+    #[test]
+    fn test_parses_like_serde_json() {
+        let text = r#"{
+            "Component2": [[4294967301, {"target": 18446744073709551615}]],
+            "Position": [[7, {"x": -1.5e-3, "y": 2.0, "name": "café \"quoted\""}]],
+            "__manifest__": {"components": {}, "metadata": {"empty": [], "none": null}}
+        }"#;
+        let parsed: SaveDocument = from_slice(text.as_bytes()).unwrap();
+        let expected: SaveDocument = serde_json::from_str(text).unwrap();
+        assert_eq!(parsed, expected);
+
+        let err = from_slice::<Value>(b"{\"Component1\": [").unwrap_err();
+        assert!(err.is_eof());
+    }
+}
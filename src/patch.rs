@@ -0,0 +1,342 @@
+//! [RFC 6902](https://www.rfc-editor.org/rfc/rfc6902) JSON Patch between save documents, so
+//! that a backend can store a small patch per session instead of every full save, and
+//! rebuild the latest save from any JSON Patch implementation.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::SaveDocument;
+
+/// One operation of a [`JsonPatch`]. Paths are JSON Pointers (RFC 6901).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+/// A JSON Patch document: a list of operations applied in order, serialized as a JSON
+/// array.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct JsonPatch(pub Vec<PatchOperation>);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PatchError {
+    /// The path isn't a valid JSON Pointer, or doesn't fit the document's structure, e.g.
+    /// a non-numeric index into an array.
+    InvalidPath(String),
+    /// The path, or its parent for `add`, doesn't exist in the document.
+    PathNotFound(String),
+    /// A `test` operation found a different value.
+    TestFailed(String),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::InvalidPath(path) => write!(f, "invalid patch path {path:?}"),
+            PatchError::PathNotFound(path) => write!(f, "patch path {path:?} doesn't exist"),
+            PatchError::TestFailed(path) => write!(f, "patch test of {path:?} failed"),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+fn escape(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+/// The reference tokens of `path`.
+fn parse_pointer(path: &str) -> Result<Vec<String>, PatchError> {
+    if path.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(rest) = path.strip_prefix('/') else {
+        return Err(PatchError::InvalidPath(path.to_string()));
+    };
+    Ok(rest
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+fn array_index(token: &str, len: usize, path: &str) -> Result<usize, PatchError> {
+    let valid = token == "0" || (!token.starts_with('0') && !token.starts_with('+'));
+    match token.parse::<usize>() {
+        Ok(index) if valid && index < len => Ok(index),
+        Ok(_) if valid => Err(PatchError::PathNotFound(path.to_string())),
+        _ => Err(PatchError::InvalidPath(path.to_string())),
+    }
+}
+
+fn get_mut<'a>(
+    doc: &'a mut Value,
+    tokens: &[String],
+    path: &str,
+) -> Result<&'a mut Value, PatchError> {
+    tokens.iter().try_fold(doc, |value, token| match value {
+        Value::Object(map) => map
+            .get_mut(token)
+            .ok_or_else(|| PatchError::PathNotFound(path.to_string())),
+        Value::Array(items) => {
+            let index = array_index(token, items.len(), path)?;
+            Ok(&mut items[index])
+        }
+        _ => Err(PatchError::PathNotFound(path.to_string())),
+    })
+}
+
+fn add(doc: &mut Value, path: &str, value: Value) -> Result<(), PatchError> {
+    let tokens = parse_pointer(path)?;
+    let Some((last, parent)) = tokens.split_last() else {
+        *doc = value;
+        return Ok(());
+    };
+    match get_mut(doc, parent, path)? {
+        Value::Object(map) => {
+            map.insert(last.clone(), value);
+        }
+        Value::Array(items) if last == "-" => items.push(value),
+        Value::Array(items) => {
+            let index = array_index(last, items.len() + 1, path)?;
+            items.insert(index, value);
+        }
+        _ => return Err(PatchError::PathNotFound(path.to_string())),
+    }
+    Ok(())
+}
+
+fn remove(doc: &mut Value, path: &str) -> Result<Value, PatchError> {
+    let tokens = parse_pointer(path)?;
+    let Some((last, parent)) = tokens.split_last() else {
+        return Ok(std::mem::take(doc));
+    };
+    match get_mut(doc, parent, path)? {
+        Value::Object(map) => map
+            .remove(last)
+            .ok_or_else(|| PatchError::PathNotFound(path.to_string())),
+        Value::Array(items) => {
+            let index = array_index(last, items.len(), path)?;
+            Ok(items.remove(index))
+        }
+        _ => Err(PatchError::PathNotFound(path.to_string())),
+    }
+}
+
+fn apply_operation(doc: &mut Value, operation: &PatchOperation) -> Result<(), PatchError> {
+    match operation {
+        PatchOperation::Add { path, value } => add(doc, path, value.clone()),
+        PatchOperation::Remove { path } => remove(doc, path).map(|_| ()),
+        PatchOperation::Replace { path, value } => {
+            *get_mut(doc, &parse_pointer(path)?, path)? = value.clone();
+            Ok(())
+        }
+        PatchOperation::Move { from, path } => {
+            if path.starts_with(&format!("{from}/")) {
+                return Err(PatchError::InvalidPath(path.clone()));
+            }
+            let value = remove(doc, from)?;
+            add(doc, path, value)
+        }
+        PatchOperation::Copy { from, path } => {
+            let value = get_mut(doc, &parse_pointer(from)?, from)?.clone();
+            add(doc, path, value)
+        }
+        PatchOperation::Test { path, value } => {
+            if get_mut(doc, &parse_pointer(path)?, path)? == value {
+                Ok(())
+            } else {
+                Err(PatchError::TestFailed(path.clone()))
+            }
+        }
+    }
+}
+
+fn diff_values(path: &str, before: &Value, after: &Value, operations: &mut Vec<PatchOperation>) {
+    match (before, after) {
+        (Value::Object(before), Value::Object(after)) => {
+            diff_objects(path, before, after, operations);
+        }
+        (Value::Array(before), Value::Array(after)) => {
+            diff_arrays(path, before, after, operations);
+        }
+        _ if before != after => operations.push(PatchOperation::Replace {
+            path: path.to_string(),
+            value: after.clone(),
+        }),
+        _ => {}
+    }
+}
+
+fn diff_objects(
+    path: &str,
+    before: &Map<String, Value>,
+    after: &Map<String, Value>,
+    operations: &mut Vec<PatchOperation>,
+) {
+    for (key, value) in before {
+        let child = format!("{path}/{}", escape(key));
+        match after.get(key) {
+            Some(new) => diff_values(&child, value, new, operations),
+            None => operations.push(PatchOperation::Remove { path: child }),
+        }
+    }
+    for (key, value) in after {
+        if !before.contains_key(key) {
+            operations.push(PatchOperation::Add {
+                path: format!("{path}/{}", escape(key)),
+                value: value.clone(),
+            });
+        }
+    }
+}
+
+/// Diffs arrays by skipping their common start and end, so that inserting or removing
+/// entities in the middle of a section touches only those entries.
+fn diff_arrays(
+    path: &str,
+    before: &[Value],
+    after: &[Value],
+    operations: &mut Vec<PatchOperation>,
+) {
+    let prefix = before
+        .iter()
+        .zip(after)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = before[prefix..]
+        .iter()
+        .rev()
+        .zip(after[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let before = &before[prefix..before.len() - suffix];
+    let after = &after[prefix..after.len() - suffix];
+    let common = before.len().min(after.len());
+    for (offset, (old, new)) in before.iter().zip(after).enumerate() {
+        diff_values(&format!("{path}/{}", prefix + offset), old, new, operations);
+    }
+    for _ in common..before.len() {
+        operations.push(PatchOperation::Remove {
+            path: format!("{path}/{}", prefix + common),
+        });
+    }
+    for (offset, new) in after.iter().enumerate().skip(common) {
+        operations.push(PatchOperation::Add {
+            path: format!("{path}/{}", prefix + offset),
+            value: new.clone(),
+        });
+    }
+}
+
+impl JsonPatch {
+    /// The patch taking `before` to `after`, using `add`, `remove` and `replace`.
+    pub fn between(before: &Value, after: &Value) -> Self {
+        let mut operations = Vec::new();
+        diff_values("", before, after, &mut operations);
+        JsonPatch(operations)
+    }
+
+    /// The patch taking the save document `before` to `after`, manifests included.
+    pub fn between_documents(before: &SaveDocument, after: &SaveDocument) -> Self {
+        let to_value = |doc| serde_json::to_value(doc).unwrap_or_default();
+        Self::between(&to_value(before), &to_value(after))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Applies the operations in order. If one fails, `doc` is left unchanged.
+    pub fn apply(&self, doc: &mut Value) -> Result<(), PatchError> {
+        let mut patched = doc.clone();
+        for operation in &self.0 {
+            apply_operation(&mut patched, operation)?;
+        }
+        *doc = patched;
+        Ok(())
+    }
+
+    /// Applies the patch to a save document, which must remain a JSON object.
+    pub fn apply_to_document(&self, doc: &mut SaveDocument) -> Result<(), PatchError> {
+        let mut value = Value::Object(doc.iter().map(|(k, v)| (k.clone(), v.clone())).collect());
+        self.apply(&mut value)?;
+        let Value::Object(map) = value else {
+            return Err(PatchError::InvalidPath(String::new()));
+        };
+        *doc = map.into_iter().collect();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ecs::prelude::*;
+    use serde_json::json;
+
+    use crate::registry::SaveRegistry;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_patch_documents() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Component2>();
+        let mut world = World::default();
+        let entities: Vec<Entity> = (0..5)
+            .map(|_| world.spawn((Component1, SerializeMe)).id())
+            .collect();
+        let before = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        world.despawn(entities[2]);
+        world.entity_mut(entities[3]).insert(Component2 {
+            target: entities[0],
+        });
+        let after = registry.serialize::<SerializeMe>(&mut world).unwrap();
+
+        let patch = JsonPatch::between_documents(&before, &after);
+        assert_eq!(
+            serde_json::to_value(&patch).unwrap(),
+            json!([
+                { "op": "remove", "path": "/Component1/2" },
+                { "op": "add", "path": "/Component2", "value": after["Component2"] },
+            ])
+        );
+        let mut patched = before.clone();
+        patch.apply_to_document(&mut patched).unwrap();
+        assert_eq!(patched, after);
+    }
+
+    #[test]
+    fn test_rfc_operations() {
+        let mut doc = json!({ "a/b": [1, 2], "c": { "d": 3 } });
+        let patch: JsonPatch = serde_json::from_value(json!([
+            { "op": "test", "path": "/a~1b/1", "value": 2 },
+            { "op": "add", "path": "/a~1b/-", "value": 4 },
+            { "op": "move", "from": "/c/d", "path": "/e" },
+            { "op": "copy", "from": "/e", "path": "/c/f" },
+            { "op": "replace", "path": "/a~1b/0", "value": 0 },
+        ]))
+        .unwrap();
+        patch.apply(&mut doc).unwrap();
+        assert_eq!(doc, json!({ "a/b": [0, 2, 4], "c": { "f": 3 }, "e": 3 }));
+
+        let failing: JsonPatch = serde_json::from_value(json!([
+            { "op": "remove", "path": "/e" },
+            { "op": "test", "path": "/c/f", "value": 4 },
+        ]))
+        .unwrap();
+        assert_eq!(
+            failing.apply(&mut doc),
+            Err(PatchError::TestFailed("/c/f".to_string()))
+        );
+        assert_eq!(doc["e"], 3);
+    }
+}
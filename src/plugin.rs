@@ -0,0 +1,171 @@
+//! The bevy_app side of the crate. Everything else works on a bare `World`, for headless
+//! simulations that use bevy_ecs without the app and schedule stack; [`PersistencePlugin`]
+//! only sets up what an app's saves and loads report to.
+
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_utils::tracing::error;
+
+use crate::async_io::{IoCompleted, IoOperation};
+use crate::events::LoadCompleted;
+use crate::lock::{PersistenceBlocked, PersistenceLock};
+use crate::metrics::{PersistenceMetrics, PersistenceTelemetry};
+use crate::profiles::Profiles;
+use crate::storage::SaveStorage;
+
+/// Adds the persistence events (`IoCompleted`, `LoadCompleted`, `PersistenceBlocked` and
+/// `PersistenceTelemetry`) and the `PersistenceMetrics`, `PersistenceLock` and `Profiles`
+/// resources. Resources inserted before the plugin, e.g. a lock with another
+/// [`LockPolicy`](crate::LockPolicy), are kept.
+#[derive(Default)]
+pub struct PersistencePlugin;
+
+impl Plugin for PersistencePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<IoCompleted>()
+            .add_event::<LoadCompleted>()
+            .add_event::<PersistenceBlocked>()
+            .add_event::<PersistenceTelemetry>()
+            .init_resource::<PersistenceMetrics>()
+            .init_resource::<PersistenceLock>()
+            .init_resource::<Profiles>();
+    }
+}
+
+/// Sent by the game when the player dies, for [`PermadeathPlugin`] to delete their save.
+#[derive(Event, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PlayerDied;
+
+/// The slot [`PermadeathPlugin`] deletes, with whether a deletion is waiting for a save in
+/// flight to finish.
+#[derive(Resource)]
+pub struct Permadeath<S> {
+    storage: S,
+    slot: String,
+    pending: bool,
+}
+
+impl<S> Permadeath<S> {
+    pub fn slot(&self) -> &str {
+        &self.slot
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending
+    }
+}
+
+/// Deletes `slot` of `storage` [permanently](SaveStorage::delete_slot_permanently) on a
+/// [`PlayerDied`] event, in [`Last`], and sends an [`IoCompleted`] event for the deletion.
+/// A save holding the world's [`PersistenceLock`] is let finish first, so that it can't
+/// write the slot back; the game should start no saves of the slot once the player died.
+/// Adds [`PersistencePlugin`] if it isn't yet.
+pub struct PermadeathPlugin<S> {
+    storage: S,
+    slot: String,
+}
+
+impl<S> PermadeathPlugin<S> {
+    pub fn new(storage: S, slot: &str) -> Self {
+        PermadeathPlugin {
+            storage,
+            slot: slot.to_string(),
+        }
+    }
+}
+
+impl<S: SaveStorage + Clone + Send + Sync + 'static> Plugin for PermadeathPlugin<S> {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<PersistencePlugin>() {
+            app.add_plugins(PersistencePlugin);
+        }
+        app.add_event::<PlayerDied>()
+            .insert_resource(Permadeath {
+                storage: self.storage.clone(),
+                slot: self.slot.clone(),
+                pending: false,
+            })
+            .add_systems(Last, delete_on_player_died::<S>);
+    }
+}
+
+fn delete_on_player_died<S: SaveStorage + Send + Sync + 'static>(
+    mut died: EventReader<PlayerDied>,
+    mut permadeath: ResMut<Permadeath<S>>,
+    lock: Option<Res<PersistenceLock>>,
+    mut completed: EventWriter<IoCompleted>,
+) {
+    if died.read().count() > 0 {
+        permadeath.pending = true;
+    }
+    if !permadeath.pending || lock.is_some_and(|lock| lock.held().is_some()) {
+        return;
+    }
+    permadeath.pending = false;
+    let Permadeath { storage, slot, .. } = &mut *permadeath;
+    let result = storage.delete_slot_permanently(slot);
+    if let Err(err) = &result {
+        error!("failed to delete slot {slot}: {err}");
+    }
+    completed.send(IoCompleted {
+        slot: slot.clone(),
+        operation: IoOperation::Delete,
+        error: result.err().map(|err| err.to_string()),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ecs::event::Events;
+
+    use crate::lock::LockPolicy;
+    use crate::storage::FileStorage;
+    use crate::tests::{Component1, SerializeMe};
+    use crate::{SaveConfig, SaveRegistry};
+
+    #[test]
+    fn test_plugin_sets_up_reporting() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>();
+        let mut app = App::new();
+        app.insert_resource(PersistenceLock::new().with_policy(LockPolicy::Queue))
+            .add_plugins(PersistencePlugin);
+        app.world.spawn((Component1, SerializeMe));
+        registry
+            .save_bytes::<SerializeMe>(&mut app.world, &SaveConfig::new())
+            .unwrap();
+
+        assert_eq!(app.world.resource::<PersistenceMetrics>().saves, 1);
+        let events = app.world.resource::<Events<PersistenceTelemetry>>();
+        assert_eq!(events.get_reader().read(events).count(), 1);
+        assert_eq!(
+            app.world.resource::<PersistenceLock>().policy(),
+            LockPolicy::Queue
+        );
+    }
+
+    #[test]
+    fn test_player_death_deletes_the_slot() {
+        let dir =
+            std::env::temp_dir().join(format!("bevy_serde_permadeath_{}", std::process::id()));
+        let mut storage = FileStorage::new(&dir);
+        storage.write("run", b"save").unwrap();
+        storage.write("run.delta", b"delta").unwrap();
+        let mut app = App::new();
+        app.add_plugins(PermadeathPlugin::new(storage.clone(), "run"));
+        app.update();
+        assert_eq!(storage.slots().unwrap().len(), 2);
+
+        app.world.send_event(PlayerDied);
+        app.update();
+        assert!(storage.slots().unwrap().is_empty());
+        let events = app.world.resource::<Events<IoCompleted>>();
+        let mut reader = events.get_reader();
+        let sent: Vec<&IoCompleted> = reader.read(events).collect();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].operation, IoOperation::Delete);
+        assert_eq!(sent[0].error, None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
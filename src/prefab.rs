@@ -0,0 +1,316 @@
+//! Prefabs exported from a running game, e.g. by an in-game editor: a selection of entity
+//! trees written to a standalone file, to be spawned any number of times later.
+
+use std::path::Path;
+
+use bevy_ecs::prelude::*;
+use serde::de::Error;
+use serde_json::{Map, Value};
+
+use crate::config::{SaveConfig, SaveError};
+use crate::registry::SaveRegistry;
+use crate::subtree::{fragment_hierarchy, spawn_from_json};
+use crate::SaveDocument;
+
+/// Marks the entities of a prefab while it is internalized.
+#[derive(Component, Clone)]
+struct PrefabEntity;
+
+/// Serializes `roots` and their descendants, as
+/// [`serialize_entities_with_descendants`](SaveRegistry::serialize_entities_with_descendants)
+/// does, with the entity references internalized: the prefab's entities are numbered from
+/// 0, and references leaving the selection point at ids no entity will have, so they
+/// dangle wherever the prefab is spawned instead of hitting an unrelated entity.
+///
+/// Asset handles are written as whatever their components save, e.g. the asset path of
+/// a component registered with [`register_from_save`](SaveRegistry::register_from_save).
+/// Those components are rebuilt once while internalizing, in a world of their own.
+pub fn prefab_document(
+    world: &World,
+    registry: &SaveRegistry,
+    roots: &[Entity],
+) -> Result<SaveDocument, serde_json::Error> {
+    let fragment = registry.serialize_entities_with_descendants(world, roots)?;
+    let mut scratch = World::new();
+    let roots = spawn_from_json(&mut scratch, registry, &fragment, PrefabEntity)?;
+    registry.serialize_entities_with_descendants(&scratch, &roots)
+}
+
+/// Writes the [`prefab_document`] of `roots` to `path`, encoded by `config`.
+pub fn export_prefab(
+    world: &World,
+    registry: &SaveRegistry,
+    roots: &[Entity],
+    path: impl AsRef<Path>,
+    config: &SaveConfig,
+) -> Result<(), SaveError> {
+    let doc = prefab_document(world, registry, roots)?;
+    std::fs::write(path, config.encode(&doc)?)?;
+    Ok(())
+}
+
+/// Reads a prefab written by [`export_prefab`] with the same `config`, to spawn with
+/// [`spawn_prefab`] or [`spawn_from_json`].
+pub fn read_prefab(path: impl AsRef<Path>, config: &SaveConfig) -> Result<SaveDocument, SaveError> {
+    config.decode(&std::fs::read(path)?)
+}
+
+/// Spawns a copy of `prefab`, adding `marker` to each of its entities, and returns the
+/// copies of its roots. The same as [`spawn_from_json`].
+pub fn spawn_prefab<M: Component + Clone>(
+    world: &mut World,
+    registry: &SaveRegistry,
+    prefab: &SaveDocument,
+    marker: M,
+) -> Result<Vec<Entity>, serde_json::Error> {
+    spawn_from_json(world, registry, prefab, marker)
+}
+
+/// Changes to a prefab's component values made as it is spawned by
+/// [`spawn_prefab_with`], so that one template yields varied instances: other positions,
+/// factions or loot tables.
+#[derive(Clone, Debug, Default)]
+pub struct PrefabOverrides {
+    overrides: Vec<Override>,
+}
+
+#[derive(Clone, Debug)]
+struct Override {
+    section: String,
+    patch: Value,
+    /// Whether the override applies to every entity of the prefab rather than its roots.
+    everywhere: bool,
+}
+
+impl PrefabOverrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Merges `patch` over the `section` component of the prefab's roots as a JSON merge
+    /// patch (RFC 7386): objects are merged key by key, `null` removes a key, and anything
+    /// else replaces the value. Roots without the component get `patch` as their value.
+    pub fn with(mut self, section: &str, patch: impl Into<Value>) -> Self {
+        self.overrides.push(Override {
+            section: section.to_string(),
+            patch: patch.into(),
+            everywhere: false,
+        });
+        self
+    }
+
+    /// Like [`with`](Self::with), but for every entity of the prefab that has the
+    /// component, and only those.
+    pub fn with_everywhere(mut self, section: &str, patch: impl Into<Value>) -> Self {
+        self.overrides.push(Override {
+            section: section.to_string(),
+            patch: patch.into(),
+            everywhere: true,
+        });
+        self
+    }
+
+    /// Applies the overrides to `prefab`, in the order they were added.
+    pub fn apply(
+        &self,
+        registry: &SaveRegistry,
+        prefab: &mut SaveDocument,
+    ) -> Result<(), serde_json::Error> {
+        let roots: Vec<u64> = fragment_hierarchy(prefab)?
+            .into_iter()
+            .filter(|(_, parent)| parent.is_none())
+            .map(|(entity, _)| entity.to_bits())
+            .collect();
+        for each in &self.overrides {
+            let Some(reg) = registry.get(&each.section) else {
+                return Err(serde_json::Error::custom(format!(
+                    "no registered component is saved in section {}",
+                    each.section
+                )));
+            };
+            let mut missing = if each.everywhere {
+                Vec::new()
+            } else {
+                roots.clone()
+            };
+            let mut entries = match prefab.remove(reg.name()) {
+                Some(Value::Array(entries)) => entries,
+                Some(_) => {
+                    return Err(serde_json::Error::custom(format!(
+                        "section {} is not a list of [entity, component] pairs",
+                        reg.name()
+                    )))
+                }
+                None => Vec::new(),
+            };
+            for entry in &mut entries {
+                let Some([entity, comp]) = entry.as_array_mut().map(Vec::as_mut_slice) else {
+                    continue;
+                };
+                let Some(bits) = entity.as_u64() else {
+                    continue;
+                };
+                if each.everywhere || roots.contains(&bits) {
+                    merge_patch(comp, &each.patch);
+                    missing.retain(|root| *root != bits);
+                }
+            }
+            entries.extend(
+                missing
+                    .into_iter()
+                    .map(|root| Value::Array(vec![root.into(), each.patch.clone()])),
+            );
+            if !entries.is_empty() {
+                prefab.insert(reg.name().to_string(), Value::Array(entries));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Merges `patch` into `target` as RFC 7386 describes.
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+/// Like [`spawn_prefab`], but with `overrides` applied to a copy of the prefab first.
+pub fn spawn_prefab_with<M: Component + Clone>(
+    world: &mut World,
+    registry: &SaveRegistry,
+    prefab: &SaveDocument,
+    overrides: &PrefabOverrides,
+    marker: M,
+) -> Result<Vec<Entity>, serde_json::Error> {
+    let mut prefab = prefab.clone();
+    overrides.apply(registry, &mut prefab)?;
+    spawn_from_json(world, registry, &prefab, marker)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_hierarchy::{BuildWorldChildren, Children};
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Unit {
+        faction: String,
+        hp: u32,
+    }
+
+    #[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Position(i32, i32);
+
+    #[test]
+    fn test_exported_prefab_is_self_contained() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .register_mapped::<Component2>();
+        let mut world = World::default();
+        for _ in 0..50 {
+            world.spawn(Component1);
+        }
+        let outside = world.spawn(Component1).id();
+        let root = world.spawn(Component1).id();
+        let child = world.spawn(Component2 { target: root }).id();
+        let leaving = world.spawn(Component2 { target: outside }).id();
+        world.entity_mut(root).push_children(&[child, leaving]);
+
+        let dir = std::env::temp_dir().join(format!("bevy_serde_prefab_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tower.json");
+        let config = SaveConfig::new().with_pretty(true);
+        export_prefab(&world, &registry, &[root], &path, &config).unwrap();
+        let prefab = read_prefab(&path, &config).unwrap();
+        let ids: Vec<u64> = prefab["Component1"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry[0].as_u64().unwrap())
+            .collect();
+        assert_eq!(ids, vec![0]);
+
+        let mut target = World::default();
+        let bystander = target.spawn(Component1).id();
+        let roots = spawn_prefab(&mut target, &registry, &prefab, SerializeMe).unwrap();
+        assert_eq!(roots.len(), 1);
+        let children = target.get::<Children>(roots[0]).unwrap().to_vec();
+        let targets: Vec<Entity> = children
+            .iter()
+            .map(|child| target.get::<Component2>(*child).unwrap().target)
+            .collect();
+        assert!(targets.contains(&roots[0]));
+        assert!(!targets.contains(&bystander));
+        assert!(targets
+            .iter()
+            .any(|target_entity| target.get_entity(*target_entity).is_none()));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_overrides_vary_instances() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Unit>().register::<Position>();
+        let mut world = World::default();
+        let unit = |faction: &str| Unit {
+            faction: faction.to_string(),
+            hp: 10,
+        };
+        let root = world.spawn(unit("neutral")).id();
+        let guard = world.spawn(unit("neutral")).id();
+        world.entity_mut(root).add_child(guard);
+        let prefab = prefab_document(&world, &registry, &[root]).unwrap();
+
+        let overrides = PrefabOverrides::new()
+            .with("Unit", serde_json::json!({"faction": "orcs"}))
+            .with_everywhere("Unit", serde_json::json!({"hp": 25}))
+            .with("Position", serde_json::json!([4, -2]));
+        let roots =
+            spawn_prefab_with(&mut world, &registry, &prefab, &overrides, SerializeMe).unwrap();
+        let new_guard = world.get::<Children>(roots[0]).unwrap()[0];
+        assert_eq!(
+            world.get::<Unit>(roots[0]),
+            Some(&Unit {
+                faction: "orcs".to_string(),
+                hp: 25
+            })
+        );
+        assert_eq!(
+            world.get::<Unit>(new_guard),
+            Some(&Unit {
+                faction: "neutral".to_string(),
+                hp: 25
+            })
+        );
+        assert_eq!(world.get::<Position>(roots[0]), Some(&Position(4, -2)));
+        assert_eq!(world.get::<Position>(new_guard), None);
+
+        let typo = PrefabOverrides::new().with("Unti", serde_json::json!({}));
+        assert!(spawn_prefab_with(&mut world, &registry, &prefab, &typo, SerializeMe).is_err());
+        let mut target = serde_json::json!({"a": {"b": 1, "c": 2}, "d": 3});
+        merge_patch(
+            &mut target,
+            &serde_json::json!({"a": {"b": null, "e": 4}, "d": [5]}),
+        );
+        assert_eq!(target, serde_json::json!({"a": {"c": 2, "e": 4}, "d": [5]}));
+    }
+}
@@ -0,0 +1,181 @@
+//! Named save profiles, e.g. "quicksave", "cloud" or "debug_full", each choosing the
+//! components to save and the [`SaveConfig`] to encode them with, so gameplay code only
+//! names the profile.
+
+use bevy_ecs::prelude::*;
+use bevy_utils::hashbrown::HashMap;
+
+use crate::async_io::IoOperation;
+use crate::config::{SaveConfig, SaveError};
+use crate::load::LoadReport;
+use crate::lock;
+use crate::registry::SaveRegistry;
+
+/// The components a profile saves and how it encodes them.
+#[derive(Clone, Debug, Default)]
+pub struct SaveProfile {
+    config: SaveConfig,
+    sections: Option<Vec<String>>,
+}
+
+impl SaveProfile {
+    /// A profile saving every registered component with `config`.
+    pub fn new(config: SaveConfig) -> Self {
+        SaveProfile {
+            config,
+            sections: None,
+        }
+    }
+
+    /// Restricts the profile to the components with these section names.
+    pub fn with_sections(mut self, sections: &[&str]) -> Self {
+        self.sections = Some(sections.iter().map(|name| name.to_string()).collect());
+        self
+    }
+
+    pub fn config(&self) -> &SaveConfig {
+        &self.config
+    }
+
+    /// Whether the profile saves and loads the section `name`.
+    pub fn includes(&self, name: &str) -> bool {
+        self.sections
+            .as_ref()
+            .is_none_or(|sections| sections.iter().any(|section| section == name))
+    }
+}
+
+/// The save profiles available to [`SaveRequest`]s, by name.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct Profiles {
+    profiles: HashMap<String, SaveProfile>,
+}
+
+impl Profiles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the profile `name`, replacing any profile of that name.
+    pub fn insert(&mut self, name: &str, profile: SaveProfile) -> &mut Self {
+        self.profiles.insert(name.to_string(), profile);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SaveProfile> {
+        self.profiles.get(name)
+    }
+}
+
+/// A save or load using one of the world's [`Profiles`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SaveRequest {
+    profile: String,
+}
+
+impl SaveRequest {
+    pub fn new(profile: &str) -> Self {
+        SaveRequest {
+            profile: profile.to_string(),
+        }
+    }
+
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+
+    pub(crate) fn resolve(&self, world: &World) -> Result<SaveProfile, SaveError> {
+        world
+            .get_resource::<Profiles>()
+            .and_then(|profiles| profiles.get(&self.profile))
+            .cloned()
+            .ok_or_else(|| SaveError::UnknownProfile(self.profile.clone()))
+    }
+}
+
+impl SaveRegistry {
+    /// Saves the entities marked with `M` as [`save_bytes`](Self::save_bytes) does, with
+    /// the components and config of the requested profile.
+    pub fn save_request<M: Component>(
+        &self,
+        world: &mut World,
+        request: &SaveRequest,
+    ) -> Result<Vec<u8>, SaveError> {
+        lock::check(world, IoOperation::Save, request.profile(), Some(request))?;
+        let profile = request.resolve(world)?;
+        self.save_sections::<M>(world, &profile.config, |name| profile.includes(name))
+    }
+
+    /// Loads a save written with the requested profile as [`load_bytes`](Self::load_bytes)
+    /// does, restoring only the profile's components.
+    pub fn load_request<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        bytes: &[u8],
+        request: &SaveRequest,
+        marker: M,
+    ) -> Result<LoadReport, SaveError> {
+        lock::check(world, IoOperation::Load, request.profile(), None)?;
+        let profile = request.resolve(world)?;
+        self.load_sections(
+            world,
+            bytes,
+            &profile.config,
+            |name| profile.includes(name),
+            marker,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_profiles() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Component2>();
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        world.spawn((Component2 { target: entity1 }, SerializeMe));
+        let mut profiles = Profiles::new();
+        profiles
+            .insert(
+                "quicksave",
+                SaveProfile::new(SaveConfig::new()).with_sections(&["Component1"]),
+            )
+            .insert(
+                "cloud",
+                SaveProfile::new(SaveConfig::new().with_pretty(true)),
+            );
+        world.insert_resource(profiles);
+
+        let quicksave = SaveRequest::new("quicksave");
+        let bytes = registry
+            .save_request::<SerializeMe>(&mut world, &quicksave)
+            .unwrap();
+        let doc = SaveConfig::new().decode(&bytes).unwrap();
+        assert!(doc.contains_key("Component1") && !doc.contains_key("Component2"));
+        let mut loaded = World::default();
+        loaded.insert_resource(world.resource::<Profiles>().clone());
+        let report = registry
+            .load_request(&mut loaded, &bytes, &quicksave, SerializeMe)
+            .unwrap();
+        assert_eq!(report.entity_map.len(), 1);
+
+        let cloud = SaveRequest::new("cloud");
+        let bytes = registry
+            .save_request::<SerializeMe>(&mut world, &cloud)
+            .unwrap();
+        assert!(bytes.contains(&b'\n'));
+        let report = registry
+            .load_request(&mut loaded, &bytes, &cloud, SerializeMe)
+            .unwrap();
+        assert_eq!(report.entity_map.len(), 2);
+        assert!(matches!(
+            registry.save_request::<SerializeMe>(&mut world, &SaveRequest::new("debug_full")),
+            Err(SaveError::UnknownProfile(_))
+        ));
+    }
+}
@@ -0,0 +1,108 @@
+//! Rounding floats on save, so that physics jitter in the last bits of a position doesn't
+//! make every autosave differ from the one before, and delta saves find their matches.
+//! Rounded values are also written canonically: `-0.0` becomes `0.0`, and a value such as
+//! `0.1 + 0.2` is written as the `0.3` it rounds to.
+
+use bevy_ecs::prelude::*;
+use serde_json::Value;
+
+use crate::registry::SaveRegistry;
+
+/// Rounds every float in `value` to `decimals` decimal places. Integers are untouched.
+pub fn quantize_floats(value: &mut Value, decimals: u32) {
+    match value {
+        Value::Number(number) if number.is_f64() => {
+            let Some(float) = number.as_f64() else {
+                return;
+            };
+            let scale = 10f64.powi(decimals.min(i32::MAX as u32) as i32);
+            let rounded = (float * scale).round() / scale;
+            if rounded.is_finite() {
+                // adding zero turns -0.0 into 0.0
+                *value = Value::from(rounded + 0.0);
+            }
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| quantize_floats(value, decimals)),
+        Value::Object(fields) => fields
+            .values_mut()
+            .for_each(|value| quantize_floats(value, decimals)),
+        _ => {}
+    }
+}
+
+impl SaveRegistry {
+    /// Rounds the floats of every saved `C` to `decimals` decimal places. A
+    /// [`SaveConfig::with_float_precision`](crate::SaveConfig::with_float_precision) applies
+    /// to every component instead.
+    ///
+    /// # Panics
+    /// If `C` hasn't been registered.
+    pub fn set_float_precision<C: Component>(&mut self, decimals: u32) -> &mut Self {
+        let Some(reg) = self.get_by_type_mut::<C>() else {
+            panic!(
+                "{} must be registered before setting its float precision",
+                std::any::type_name::<C>()
+            );
+        };
+        reg.float_precision = Some(decimals);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::config::SaveConfig;
+    use crate::tests::SerializeMe;
+
+    #[derive(Component, Serialize, Deserialize)]
+    struct Position {
+        x: f32,
+        y: f64,
+        tile: u32,
+    }
+
+    #[test]
+    fn test_jitter_is_rounded_away() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Position>()
+            .set_float_precision::<Position>(3);
+        let mut world = World::default();
+        let entity = world
+            .spawn((
+                Position {
+                    x: 1.000_01,
+                    y: 0.1 + 0.2,
+                    tile: 7,
+                },
+                SerializeMe,
+            ))
+            .id();
+        let first = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        assert_eq!(
+            first["Position"][0][1],
+            serde_json::json!({"x": 1.0, "y": 0.3, "tile": 7})
+        );
+        world.get_mut::<Position>(entity).unwrap().x = 0.999_99;
+        let second = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        assert_eq!(first["Position"], second["Position"]);
+
+        let mut value = serde_json::json!([-0.0001, 2.5, 1u64 << 60]);
+        quantize_floats(&mut value, 2);
+        assert_eq!(value.to_string(), format!("[0.0,2.5,{}]", 1u64 << 60));
+
+        let config = SaveConfig::new().with_float_precision(1);
+        let mut registry = SaveRegistry::new();
+        registry.register::<Position>();
+        let bytes = registry
+            .save_bytes::<SerializeMe>(&mut world, &config)
+            .unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.contains(r#"{"tile":7,"x":1.0,"y":0.3}"#), "{text}");
+    }
+}
@@ -0,0 +1,217 @@
+//! Sanitized copies of saves for bug reports: personal data such as profile names embedded
+//! in components is dropped, hashed or zeroed, so players can attach the copy without
+//! exposing it.
+
+use serde_json::{Map, Value};
+
+use crate::config::{SaveConfig, SaveError};
+use crate::hash::StableHasher;
+use crate::SaveDocument;
+
+/// What a [`Redaction`] does to a component or field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RedactAction {
+    /// Removes it: the component's whole section, or the field from the component.
+    Drop,
+    /// Replaces it with a string of its stable hash, so that equal values still match up
+    /// across the save, e.g. a name shared by a profile and a save slot label.
+    Hash,
+    /// Replaces it with a value of the same shape: numbers become `0`, strings empty,
+    /// booleans `false`, and sequences empty; objects keep their keys. Zeroed components
+    /// usually still load, which keeps the copy useful for reproducing the bug.
+    Zero,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    section: String,
+    path: Vec<String>,
+    action: RedactAction,
+}
+
+/// A redaction pass, configured per component section and field.
+#[derive(Clone, Debug, Default)]
+pub struct Redaction {
+    rules: Vec<Rule>,
+}
+
+impl Redaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Redacts every value of the component saved in `section`.
+    pub fn with_component(mut self, section: &str, action: RedactAction) -> Self {
+        self.rules.push(Rule {
+            section: section.to_string(),
+            path: Vec::new(),
+            action,
+        });
+        self
+    }
+
+    /// Redacts a field of the component saved in `section`, given as a dotted path such as
+    /// `"profile.name"`. A path going through a sequence applies to each of its elements;
+    /// components without the field are left alone.
+    pub fn with_field(mut self, section: &str, path: &str, action: RedactAction) -> Self {
+        self.rules.push(Rule {
+            section: section.to_string(),
+            path: path.split('.').map(str::to_string).collect(),
+            action,
+        });
+        self
+    }
+
+    /// Applies the rules to `doc`, in the order they were added.
+    pub fn apply(&self, doc: &mut SaveDocument) {
+        for rule in &self.rules {
+            if rule.path.is_empty() && rule.action == RedactAction::Drop {
+                doc.remove(&rule.section);
+                continue;
+            }
+            let Some(Value::Array(entries)) = doc.get_mut(&rule.section) else {
+                continue;
+            };
+            for entry in entries {
+                if let Some([_, comp]) = entry.as_array_mut().map(Vec::as_mut_slice) {
+                    redact_path(comp, &rule.path, rule.action);
+                }
+            }
+        }
+    }
+
+    /// Decodes a save written with `config`, redacts it, and encodes the sanitized copy
+    /// with `config` again.
+    pub fn redact_bytes(&self, bytes: &[u8], config: &SaveConfig) -> Result<Vec<u8>, SaveError> {
+        let mut doc = config.decode(bytes)?;
+        self.apply(&mut doc);
+        config.encode(&doc)
+    }
+}
+
+fn redact_path(value: &mut Value, path: &[String], action: RedactAction) {
+    let Some((field, rest)) = path.split_first() else {
+        redact_value(value, action);
+        return;
+    };
+    match value {
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| redact_path(value, path, action)),
+        Value::Object(fields) if rest.is_empty() && action == RedactAction::Drop => {
+            fields.remove(field);
+        }
+        Value::Object(fields) => {
+            if let Some(value) = fields.get_mut(field) {
+                redact_path(value, rest, action);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn redact_value(value: &mut Value, action: RedactAction) {
+    match action {
+        // only reached for whole components, which `apply` drops by section
+        RedactAction::Drop => *value = Value::Null,
+        RedactAction::Hash => {
+            let mut hasher = StableHasher::new();
+            // serde_json objects serialize with sorted keys, so this is canonical
+            hasher.write_bytes(value.to_string().as_bytes());
+            *value = Value::String(format!("{:016x}", hasher.finish()));
+        }
+        RedactAction::Zero => *value = zeroed(value),
+    }
+}
+
+fn zeroed(value: &Value) -> Value {
+    match value {
+        Value::Null => Value::Null,
+        Value::Bool(_) => Value::Bool(false),
+        Value::Number(_) => Value::from(0),
+        Value::String(_) => Value::String(String::new()),
+        Value::Array(_) => Value::Array(Vec::new()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| (key.clone(), zeroed(value)))
+                .collect::<Map<String, Value>>(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ecs::prelude::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::load::LoadMode;
+    use crate::registry::SaveRegistry;
+    use crate::tests::{Component1, SerializeMe};
+
+    #[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Profile {
+        name: String,
+        #[serde(default)]
+        email: String,
+        friends: Vec<Friend>,
+        playtime: u32,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Friend {
+        name: String,
+    }
+
+    #[test]
+    fn test_redacted_copy_hides_personal_data() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Profile>().register::<Component1>();
+        let mut world = World::default();
+        world.spawn((
+            Profile {
+                name: "Alice".to_string(),
+                email: "alice@example.com".to_string(),
+                friends: vec![Friend {
+                    name: "Bob".to_string(),
+                }],
+                playtime: 7200,
+            },
+            SerializeMe,
+        ));
+        world.spawn((Component1, SerializeMe));
+        let config = SaveConfig::new();
+        let bytes = registry
+            .save_bytes::<SerializeMe>(&mut world, &config)
+            .unwrap();
+
+        let redaction = Redaction::new()
+            .with_field("Profile", "name", RedactAction::Hash)
+            .with_field("Profile", "email", RedactAction::Drop)
+            .with_field("Profile", "friends.name", RedactAction::Zero)
+            .with_component("Component1", RedactAction::Drop);
+        let sanitized = redaction.redact_bytes(&bytes, &config).unwrap();
+        let text = String::from_utf8(sanitized.clone()).unwrap();
+        assert!(!text.contains("Alice") && !text.contains("alice@") && !text.contains("Bob"));
+
+        let mut doc = config.decode(&sanitized).unwrap();
+        assert!(!doc.contains_key("Component1"));
+        let mut loaded = World::default();
+        registry
+            .load(&mut loaded, &mut doc, LoadMode::Merge, SerializeMe)
+            .unwrap();
+        let profile = loaded.query::<&Profile>().single(&loaded).clone();
+        assert_eq!(profile.name.len(), 16);
+        assert_eq!(profile.email, "");
+        assert_eq!(profile.friends[0].name, "");
+        assert_eq!(profile.playtime, 7200);
+
+        let mut zeroed = serde_json::json!({"a": [1, 2], "b": {"c": 1.5, "d": true}});
+        redact_value(&mut zeroed, RedactAction::Zero);
+        assert_eq!(
+            zeroed,
+            serde_json::json!({"a": [], "b": {"c": 0, "d": false}})
+        );
+    }
+}
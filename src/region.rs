@@ -0,0 +1,303 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::Hash;
+use std::io;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use bevy_ecs::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::parse;
+use crate::registry::SaveRegistry;
+use crate::SaveDocument;
+
+type FileNameFn<R> = Box<dyn Fn(&R) -> String + Send + Sync>;
+
+#[derive(Debug)]
+pub enum RegionError {
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for RegionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegionError::Io(err) => write!(f, "{err}"),
+            RegionError::Json(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for RegionError {}
+
+impl From<io::Error> for RegionError {
+    fn from(err: io::Error) -> Self {
+        RegionError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for RegionError {
+    fn from(err: serde_json::Error) -> Self {
+        RegionError::Json(err)
+    }
+}
+
+/// A key's JSON encoding with anything but alphanumerics and `-` replaced, for use in file
+/// names, so `(3, -2)` becomes `3_-2`.
+pub(crate) fn file_stem<K: Serialize>(key: &K) -> String {
+    let json = serde_json::to_string(key).unwrap_or_default();
+    json.trim_matches(|c: char| !c.is_alphanumeric() && c != '-')
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// The default region file name, so `(3, -2)` is stored as `region_3_-2.json`.
+fn default_file_name<R: Serialize>(key: &R) -> String {
+    format!("region_{}.json", file_stem(key))
+}
+
+/// Chunked persistence for open worlds: the entities marked with `M` are bucketed by their
+/// region component `R` (e.g. chunk coordinates) and each region is saved to its own file
+/// under a directory, to be loaded and unloaded as the region streams in and out.
+///
+/// `R` must be registered with the [`SaveRegistry`] used, so that loaded entities get
+/// their region back. Regions are loaded through [`SaveRegistry::load_additive`], so a
+/// region's references into regions loaded before it resolve to their entities.
+#[derive(Resource)]
+pub struct RegionStore<R, M> {
+    dir: PathBuf,
+    file_name: FileNameFn<R>,
+    loaded: HashSet<R>,
+    _marker: PhantomData<fn(M)>,
+}
+
+impl<R, M> RegionStore<R, M>
+where
+    R: Component + Clone + Eq + Hash + Serialize + DeserializeOwned,
+    M: Component + Clone,
+{
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        RegionStore {
+            dir: dir.into(),
+            file_name: Box::new(default_file_name::<R>),
+            loaded: HashSet::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Overrides how region keys are turned into file names within the store's directory.
+    pub fn with_file_names(
+        mut self,
+        file_name: impl Fn(&R) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.file_name = Box::new(file_name);
+        self
+    }
+
+    pub fn path(&self, key: &R) -> PathBuf {
+        self.dir.join((self.file_name)(key))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn is_loaded(&self, key: &R) -> bool {
+        self.loaded.contains(key)
+    }
+
+    pub fn loaded_regions(&self) -> impl Iterator<Item = &R> {
+        self.loaded.iter()
+    }
+
+    fn region_entities(world: &mut World, key: &R) -> Vec<Entity> {
+        world
+            .query_filtered::<(Entity, &R), With<M>>()
+            .iter(world)
+            .filter(|(_, region)| *region == key)
+            .map(|(entity, _)| entity)
+            .collect()
+    }
+
+    /// Writes the entities currently in region `key` to its file, replacing what was stored.
+    pub fn save_region(
+        &self,
+        world: &mut World,
+        registry: &SaveRegistry,
+        key: &R,
+    ) -> Result<(), RegionError> {
+        let doc = registry
+            .serialize_filtered::<M>(world, |entity, world| world.get::<R>(entity) == Some(key))?;
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(self.path(key), serde_json::to_vec(&doc)?)?;
+        Ok(())
+    }
+
+    /// Saves every region that currently has entities in the world.
+    pub fn save_all(&self, world: &mut World, registry: &SaveRegistry) -> Result<(), RegionError> {
+        let keys: HashSet<R> = world
+            .query_filtered::<&R, With<M>>()
+            .iter(world)
+            .cloned()
+            .collect();
+        for key in &keys {
+            self.save_region(world, registry, key)?;
+        }
+        Ok(())
+    }
+
+    /// Spawns the stored entities of region `key`, adding `marker` to each. Returns `false`
+    /// if nothing has been stored for the region yet; it is still considered loaded.
+    pub fn load_region(
+        &mut self,
+        world: &mut World,
+        registry: &SaveRegistry,
+        key: &R,
+        marker: M,
+    ) -> Result<bool, RegionError> {
+        self.loaded.insert(key.clone());
+        let bytes = match std::fs::read(self.path(key)) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err.into()),
+        };
+        let mut doc: SaveDocument = parse::from_slice(&bytes)?;
+        registry.load_additive(world, &mut doc, marker)?;
+        Ok(true)
+    }
+
+    /// Saves region `key` and despawns its entities.
+    pub fn unload_region(
+        &mut self,
+        world: &mut World,
+        registry: &SaveRegistry,
+        key: &R,
+    ) -> Result<(), RegionError> {
+        self.save_region(world, registry, key)?;
+        for entity in Self::region_entities(world, key) {
+            world.despawn(entity);
+        }
+        self.loaded.remove(key);
+        Ok(())
+    }
+
+    /// Makes `wanted` the set of loaded regions: loaded regions not in it are unloaded,
+    /// and regions in it that aren't loaded yet are loaded.
+    pub fn stream(
+        &mut self,
+        world: &mut World,
+        registry: &SaveRegistry,
+        wanted: &[R],
+        marker: M,
+    ) -> Result<(), RegionError> {
+        let leaving: Vec<R> = self
+            .loaded
+            .iter()
+            .filter(|key| !wanted.contains(key))
+            .cloned()
+            .collect();
+        for key in &leaving {
+            self.unload_region(world, registry, key)?;
+        }
+        for key in wanted {
+            if !self.is_loaded(key) {
+                self.load_region(world, registry, key, marker.clone())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[derive(Clone, Component, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+    struct Chunk(i32, i32);
+
+    #[test]
+    fn test_region_streaming() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Chunk>();
+        let dir = std::env::temp_dir().join(format!("bevy_serde_regions_{}", std::process::id()));
+        let mut store = RegionStore::<Chunk, SerializeMe>::new(&dir);
+        assert!(store.path(&Chunk(3, -2)).ends_with("region_3_-2.json"));
+
+        let mut world = World::default();
+        let count = |world: &mut World, key: Chunk| {
+            RegionStore::<Chunk, SerializeMe>::region_entities(world, &key).len()
+        };
+        store
+            .stream(
+                &mut world,
+                &registry,
+                &[Chunk(0, 0), Chunk(0, 1)],
+                SerializeMe,
+            )
+            .unwrap();
+        world.spawn((Component1, Chunk(0, 0), SerializeMe));
+        world.spawn((Component1, Chunk(0, 0), SerializeMe));
+        world.spawn((Component1, Chunk(0, 1), SerializeMe));
+
+        store
+            .stream(&mut world, &registry, &[Chunk(0, 1)], SerializeMe)
+            .unwrap();
+        assert!(!store.is_loaded(&Chunk(0, 0)));
+        assert_eq!(count(&mut world, Chunk(0, 0)), 0);
+        assert_eq!(count(&mut world, Chunk(0, 1)), 1);
+
+        store
+            .stream(
+                &mut world,
+                &registry,
+                &[Chunk(0, 0), Chunk(0, 1)],
+                SerializeMe,
+            )
+            .unwrap();
+        assert_eq!(count(&mut world, Chunk(0, 0)), 2);
+        assert_eq!(count(&mut world, Chunk(0, 1)), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_references_into_loaded_regions_resolve() {
+        let mut registry = SaveRegistry::new();
+        registry.register_mapped::<Component2>().register::<Chunk>();
+        let dir =
+            std::env::temp_dir().join(format!("bevy_serde_region_refs_{}", std::process::id()));
+        let mut store = RegionStore::<Chunk, SerializeMe>::new(&dir);
+        let regions = [Chunk(0, 0), Chunk(0, 1)];
+        let mut world = World::default();
+        store
+            .stream(&mut world, &registry, &regions, SerializeMe)
+            .unwrap();
+        let target = world.spawn((Chunk(0, 0), SerializeMe)).id();
+        world.spawn((Component2 { target }, Chunk(0, 1), SerializeMe));
+
+        for _ in 0..2 {
+            store
+                .stream(&mut world, &registry, &[], SerializeMe)
+                .unwrap();
+            assert_eq!(world.entities().len(), 0);
+            store
+                .stream(&mut world, &registry, &regions, SerializeMe)
+                .unwrap();
+            assert_eq!(world.entities().len(), 2);
+            let target = world.query::<&Component2>().single(&world).target;
+            assert_eq!(world.get::<Chunk>(target), Some(&Chunk(0, 0)));
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
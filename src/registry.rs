@@ -0,0 +1,1255 @@
+use std::any::{Any, TypeId};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use bevy_ecs::component::ComponentId;
+use bevy_ecs::entity::MapEntities;
+use bevy_ecs::prelude::*;
+use bevy_hierarchy::Children;
+use bevy_utils::hashbrown::{HashMap, HashSet};
+use bevy_utils::tracing::info_span;
+use bevy_utils::Instant;
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use serde_json::Value;
+
+use crate::delta::section_entries;
+use crate::dynamic::DynamicComponentStore;
+use crate::entity_map::{get_or_insert, map_component_entities, EntityMap};
+use crate::events::send_load_completed;
+use crate::exempt::SaveExempt;
+use crate::layout::is_reserved;
+use crate::load::{DanglingReference, EntityGenerations, LoadReport, SkippedEntry};
+use crate::manifest::{ComponentInfo, Manifest, SchemaHash, MANIFEST_KEY};
+use crate::mods::mod_section_name;
+use crate::quantize::quantize_floats;
+use crate::schema::{trace_or_opaque, Format};
+use crate::snapshot::{capture_column, SnapshotColumn};
+use crate::stats::SaveStats;
+use crate::subtree::{hierarchy_section, HIERARCHY_KEY};
+use crate::ticks::{suppress_changes, LastLoad, LoadChangeDetection};
+use crate::unknown_variants::{
+    is_unknown_variant, write_stashed, StashedComponents, UnknownVariantFallback,
+};
+use crate::validate::ValidateFn;
+use crate::SaveDocument;
+use crate::EMPTY_JS_ARRAY;
+
+pub(crate) type ExtractFn = fn(&World, &[Entity]) -> Result<Option<Value>, serde_json::Error>;
+pub(crate) type InsertFn = Box<
+    dyn Fn(
+            &mut World,
+            &mut EntityMap,
+            Value,
+            &[LoadHookFn],
+        ) -> Result<Vec<Entity>, serde_json::Error>
+        + Send
+        + Sync,
+>;
+pub(crate) type LoadHookFn = Box<dyn Fn(&mut dyn Any, &EntityMap) + Send + Sync>;
+type SaveHookFn = Box<dyn Fn(&mut World, &[Entity]) + Send + Sync>;
+type RemoveFn = fn(&mut World, Entity);
+pub(crate) type SkipSavingFn = Box<dyn Fn(&World, Entity) -> bool + Send + Sync>;
+pub(crate) type FillMissingFn = Box<dyn Fn(&mut World, &[Entity]) -> Vec<Entity> + Send + Sync>;
+pub(crate) type MapEntitiesFn = fn(&mut World, &[Entity], &EntityMap) -> Vec<Entity>;
+pub(crate) type CaptureFn = fn(&World, &[Entity]) -> Option<Box<dyn SnapshotColumn>>;
+
+/// Strips the module path from every path segment of a type name, so that
+/// `my_game::Stat<my_game::Strength>` becomes `Stat<Strength>`. Also accepts the
+/// whitespace-separated output of `stringify!`.
+pub fn short_type_name(type_path: &str) -> String {
+    let compact: String = type_path.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut short = String::with_capacity(compact.len());
+    let mut segment = String::new();
+    for c in compact.chars() {
+        if c.is_alphanumeric() || c == '_' || c == ':' {
+            segment.push(c);
+        } else {
+            short.push_str(segment.rsplit("::").next().unwrap_or(&segment));
+            segment.clear();
+            short.push(c);
+            if c == ',' {
+                short.push(' ');
+            }
+        }
+    }
+    short.push_str(segment.rsplit("::").next().unwrap_or(&segment));
+    short
+}
+
+/// How sections are keyed in documents written by a [`SaveRegistry`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NamingScheme {
+    /// The type name without module paths, e.g. `Health`, as written by the macros.
+    #[default]
+    ShortName,
+    /// The full type path, e.g. `my_game::combat::Health`. Avoids collisions between types
+    /// of the same name and makes saves self-describing.
+    TypePath,
+}
+
+impl NamingScheme {
+    fn section_name(self, type_path: &str) -> String {
+        match self {
+            NamingScheme::ShortName => short_type_name(type_path),
+            NamingScheme::TypePath => type_path.to_string(),
+        }
+    }
+}
+
+/// Everything the registry knows about one serializable component type.
+pub struct ComponentRegistration {
+    name: String,
+    short_name: String,
+    type_path: &'static str,
+    /// The type path section names are derived from, `type_path` unless the component is
+    /// saved as another type, see [`named_after`](Self::named_after).
+    name_path: &'static str,
+    pub(crate) type_id: TypeId,
+    version: u32,
+    schema: Format,
+    pub(crate) extract: ExtractFn,
+    pub(crate) insert: InsertFn,
+    pub(crate) remove: RemoveFn,
+    pub(crate) capture: Option<CaptureFn>,
+    pub(crate) map_entities: Option<MapEntitiesFn>,
+    load_hooks: Vec<LoadHookFn>,
+    save_hooks: Vec<SaveHookFn>,
+    pub(crate) validate: Option<ValidateFn>,
+    /// Components whose sections must be inserted before this one's.
+    pub(crate) dependencies: Vec<TypeId>,
+    mod_name: Option<String>,
+    pub(crate) unknown_variant: Option<UnknownVariantFallback>,
+    pub(crate) float_precision: Option<u32>,
+    pub(crate) skip_saving_if: Option<SkipSavingFn>,
+    pub(crate) fill_missing: Option<FillMissingFn>,
+}
+
+impl ComponentRegistration {
+    fn of<C: Component + Serialize + DeserializeOwned>(version: u32, naming: NamingScheme) -> Self {
+        Self::custom::<C>(
+            version,
+            naming,
+            trace_or_opaque::<C>(),
+            extract_section::<C>,
+            Box::new(insert_section::<C>),
+        )
+    }
+
+    /// A registration of `C` that saves and loads its sections through `extract` and
+    /// `insert` rather than `C`'s serde impls.
+    pub(crate) fn custom<C: Component>(
+        version: u32,
+        naming: NamingScheme,
+        schema: Format,
+        extract: ExtractFn,
+        insert: InsertFn,
+    ) -> Self {
+        let type_path = std::any::type_name::<C>();
+        ComponentRegistration {
+            name: naming.section_name(type_path),
+            short_name: short_type_name(type_path),
+            type_path,
+            name_path: type_path,
+            type_id: TypeId::of::<C>(),
+            version,
+            schema,
+            extract,
+            insert,
+            remove: remove_component::<C>,
+            capture: None,
+            map_entities: None,
+            load_hooks: Vec::new(),
+            save_hooks: Vec::new(),
+            validate: None,
+            dependencies: Vec::new(),
+            mod_name: None,
+            unknown_variant: None,
+            float_precision: None,
+            skip_saving_if: None,
+            fill_missing: None,
+        }
+    }
+
+    /// The entities among `entities` whose component is saved, i.e. not skipped by
+    /// [`SaveRegistry::skip_saving_if`].
+    pub(crate) fn kept_entities<'a>(
+        &self,
+        world: &World,
+        entities: &'a [Entity],
+    ) -> Cow<'a, [Entity]> {
+        match &self.skip_saving_if {
+            Some(skip) => entities
+                .iter()
+                .copied()
+                .filter(|entity| !skip(world, *entity))
+                .collect(),
+            None => Cow::Borrowed(entities),
+        }
+    }
+
+    /// Serializes the components of `entities` that this registration keeps, with its
+    /// float precision applied, or `None` if none of them has one.
+    pub(crate) fn serialize_kept(
+        &self,
+        world: &World,
+        entities: &[Entity],
+    ) -> Result<Option<Value>, serde_json::Error> {
+        let mut section = (self.extract)(world, &self.kept_entities(world, entities))?;
+        if let (Some(section), Some(decimals)) = (&mut section, self.float_precision) {
+            quantize_floats(section, decimals);
+        }
+        Ok(section)
+    }
+
+    /// The key of this component's section in a save document.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The mod this component was registered for, see [`SaveRegistry::set_mod`].
+    pub fn mod_name(&self) -> Option<&str> {
+        self.mod_name.as_deref()
+    }
+
+    /// Keys this component's section as `T`'s, so that its documents are interchangeable
+    /// with those of a registered `T`.
+    pub(crate) fn named_after<T>(mut self, naming: NamingScheme) -> Self {
+        self.name_path = std::any::type_name::<T>();
+        self.rename(naming);
+        self
+    }
+
+    /// Keys this component's section under `naming`, in its mod's namespace if it has one.
+    fn rename(&mut self, naming: NamingScheme) {
+        let (name, short_name) = (
+            naming.section_name(self.name_path),
+            short_type_name(self.name_path),
+        );
+        (self.name, self.short_name) = match &self.mod_name {
+            Some(mod_name) => (
+                mod_section_name(mod_name, &name),
+                mod_section_name(mod_name, &short_name),
+            ),
+            None => (name, short_name),
+        };
+    }
+
+    /// Whether `name` refers to this component under either [`NamingScheme`].
+    fn is_named(&self, name: &str) -> bool {
+        name == self.short_name || name == self.name_path
+    }
+
+    /// Takes this component's section out of a document written under either scheme.
+    fn take_section(&self, doc: &mut SaveDocument) -> Option<Value> {
+        doc.remove(&self.name)
+            .or_else(|| doc.remove(self.short_name.as_str()))
+            .or_else(|| doc.remove(self.name_path))
+    }
+
+    fn section<'a>(&self, doc: &'a SaveDocument) -> Option<&'a Value> {
+        doc.get(&self.name)
+            .or_else(|| doc.get(self.short_name.as_str()))
+            .or_else(|| doc.get(self.name_path))
+    }
+
+    pub fn type_path(&self) -> &'static str {
+        self.type_path
+    }
+
+    fn component_id(&self, world: &World) -> Option<ComponentId> {
+        world.components().get_id(self.type_id)
+    }
+
+    /// The data version saves of this component are written at.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// The serde structure of the component, as traced at registration time.
+    pub fn schema(&self) -> &Format {
+        &self.schema
+    }
+
+    pub fn schema_hash(&self) -> SchemaHash {
+        SchemaHash(self.schema.stable_hash())
+    }
+}
+
+/// `entities` followed by all of their descendants, leaving out [`SaveExempt`] entities
+/// along with theirs, so that no saved entity has an unsaved parent.
+pub(crate) fn with_descendants(world: &World, entities: &[Entity]) -> Vec<Entity> {
+    let is_saved = |entity: &Entity| world.get::<SaveExempt>(*entity).is_none();
+    let mut all: Vec<Entity> = entities.iter().copied().filter(is_saved).collect();
+    let mut next = 0;
+    while let Some(entity) = all.get(next).copied() {
+        if let Some(children) = world.get::<Children>(entity) {
+            all.extend(children.iter().copied().filter(is_saved));
+        }
+        next += 1;
+    }
+    all
+}
+
+/// Serializes the `C` components of `entities` in the same `[[entity, component], ...]`
+/// layout produced by [`SerializeComponents`](crate::SerializeComponents).
+pub(crate) fn extract_section<C: Component + Serialize>(
+    world: &World,
+    entities: &[Entity],
+) -> Result<Option<Value>, serde_json::Error> {
+    let comp_values = entities
+        .iter()
+        .filter_map(|entity| world.get::<C>(*entity).map(|comp| (*entity, comp)))
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<Value>, serde_json::Error>>()?;
+    if comp_values.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(Value::Array(comp_values)))
+    }
+}
+
+pub(crate) fn insert_section<C: Component + DeserializeOwned>(
+    world: &mut World,
+    entity_map: &mut EntityMap,
+    section: Value,
+    load_hooks: &[LoadHookFn],
+) -> Result<Vec<Entity>, serde_json::Error> {
+    #[cfg(feature = "arena")]
+    return crate::arena::insert_section::<C>(world, entity_map, section, load_hooks);
+    #[cfg(not(feature = "arena"))]
+    {
+        let entity_comps: Vec<(Entity, C)> = serde_json::from_value(section)?;
+        Ok(insert_components(
+            world,
+            entity_map,
+            entity_comps,
+            load_hooks,
+        ))
+    }
+}
+
+/// Inserts deserialized components, spawning their entities as needed and running the
+/// load hooks on each, and returns the entities they were inserted on.
+pub(crate) fn insert_components<C: Component>(
+    world: &mut World,
+    entity_map: &mut EntityMap,
+    entity_comps: impl IntoIterator<Item = (Entity, C)>,
+    load_hooks: &[LoadHookFn],
+) -> Vec<Entity> {
+    entity_comps
+        .into_iter()
+        .map(|(entity, mut comp)| {
+            let new_entity = get_or_insert(world, entity_map, entity);
+            for hook in load_hooks {
+                hook(&mut comp, entity_map);
+            }
+            world.entity_mut(new_entity).insert(comp);
+            new_entity
+        })
+        .collect()
+}
+
+/// The entries of a section that [`insert_entries`] didn't insert as saved.
+#[derive(Default)]
+struct FailedEntries {
+    skipped: Vec<SkippedEntry>,
+    unknown_variants: Vec<SkippedEntry>,
+    /// Saved entities to leave out of the load altogether.
+    skip_entities: Vec<Entity>,
+    /// Loaded entities that only received a stashed component.
+    stashed: Vec<Entity>,
+}
+
+/// Inserts `section` through `reg`, falling back to one entry at a time if it fails, so
+/// that only the entries that don't deserialize are skipped, if `skip_corrupt`, or
+/// handled by the registration's unknown variant fallback.
+fn insert_entries(
+    reg: &ComponentRegistration,
+    world: &mut World,
+    entity_map: &mut EntityMap,
+    section: Value,
+    skip_corrupt: bool,
+    failed: &mut FailedEntries,
+) -> Result<Vec<Entity>, serde_json::Error> {
+    let err = match (reg.insert)(world, entity_map, section.clone(), &reg.load_hooks) {
+        Ok(entities) => return Ok(entities),
+        Err(err) => err,
+    };
+    let Value::Array(entries) = section else {
+        return Err(err);
+    };
+    let mut entities = Vec::new();
+    for (index, entry) in entries.into_iter().enumerate() {
+        let saved = entry[0].as_u64().map(Entity::from_bits);
+        let err = match (reg.insert)(
+            world,
+            entity_map,
+            Value::Array(vec![entry.clone()]),
+            &reg.load_hooks,
+        ) {
+            Ok(inserted) => {
+                entities.extend(inserted);
+                continue;
+            }
+            Err(err) => err,
+        };
+        let failure = SkippedEntry {
+            component: reg.name.clone(),
+            index,
+            message: err.to_string(),
+        };
+        match (&reg.unknown_variant, saved) {
+            (Some(fallback), Some(saved)) if is_unknown_variant(&err) => {
+                match fallback {
+                    UnknownVariantFallback::SkipEntity => failed.skip_entities.push(saved),
+                    UnknownVariantFallback::Substitute(substitute) => {
+                        entities.extend(substitute(world, entity_map, saved, &reg.load_hooks));
+                    }
+                    UnknownVariantFallback::Stash => {
+                        let loaded = get_or_insert(world, entity_map, saved);
+                        let mut entity_mut = world.entity_mut(loaded);
+                        if !entity_mut.contains::<StashedComponents>() {
+                            entity_mut.insert(StashedComponents::default());
+                        }
+                        let mut stashed = entity_mut.get_mut::<StashedComponents>().unwrap();
+                        stashed.0.insert(reg.name.clone(), entry[1].clone());
+                        failed.stashed.push(loaded);
+                    }
+                }
+                failed.unknown_variants.push(failure);
+            }
+            _ if skip_corrupt => failed.skipped.push(failure),
+            _ => return Err(err),
+        }
+    }
+    Ok(entities)
+}
+
+fn remove_component<C: Component>(world: &mut World, entity: Entity) {
+    if let Some(mut entity_mut) = world.get_entity_mut(entity) {
+        entity_mut.remove::<C>();
+    }
+}
+
+/// A runtime list of the component types that make up a save, the dynamic counterpart
+/// to the type lists passed to `serialize_individually!` and `deserialize_individually!`.
+///
+/// By default section names follow the macros' convention (the type name without its
+/// module path), so documents written by either are interchangeable, except that the
+/// registry also writes a [`Manifest`] describing the components it saved. See
+/// [`set_naming`](Self::set_naming) for keying sections by full type path instead.
+#[derive(Default)]
+pub struct SaveRegistry {
+    registrations: Vec<ComponentRegistration>,
+    naming: NamingScheme,
+    change_detection: LoadChangeDetection,
+    skip_corrupt_entries: bool,
+    entity_generations: EntityGenerations,
+    pub(crate) dynamic_stores: Vec<Box<dyn DynamicComponentStore>>,
+    /// Mods assigned with [`set_mod`](Self::set_mod) to components not registered yet.
+    pending_mods: HashMap<TypeId, String>,
+}
+
+impl SaveRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Chooses how sections are keyed in the documents this registry writes. Loading
+    /// accepts sections keyed under either scheme, so existing saves stay loadable.
+    pub fn set_naming(&mut self, naming: NamingScheme) -> &mut Self {
+        self.naming = naming;
+        for reg in &mut self.registrations {
+            reg.rename(naming);
+        }
+        for reg in &self.registrations {
+            self.assert_section_free(reg);
+        }
+        self
+    }
+
+    pub fn naming(&self) -> NamingScheme {
+        self.naming
+    }
+
+    /// Chooses whether the components restored by [`deserialize`](Self::deserialize) and
+    /// the loads built on it show up as changed.
+    pub fn set_change_detection(&mut self, change_detection: LoadChangeDetection) -> &mut Self {
+        self.change_detection = change_detection;
+        self
+    }
+
+    /// With `skip` set, entries of a section that fail to deserialize are skipped and
+    /// listed in the [`LoadReport`] rather than failing the whole load.
+    pub fn set_skip_corrupt_entries(&mut self, skip: bool) -> &mut Self {
+        self.skip_corrupt_entries = skip;
+        self
+    }
+
+    /// Chooses whether loads restore entities under their saved ids, see
+    /// [`EntityGenerations`] for the trade-offs. Loads remap entities by default.
+    pub fn set_entity_generations(&mut self, generations: EntityGenerations) -> &mut Self {
+        self.entity_generations = generations;
+        self
+    }
+
+    pub fn entity_generations(&self) -> EntityGenerations {
+        self.entity_generations
+    }
+
+    /// Registers `C`, tracing its serde structure for the manifest. Registering the same
+    /// type twice has no effect.
+    ///
+    /// # Panics
+    /// If another registered type is saved in the section `C` would be, as happens with
+    /// types sharing a name in different modules.
+    pub fn register<C: Component + Serialize + DeserializeOwned>(&mut self) -> &mut Self {
+        self.register_versioned::<C>(0)
+    }
+
+    /// Registers `C` at a data version other than 0, i.e. after its on-disk structure has
+    /// changed and [`Migrations`](crate::migration::Migrations) were added to upgrade old saves.
+    pub fn register_versioned<C: Component + Serialize + DeserializeOwned>(
+        &mut self,
+        version: u32,
+    ) -> &mut Self {
+        if self.get_by_type::<C>().is_none() {
+            self.push_registration(ComponentRegistration::of::<C>(version, self.naming));
+        }
+        self
+    }
+
+    /// Registers `C` like [`register`](Self::register), and additionally allows it to be
+    /// captured in a [`WorldSnapshot`](crate::snapshot::WorldSnapshot).
+    pub fn register_cloneable<C: Component + Clone + Serialize + DeserializeOwned>(
+        &mut self,
+    ) -> &mut Self {
+        self.register::<C>();
+        if let Some(reg) = self.get_by_type_mut::<C>() {
+            reg.capture = Some(capture_column::<C>);
+        }
+        self
+    }
+
+    /// Registers `C` like [`register`](Self::register), and additionally remaps the entity
+    /// references it holds whenever it is loaded, so they point at the loaded entities
+    /// rather than at the ids of the world that was saved.
+    pub fn register_mapped<C: Component + MapEntities + Serialize + DeserializeOwned>(
+        &mut self,
+    ) -> &mut Self {
+        self.register::<C>();
+        if let Some(reg) = self.get_by_type_mut::<C>() {
+            reg.map_entities = Some(map_component_entities::<C>);
+        }
+        self
+    }
+
+    /// Moves `C` into the namespace of the mod `mod_name`: its section is keyed
+    /// `my_mod:Turret`, so it never collides with a base game component or another mod's
+    /// of the same name, and saved files keep it under their
+    /// [`MODS_KEY`](crate::mods::MODS_KEY) section with the rest of the mod's data. Call it
+    /// before registering `C` when the base game has a component of the same name.
+    pub fn set_mod<C: Component>(&mut self, mod_name: &str) -> &mut Self {
+        let naming = self.naming;
+        let Some(reg) = self.get_by_type_mut::<C>() else {
+            self.pending_mods
+                .insert(TypeId::of::<C>(), mod_name.to_string());
+            return self;
+        };
+        reg.mod_name = Some(mod_name.to_string());
+        reg.rename(naming);
+        if let Some(reg) = self.get_by_type::<C>() {
+            self.assert_section_free(reg);
+        }
+        self
+    }
+
+    /// Adds `reg` unless its component type is already registered.
+    ///
+    /// # Panics
+    /// If another type is already saved in `reg`'s section.
+    pub(crate) fn push_registration(&mut self, mut reg: ComponentRegistration) -> &mut Self {
+        if !self
+            .iter()
+            .any(|existing| existing.type_path == reg.type_path)
+        {
+            if let Some(mod_name) = self.pending_mods.remove(&reg.type_id) {
+                reg.mod_name = Some(mod_name);
+                reg.rename(self.naming);
+            }
+            self.assert_section_free(&reg);
+            self.registrations.push(reg);
+        }
+        self
+    }
+
+    /// Panics if a type other than `reg`'s is saved in `reg`'s section.
+    fn assert_section_free(&self, reg: &ComponentRegistration) {
+        if let Some(earlier) = self
+            .iter()
+            .find(|other| other.type_id != reg.type_id && other.name == reg.name)
+        {
+            panic!(
+                "`{}` and `{}` would both be saved in section {:?}; move one of them into a \
+                 mod with `set_mod` before registering it, or key sections by type path",
+                earlier.type_path, reg.type_path, reg.name
+            );
+        }
+    }
+
+    /// Adds a hook run on every deserialized `C` before it is inserted, e.g. to offset
+    /// positions when pasting a prefab or to clamp values read from untrusted saves. Hooks
+    /// run in the order they were added and receive the map from saved entities to loaded
+    /// ones; when a whole document is deserialized, every entity of it is already in the map.
+    ///
+    /// # Panics
+    /// If `C` hasn't been registered.
+    pub fn add_load_hook<C: Component>(
+        &mut self,
+        hook: impl Fn(&mut C, &EntityMap) + Send + Sync + 'static,
+    ) -> &mut Self {
+        let Some(reg) = self.get_by_type_mut::<C>() else {
+            panic!(
+                "{} must be registered before adding load hooks",
+                std::any::type_name::<C>()
+            );
+        };
+        reg.load_hooks.push(Box::new(move |comp, entity_map| {
+            if let Some(comp) = comp.downcast_mut::<C>() {
+                hook(comp, entity_map);
+            }
+        }));
+        self
+    }
+
+    /// Adds a hook run with full world access before `C` is serialized, e.g. to flush a
+    /// cache into `C`'s saved fields or to round positions. It receives the entities being
+    /// saved, in ascending order. Hooks run in the order they were added, as part of every
+    /// save of marked entities ([`serialize`](Self::serialize) and the functions built on
+    /// it); fragment exports such as [`serialize_entities`](Self::serialize_entities) only
+    /// borrow the world and don't run them.
+    ///
+    /// # Panics
+    /// If `C` hasn't been registered.
+    pub fn add_save_hook<C: Component>(
+        &mut self,
+        hook: impl Fn(&mut World, &[Entity]) + Send + Sync + 'static,
+    ) -> &mut Self {
+        let Some(reg) = self.get_by_type_mut::<C>() else {
+            panic!(
+                "{} must be registered before adding save hooks",
+                std::any::type_name::<C>()
+            );
+        };
+        reg.save_hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Looks up a registration by section name under either [`NamingScheme`].
+    pub fn get(&self, name: &str) -> Option<&ComponentRegistration> {
+        self.registrations
+            .iter()
+            .find(|reg| reg.name == name)
+            .or_else(|| self.registrations.iter().find(|reg| reg.is_named(name)))
+    }
+
+    pub fn get_by_type<C: Component>(&self) -> Option<&ComponentRegistration> {
+        let type_path = std::any::type_name::<C>();
+        self.registrations
+            .iter()
+            .find(|reg| reg.type_path == type_path)
+    }
+
+    pub(crate) fn get_by_type_mut<C: Component>(&mut self) -> Option<&mut ComponentRegistration> {
+        let type_path = std::any::type_name::<C>();
+        self.registrations
+            .iter_mut()
+            .find(|reg| reg.type_path == type_path)
+    }
+
+    /// Registrations in the order they were registered.
+    pub fn iter(&self) -> impl Iterator<Item = &ComponentRegistration> {
+        self.registrations.iter()
+    }
+
+    /// Describes the components of the running build.
+    pub fn manifest(&self) -> Manifest {
+        Manifest {
+            components: self
+                .registrations
+                .iter()
+                .map(|reg| {
+                    (
+                        reg.name.clone(),
+                        ComponentInfo {
+                            version: reg.version,
+                            schema_hash: Some(reg.schema_hash()),
+                        },
+                    )
+                })
+                .collect(),
+            ..Manifest::default()
+        }
+    }
+
+    /// Serializes every registered component of the entities marked with `M`, keyed by
+    /// component name, together with the registry's manifest.
+    pub fn serialize<M: Component>(
+        &self,
+        world: &mut World,
+    ) -> Result<SaveDocument, serde_json::Error> {
+        self.serialize_filtered::<M>(world, |_, _| true)
+    }
+
+    /// Like [`serialize`](Self::serialize), but only includes the marked entities for which
+    /// `filter` returns true, e.g. those near the player for streaming or interest management.
+    pub fn serialize_filtered<M: Component>(
+        &self,
+        world: &mut World,
+        filter: impl Fn(Entity, &World) -> bool,
+    ) -> Result<SaveDocument, serde_json::Error> {
+        self.serialize_marked::<M>(world, filter, None)
+    }
+
+    pub(crate) fn serialize_marked<M: Component>(
+        &self,
+        world: &mut World,
+        filter: impl Fn(Entity, &World) -> bool,
+        stats: Option<&mut SaveStats>,
+    ) -> Result<SaveDocument, serde_json::Error> {
+        let _save_span = info_span!("save").entered();
+        let entities = self.entities_to_save::<M>(world, filter);
+        let mut data_map = self.serialize_sections(world, &entities, stats)?;
+        data_map.insert(
+            MANIFEST_KEY.to_string(),
+            serde_json::to_value(self.manifest())?,
+        );
+        Ok(data_map)
+    }
+
+    /// The marked entities passing `filter`, in ascending order, once the save hooks have
+    /// run on them.
+    pub(crate) fn entities_to_save<M: Component>(
+        &self,
+        world: &mut World,
+        filter: impl Fn(Entity, &World) -> bool,
+    ) -> Vec<Entity> {
+        let mut entities: Vec<Entity> = {
+            let _span = info_span!("query").entered();
+            world
+                .query_filtered::<Entity, (With<M>, Without<SaveExempt>)>()
+                .iter(world)
+                .filter(|entity| filter(*entity, world))
+                .collect()
+        };
+        entities.sort();
+        {
+            let _span = info_span!("save_hooks").entered();
+            for hook in self.registrations.iter().flat_map(|reg| &reg.save_hooks) {
+                hook(world, &entities);
+            }
+        }
+        entities
+    }
+
+    /// Serializes every registered component of exactly `entities`, regardless of markers,
+    /// e.g. to export an editor selection. Entities that don't exist are skipped. No
+    /// manifest is included, as the result is a fragment rather than a full save.
+    pub fn serialize_entities(
+        &self,
+        world: &World,
+        entities: &[Entity],
+    ) -> Result<SaveDocument, serde_json::Error> {
+        let mut entities: Vec<Entity> = entities
+            .iter()
+            .copied()
+            .filter(|entity| world.get::<SaveExempt>(*entity).is_none())
+            .collect();
+        entities.sort();
+        entities.dedup();
+        self.serialize_sections(world, &entities, None)
+    }
+
+    fn serialize_sections(
+        &self,
+        world: &World,
+        entities: &[Entity],
+        mut stats: Option<&mut SaveStats>,
+    ) -> Result<SaveDocument, serde_json::Error> {
+        let _span = info_span!("serialize", entities = entities.len()).entered();
+        let mut data_map = SaveDocument::new();
+        for reg in &self.registrations {
+            let _span = info_span!("section", name = reg.name.as_str()).entered();
+            let start = Instant::now();
+            if let Some(comp_data) = reg.serialize_kept(world, entities)? {
+                if let Some(stats) = stats.as_deref_mut() {
+                    stats.record(&reg.name, &comp_data, start.elapsed())?;
+                }
+                data_map.insert(reg.name.clone(), comp_data);
+            }
+        }
+        self.write_dynamic_sections(world, entities, &mut data_map, stats)?;
+        write_stashed(world, entities, &mut data_map);
+        Ok(data_map)
+    }
+
+    /// The names of the registered sections, in registration order.
+    pub(crate) fn section_names(&self) -> impl Iterator<Item = &str> {
+        self.registrations.iter().map(|reg| reg.name.as_str())
+    }
+
+    /// Like [`serialize_entities`](Self::serialize_entities), but also includes every
+    /// descendant of `entities`, following their [`Children`]. The hierarchy is recorded
+    /// in a [`HIERARCHY_KEY`] section, so that [`spawn_from_json`](crate::spawn_from_json)
+    /// can rebuild it.
+    pub fn serialize_entities_with_descendants(
+        &self,
+        world: &World,
+        entities: &[Entity],
+    ) -> Result<SaveDocument, serde_json::Error> {
+        let all = with_descendants(world, entities);
+        let mut doc = self.serialize_entities(world, &all)?;
+        doc.insert(
+            HIERARCHY_KEY.to_string(),
+            hierarchy_section(world, entities, &all)?,
+        );
+        Ok(doc)
+    }
+
+    /// Restores every registered component found in `component_json_obj`, adding `marker`
+    /// to each restored entity. Sections are removed from the map as they are consumed;
+    /// sections of unregistered components are left in place. Once all sections are in,
+    /// entity references of components registered with
+    /// [`register_mapped`](Self::register_mapped) are remapped through `entity_map`.
+    pub fn deserialize<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        entity_map: &mut EntityMap,
+        component_json_obj: &mut SaveDocument,
+        marker: M,
+    ) -> Result<(), serde_json::Error> {
+        component_json_obj.remove(MANIFEST_KEY);
+        self.deserialize_filtered(world, entity_map, component_json_obj, |_| true, marker)
+    }
+
+    /// Like [`deserialize`](Self::deserialize), but only restores the components whose
+    /// section name passes `filter`, e.g. just `Position` and `Health` when respawning.
+    /// Every other section, including the manifest, is left in the map untouched.
+    pub fn deserialize_filtered<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        entity_map: &mut EntityMap,
+        component_json_obj: &mut SaveDocument,
+        filter: impl Fn(&str) -> bool,
+        marker: M,
+    ) -> Result<(), serde_json::Error> {
+        let reused = entity_map.keys().copied().collect();
+        self.deserialize_marked(
+            world,
+            entity_map,
+            component_json_obj,
+            filter,
+            marker,
+            &reused,
+            None,
+        )
+    }
+
+    /// [`deserialize_filtered`](Self::deserialize_filtered), recording what was restored,
+    /// skipped and left dangling in `report`, if given. `reused` holds the saved entities
+    /// restored into entities that were there before the load, which an entity skipped on
+    /// load is left alive for.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn deserialize_marked<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        entity_map: &mut EntityMap,
+        component_json_obj: &mut SaveDocument,
+        filter: impl Fn(&str) -> bool,
+        marker: M,
+        reused: &BTreeSet<Entity>,
+        mut report: Option<&mut LoadReport>,
+    ) -> Result<(), serde_json::Error> {
+        let _load_span = info_span!("load").entered();
+        let load_tick = world.change_tick();
+        // spawn every entity up front, so load hooks see the complete entity map
+        let mut spawned = Vec::new();
+        {
+            let _span = info_span!("spawn").entered();
+            for reg in self.registrations.iter().filter(|reg| filter(&reg.name)) {
+                if let Some(section) = reg.section(component_json_obj) {
+                    match section_entries(&reg.name, section) {
+                        Ok(entries) => {
+                            for (entity, _) in entries {
+                                spawned.push(get_or_insert(world, entity_map, entity));
+                            }
+                        }
+                        // the corrupt entries are skipped, and the rest spawned, on insert
+                        Err(_) if self.skip_corrupt_entries => {}
+                        Err(err) => return Err(err),
+                    }
+                }
+            }
+            spawned.extend(self.spawn_dynamic(world, entity_map, component_json_obj, &filter)?);
+        }
+        let mut inserted = Vec::new();
+        let mut failed = FailedEntries::default();
+        {
+            let _span = info_span!("insert").entered();
+            for reg in self
+                .load_order()
+                .into_iter()
+                .filter(|reg| filter(&reg.name))
+            {
+                let _span = info_span!("section", name = reg.name.as_str()).entered();
+                let comp_vec_value = reg
+                    .take_section(component_json_obj)
+                    .unwrap_or(EMPTY_JS_ARRAY);
+                let mut entities = if self.skip_corrupt_entries || reg.unknown_variant.is_some() {
+                    insert_entries(
+                        reg,
+                        world,
+                        entity_map,
+                        comp_vec_value,
+                        self.skip_corrupt_entries,
+                        &mut failed,
+                    )?
+                } else {
+                    (reg.insert)(world, entity_map, comp_vec_value, &reg.load_hooks)?
+                };
+                if let Some(fill_missing) = &reg.fill_missing {
+                    entities.extend(fill_missing(world, &spawned));
+                }
+                for entity in &entities {
+                    world.entity_mut(*entity).insert(marker.clone());
+                }
+                if let Some(report) = report.as_mut().filter(|_| !entities.is_empty()) {
+                    report.restored.insert(reg.name.clone(), entities.len());
+                }
+                inserted.push((reg, entities));
+            }
+        }
+        let dynamic = self.insert_dynamic(
+            world,
+            entity_map,
+            component_json_obj,
+            &filter,
+            &marker,
+            report.as_deref_mut(),
+        )?;
+        for entity in &failed.stashed {
+            world.entity_mut(*entity).insert(marker.clone());
+        }
+        if !failed.skip_entities.is_empty() {
+            let mut skipped = HashSet::new();
+            let mut kept = HashSet::new();
+            for saved in &failed.skip_entities {
+                if reused.contains(saved) {
+                    // the entity predates the load, so it stays, only without what was loaded
+                    kept.extend(entity_map.get(saved).copied());
+                } else if let Some(entity) = entity_map.remove(saved) {
+                    world.despawn(entity);
+                    skipped.insert(entity);
+                }
+            }
+            for (reg, entities) in &mut inserted {
+                entities.retain(|entity| {
+                    if kept.contains(entity) {
+                        (reg.remove)(world, *entity);
+                    }
+                    !skipped.contains(entity) && !kept.contains(entity)
+                });
+                if let Some(report) = report.as_mut() {
+                    match entities.len() {
+                        0 => report.restored.remove(&reg.name),
+                        restored => report.restored.insert(reg.name.clone(), restored),
+                    };
+                }
+            }
+        }
+        if let Some(report) = report.as_mut() {
+            report.skipped_entries.extend(failed.skipped);
+            report.unknown_variants.extend(failed.unknown_variants);
+        }
+        {
+            let _span = info_span!("map_entities").entered();
+            for (reg, entities) in &inserted {
+                if let Some(map_entities) = reg.map_entities {
+                    let dangling = map_entities(world, entities, entity_map);
+                    if let Some(report) = report.as_mut() {
+                        report
+                            .dangling_references
+                            .extend(dangling.into_iter().map(|target| DanglingReference {
+                                component: reg.name.clone(),
+                                target,
+                            }));
+                    }
+                }
+            }
+        }
+        if let Some(report) = report {
+            let mut unknown: Vec<String> = component_json_obj
+                .keys()
+                .filter(|name| !is_reserved(name) && self.get(name).is_none())
+                .cloned()
+                .collect();
+            unknown.sort();
+            report.unknown_sections = unknown;
+        }
+        if self.change_detection == LoadChangeDetection::Suppress {
+            let marker_id = world.component_id::<M>();
+            for (reg, entities) in &inserted {
+                suppress_changes(world, reg.component_id(world), entities);
+                suppress_changes(world, marker_id, entities);
+            }
+        }
+        world.insert_resource(LastLoad { tick: load_tick });
+        let loaded = inserted
+            .into_iter()
+            .flat_map(|(_, entities)| entities)
+            .chain(dynamic)
+            .collect();
+        send_load_completed(world, loaded);
+        Ok(())
+    }
+
+    /// Restores a single component section, for callers that receive sections one at a
+    /// time. Returns the entities the section's components were inserted on, or nothing
+    /// if `name` isn't registered.
+    ///
+    /// Entity references are not remapped, as the entities they point at may not have
+    /// arrived yet; pass the returned entities to
+    /// [`map_section_references`](Self::map_section_references) once all sections are in.
+    pub fn deserialize_section<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        entity_map: &mut EntityMap,
+        name: &str,
+        section: Value,
+        marker: M,
+    ) -> Result<Vec<Entity>, serde_json::Error> {
+        let Some(reg) = self.get(name) else {
+            return Ok(Vec::new());
+        };
+        let _span = info_span!("insert", name = reg.name.as_str()).entered();
+        let entities = (reg.insert)(world, entity_map, section, &reg.load_hooks)?;
+        for entity in &entities {
+            world.entity_mut(*entity).insert(marker.clone());
+        }
+        Ok(entities)
+    }
+
+    /// Remaps the entity references of the `name` components on `entities`, if `name` was
+    /// registered with [`register_mapped`](Self::register_mapped). Only pass entities whose
+    /// `name` component was just loaded: references that were already mapped would be
+    /// mapped a second time.
+    pub fn map_section_references(
+        &self,
+        world: &mut World,
+        name: &str,
+        entities: &[Entity],
+        entity_map: &EntityMap,
+    ) {
+        if let Some(map_entities) = self.get(name).and_then(|reg| reg.map_entities) {
+            map_entities(world, entities, entity_map);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{save_game, Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_short_type_name() {
+        assert_eq!(short_type_name("my_game::combat::Health"), "Health");
+        assert_eq!(short_type_name("tests :: Component1"), "Component1");
+        assert_eq!(
+            short_type_name("game::Stat<game::attr::Strength, u8>"),
+            "Stat<Strength, u8>"
+        );
+    }
+
+    #[test]
+    fn test_registry_matches_macro_output() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Component2>();
+
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        world.spawn((Component1, Component2 { target: entity1 }, SerializeMe));
+
+        let mut data_map = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        assert!(data_map.contains_key(MANIFEST_KEY));
+        let macro_map: SaveDocument = serde_json::from_slice(&save_game(&mut world)).unwrap();
+        assert_eq!(data_map["Component1"], macro_map["Component1"]);
+        assert_eq!(data_map["Component2"], macro_map["Component2"]);
+
+        let mut restored = World::default();
+        let mut entity_map = EntityMap::new();
+        registry
+            .deserialize(&mut restored, &mut entity_map, &mut data_map, SerializeMe)
+            .unwrap();
+        assert!(data_map.is_empty());
+        assert_eq!(
+            restored
+                .query_filtered::<Entity, (With<Component1>, With<SerializeMe>)>()
+                .iter(&restored)
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_serialize_entities() {
+        use bevy_hierarchy::BuildWorldChildren;
+
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Component2>();
+        let mut world = World::default();
+        let root = world.spawn(Component1).id();
+        let child = world.spawn(Component2 { target: root }).id();
+        let grandchild = world.spawn(Component1).id();
+        world.entity_mut(root).push_children(&[child]);
+        world.entity_mut(child).push_children(&[grandchild]);
+        let other = world.spawn((Component1, SerializeMe)).id();
+
+        let doc = registry.serialize_entities(&world, &[root, root]).unwrap();
+        assert_eq!(doc.len(), 1);
+        assert_eq!(doc["Component1"], serde_json::json!([[root, null]]));
+
+        let doc = registry
+            .serialize_entities_with_descendants(&world, &[root])
+            .unwrap();
+        let entities: Vec<Entity> = crate::delta::section_entries("Component1", &doc["Component1"])
+            .unwrap()
+            .into_iter()
+            .map(|(entity, _)| entity)
+            .collect();
+        assert_eq!(entities, vec![root, grandchild]);
+        assert!(!entities.contains(&other));
+        assert_eq!(doc["Component2"][0][0], serde_json::json!(child));
+    }
+
+    #[test]
+    fn test_load_hooks_see_entity_map() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component2>()
+            .register::<Component1>()
+            .add_load_hook::<Component2>(|comp, entity_map| {
+                comp.target = entity_map.get(&comp.target).copied().unwrap_or(comp.target);
+            });
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        let entity2 = world
+            .spawn((Component2 { target: entity1 }, SerializeMe))
+            .id();
+        let mut doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+
+        let mut entity_map = EntityMap::new();
+        registry
+            .deserialize(&mut world, &mut entity_map, &mut doc, SerializeMe)
+            .unwrap();
+        assert_eq!(
+            world
+                .get::<Component2>(entity_map[&entity2])
+                .unwrap()
+                .target,
+            entity_map[&entity1]
+        );
+    }
+
+    #[test]
+    fn test_type_path_naming_loads_either_way() {
+        let mut long = SaveRegistry::new();
+        long.set_naming(NamingScheme::TypePath)
+            .register::<Component1>()
+            .register::<Component2>();
+        let mut short = SaveRegistry::new();
+        short.register::<Component1>().register::<Component2>();
+
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        world.spawn((Component2 { target: entity1 }, SerializeMe));
+        let long_doc = long.serialize::<SerializeMe>(&mut world).unwrap();
+        let short_doc = short.serialize::<SerializeMe>(&mut world).unwrap();
+        let type_path = std::any::type_name::<Component1>();
+        assert!(long_doc.contains_key(type_path));
+        assert!(short_doc.contains_key("Component1"));
+        assert_eq!(long.get("Component1").unwrap().name(), type_path);
+
+        for (registry, doc) in [(&short, &long_doc), (&long, &short_doc)] {
+            let save_data = serde_json::to_vec(doc).unwrap();
+            assert!(registry.can_load(&save_data).unwrap().is_compatible());
+            let mut entity_map = EntityMap::new();
+            let mut new_world = World::default();
+            registry
+                .deserialize(
+                    &mut new_world,
+                    &mut entity_map,
+                    &mut doc.clone(),
+                    SerializeMe,
+                )
+                .unwrap();
+            assert_eq!(new_world.query::<&Component1>().iter(&new_world).count(), 1);
+            assert_eq!(new_world.query::<&Component2>().iter(&new_world).count(), 1);
+        }
+    }
+
+    #[test]
+    fn test_save_hooks_run_before_serializing() {
+        #[derive(Component, serde::Serialize, serde::Deserialize)]
+        struct Position(f32);
+
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Position>()
+            .add_save_hook::<Position>(|world, entities| {
+                for entity in entities {
+                    if let Some(mut position) = world.get_mut::<Position>(*entity) {
+                        position.0 = position.0.round();
+                    }
+                }
+            });
+        let mut world = World::default();
+        world.spawn((Position(1.4), SerializeMe));
+        let unsaved = world.spawn(Position(2.6)).id();
+
+        let doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        assert_eq!(doc["Position"][0][1], 1.0);
+        assert_eq!(world.get::<Position>(unsaved).unwrap().0, 2.6);
+    }
+
+    mod a {
+        #[derive(bevy_ecs::component::Component, serde::Serialize, serde::Deserialize)]
+        pub struct Health(pub u32);
+    }
+
+    mod b {
+        #[derive(bevy_ecs::component::Component, serde::Serialize, serde::Deserialize)]
+        pub struct Health(pub String);
+    }
+
+    #[test]
+    #[should_panic(expected = "would both be saved in section \"Health\"")]
+    fn test_same_named_types_cannot_share_a_section() {
+        let mut by_path = SaveRegistry::new();
+        by_path
+            .set_naming(NamingScheme::TypePath)
+            .register::<a::Health>()
+            .register::<b::Health>();
+        let mut modded = SaveRegistry::new();
+        modded
+            .register::<a::Health>()
+            .set_mod::<b::Health>("my_mod")
+            .register::<b::Health>();
+        assert!(modded.get("my_mod:Health").is_some());
+
+        SaveRegistry::new()
+            .register::<a::Health>()
+            .register::<b::Health>();
+    }
+}
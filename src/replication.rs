@@ -0,0 +1,256 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+
+use bevy_ecs::prelude::*;
+use bevy_utils::hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::delta::{section_entries, SaveDelta};
+use crate::entity_map::EntityMap;
+use crate::manifest::MANIFEST_KEY;
+use crate::registry::SaveRegistry;
+use crate::SaveDocument;
+
+pub type ClientId = u64;
+
+/// A replication message for one client: the delta taking the state the client last
+/// acknowledged (`baseline`, or nothing at all) to the state at `tick`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReplicationUpdate {
+    pub tick: u64,
+    pub baseline: Option<u64>,
+    pub delta: SaveDelta,
+}
+
+impl ReplicationUpdate {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, serde_json::Error> {
+        serde_json::to_vec(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, serde_json::Error> {
+        serde_json::from_slice(bytes)
+    }
+
+    /// Applies the update on the client. `entity_map` maps server entities to client
+    /// entities and must persist between updates.
+    pub fn apply<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        registry: &SaveRegistry,
+        entity_map: &mut EntityMap,
+        marker: M,
+    ) -> Result<(), serde_json::Error> {
+        self.delta
+            .apply_to_world(world, registry, entity_map, marker)
+    }
+}
+
+/// Server-side state for authoritative replication. Each tick the replicated state is
+/// recorded through the registry; per client, [`Replicator::update_for`] then produces a
+/// delta against the last tick that client acknowledged, restricted to the entities the
+/// client is interested in. Entities entering a client's interest set are sent in full,
+/// entities leaving it are sent as removals.
+#[derive(Resource)]
+pub struct Replicator {
+    history_len: usize,
+    history: VecDeque<(u64, SaveDocument)>,
+    clients: HashMap<ClientId, ClientState>,
+}
+
+#[derive(Default)]
+struct ClientState {
+    acked: Option<u64>,
+    /// The interest set each unacknowledged update was built for, as the baseline a client
+    /// holds only covers the entities it was sent.
+    sent: VecDeque<(u64, HashSet<Entity>)>,
+}
+
+fn filter_document(
+    doc: &SaveDocument,
+    interest: &HashSet<Entity>,
+) -> Result<SaveDocument, serde_json::Error> {
+    let mut filtered = SaveDocument::new();
+    for (name, section) in doc.iter().filter(|(name, _)| *name != MANIFEST_KEY) {
+        let entries: BTreeMap<Entity, &Value> = section_entries(name, section)?
+            .into_iter()
+            .filter(|(entity, _)| interest.contains(entity))
+            .collect();
+        if !entries.is_empty() {
+            filtered.insert(
+                name.clone(),
+                serde_json::to_value(entries.into_iter().collect::<Vec<_>>())?,
+            );
+        }
+    }
+    Ok(filtered)
+}
+
+impl Replicator {
+    /// `history_len` bounds how many ticks a client may lag behind before it is sent
+    /// the full state again.
+    pub fn new(history_len: usize) -> Self {
+        assert!(
+            history_len > 0,
+            "the replication history must hold at least one tick"
+        );
+        Replicator {
+            history_len,
+            history: VecDeque::with_capacity(history_len),
+            clients: HashMap::new(),
+        }
+    }
+
+    /// Records the replicated state of the entities marked with `M` at `tick`.
+    pub fn record<M: Component>(
+        &mut self,
+        tick: u64,
+        world: &mut World,
+        registry: &SaveRegistry,
+    ) -> Result<(), serde_json::Error> {
+        let mut doc = registry.serialize::<M>(world)?;
+        doc.remove(MANIFEST_KEY);
+        self.history.retain(|(recorded, _)| *recorded < tick);
+        if self.history.len() == self.history_len {
+            self.history.pop_front();
+        }
+        self.history.push_back((tick, doc));
+        Ok(())
+    }
+
+    pub fn latest_tick(&self) -> Option<u64> {
+        self.history.back().map(|(tick, _)| *tick)
+    }
+
+    /// Notes that `client` has applied the update for `tick`. Acknowledgements older than
+    /// the current one are ignored, as they may arrive out of order.
+    pub fn acknowledge(&mut self, client: ClientId, tick: u64) {
+        let state = self.clients.entry(client).or_default();
+        if state.acked.is_some_and(|acked| acked >= tick)
+            || !state.sent.iter().any(|(sent, _)| *sent == tick)
+        {
+            return;
+        }
+        state.acked = Some(tick);
+        // keep the acknowledged interest set, it describes the new baseline
+        state.sent.retain(|(sent, _)| *sent >= tick);
+    }
+
+    pub fn remove_client(&mut self, client: ClientId) {
+        self.clients.remove(&client);
+    }
+
+    /// Builds the update bringing `client` to the latest recorded tick, covering only the
+    /// entities in `interest`. Returns `None` if nothing has been recorded yet.
+    pub fn update_for(
+        &mut self,
+        client: ClientId,
+        interest: &[Entity],
+    ) -> Result<Option<ReplicationUpdate>, serde_json::Error> {
+        let Some((tick, current)) = self.history.back() else {
+            return Ok(None);
+        };
+        let interest: HashSet<Entity> = interest.iter().copied().collect();
+        let state = self.clients.entry(client).or_default();
+        let baseline = state.acked.and_then(|acked| {
+            let doc = self
+                .history
+                .iter()
+                .find(|(recorded, _)| *recorded == acked)?;
+            let sent = state.sent.iter().find(|(sent, _)| *sent == acked)?;
+            Some((acked, &doc.1, &sent.1))
+        });
+        let baseline_doc = match baseline {
+            Some((_, doc, sent_interest)) => filter_document(doc, sent_interest)?,
+            None => SaveDocument::new(),
+        };
+        let delta =
+            SaveDelta::between_documents(&baseline_doc, &filter_document(current, &interest)?)?;
+        let update = ReplicationUpdate {
+            tick: *tick,
+            baseline: baseline.map(|(tick, _, _)| tick),
+            delta,
+        };
+        state.sent.retain(|(sent, _)| *sent != *tick);
+        state.sent.push_back((*tick, interest));
+        if state.sent.len() > self.history_len {
+            state.sent.pop_front();
+        }
+        Ok(Some(update))
+    }
+
+    /// Builds serialized updates for every client in `interests`.
+    pub fn updates(
+        &mut self,
+        interests: &HashMap<ClientId, Vec<Entity>>,
+    ) -> Result<HashMap<ClientId, Vec<u8>>, serde_json::Error> {
+        let mut updates = HashMap::new();
+        for (client, interest) in interests {
+            if let Some(update) = self.update_for(*client, interest)? {
+                updates.insert(*client, update.to_bytes()?);
+            }
+        }
+        Ok(updates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_replication_updates() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Component2>();
+        let mut replicator = Replicator::new(8);
+
+        let mut server = World::default();
+        let near = server.spawn((Component1, SerializeMe)).id();
+        let far = server.spawn((Component1, SerializeMe)).id();
+        replicator
+            .record::<SerializeMe>(1, &mut server, &registry)
+            .unwrap();
+
+        let update = replicator.update_for(7, &[near]).unwrap().unwrap();
+        assert_eq!(update.baseline, None);
+        assert_eq!(update.delta.added_entities, vec![near]);
+
+        let mut client = World::default();
+        let mut entity_map = EntityMap::new();
+        let bytes = replicator
+            .updates(&[(7, vec![near])].into_iter().collect())
+            .unwrap();
+        ReplicationUpdate::from_bytes(&bytes[&7])
+            .unwrap()
+            .apply(&mut client, &registry, &mut entity_map, SerializeMe)
+            .unwrap();
+        replicator.acknowledge(7, 1);
+
+        server.entity_mut(far).insert(Component2 { target: near });
+        replicator
+            .record::<SerializeMe>(2, &mut server, &registry)
+            .unwrap();
+        let update = replicator.update_for(7, &[near]).unwrap().unwrap();
+        assert_eq!(update.baseline, Some(1));
+        assert!(update.delta.is_empty());
+
+        server.entity_mut(near).insert(Component2 { target: far });
+        replicator
+            .record::<SerializeMe>(3, &mut server, &registry)
+            .unwrap();
+        let update = replicator.update_for(7, &[near, far]).unwrap().unwrap();
+        assert_eq!(update.baseline, Some(1));
+        assert_eq!(update.delta.added_entities, vec![far]);
+        assert_eq!(update.delta.changed["Component2"].len(), 2);
+        update
+            .apply(&mut client, &registry, &mut entity_map, SerializeMe)
+            .unwrap();
+        assert_eq!(
+            client
+                .query_filtered::<Entity, With<SerializeMe>>()
+                .iter(&client)
+                .count(),
+            2
+        );
+    }
+}
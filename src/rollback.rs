@@ -0,0 +1,175 @@
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+
+use bevy_ecs::prelude::*;
+
+use crate::entity_map::EntityMap;
+use crate::registry::SaveRegistry;
+use crate::snapshot::{restore_in_place, take_snapshot_filtered, WorldSnapshot};
+
+/// A fixed-capacity history of [`WorldSnapshot`]s of the entities marked with `M`, keyed by
+/// simulation tick, for rollback networking and client-side prediction.
+///
+/// Rolling back restores a snapshot into the world it was taken from: entities that still
+/// exist keep their ids, marked entities spawned after the snapshot are despawned, and
+/// entities despawned since are respawned under new ids. The buffer remembers those new ids,
+/// so later rollbacks to older ticks resolve to the same entities.
+#[derive(Resource)]
+pub struct RollbackBuffer<M> {
+    capacity: usize,
+    components: Option<Vec<String>>,
+    frames: VecDeque<(u64, WorldSnapshot)>,
+    entity_map: EntityMap,
+    _marker: PhantomData<fn(M)>,
+}
+
+fn tracks(components: &Option<Vec<String>>, name: &str) -> bool {
+    components
+        .as_ref()
+        .is_none_or(|names| names.iter().any(|tracked| tracked == name))
+}
+
+impl<M: Component + Clone> RollbackBuffer<M> {
+    pub fn new(capacity: usize) -> Self {
+        assert!(
+            capacity > 0,
+            "a rollback buffer needs room for at least one frame"
+        );
+        RollbackBuffer {
+            capacity,
+            components: None,
+            frames: VecDeque::with_capacity(capacity),
+            entity_map: EntityMap::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Restricts the snapshots to the named component sections, e.g. only the components
+    /// that are actually predicted. By default every cloneable registered component is kept.
+    pub fn with_components(mut self, names: &[&str]) -> Self {
+        self.components = Some(names.iter().map(|name| name.to_string()).collect());
+        self
+    }
+
+    /// Records the state of the world at `tick`. Frames at or after `tick` are discarded
+    /// first, as they belong to a timeline that is being re-simulated; the oldest frame is
+    /// evicted once the buffer is full.
+    pub fn push(&mut self, tick: u64, world: &mut World, registry: &SaveRegistry) {
+        self.frames.retain(|(frame_tick, _)| *frame_tick < tick);
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        let snapshot =
+            take_snapshot_filtered::<M>(world, registry, |name| tracks(&self.components, name));
+        self.frames.push_back((tick, snapshot));
+    }
+
+    pub fn get(&self, tick: u64) -> Option<&WorldSnapshot> {
+        self.frames
+            .iter()
+            .find(|(frame_tick, _)| *frame_tick == tick)
+            .map(|(_, snapshot)| snapshot)
+    }
+
+    pub fn oldest_tick(&self) -> Option<u64> {
+        self.frames.front().map(|(tick, _)| *tick)
+    }
+
+    pub fn latest_tick(&self) -> Option<u64> {
+        self.frames.back().map(|(tick, _)| *tick)
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Restores the world to the state recorded at `tick` and discards all later frames.
+    /// Returns `false`, leaving the world untouched, if `tick` is not in the buffer.
+    pub fn rollback_to(
+        &mut self,
+        tick: u64,
+        world: &mut World,
+        registry: &SaveRegistry,
+        marker: M,
+    ) -> bool {
+        let Some(index) = self.frames.iter().position(|(t, _)| *t == tick) else {
+            return false;
+        };
+        self.frames.truncate(index + 1);
+        let snapshot = &self.frames[index].1;
+        let components = &self.components;
+        restore_in_place(
+            world,
+            registry,
+            snapshot,
+            &mut self.entity_map,
+            marker,
+            |name| tracks(components, name),
+        );
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_rollback() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register_cloneable::<Component1>()
+            .register_cloneable::<Component2>();
+        let mut buffer = RollbackBuffer::<SerializeMe>::new(2);
+
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        let entity2 = world
+            .spawn((Component2 { target: entity1 }, SerializeMe))
+            .id();
+        buffer.push(1, &mut world, &registry);
+
+        world.despawn(entity2);
+        world
+            .entity_mut(entity1)
+            .insert(Component2 { target: entity1 });
+        let entity3 = world.spawn((Component1, SerializeMe)).id();
+        buffer.push(2, &mut world, &registry);
+        buffer.push(3, &mut world, &registry);
+        assert_eq!(buffer.oldest_tick(), Some(2));
+        assert!(!buffer.rollback_to(1, &mut world, &registry, SerializeMe));
+
+        // rolling back to tick 2 then re-simulating overwrites tick 3
+        assert!(buffer.rollback_to(2, &mut world, &registry, SerializeMe));
+        assert_eq!(buffer.latest_tick(), Some(2));
+        assert!(world.get_entity(entity3).is_some());
+        buffer.push(3, &mut world, &registry);
+
+        let mut buffer = RollbackBuffer::<SerializeMe>::new(4).with_components(&["Component2"]);
+        buffer.push(10, &mut world, &registry);
+        world.despawn(entity1);
+        world
+            .entity_mut(entity3)
+            .insert(Component2 { target: entity3 });
+        world.spawn((Component1, SerializeMe));
+        assert!(buffer.rollback_to(10, &mut world, &registry, SerializeMe));
+
+        assert_eq!(
+            world
+                .query_filtered::<Entity, With<SerializeMe>>()
+                .iter(&world)
+                .count(),
+            2
+        );
+        assert!(world.get::<Component2>(entity3).is_none());
+        assert!(world.get::<Component1>(entity3).is_some());
+        let respawned = buffer.entity_map[&entity1];
+        assert_ne!(respawned, entity1);
+        assert!(world.get::<Component2>(respawned).is_some());
+    }
+}
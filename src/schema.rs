@@ -0,0 +1,703 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, Visitor};
+use serde::{Deserialize, Serialize};
+
+use crate::hash::StableHasher;
+
+/// Upper bound on nesting while tracing, guarding against recursive types whose
+/// recursion is not broken by an `Option`, sequence or map.
+const MAX_TRACE_DEPTH: usize = 64;
+
+/// Upper bound on the number of tracing passes used to visit every enum variant.
+const MAX_TRACE_PASSES: usize = 256;
+
+/// The serde data-model shape of a type, as discovered by [`trace`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Format {
+    Unit,
+    Bool,
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    F32,
+    F64,
+    Char,
+    Str,
+    Bytes,
+    Option(Box<Format>),
+    Seq(Box<Format>),
+    Map {
+        key: Box<Format>,
+        value: Box<Format>,
+    },
+    Tuple(Vec<Format>),
+    UnitStruct(String),
+    NewtypeStruct(String, Box<Format>),
+    TupleStruct(String, Vec<Format>),
+    Struct(String, Vec<(String, Format)>),
+    Enum(String, Vec<(String, VariantFormat)>),
+    /// A reference back to an enclosing struct or enum of the given name.
+    Recursive(String),
+    /// A self-describing value (e.g. `serde_json::Value`) whose shape is only known at runtime.
+    Any,
+    /// A type that could not be traced; identified only by its Rust type path.
+    Opaque(String),
+}
+
+/// The shape of a single enum variant.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VariantFormat {
+    Unit,
+    Newtype(Box<Format>),
+    Tuple(Vec<Format>),
+    Struct(Vec<(String, Format)>),
+    /// The variant was never reached while tracing.
+    Untraced,
+}
+
+impl Format {
+    /// A hash of the format which is stable across builds and platforms, suitable for
+    /// storing in save files and comparing against the running build.
+    pub fn stable_hash(&self) -> u64 {
+        let canonical = serde_json::to_string(self).expect("formats always serialize");
+        let mut hasher = StableHasher::new();
+        hasher.write_bytes(canonical.as_bytes());
+        hasher.finish()
+    }
+}
+
+/// Discovers the serde structure of `T` by driving its `Deserialize` impl with a
+/// recording deserializer. Every enum variant is visited by re-running the trace
+/// once per untraced variant.
+///
+/// Tracing fails for types whose `Deserialize` impl rejects the placeholder values
+/// the tracer supplies (zeroes, empty strings, ...), and for types that recurse
+/// without an `Option`, sequence or map in between.
+pub fn trace<T: DeserializeOwned>() -> Result<Format, TraceError> {
+    let mut state = TraceState::default();
+    let mut root = Format::Unit;
+    for _ in 0..MAX_TRACE_PASSES {
+        let mut format = Format::Unit;
+        T::deserialize(Tracer {
+            state: &mut state,
+            out: &mut format,
+        })?;
+        if state.passes == 0 {
+            root = format;
+        }
+        state.passes += 1;
+        match state.next_untraced() {
+            Some((name, index)) => {
+                state.choices.insert(name, index);
+            }
+            None => {
+                state.fill_variants(&mut root, &mut Vec::new());
+                return Ok(root);
+            }
+        }
+    }
+    Err(TraceError("too many enum variants to trace".to_string()))
+}
+
+/// Like [`trace`], but falls back to [`Format::Opaque`] when tracing fails, so that a
+/// format (and hence a hash) is always available.
+pub fn trace_or_opaque<T: DeserializeOwned>() -> Format {
+    trace::<T>().unwrap_or_else(|_| Format::Opaque(std::any::type_name::<T>().to_string()))
+}
+
+#[derive(Debug)]
+pub struct TraceError(String);
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "schema tracing failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+impl de::Error for TraceError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        TraceError(msg.to_string())
+    }
+}
+
+#[derive(Default)]
+struct TraceState {
+    passes: usize,
+    /// Variant formats per enum name, accumulated over all passes.
+    enums: BTreeMap<&'static str, Vec<(&'static str, VariantFormat)>>,
+    /// Which variant to pick for each enum in the current pass.
+    choices: BTreeMap<&'static str, u32>,
+    /// Names of the structs and enums currently being traced.
+    stack: Vec<&'static str>,
+    /// Non-zero while producing placeholder values for a recursive reference.
+    quiet: usize,
+}
+
+impl TraceState {
+    fn next_untraced(&self) -> Option<(&'static str, u32)> {
+        self.enums.iter().find_map(|(name, variants)| {
+            variants
+                .iter()
+                .position(|(_, format)| *format == VariantFormat::Untraced)
+                .map(|index| (*name, index as u32))
+        })
+    }
+
+    /// Replaces the single variant recorded in each pass with everything learned
+    /// about the enum over all passes.
+    fn fill_variants(&self, format: &mut Format, seen: &mut Vec<String>) {
+        match format {
+            Format::Enum(name, variants) => {
+                if seen.contains(name) {
+                    return;
+                }
+                if let Some(all) = self.enums.get(name.as_str()) {
+                    *variants = all
+                        .iter()
+                        .map(|(name, format)| (name.to_string(), format.clone()))
+                        .collect();
+                }
+                seen.push(name.clone());
+                for (_, variant) in variants.iter_mut() {
+                    match variant {
+                        VariantFormat::Newtype(inner) => self.fill_variants(inner, seen),
+                        VariantFormat::Tuple(formats) => {
+                            formats.iter_mut().for_each(|f| self.fill_variants(f, seen))
+                        }
+                        VariantFormat::Struct(fields) => fields
+                            .iter_mut()
+                            .for_each(|(_, f)| self.fill_variants(f, seen)),
+                        VariantFormat::Unit | VariantFormat::Untraced => {}
+                    }
+                }
+                seen.pop();
+            }
+            Format::Option(inner) | Format::Seq(inner) | Format::NewtypeStruct(_, inner) => {
+                self.fill_variants(inner, seen)
+            }
+            Format::Map { key, value } => {
+                self.fill_variants(key, seen);
+                self.fill_variants(value, seen);
+            }
+            Format::Tuple(formats) | Format::TupleStruct(_, formats) => {
+                formats.iter_mut().for_each(|f| self.fill_variants(f, seen))
+            }
+            Format::Struct(_, fields) => fields
+                .iter_mut()
+                .for_each(|(_, f)| self.fill_variants(f, seen)),
+            _ => {}
+        }
+    }
+
+    fn depth(&self) -> usize {
+        self.stack.len() + self.quiet
+    }
+}
+
+struct Tracer<'a> {
+    state: &'a mut TraceState,
+    out: &'a mut Format,
+}
+
+impl<'a> Tracer<'a> {
+    fn check_depth(&self) -> Result<(), TraceError> {
+        if self.state.depth() > MAX_TRACE_DEPTH {
+            Err(TraceError("type nesting is too deep".to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Runs `f` with the container `name` pushed on the stack, or in quiet mode with a
+    /// [`Format::Recursive`] result if the container is already being traced.
+    fn container<R>(
+        self,
+        name: &'static str,
+        f: impl FnOnce(&mut TraceState, &mut Format) -> Result<R, TraceError>,
+    ) -> Result<R, TraceError> {
+        self.check_depth()?;
+        if self.state.quiet > 0 || self.state.stack.contains(&name) {
+            *self.out = Format::Recursive(name.to_string());
+            self.state.quiet += 1;
+            let mut ignored = Format::Unit;
+            let result = f(self.state, &mut ignored);
+            self.state.quiet -= 1;
+            result
+        } else {
+            self.state.stack.push(name);
+            let result = f(self.state, self.out);
+            self.state.stack.pop();
+            result
+        }
+    }
+}
+
+macro_rules! trace_primitive {
+    ($method:ident, $format:ident, $visit:ident, $value:expr) => {
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+            *self.out = Format::$format;
+            visitor.$visit($value)
+        }
+    };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for Tracer<'a> {
+    type Error = TraceError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        *self.out = Format::Any;
+        visitor.visit_unit()
+    }
+
+    trace_primitive!(deserialize_bool, Bool, visit_bool, false);
+    trace_primitive!(deserialize_i8, I8, visit_i8, 0);
+    trace_primitive!(deserialize_i16, I16, visit_i16, 0);
+    trace_primitive!(deserialize_i32, I32, visit_i32, 0);
+    trace_primitive!(deserialize_i64, I64, visit_i64, 0);
+    trace_primitive!(deserialize_i128, I128, visit_i128, 0);
+    trace_primitive!(deserialize_u8, U8, visit_u8, 0);
+    trace_primitive!(deserialize_u16, U16, visit_u16, 0);
+    trace_primitive!(deserialize_u32, U32, visit_u32, 0);
+    trace_primitive!(deserialize_u64, U64, visit_u64, 0);
+    trace_primitive!(deserialize_u128, U128, visit_u128, 0);
+    trace_primitive!(deserialize_f32, F32, visit_f32, 0.0);
+    trace_primitive!(deserialize_f64, F64, visit_f64, 0.0);
+    trace_primitive!(deserialize_char, Char, visit_char, 'a');
+    trace_primitive!(deserialize_str, Str, visit_borrowed_str, "");
+    trace_primitive!(deserialize_string, Str, visit_string, String::new());
+    trace_primitive!(deserialize_bytes, Bytes, visit_borrowed_bytes, &[]);
+    trace_primitive!(deserialize_byte_buf, Bytes, visit_byte_buf, Vec::new());
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        *self.out = Format::Unit;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        self.check_depth()?;
+        if self.state.quiet > 0 {
+            return visitor.visit_none();
+        }
+        let mut inner = Format::Unit;
+        let value = visitor.visit_some(Tracer {
+            state: self.state,
+            out: &mut inner,
+        })?;
+        *self.out = Format::Option(Box::new(inner));
+        Ok(value)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        *self.out = Format::UnitStruct(name.to_string());
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        self.container(name, |state, out| {
+            let mut inner = Format::Unit;
+            let value = visitor.visit_newtype_struct(Tracer {
+                state,
+                out: &mut inner,
+            })?;
+            *out = Format::NewtypeStruct(name.to_string(), Box::new(inner));
+            Ok(value)
+        })
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        self.check_depth()?;
+        let len = if self.state.quiet > 0 { 0 } else { 1 };
+        let mut formats = Vec::new();
+        let value = visitor.visit_seq(TraceSeq {
+            state: self.state,
+            formats: &mut formats,
+            remaining: len,
+        })?;
+        *self.out = Format::Seq(Box::new(formats.pop().unwrap_or(Format::Any)));
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        self.check_depth()?;
+        let mut formats = Vec::new();
+        let value = visitor.visit_seq(TraceSeq {
+            state: self.state,
+            formats: &mut formats,
+            remaining: len,
+        })?;
+        *self.out = Format::Tuple(formats);
+        Ok(value)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        self.container(name, |state, out| {
+            let mut formats = Vec::new();
+            let value = visitor.visit_seq(TraceSeq {
+                state,
+                formats: &mut formats,
+                remaining: len,
+            })?;
+            *out = Format::TupleStruct(name.to_string(), formats);
+            Ok(value)
+        })
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        self.check_depth()?;
+        let len = if self.state.quiet > 0 { 0 } else { 1 };
+        let mut formats = Vec::new();
+        let value = visitor.visit_map(TraceMap {
+            state: self.state,
+            formats: &mut formats,
+            remaining: len,
+        })?;
+        let value_format = formats.pop().unwrap_or(Format::Any);
+        let key_format = formats.pop().unwrap_or(Format::Any);
+        *self.out = Format::Map {
+            key: Box::new(key_format),
+            value: Box::new(value_format),
+        };
+        Ok(value)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        self.container(name, |state, out| {
+            let mut formats = Vec::new();
+            let value = visitor.visit_seq(TraceSeq {
+                state,
+                formats: &mut formats,
+                remaining: fields.len(),
+            })?;
+            *out = Format::Struct(
+                name.to_string(),
+                fields
+                    .iter()
+                    .map(|field| field.to_string())
+                    .zip(formats)
+                    .collect(),
+            );
+            Ok(value)
+        })
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        if variants.is_empty() {
+            return Err(TraceError(format!("enum {name} has no variants")));
+        }
+        self.container(name, |state, out| {
+            let quiet = state.quiet > 0;
+            let index = if quiet {
+                // Prefer a variant known not to recurse any further.
+                state
+                    .enums
+                    .get(name)
+                    .and_then(|known| {
+                        known
+                            .iter()
+                            .position(|(_, format)| *format == VariantFormat::Unit)
+                    })
+                    .unwrap_or(0) as u32
+            } else {
+                state.enums.entry(name).or_insert_with(|| {
+                    variants
+                        .iter()
+                        .map(|variant| (*variant, VariantFormat::Untraced))
+                        .collect()
+                });
+                state.choices.get(name).copied().unwrap_or(0)
+            };
+            let mut variant_format = VariantFormat::Untraced;
+            let value = visitor.visit_enum(TraceEnum {
+                state: &mut *state,
+                index,
+                out: &mut variant_format,
+            })?;
+            if !quiet {
+                let known = state.enums.get_mut(name).expect("inserted above");
+                known[index as usize].1 = variant_format.clone();
+                *out = Format::Enum(
+                    name.to_string(),
+                    vec![(variants[index as usize].to_string(), variant_format)],
+                );
+            }
+            Ok(value)
+        })
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        *self.out = Format::Str;
+        visitor.visit_borrowed_str("")
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        self.deserialize_any(visitor)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        true
+    }
+}
+
+struct TraceSeq<'a> {
+    state: &'a mut TraceState,
+    formats: &'a mut Vec<Format>,
+    remaining: usize,
+}
+
+impl<'de, 'a> de::SeqAccess<'de> for TraceSeq<'a> {
+    type Error = TraceError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, TraceError> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        self.formats.push(Format::Unit);
+        let out = self.formats.last_mut().expect("just pushed");
+        seed.deserialize(Tracer {
+            state: &mut *self.state,
+            out,
+        })
+        .map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+struct TraceMap<'a> {
+    state: &'a mut TraceState,
+    formats: &'a mut Vec<Format>,
+    remaining: usize,
+}
+
+impl<'de, 'a> de::MapAccess<'de> for TraceMap<'a> {
+    type Error = TraceError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, TraceError> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        self.formats.push(Format::Unit);
+        let out = self.formats.last_mut().expect("just pushed");
+        seed.deserialize(Tracer {
+            state: &mut *self.state,
+            out,
+        })
+        .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, TraceError> {
+        self.formats.push(Format::Unit);
+        let out = self.formats.last_mut().expect("just pushed");
+        seed.deserialize(Tracer {
+            state: &mut *self.state,
+            out,
+        })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+struct TraceEnum<'a> {
+    state: &'a mut TraceState,
+    index: u32,
+    out: &'a mut VariantFormat,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for TraceEnum<'a> {
+    type Error = TraceError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self), TraceError> {
+        let variant =
+            seed.deserialize(de::value::U32Deserializer::<TraceError>::new(self.index))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 'a> de::VariantAccess<'de> for TraceEnum<'a> {
+    type Error = TraceError;
+
+    fn unit_variant(self) -> Result<(), TraceError> {
+        *self.out = VariantFormat::Unit;
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, TraceError> {
+        let mut inner = Format::Unit;
+        let value = seed.deserialize(Tracer {
+            state: self.state,
+            out: &mut inner,
+        })?;
+        *self.out = VariantFormat::Newtype(Box::new(inner));
+        Ok(value)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        let mut formats = Vec::new();
+        let value = visitor.visit_seq(TraceSeq {
+            state: self.state,
+            formats: &mut formats,
+            remaining: len,
+        })?;
+        *self.out = VariantFormat::Tuple(formats);
+        Ok(value)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        let mut formats = Vec::new();
+        let value = visitor.visit_seq(TraceSeq {
+            state: self.state,
+            formats: &mut formats,
+            remaining: fields.len(),
+        })?;
+        *self.out = VariantFormat::Struct(
+            fields
+                .iter()
+                .map(|field| field.to_string())
+                .zip(formats)
+                .collect(),
+        );
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ecs::entity::Entity;
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    enum Shape {
+        Empty,
+        Circle(f32),
+        Rect { w: f32, h: f32 },
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    struct Node {
+        target: Entity,
+        shape: Shape,
+        children: Vec<Node>,
+        label: Option<String>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    #[serde(rename = "Node")]
+    struct NodeRenamedField {
+        target: Entity,
+        shape: Shape,
+        children: Vec<Node>,
+        name: Option<String>,
+    }
+
+    #[test]
+    fn test_trace_struct_with_enum_and_recursion() {
+        let format = trace::<Node>().unwrap();
+        let Format::Struct(name, fields) = &format else {
+            panic!("expected a struct, got {format:?}");
+        };
+        assert_eq!(name, "Node");
+        assert_eq!(fields[0], ("target".to_string(), Format::U64));
+        assert_eq!(
+            fields[1].1,
+            Format::Enum(
+                "Shape".to_string(),
+                vec![
+                    ("Empty".to_string(), VariantFormat::Unit),
+                    (
+                        "Circle".to_string(),
+                        VariantFormat::Newtype(Box::new(Format::F32))
+                    ),
+                    (
+                        "Rect".to_string(),
+                        VariantFormat::Struct(vec![
+                            ("w".to_string(), Format::F32),
+                            ("h".to_string(), Format::F32)
+                        ])
+                    ),
+                ]
+            )
+        );
+        assert_eq!(
+            fields[2].1,
+            Format::Seq(Box::new(Format::Recursive("Node".to_string())))
+        );
+        assert_eq!(fields[3].1, Format::Option(Box::new(Format::Str)));
+
+        assert_eq!(format.stable_hash(), trace::<Node>().unwrap().stable_hash());
+        assert_ne!(
+            format.stable_hash(),
+            trace::<NodeRenamedField>().unwrap().stable_hash()
+        );
+    }
+}
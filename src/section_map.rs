@@ -0,0 +1,110 @@
+//! The map types `deserialize_individually!` reads component sections from, so callers can
+//! keep the ordered `BTreeMap` that `serialize_individually!` returns, or their own map
+//! type, instead of converting to hashbrown's `HashMap`.
+
+use std::collections::BTreeMap;
+use std::hash::BuildHasher;
+
+use serde_json::{Map, Value};
+
+/// A map from section name to component section.
+pub trait SectionMap {
+    /// Removes the section `name`, handing its value to the caller.
+    fn take_section(&mut self, name: &str) -> Option<Value>;
+}
+
+impl<S: BuildHasher> SectionMap for bevy_utils::hashbrown::HashMap<String, Value, S> {
+    fn take_section(&mut self, name: &str) -> Option<Value> {
+        let section = self.remove(name);
+        self.shrink_to_fit();
+        section
+    }
+}
+
+impl<S: BuildHasher> SectionMap for std::collections::HashMap<String, Value, S> {
+    fn take_section(&mut self, name: &str) -> Option<Value> {
+        let section = self.remove(name);
+        self.shrink_to_fit();
+        section
+    }
+}
+
+impl SectionMap for BTreeMap<String, Value> {
+    fn take_section(&mut self, name: &str) -> Option<Value> {
+        self.remove(name)
+    }
+}
+
+impl SectionMap for Map<String, Value> {
+    fn take_section(&mut self, name: &str) -> Option<Value> {
+        self.remove(name)
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<S: BuildHasher> SectionMap for indexmap::IndexMap<String, Value, S> {
+    fn take_section(&mut self, name: &str) -> Option<Value> {
+        // shift rather than swap, so the remaining sections keep their order
+        self.shift_remove(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ecs::prelude::*;
+
+    use crate::entity_map::EntityMap;
+    use crate::registry::SaveRegistry;
+    use crate::tests::{Component1, Component2, SerializeMe};
+    use crate::SaveDocument;
+
+    #[test]
+    fn test_load_from_ordered_map() {
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        world.spawn((Component2 { target: entity1 }, SerializeMe));
+        let ecs = &mut world;
+        let mut saved: BTreeMap<String, Value> =
+            crate::serialize_individually!(ecs, SerializeMe, Component1, Component2,);
+
+        let mut loaded = World::default();
+        let ecs = &mut loaded;
+        let mut entity_map = EntityMap::new();
+        crate::deserialize_individually!(
+            ecs,
+            &mut entity_map,
+            &mut saved,
+            SerializeMe,
+            Component1,
+            Component2,
+        );
+        assert!(saved.is_empty());
+        assert_eq!(loaded.query::<&Component2>().iter(&loaded).count(), 1);
+    }
+
+    #[test]
+    fn test_registry_documents_are_ordered() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component2>().register::<Component1>();
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        world.spawn((Component2 { target: entity1 }, SerializeMe));
+        let mut doc: SaveDocument = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        let text = serde_json::to_string(&doc).unwrap();
+        assert!(text.find("\"Component1\"") < text.find("\"Component2\""));
+
+        let mut loaded = World::default();
+        let ecs = &mut loaded;
+        let mut entity_map = EntityMap::new();
+        crate::deserialize_individually!(
+            ecs,
+            &mut entity_map,
+            &mut doc,
+            SerializeMe,
+            Component1,
+            Component2,
+        );
+        assert_eq!(loaded.query::<&Component2>().iter(&loaded).count(), 1);
+    }
+}
@@ -0,0 +1,165 @@
+//! Several marker-scoped sections, e.g. "player", "level" and "meta", written into one save
+//! document in a single pass, any subset of which can be loaded later.
+
+use bevy_ecs::prelude::*;
+use serde::de::Error;
+
+use crate::entity_map::{get_or_insert, EntityMap};
+use crate::load::saved_entities;
+use crate::namespace::{namespace_key, take_namespace};
+use crate::registry::SaveRegistry;
+use crate::SaveDocument;
+
+type SectionSaveFn =
+    Box<dyn Fn(&SaveRegistry, &mut World) -> Result<SaveDocument, serde_json::Error> + Send + Sync>;
+type SectionLoadFn = Box<
+    dyn Fn(
+            &SaveRegistry,
+            &mut World,
+            &mut EntityMap,
+            &mut SaveDocument,
+        ) -> Result<(), serde_json::Error>
+        + Send
+        + Sync,
+>;
+
+struct Section {
+    name: String,
+    save: SectionSaveFn,
+    load: SectionLoadFn,
+}
+
+/// The sections of a save document, each holding the entities of one marker component and
+/// stored under its [namespace](crate::namespace).
+#[derive(Default)]
+pub struct SaveSections {
+    sections: Vec<Section>,
+}
+
+impl SaveSections {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the section `name`, holding the entities marked with `marker`'s type. Loading
+    /// the section puts `marker` back on its entities.
+    pub fn with_section<M: Component + Clone>(mut self, name: &str, marker: M) -> Self {
+        self.sections.push(Section {
+            name: name.to_string(),
+            save: Box::new(|registry, world| registry.serialize::<M>(world)),
+            load: Box::new(move |registry, world, entity_map, doc| {
+                registry.deserialize(world, entity_map, doc, marker.clone())
+            }),
+        });
+        self
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.sections.iter().map(|section| section.name.as_str())
+    }
+
+    /// Serializes every section into one document.
+    pub fn serialize(
+        &self,
+        registry: &SaveRegistry,
+        world: &mut World,
+    ) -> Result<SaveDocument, serde_json::Error> {
+        let mut doc = SaveDocument::new();
+        for section in &self.sections {
+            let nested = (section.save)(registry, world)?;
+            doc.insert(namespace_key(&section.name), serde_json::to_value(nested)?);
+        }
+        Ok(doc)
+    }
+
+    /// Loads the sections named in `names` from `doc`, leaving the others in place, and
+    /// returns the map from saved entities to restored ones. The sections share that map,
+    /// so references from one loaded section into another are remapped; references into
+    /// sections that aren't loaded are not. Fails if a name isn't one of these sections or
+    /// is missing from `doc`.
+    pub fn load(
+        &self,
+        registry: &SaveRegistry,
+        world: &mut World,
+        doc: &mut SaveDocument,
+        names: &[&str],
+    ) -> Result<EntityMap, serde_json::Error> {
+        let mut selected = Vec::with_capacity(names.len());
+        for name in names {
+            let Some(section) = self.sections.iter().find(|section| section.name == *name) else {
+                return Err(serde_json::Error::custom(format!("unknown section {name}")));
+            };
+            let Some(nested) = take_namespace(doc, name)? else {
+                return Err(serde_json::Error::custom(format!(
+                    "document has no {name} section"
+                )));
+            };
+            selected.push((section, nested));
+        }
+        // spawn the entities of every selected section first, so that references between
+        // sections find their targets in the map
+        let mut entity_map = EntityMap::new();
+        for (_, nested) in &selected {
+            for entity in saved_entities(nested)? {
+                get_or_insert(world, &mut entity_map, entity);
+            }
+        }
+        for (section, mut nested) in selected {
+            (section.load)(registry, world, &mut entity_map, &mut nested)?;
+        }
+        Ok(entity_map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::tests::{Component1, Component2};
+
+    #[derive(Component, Clone, Serialize, Deserialize)]
+    struct Player;
+
+    #[derive(Component, Clone, Serialize, Deserialize)]
+    struct Level;
+
+    #[test]
+    fn test_load_selected_sections() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .register_mapped::<Component2>();
+        let sections = SaveSections::new()
+            .with_section("player", Player)
+            .with_section("level", Level);
+        let mut world = World::default();
+        let door = world.spawn((Component1, Level)).id();
+        world.spawn((Component2 { target: door }, Player));
+        let mut doc = sections.serialize(&registry, &mut world).unwrap();
+        assert!(doc.contains_key("__ns:player__") && doc.contains_key("__ns:level__"));
+
+        let mut loaded = World::default();
+        let entity_map = sections
+            .load(
+                &registry,
+                &mut loaded,
+                &mut doc.clone(),
+                &["player", "level"],
+            )
+            .unwrap();
+        let (component2, _) = loaded.query::<(&Component2, &Player)>().single(&loaded);
+        assert_eq!(component2.target, entity_map[&door]);
+        assert!(loaded.get::<Level>(entity_map[&door]).is_some());
+
+        let mut loaded = World::default();
+        sections
+            .load(&registry, &mut loaded, &mut doc, &["level"])
+            .unwrap();
+        assert_eq!(loaded.query::<&Player>().iter(&loaded).count(), 0);
+        assert!(doc.contains_key("__ns:player__"));
+        assert!(sections
+            .load(&registry, &mut loaded, &mut doc, &["meta"])
+            .is_err());
+    }
+}
@@ -0,0 +1,162 @@
+//! Loading components that need outside context to deserialize, such as a string interner
+//! or an id table, through [`DeserializeSeed`].
+
+use std::fmt;
+
+use bevy_ecs::prelude::*;
+use serde::de::{self, DeserializeSeed, Deserializer, SeqAccess, Visitor};
+use serde::ser::Serialize;
+
+use crate::registry::{extract_section, insert_components, ComponentRegistration, SaveRegistry};
+use crate::schema::Format;
+
+/// Deserializes a section's `[[entity, component], ...]` pairs, the components through
+/// a clone of the seed.
+struct SectionSeed<S>(S);
+
+impl<'de, S: DeserializeSeed<'de> + Clone> DeserializeSeed<'de> for SectionSeed<S> {
+    type Value = Vec<(Entity, S::Value)>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, S: DeserializeSeed<'de> + Clone> Visitor<'de> for SectionSeed<S> {
+    type Value = Vec<(Entity, S::Value)>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence of [entity, component] pairs")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut entries = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(entry) = seq.next_element_seed(EntrySeed(self.0.clone()))? {
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+}
+
+struct EntrySeed<S>(S);
+
+impl<'de, S: DeserializeSeed<'de>> DeserializeSeed<'de> for EntrySeed<S> {
+    type Value = (Entity, S::Value);
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_tuple(2, self)
+    }
+}
+
+impl<'de, S: DeserializeSeed<'de>> Visitor<'de> for EntrySeed<S> {
+    type Value = (Entity, S::Value);
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an [entity, component] pair")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let entity = seq
+            .next_element::<Entity>()?
+            .ok_or_else(|| de::Error::invalid_length(0, &"an [entity, component] pair"))?;
+        let comp = seq
+            .next_element_seed(self.0)?
+            .ok_or_else(|| de::Error::invalid_length(1, &"an [entity, component] pair"))?;
+        Ok((entity, comp))
+    }
+}
+
+impl SaveRegistry {
+    /// Registers `C`, loading it through the seed `seed` builds from the world's `R`
+    /// resource instead of a [`Deserialize`](serde::Deserialize) impl. The seed is built
+    /// once per section and cloned for each component, so context it shares should be
+    /// cheap to clone, e.g. behind an `Arc`. Loading fails if `R` is missing.
+    ///
+    /// The serde structure of `C` can't be traced without its context, so the manifest
+    /// only records it as opaque.
+    pub fn register_seeded<C, R, S>(
+        &mut self,
+        seed: impl Fn(&R) -> S + Send + Sync + 'static,
+    ) -> &mut Self
+    where
+        C: Component + Serialize,
+        R: Resource,
+        S: for<'de> DeserializeSeed<'de, Value = C> + Clone,
+    {
+        let insert = move |world: &mut World, entity_map: &mut _, section, load_hooks: &[_]| {
+            let Some(context) = world.get_resource::<R>() else {
+                return Err(de::Error::custom(format!(
+                    "{} must be present to load {}",
+                    std::any::type_name::<R>(),
+                    std::any::type_name::<C>()
+                )));
+            };
+            let entity_comps = SectionSeed(seed(context)).deserialize(section)?;
+            Ok(insert_components(
+                world,
+                entity_map,
+                entity_comps,
+                load_hooks,
+            ))
+        };
+        let schema = Format::Opaque(std::any::type_name::<C>().to_string());
+        self.push_registration(ComponentRegistration::custom::<C>(
+            0,
+            self.naming(),
+            schema,
+            extract_section::<C>,
+            Box::new(insert),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity_map::EntityMap;
+    use serde::{Deserialize, Serialize};
+
+    use crate::tests::SerializeMe;
+
+    #[derive(Component, Serialize)]
+    struct Distance(f32);
+
+    #[derive(Resource)]
+    struct UnitScale(f32);
+
+    #[derive(Clone)]
+    struct ScaleSeed(f32);
+
+    impl<'de> DeserializeSeed<'de> for ScaleSeed {
+        type Value = Distance;
+
+        fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Distance, D::Error> {
+            f32::deserialize(deserializer).map(|value| Distance(value * self.0))
+        }
+    }
+
+    #[test]
+    fn test_seeded_components_use_context() {
+        let mut registry = SaveRegistry::new();
+        registry.register_seeded::<Distance, UnitScale, _>(|scale| ScaleSeed(scale.0));
+        let mut world = World::default();
+        world.spawn((Distance(1.5), SerializeMe));
+        let doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+
+        let mut loaded = World::default();
+        let mut entity_map = EntityMap::new();
+        assert!(registry
+            .deserialize(&mut loaded, &mut entity_map, &mut doc.clone(), SerializeMe)
+            .is_err());
+        loaded.insert_resource(UnitScale(2.0));
+        registry
+            .deserialize(&mut loaded, &mut entity_map, &mut doc.clone(), SerializeMe)
+            .unwrap();
+        let distances: Vec<f32> = loaded
+            .query::<&Distance>()
+            .iter(&loaded)
+            .map(|distance| distance.0)
+            .collect();
+        assert_eq!(distances, vec![3.0]);
+    }
+}
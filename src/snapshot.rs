@@ -0,0 +1,279 @@
+use std::any::Any;
+
+use bevy_ecs::prelude::*;
+use bevy_utils::hashbrown::HashSet;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::entity_map::{get_or_insert, EntityMap};
+use crate::exempt::SaveExempt;
+use crate::registry::{MapEntitiesFn, SaveRegistry};
+use crate::SaveDocument;
+
+/// The in-memory components of one type, as captured by a [`WorldSnapshot`].
+pub(crate) trait SnapshotColumn: Send + Sync {
+    /// Inserts clones of the captured components, mapping entities through `entity_map`,
+    /// and returns the entities they were inserted into.
+    fn restore(&self, world: &mut World, entity_map: &mut EntityMap) -> Vec<Entity>;
+
+    fn clone_column(&self) -> Box<dyn SnapshotColumn>;
+
+    /// Serializes the column as a save document section.
+    fn to_value(&self) -> Result<Value, serde_json::Error>;
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+pub(crate) struct Column<C>(pub(crate) Vec<(Entity, C)>);
+
+impl<C: Component + Clone + Serialize> SnapshotColumn for Column<C> {
+    fn restore(&self, world: &mut World, entity_map: &mut EntityMap) -> Vec<Entity> {
+        self.0
+            .iter()
+            .map(|(entity, comp)| {
+                let new_entity = get_or_insert(world, entity_map, *entity);
+                world.entity_mut(new_entity).insert(comp.clone());
+                new_entity
+            })
+            .collect()
+    }
+
+    fn clone_column(&self) -> Box<dyn SnapshotColumn> {
+        Box::new(Column(self.0.clone()))
+    }
+
+    fn to_value(&self) -> Result<Value, serde_json::Error> {
+        serde_json::to_value(&self.0)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+pub(crate) fn capture_column<C: Component + Clone + Serialize>(
+    world: &World,
+    entities: &[Entity],
+) -> Option<Box<dyn SnapshotColumn>> {
+    let comps: Vec<(Entity, C)> = entities
+        .iter()
+        .filter_map(|entity| world.get::<C>(*entity).map(|comp| (*entity, comp.clone())))
+        .collect();
+    if comps.is_empty() {
+        None
+    } else {
+        Some(Box::new(Column(comps)))
+    }
+}
+
+/// An in-memory copy of the marked entities' components, taken without going through
+/// serde. Snapshots are cheap to take and restore, which makes them suitable for
+/// rollback, undo and quickload, but they only live as long as the process.
+///
+/// Only components registered with
+/// [`SaveRegistry::register_cloneable`](crate::SaveRegistry::register_cloneable) are captured.
+pub struct WorldSnapshot {
+    entities: Vec<Entity>,
+    columns: Vec<(String, Option<MapEntitiesFn>, Box<dyn SnapshotColumn>)>,
+}
+
+impl Clone for WorldSnapshot {
+    fn clone(&self) -> Self {
+        WorldSnapshot {
+            entities: self.entities.clone(),
+            columns: self
+                .columns
+                .iter()
+                .map(|(name, map_entities, column)| {
+                    (name.clone(), *map_entities, column.clone_column())
+                })
+                .collect(),
+        }
+    }
+}
+
+impl WorldSnapshot {
+    /// The marked entities at the time the snapshot was taken, as ids of the source world.
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    /// Names of the component sections held by the snapshot.
+    pub fn component_names(&self) -> impl Iterator<Item = &str> {
+        self.columns.iter().map(|(name, _, _)| name.as_str())
+    }
+
+    /// Serializes the snapshot into the layout written by
+    /// [`SaveRegistry::serialize`](crate::SaveRegistry::serialize), minus the manifest.
+    pub fn to_document(&self) -> Result<SaveDocument, serde_json::Error> {
+        self.columns
+            .iter()
+            .map(|(name, _, column)| column.to_value().map(|value| (name.clone(), value)))
+            .collect()
+    }
+
+    /// The captured `C` components, if `C` was captured at all.
+    pub fn components<C: Component + Clone>(&self) -> Option<&[(Entity, C)]> {
+        self.columns.iter().find_map(|(_, _, column)| {
+            column
+                .as_any()
+                .downcast_ref::<Column<C>>()
+                .map(|column| column.0.as_slice())
+        })
+    }
+}
+
+/// Captures the cloneable registered components of every entity marked with `M`.
+pub fn take_snapshot<M: Component>(world: &mut World, registry: &SaveRegistry) -> WorldSnapshot {
+    take_snapshot_filtered::<M>(world, registry, |_| true)
+}
+
+/// Like [`take_snapshot`], but only captures the components whose section name passes
+/// `filter`. Entities marked [`SaveExempt`] are left out, as they are from saves.
+pub fn take_snapshot_filtered<M: Component>(
+    world: &mut World,
+    registry: &SaveRegistry,
+    filter: impl Fn(&str) -> bool,
+) -> WorldSnapshot {
+    let mut entities: Vec<Entity> = world
+        .query_filtered::<Entity, (With<M>, Without<SaveExempt>)>()
+        .iter(world)
+        .collect();
+    entities.sort();
+    let columns = registry
+        .iter()
+        .filter(|reg| filter(reg.name()))
+        .filter_map(|reg| {
+            reg.capture
+                .and_then(|capture| capture(world, &entities))
+                .map(|column| (reg.name().to_string(), reg.map_entities, column))
+        })
+        .collect();
+    WorldSnapshot { entities, columns }
+}
+
+/// Restores a snapshot into `world`, mapping snapshot entities to world entities through
+/// `entity_map` exactly as [`deserialize`](crate::deserialize) does, entity references of
+/// components registered with [`register_mapped`](SaveRegistry::register_mapped) included,
+/// and adding `marker` to every restored entity. The snapshot is left intact, so it may be
+/// restored again.
+pub fn restore_snapshot<M: Component + Clone>(
+    world: &mut World,
+    snapshot: &WorldSnapshot,
+    entity_map: &mut EntityMap,
+    marker: M,
+) {
+    for entity in &snapshot.entities {
+        let new_entity = get_or_insert(world, entity_map, *entity);
+        world.entity_mut(new_entity).insert(marker.clone());
+    }
+    let mut restored = Vec::new();
+    for (_, map_entities, column) in &snapshot.columns {
+        let entities = column.restore(world, entity_map);
+        if let Some(map_entities) = map_entities {
+            restored.push((*map_entities, entities));
+        }
+    }
+    // once every entity is in the map, as references may point to any of them
+    for (map_entities, entities) in restored {
+        map_entities(world, &entities, entity_map);
+    }
+}
+
+/// Restores a snapshot into the world it was taken from, rather than into fresh entities:
+/// entities that still exist keep their ids, marked entities that aren't in the snapshot are
+/// despawned, and snapshot entities despawned since are respawned under new ids.
+/// `entity_map` records those new ids and must persist across restores of snapshots of the
+/// same world. Only the sections passing `tracks` are reset to their captured state.
+pub(crate) fn restore_in_place<M: Component + Clone>(
+    world: &mut World,
+    registry: &SaveRegistry,
+    snapshot: &WorldSnapshot,
+    entity_map: &mut EntityMap,
+    marker: M,
+    tracks: impl Fn(&str) -> bool,
+) {
+    // resolve snapshot entities to live ones, keeping ids where the entity survived
+    for entity in snapshot.entities() {
+        let alive = entity_map.get(entity).copied().unwrap_or(*entity);
+        if world.get_entity(alive).is_some() {
+            entity_map.insert(*entity, alive);
+        } else {
+            entity_map.remove(entity);
+        }
+    }
+    let restored: HashSet<Entity> = snapshot
+        .entities()
+        .iter()
+        .filter_map(|entity| entity_map.get(entity).copied())
+        .collect();
+    let spawned_since: Vec<Entity> = world
+        .query_filtered::<Entity, With<M>>()
+        .iter(world)
+        .filter(|entity| !restored.contains(entity))
+        .collect();
+    for entity in spawned_since {
+        world.despawn(entity);
+    }
+    for reg in registry
+        .iter()
+        .filter(|reg| reg.capture.is_some() && tracks(reg.name()))
+    {
+        for entity in &restored {
+            (reg.remove)(world, *entity);
+        }
+    }
+    restore_snapshot(world, snapshot, entity_map, marker);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register_cloneable::<Component1>()
+            .register_cloneable::<Component2>()
+            .register_mapped::<Component2>();
+
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        let entity2 = world
+            .spawn((Component2 { target: entity1 }, SerializeMe))
+            .id();
+        world.spawn(Component1);
+        world.spawn((Component1, SerializeMe, SaveExempt));
+
+        let snapshot = take_snapshot::<SerializeMe>(&mut world, &registry);
+        assert_eq!(snapshot.entities().len(), 2);
+        assert_eq!(snapshot.components::<Component2>().unwrap()[0].0, entity2);
+
+        let mut restored = World::default();
+        for _ in 0..2 {
+            let mut entity_map = EntityMap::new();
+            restore_snapshot(&mut restored, &snapshot, &mut entity_map, SerializeMe);
+            let new_entity2 = entity_map[&entity2];
+            assert_eq!(
+                restored.get::<Component2>(new_entity2).unwrap().target,
+                entity_map[&entity1]
+            );
+        }
+        assert_eq!(
+            restored
+                .query_filtered::<Entity, With<SerializeMe>>()
+                .iter(&restored)
+                .count(),
+            4
+        );
+        assert_eq!(
+            restored
+                .query_filtered::<Entity, With<Component1>>()
+                .iter(&restored)
+                .count(),
+            2
+        );
+    }
+}
@@ -0,0 +1,237 @@
+//! A description of the save files a build writes, generated from its [`SaveRegistry`] so
+//! that it can't drift from the code: every section with its component's fields, traced
+//! through serde, as JSON for tools or as markdown for people.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::manifest::{SchemaHash, FORMAT_VERSION, MANIFEST_KEY};
+use crate::mods::MODS_KEY;
+use crate::registry::SaveRegistry;
+use crate::schema::{Format, VariantFormat};
+use crate::subtree::HIERARCHY_KEY;
+
+/// One component section of a [`FormatSpec`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComponentSpec {
+    pub section: String,
+    pub type_path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mod_name: Option<String>,
+    pub version: u32,
+    pub schema_hash: SchemaHash,
+    pub format: Format,
+}
+
+/// The save format of a build: the document layout version and its component sections.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FormatSpec {
+    pub format_version: u32,
+    pub components: Vec<ComponentSpec>,
+}
+
+impl SaveRegistry {
+    /// Describes the save files written through this registry, components in section order.
+    pub fn format_spec(&self) -> FormatSpec {
+        let mut components: Vec<ComponentSpec> = self
+            .iter()
+            .map(|reg| ComponentSpec {
+                section: reg.name().to_string(),
+                type_path: reg.type_path().to_string(),
+                mod_name: reg.mod_name().map(str::to_string),
+                version: reg.version(),
+                schema_hash: reg.schema_hash(),
+                format: reg.schema().clone(),
+            })
+            .collect();
+        components.sort_by(|a, b| a.section.cmp(&b.section));
+        FormatSpec {
+            format_version: FORMAT_VERSION,
+            components,
+        }
+    }
+}
+
+/// How a value of `format` is written in JSON, in a few words.
+fn describe(format: &Format) -> String {
+    match format {
+        Format::Unit | Format::UnitStruct(_) => "`null`".to_string(),
+        Format::Bool => "bool".to_string(),
+        Format::I8 => "i8".to_string(),
+        Format::I16 => "i16".to_string(),
+        Format::I32 => "i32".to_string(),
+        Format::I64 => "i64".to_string(),
+        Format::I128 => "i128".to_string(),
+        Format::U8 => "u8".to_string(),
+        Format::U16 => "u16".to_string(),
+        Format::U32 => "u32".to_string(),
+        Format::U64 => "u64".to_string(),
+        Format::U128 => "u128".to_string(),
+        Format::F32 => "f32".to_string(),
+        Format::F64 => "f64".to_string(),
+        Format::Char => "string of one character".to_string(),
+        Format::Str => "string".to_string(),
+        Format::Bytes => "list of bytes".to_string(),
+        Format::Option(inner) => format!("{} or `null`", describe(inner)),
+        Format::Seq(inner) => format!("list of {}", describe(inner)),
+        Format::Map { key, value } => {
+            format!("map from {} to {}", describe(key), describe(value))
+        }
+        Format::Tuple(items) | Format::TupleStruct(_, items) => {
+            let items: Vec<String> = items.iter().map(describe).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Format::NewtypeStruct(_, inner) => describe(inner),
+        Format::Struct(name, _) | Format::Enum(name, _) | Format::Recursive(name) => {
+            format!("`{name}`")
+        }
+        Format::Any => "any JSON value".to_string(),
+        Format::Opaque(type_path) => format!("`{type_path}` (not traceable)"),
+    }
+}
+
+/// Collects the structs and enums reachable from `format`, by name.
+fn collect_named<'a>(format: &'a Format, named: &mut BTreeMap<&'a str, &'a Format>) {
+    let children: Vec<&Format> = match format {
+        Format::Option(inner) | Format::Seq(inner) | Format::NewtypeStruct(_, inner) => {
+            vec![inner]
+        }
+        Format::Map { key, value } => vec![key, value],
+        Format::Tuple(items) | Format::TupleStruct(_, items) => items.iter().collect(),
+        // named types are visited once, which also ends recursion through them
+        Format::Struct(name, _) | Format::Enum(name, _) if named.insert(name, format).is_some() => {
+            return
+        }
+        Format::Struct(_, fields) => fields.iter().map(|(_, field)| field).collect(),
+        Format::Enum(_, variants) => variants
+            .iter()
+            .flat_map(|(_, variant)| match variant {
+                VariantFormat::Newtype(inner) => vec![&**inner],
+                VariantFormat::Tuple(items) => items.iter().collect(),
+                VariantFormat::Struct(fields) => fields.iter().map(|(_, field)| field).collect(),
+                VariantFormat::Unit | VariantFormat::Untraced => Vec::new(),
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    for child in children {
+        collect_named(child, named);
+    }
+}
+
+fn write_definition(out: &mut String, name: &str, format: &Format) {
+    match format {
+        Format::Struct(_, fields) => {
+            let _ = writeln!(out, "### `{name}`\n\nAn object with the fields:\n");
+            let _ = writeln!(out, "| Field | Type |\n| --- | --- |");
+            for (field, format) in fields {
+                let _ = writeln!(out, "| `{field}` | {} |", describe(format));
+            }
+        }
+        Format::Enum(_, variants) => {
+            let _ = writeln!(
+                out,
+                "### `{name}`\n\nAn enum, written as the variant's name, or an object with the \
+                 variant's name as its only key and its contents as the value:\n"
+            );
+            let _ = writeln!(out, "| Variant | Contents |\n| --- | --- |");
+            for (variant, format) in variants {
+                let contents = match format {
+                    VariantFormat::Unit => "none".to_string(),
+                    VariantFormat::Newtype(inner) => describe(inner),
+                    VariantFormat::Tuple(items) => describe(&Format::Tuple(items.clone())),
+                    VariantFormat::Struct(fields) => {
+                        let fields: Vec<String> = fields
+                            .iter()
+                            .map(|(field, format)| format!("`{field}`: {}", describe(format)))
+                            .collect();
+                        format!("object with {}", fields.join(", "))
+                    }
+                    VariantFormat::Untraced => "unknown".to_string(),
+                };
+                let _ = writeln!(out, "| `{variant}` | {contents} |");
+            }
+        }
+        _ => return,
+    }
+    out.push('\n');
+}
+
+impl FormatSpec {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("specs always serialize")
+    }
+
+    /// The spec as a markdown document: the document layout, a table of the component
+    /// sections, and the fields or variants of every struct and enum they contain.
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "# Save format\n\n\
+             Layout version {}. A save is a JSON object mapping each component's section name \
+             to a list of `[entity, component]` pairs, where `entity` is the saved entity's id \
+             as a u64 (or a decimal string in saves written for JavaScript readers). Keys \
+             starting with `__` are reserved: `{MANIFEST_KEY}` describes the build that wrote \
+             the save, `{HIERARCHY_KEY}` lists `[child, parent]` pairs, and `{MODS_KEY}` groups \
+             the sections of mods by mod name.\n",
+            self.format_version
+        );
+        let _ = writeln!(
+            out,
+            "## Sections\n\n| Section | Rust type | Version | Schema hash | Value |\n\
+             | --- | --- | --- | --- | --- |"
+        );
+        for component in &self.components {
+            let _ = writeln!(
+                out,
+                "| `{}` | `{}` | {} | `{}` | {} |",
+                component.section,
+                component.type_path,
+                component.version,
+                component.schema_hash,
+                describe(&component.format)
+            );
+        }
+        let mut named = BTreeMap::new();
+        for component in &self.components {
+            collect_named(&component.format, &mut named);
+        }
+        if !named.is_empty() {
+            out.push_str("\n## Types\n\n");
+            for (name, format) in named {
+                write_definition(&mut out, name, format);
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Component1, Component2, Component3};
+
+    #[test]
+    fn test_format_spec() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component3>()
+            .register::<Component1>()
+            .register_mapped::<Component2>();
+        let spec = registry.format_spec();
+        let sections: Vec<&str> = spec.components.iter().map(|c| c.section.as_str()).collect();
+        assert_eq!(sections, vec!["Component1", "Component2", "Component3"]);
+        let parsed: FormatSpec = serde_json::from_str(&spec.to_json()).unwrap();
+        assert_eq!(parsed, spec);
+
+        let markdown = spec.to_markdown();
+        assert!(markdown.contains("| `Component3` | `"));
+        assert!(markdown.contains("### `Component3`"));
+        assert!(markdown.contains("| `target` | u64 |"));
+        assert!(markdown.contains("| `ATest` | string |"));
+        assert!(markdown.contains("| `CTest` | none |"));
+    }
+}
@@ -0,0 +1,135 @@
+//! Saves written as two objects, a small header and the body, for cloud storage that limits
+//! the size of metadata but not of blobs. Save menus can list saves from the headers alone.
+
+use std::collections::BTreeMap;
+
+use bevy_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::{SaveConfig, SaveError};
+use crate::hash::StableHasher;
+use crate::load::LoadReport;
+use crate::registry::SaveRegistry;
+
+/// A save split by [`SaveRegistry::save_split`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SplitSave {
+    /// The JSON encoded [`SaveHeader`].
+    pub header: Vec<u8>,
+    /// The save itself, as written by [`SaveRegistry::save_bytes`].
+    pub body: Vec<u8>,
+}
+
+/// The metadata half of a [`SplitSave`]: the config's
+/// [`metadata`](SaveConfig::with_metadata) and a fingerprint of the body it goes with.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SaveHeader {
+    pub metadata: BTreeMap<String, Value>,
+    pub body_len: usize,
+    /// The [`StableHasher`] hash of the body, in hexadecimal.
+    pub body_hash: String,
+}
+
+fn body_hash(body: &[u8]) -> String {
+    let mut hasher = StableHasher::new();
+    hasher.write_bytes(body);
+    format!("{:016x}", hasher.finish())
+}
+
+impl SaveHeader {
+    pub fn read(bytes: &[u8]) -> Result<Self, serde_json::Error> {
+        serde_json::from_slice(bytes)
+    }
+
+    /// True if `body` is the body this header was written with.
+    pub fn matches(&self, body: &[u8]) -> bool {
+        self.body_len == body.len() && self.body_hash == body_hash(body)
+    }
+}
+
+impl SaveRegistry {
+    /// Saves the entities marked with `M` as [`save_bytes`](Self::save_bytes) does, along
+    /// with a separate [`SaveHeader`].
+    pub fn save_split<M: Component>(
+        &self,
+        world: &mut World,
+        config: &SaveConfig,
+    ) -> Result<SplitSave, SaveError> {
+        let body = self.save_bytes::<M>(world, config)?;
+        let header = SaveHeader {
+            metadata: config.metadata().clone(),
+            body_len: body.len(),
+            body_hash: body_hash(&body),
+        };
+        Ok(SplitSave {
+            header: serde_json::to_vec(&header)?,
+            body,
+        })
+    }
+
+    /// Loads a save written by [`save_split`](Self::save_split) as
+    /// [`load_bytes`](Self::load_bytes) does, failing with [`SaveError::BodyMismatch`] if
+    /// `body` isn't the one `header` was written with, e.g. because only one of them was
+    /// uploaded.
+    pub fn load_split<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        header: &[u8],
+        body: &[u8],
+        config: &SaveConfig,
+        marker: M,
+    ) -> Result<LoadReport, SaveError> {
+        if !SaveHeader::read(header)?.matches(body) {
+            return Err(SaveError::BodyMismatch);
+        }
+        self.load_bytes(world, body, config, marker)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Component1, SerializeMe};
+
+    #[test]
+    fn test_split_roundtrip() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>();
+        let mut world = World::default();
+        world.spawn((Component1, SerializeMe));
+
+        let config = SaveConfig::new().with_metadata("slot", "autosave");
+        let first = registry
+            .save_split::<SerializeMe>(&mut world, &config)
+            .unwrap();
+        let header = SaveHeader::read(&first.header).unwrap();
+        assert_eq!(header.metadata["slot"], "autosave");
+        assert!(header.matches(&first.body));
+
+        world.spawn((Component1, SerializeMe));
+        let second = registry
+            .save_split::<SerializeMe>(&mut world, &config)
+            .unwrap();
+        assert!(matches!(
+            registry.load_split(
+                &mut World::default(),
+                &first.header,
+                &second.body,
+                &config,
+                SerializeMe
+            ),
+            Err(SaveError::BodyMismatch)
+        ));
+        let report = registry
+            .load_split(
+                &mut World::default(),
+                &second.header,
+                &second.body,
+                &config,
+                SerializeMe,
+            )
+            .unwrap();
+        assert_eq!(report.entity_map.len(), 2);
+    }
+}
@@ -0,0 +1,170 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+use bevy_ecs::prelude::*;
+use serde_json::Value;
+
+use crate::manifest::MANIFEST_KEY;
+use crate::registry::SaveRegistry;
+use crate::subtree::HIERARCHY_KEY;
+use crate::SaveDocument;
+
+/// Size and cost of one component section of a save.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SectionStats {
+    pub entities: usize,
+    /// The length of the section as compact JSON, before compression.
+    pub bytes: usize,
+    /// Time spent querying and serializing the section.
+    pub duration: Duration,
+}
+
+/// A save, or one of its sections, that is larger than its budget in
+/// [`SaveConfig::with_section_budget`](crate::SaveConfig::with_section_budget) or
+/// [`SaveConfig::with_total_budget`](crate::SaveConfig::with_total_budget).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BudgetWarning {
+    /// The section over budget, or `None` for the whole save.
+    pub section: Option<String>,
+    /// The size as compact JSON, before compression.
+    pub bytes: usize,
+    pub budget: usize,
+}
+
+impl fmt::Display for BudgetWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.section {
+            Some(section) => write!(f, "section {section}")?,
+            None => write!(f, "save")?,
+        }
+        write!(
+            f,
+            " is {} bytes, over its budget of {} bytes",
+            self.bytes, self.budget
+        )
+    }
+}
+
+/// Per-section statistics of a save, produced by [`SaveRegistry::serialize_with_stats`],
+/// to find out which components a save's size and time go to.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SaveStats {
+    pub sections: BTreeMap<String, SectionStats>,
+}
+
+impl SaveStats {
+    pub fn total_bytes(&self) -> usize {
+        self.sections.values().map(|section| section.bytes).sum()
+    }
+
+    pub fn total_duration(&self) -> Duration {
+        self.sections.values().map(|section| section.duration).sum()
+    }
+
+    /// The sections ordered from the largest to the smallest in bytes.
+    pub fn largest(&self) -> Vec<(&str, &SectionStats)> {
+        let mut sections: Vec<(&str, &SectionStats)> = self
+            .sections
+            .iter()
+            .map(|(name, section)| (name.as_str(), section))
+            .collect();
+        sections.sort_by_key(|(_, section)| Reverse(section.bytes));
+        sections
+    }
+
+    /// Measures the sections of an already written document. Durations are left at zero.
+    pub fn from_document(doc: &SaveDocument) -> Result<Self, serde_json::Error> {
+        let mut stats = SaveStats::default();
+        for (name, section) in doc
+            .iter()
+            .filter(|(name, _)| *name != MANIFEST_KEY && *name != HIERARCHY_KEY)
+        {
+            stats.record(name, section, Duration::ZERO)?;
+        }
+        Ok(stats)
+    }
+
+    pub(crate) fn record(
+        &mut self,
+        name: &str,
+        section: &Value,
+        duration: Duration,
+    ) -> Result<(), serde_json::Error> {
+        let mut counter = ByteCounter(0);
+        serde_json::to_writer(&mut counter, section)?;
+        self.sections.insert(
+            name.to_string(),
+            SectionStats {
+                entities: section.as_array().map_or(0, Vec::len),
+                bytes: counter.0,
+                duration,
+            },
+        );
+        Ok(())
+    }
+}
+
+/// Measures serialized sizes without buffering the output.
+struct ByteCounter(usize);
+
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SaveRegistry {
+    /// Like [`serialize`](Self::serialize), but also measures every section written.
+    /// Measuring costs an extra pass over each section, so plain saves should use
+    /// [`serialize`](Self::serialize).
+    pub fn serialize_with_stats<M: Component>(
+        &self,
+        world: &mut World,
+    ) -> Result<(SaveDocument, SaveStats), serde_json::Error> {
+        let mut stats = SaveStats::default();
+        let doc = self.serialize_marked::<M>(world, |_, _| true, Some(&mut stats))?;
+        Ok((doc, stats))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_stats_match_sections() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Component2>();
+        let mut world = World::default();
+        for _ in 0..10 {
+            let entity = world.spawn((Component1, SerializeMe)).id();
+            world.spawn((Component2 { target: entity }, SerializeMe));
+        }
+        world.spawn((
+            Component2 {
+                target: Entity::PLACEHOLDER,
+            },
+            SerializeMe,
+        ));
+
+        let (doc, stats) = registry
+            .serialize_with_stats::<SerializeMe>(&mut world)
+            .unwrap();
+        assert_eq!(stats.sections["Component1"].entities, 10);
+        assert_eq!(stats.sections["Component2"].entities, 11);
+        let component2_len = serde_json::to_vec(&doc["Component2"]).unwrap().len();
+        assert_eq!(stats.sections["Component2"].bytes, component2_len);
+        assert_eq!(stats.largest()[0].0, "Component2");
+        let measured = SaveStats::from_document(&doc).unwrap();
+        assert_eq!(measured.total_bytes(), stats.total_bytes());
+    }
+}
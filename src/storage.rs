@@ -0,0 +1,416 @@
+//! Named save slots on top of a pluggable byte store, so that games pick the storage of
+//! their platform and save and load the same way everywhere.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use bevy_ecs::prelude::*;
+
+use crate::config::{SaveConfig, SaveError};
+use crate::hash::StableHasher;
+use crate::load::LoadReport;
+use crate::registry::SaveRegistry;
+
+/// Where save slots live: a key-value store of encoded saves, keyed by slot name.
+pub trait SaveStorage {
+    /// The bytes stored in `slot`, or `None` if it's empty.
+    fn read(&self, slot: &str) -> io::Result<Option<Vec<u8>>>;
+
+    /// Stores `bytes` in `slot`, replacing what was there.
+    fn write(&mut self, slot: &str, bytes: &[u8]) -> io::Result<()>;
+
+    /// Empties `slot`. Emptying an empty slot is not an error.
+    fn remove(&mut self, slot: &str) -> io::Result<()>;
+
+    /// The names of the slots holding a save, in no particular order.
+    fn slots(&self) -> io::Result<Vec<String>>;
+
+    /// Empties `slot` together with its backups, the slots named `{slot}.…` such as an
+    /// [`AutosaveChain`](crate::AutosaveChain)'s delta slot, and checks that they're gone,
+    /// for permadeath games where a surviving save is as bad as a lost one. The slot itself
+    /// goes first, so an interrupted deletion leaves at most backups that don't load alone.
+    fn delete_slot_permanently(&mut self, slot: &str) -> io::Result<()> {
+        let mut doomed = backup_slots(self, slot)?;
+        doomed.insert(0, slot.to_string());
+        for slot in &doomed {
+            self.remove(slot)?;
+        }
+        verify_deleted(self, slot)
+    }
+}
+
+/// The slots of `storage` that back up `slot`, i.e. are named `{slot}.…`.
+fn backup_slots(storage: &(impl SaveStorage + ?Sized), slot: &str) -> io::Result<Vec<String>> {
+    let prefix = format!("{slot}.");
+    Ok(storage
+        .slots()?
+        .into_iter()
+        .filter(|name| name.starts_with(&prefix))
+        .collect())
+}
+
+fn verify_deleted(storage: &(impl SaveStorage + ?Sized), slot: &str) -> io::Result<()> {
+    if storage.read(slot)?.is_some() || !backup_slots(storage, slot)?.is_empty() {
+        return Err(io::Error::other(format!(
+            "slot {slot} still holds data after its deletion"
+        )));
+    }
+    Ok(())
+}
+
+/// One file per slot in a directory, named after the slot. Writes go through a temporary
+/// file that replaces the slot's file once complete, so a crash mid-save leaves the
+/// previous save intact.
+///
+/// With a [write-ahead log](FileStorage::with_write_ahead_log), that also holds on power
+/// loss: each write is first committed to the slot's log and synced to disk, and
+/// [`recover`](FileStorage::recover) finishes writes interrupted after their commit.
+///
+/// [`delete_slot_permanently`](SaveStorage::delete_slot_permanently) is atomic too: it
+/// commits to the deletion by creating a `{slot}.{extension}.deleting` directory, moves the
+/// slot's files, logs and backups into it and removes it; `recover` finishes deletions
+/// interrupted after the commit.
+#[derive(Clone, Debug)]
+pub struct FileStorage {
+    dir: PathBuf,
+    extension: String,
+    write_ahead_log: bool,
+}
+
+/// Log files hold the payload's length, the payload, and its [`StableHasher`] hash; the
+/// hash is the commit record, so a log cut short by a crash is recognised and discarded.
+fn encode_log(bytes: &[u8]) -> Vec<u8> {
+    let mut hasher = StableHasher::new();
+    hasher.write_bytes(bytes);
+    let mut log = Vec::with_capacity(bytes.len() + 16);
+    log.extend((bytes.len() as u64).to_le_bytes());
+    log.extend_from_slice(bytes);
+    log.extend(hasher.finish().to_le_bytes());
+    log
+}
+
+/// The payload of a committed log, or `None` if the log is incomplete.
+fn decode_log(log: &[u8]) -> Option<&[u8]> {
+    let (len, rest) = log.split_first_chunk::<8>()?;
+    let len = usize::try_from(u64::from_le_bytes(*len)).ok()?;
+    let (payload, hash) = rest.split_at_checked(len)?;
+    let mut hasher = StableHasher::new();
+    hasher.write_bytes(payload);
+    (hash == hasher.finish().to_le_bytes()).then_some(payload)
+}
+
+/// Writes `bytes` to `path` and syncs them to disk.
+fn write_synced(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(bytes)?;
+    file.sync_all()
+}
+
+/// Syncs a directory, making renames in it durable. Not every platform can open
+/// directories, so failing to is not an error.
+fn sync_dir(dir: &Path) {
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+}
+
+impl FileStorage {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        FileStorage {
+            dir: dir.into(),
+            extension: "sav".to_string(),
+            write_ahead_log: false,
+        }
+    }
+
+    /// Commits every write to a log file, synced to disk, before touching the slot's file.
+    /// Call [`recover`](Self::recover) on startup to finish writes a crash interrupted.
+    pub fn with_write_ahead_log(mut self, write_ahead_log: bool) -> Self {
+        self.write_ahead_log = write_ahead_log;
+        self
+    }
+
+    /// Sets the extension of slot files, `sav` by default.
+    pub fn with_extension(mut self, extension: &str) -> Self {
+        self.extension = extension.to_string();
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn path(&self, slot: &str) -> PathBuf {
+        self.dir.join(format!("{slot}.{}", self.extension))
+    }
+
+    /// The write-ahead log of `slot`.
+    pub fn log_path(&self, slot: &str) -> PathBuf {
+        self.dir.join(format!("{slot}.{}.wal", self.extension))
+    }
+
+    /// Replaces the slot's file with `bytes` through a temporary file.
+    fn replace(&self, slot: &str, bytes: &[u8]) -> io::Result<()> {
+        let path = self.path(slot);
+        let tmp = path.with_extension(format!("{}.tmp", self.extension));
+        if self.write_ahead_log {
+            write_synced(&tmp, bytes)?;
+            std::fs::rename(tmp, path)?;
+            sync_dir(&self.dir);
+            Ok(())
+        } else {
+            std::fs::write(&tmp, bytes)?;
+            std::fs::rename(tmp, path)
+        }
+    }
+
+    /// The directory a permanent deletion of `slot` moves its files into.
+    fn tombstone_path(&self, slot: &str) -> PathBuf {
+        self.dir.join(format!("{slot}.{}.deleting", self.extension))
+    }
+
+    /// The files of `slot` and its backups: the slot file, its log and temporary file, and
+    /// the same of every slot named `{slot}.…`.
+    fn slot_files(&self, slot: &str) -> io::Result<Vec<PathBuf>> {
+        let tombstone = self.tombstone_path(slot);
+        let prefix = format!("{slot}.");
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let owned = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(&prefix));
+            if owned && path != tombstone {
+                files.push(path);
+            }
+        }
+        Ok(files)
+    }
+
+    /// Moves the files of `slot` into its tombstone and removes it.
+    fn finish_deletion(&self, slot: &str) -> io::Result<()> {
+        let tombstone = self.tombstone_path(slot);
+        for path in self.slot_files(slot)? {
+            if let Some(name) = path.file_name() {
+                std::fs::rename(&path, tombstone.join(name))?;
+            }
+        }
+        sync_dir(&self.dir);
+        std::fs::remove_dir_all(&tombstone)?;
+        sync_dir(&self.dir);
+        Ok(())
+    }
+
+    /// Applies the write-ahead logs left behind by a crash: writes committed to their log
+    /// are finished, and logs cut short before their commit are dropped, leaving the slot's
+    /// previous save. Deletions interrupted after their commit are finished first. Returns
+    /// the slots whose writes were finished.
+    pub fn recover(&mut self) -> io::Result<Vec<String>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        let deleting = format!(".{}.deleting", self.extension);
+        for entry in entries {
+            let path = entry?.path();
+            if let Some(slot) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(&deleting))
+            {
+                self.finish_deletion(slot)?;
+            }
+        }
+        let entries = std::fs::read_dir(&self.dir)?;
+        let suffix = format!(".{}.wal", self.extension);
+        let mut recovered = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            let Some(slot) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(&suffix))
+            else {
+                continue;
+            };
+            let log = std::fs::read(&path)?;
+            if let Some(bytes) = decode_log(&log) {
+                self.replace(slot, bytes)?;
+                recovered.push(slot.to_string());
+            }
+            std::fs::remove_file(&path)?;
+        }
+        Ok(recovered)
+    }
+}
+
+impl SaveStorage for FileStorage {
+    fn read(&self, slot: &str) -> io::Result<Option<Vec<u8>>> {
+        match std::fs::read(self.path(slot)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn write(&mut self, slot: &str, bytes: &[u8]) -> io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        if !self.write_ahead_log {
+            return self.replace(slot, bytes);
+        }
+        let log = self.log_path(slot);
+        write_synced(&log, &encode_log(bytes))?;
+        self.replace(slot, bytes)?;
+        std::fs::remove_file(log)
+    }
+
+    fn remove(&mut self, slot: &str) -> io::Result<()> {
+        match std::fs::remove_file(self.path(slot)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    fn slots(&self) -> io::Result<Vec<String>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        let mut slots = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) == Some(&self.extension) {
+                if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    slots.push(stem.to_string());
+                }
+            }
+        }
+        Ok(slots)
+    }
+
+    fn delete_slot_permanently(&mut self, slot: &str) -> io::Result<()> {
+        match std::fs::create_dir(self.tombstone_path(slot)) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) if err.kind() != io::ErrorKind::AlreadyExists => return Err(err),
+            _ => {}
+        }
+        sync_dir(&self.dir);
+        self.finish_deletion(slot)?;
+        verify_deleted(self, slot)
+    }
+}
+
+impl SaveRegistry {
+    /// Saves the entities marked with `M` into `slot` of `storage`, encoded as
+    /// [`save_bytes`](Self::save_bytes) does.
+    pub fn save_slot<M: Component>(
+        &self,
+        world: &mut World,
+        storage: &mut impl SaveStorage,
+        slot: &str,
+        config: &SaveConfig,
+    ) -> Result<(), SaveError> {
+        let bytes = self.save_bytes::<M>(world, config)?;
+        storage.write(slot, &bytes)?;
+        Ok(())
+    }
+
+    /// Loads the save in `slot` of `storage` as [`load_bytes`](Self::load_bytes) does.
+    /// Fails with [`io::ErrorKind::NotFound`] if the slot is empty.
+    pub fn load_slot<M: Component + Clone>(
+        &self,
+        world: &mut World,
+        storage: &impl SaveStorage,
+        slot: &str,
+        config: &SaveConfig,
+        marker: M,
+    ) -> Result<LoadReport, SaveError> {
+        let Some(bytes) = storage.read(slot)? else {
+            return Err(
+                io::Error::new(io::ErrorKind::NotFound, format!("slot {slot} is empty")).into(),
+            );
+        };
+        self.load_bytes(world, &bytes, config, marker)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Component1, SerializeMe};
+
+    #[test]
+    fn test_file_slots() {
+        let dir = std::env::temp_dir().join(format!("bevy_serde_slots_{}", std::process::id()));
+        let mut storage = FileStorage::new(&dir);
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>();
+        let mut world = World::default();
+        world.spawn((Component1, SerializeMe));
+
+        let config = SaveConfig::new();
+        assert!(registry
+            .load_slot(&mut World::default(), &storage, "one", &config, SerializeMe)
+            .is_err());
+        registry
+            .save_slot::<SerializeMe>(&mut world, &mut storage, "one", &config)
+            .unwrap();
+        assert_eq!(storage.slots().unwrap(), vec!["one".to_string()]);
+        let report = registry
+            .load_slot(&mut World::default(), &storage, "one", &config, SerializeMe)
+            .unwrap();
+        assert_eq!(report.entity_map.len(), 1);
+
+        storage.remove("one").unwrap();
+        storage.remove("one").unwrap();
+        assert!(storage.slots().unwrap().is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_permanent_deletion() {
+        let dir = std::env::temp_dir().join(format!("bevy_serde_delete_{}", std::process::id()));
+        let mut storage = FileStorage::new(&dir).with_write_ahead_log(true);
+        for slot in ["hero", "hero.delta", "heroine"] {
+            storage.write(slot, b"save").unwrap();
+        }
+        std::fs::write(storage.log_path("hero"), encode_log(b"newer")).unwrap();
+        storage.delete_slot_permanently("hero").unwrap();
+        assert_eq!(storage.slots().unwrap(), vec!["heroine".to_string()]);
+        assert!(!storage.log_path("hero").exists());
+
+        // a crash after committing to a deletion, with a log that must not be replayed
+        storage.write("heroine.delta", b"save").unwrap();
+        std::fs::write(storage.log_path("heroine"), encode_log(b"newer")).unwrap();
+        std::fs::create_dir(storage.tombstone_path("heroine")).unwrap();
+        assert!(storage.recover().unwrap().is_empty());
+        assert!(storage.slots().unwrap().is_empty());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        storage.delete_slot_permanently("missing").unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_ahead_log_recovery() {
+        let dir = std::env::temp_dir().join(format!("bevy_serde_wal_{}", std::process::id()));
+        let mut storage = FileStorage::new(&dir).with_write_ahead_log(true);
+        storage.write("one", b"first").unwrap();
+        storage.write("two", b"first").unwrap();
+        assert!(!storage.log_path("one").exists());
+
+        // a crash after committing the log of "one", and one before committing that of "two"
+        std::fs::write(storage.log_path("one"), encode_log(b"second")).unwrap();
+        let torn = encode_log(b"second");
+        std::fs::write(storage.log_path("two"), &torn[..torn.len() - 1]).unwrap();
+
+        assert_eq!(storage.recover().unwrap(), vec!["one".to_string()]);
+        assert_eq!(storage.read("one").unwrap().unwrap(), b"second");
+        assert_eq!(storage.read("two").unwrap().unwrap(), b"first");
+        assert!(!storage.log_path("two").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,321 @@
+//! Saving without materializing the document, for platforms that can't afford a second copy
+//! of the world as JSON: each section is serialized, encoded and compressed into the writer
+//! before the next one is, so peak memory is bounded by the largest section rather than the
+//! whole save.
+
+use std::cell::Cell;
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+use bevy_ecs::prelude::*;
+use bevy_utils::tracing::{info_span, warn};
+use bevy_utils::Instant;
+use serde::ser::{SerializeMap, Serializer};
+use serde_json::ser::Formatter;
+use serde_json::Value;
+
+use crate::config::{Compression, SaveConfig, SaveError};
+use crate::layout::SaveLayout;
+use crate::manifest::MANIFEST_KEY;
+use crate::metrics::PersistenceMetrics;
+use crate::mods::{nest_mod_sections, split_mod_section, MODS_KEY};
+use crate::registry::SaveRegistry;
+use crate::stats::SaveStats;
+use crate::unknown_variants::{stashed_section_names, write_stashed_section};
+use crate::SaveDocument;
+
+/// Counts the bytes passed through to `inner`.
+struct Counted<'a, W> {
+    inner: W,
+    written: &'a Cell<usize>,
+}
+
+impl<W: Write> Write for Counted<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.written.set(self.written.get() + len);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The writer, behind the config's compression.
+enum Sink<W: Write> {
+    Plain(W),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzEncoder<W>),
+}
+
+impl<W: Write> Sink<W> {
+    fn new(writer: W, compression: Compression) -> Self {
+        match compression {
+            Compression::None => Sink::Plain(writer),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Sink::Gzip(flate2::write::GzEncoder::new(
+                writer,
+                flate2::Compression::default(),
+            )),
+        }
+    }
+
+    fn finish(self) -> io::Result<W> {
+        match self {
+            Sink::Plain(mut writer) => {
+                writer.flush()?;
+                Ok(writer)
+            }
+            #[cfg(feature = "gzip")]
+            Sink::Gzip(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Sink<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Sink::Plain(writer) => writer.write(buf),
+            #[cfg(feature = "gzip")]
+            Sink::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Plain(writer) => writer.flush(),
+            #[cfg(feature = "gzip")]
+            Sink::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+impl SaveRegistry {
+    /// Saves the entities marked with `M` into `writer` as [`save_bytes`](Self::save_bytes)
+    /// would, without ever holding the whole document: sections are serialized, encoded
+    /// and compressed one at a time. Returns the writer once the save is complete. With the
+    /// default config, the bytes written are those `save_bytes` returns.
+    ///
+    /// Mod sections are the exception, as they are nested under one key: they are held
+    /// together while written. The config's [`max_size`](SaveConfig::with_max_size) is
+    /// checked against the uncompressed length after each section, so a save over it fails
+    /// with part of it written. [`SaveLayout::ByEntity`] regroups the whole document and
+    /// can't be streamed; it fails with an [`io::ErrorKind::InvalidInput`] error.
+    ///
+    /// The writer is blocking. To stream into an async sink, pass a writer that forwards
+    /// each chunk to it, e.g. over a bounded channel.
+    pub fn save_streaming<M: Component, W: Write>(
+        &self,
+        world: &mut World,
+        writer: W,
+        config: &SaveConfig,
+    ) -> Result<W, SaveError> {
+        let start = Instant::now();
+        let stored = Cell::new(0);
+        let result = self.write_streaming::<M, W>(world, writer, config, &stored);
+        if PersistenceMetrics::is_observed(world) {
+            let entities = match &result {
+                Ok(_) => world.query_filtered::<(), With<M>>().iter(world).count(),
+                Err(_) => 0,
+            };
+            let size = result.as_ref().map_or(0, |_| stored.get());
+            let error = result.as_ref().err();
+            PersistenceMetrics::record_save(world, start.elapsed(), size, entities, error);
+        }
+        result
+    }
+
+    /// Counts the bytes written to `writer`, after compression, in `stored`.
+    fn write_streaming<M: Component, W: Write>(
+        &self,
+        world: &mut World,
+        writer: W,
+        config: &SaveConfig,
+        stored: &Cell<usize>,
+    ) -> Result<W, SaveError> {
+        if config.layout() == SaveLayout::ByEntity {
+            return Err(SaveError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "saves laid out by entity can't be streamed",
+            )));
+        }
+        let _save_span = info_span!("save").entered();
+        let entities = self.entities_to_save::<M>(world, |_, _| true);
+        let stashed = stashed_section_names(world, &entities);
+        // the max size applies before compression, as in save_bytes
+        let written = Cell::new(0);
+        let writer = Counted {
+            inner: writer,
+            written: stored,
+        };
+        let mut counted = Counted {
+            inner: Sink::new(writer, config.compression()),
+            written: &written,
+        };
+        let stats = if config.is_pretty() {
+            let mut serializer = serde_json::Serializer::pretty(&mut counted);
+            self.write_sections(
+                world,
+                &entities,
+                &stashed,
+                config,
+                &written,
+                &mut serializer,
+            )?
+        } else {
+            let mut serializer = serde_json::Serializer::new(&mut counted);
+            self.write_sections(
+                world,
+                &entities,
+                &stashed,
+                config,
+                &written,
+                &mut serializer,
+            )?
+        };
+        for warning in config.budget_warnings(&stats) {
+            warn!("{warning}");
+        }
+        Ok(counted.inner.finish()?.inner)
+    }
+
+    /// Writes the document as one JSON object, a section at a time, in key order.
+    fn write_sections<F: Formatter, W: Write>(
+        &self,
+        world: &World,
+        entities: &[Entity],
+        stashed: &BTreeSet<String>,
+        config: &SaveConfig,
+        written: &Cell<usize>,
+        serializer: &mut serde_json::Serializer<W, F>,
+    ) -> Result<SaveStats, SaveError> {
+        let dynamic = self.dynamic_section_names(world);
+        let names: BTreeSet<&str> = self
+            .section_names()
+            .chain(dynamic.iter().map(String::as_str))
+            .chain(stashed.iter().map(String::as_str))
+            .collect();
+        let (mod_names, names): (Vec<&str>, Vec<&str>) = names
+            .into_iter()
+            .partition(|name| split_mod_section(name).is_some());
+        let mut keys: BTreeSet<&str> = names.into_iter().collect();
+        keys.insert(MANIFEST_KEY);
+        if !mod_names.is_empty() {
+            keys.insert(MODS_KEY);
+        }
+
+        let mut stats = SaveStats::default();
+        let mut map = serializer.serialize_map(None)?;
+        for key in keys {
+            let start = Instant::now();
+            let mut sections = SaveDocument::new();
+            if key == MANIFEST_KEY {
+                sections.insert(
+                    key.to_string(),
+                    serde_json::to_value(self.manifest_for(config))?,
+                );
+            } else if key == MODS_KEY {
+                for name in &mod_names {
+                    if let Some(section) = self.stream_section(world, entities, stashed, name)? {
+                        stats.record(name, &section, start.elapsed())?;
+                        sections.insert(name.to_string(), section);
+                    }
+                }
+                nest_mod_sections(&mut sections)?;
+            } else if let Some(section) = self.stream_section(world, entities, stashed, key)? {
+                stats.record(key, &section, start.elapsed())?;
+                sections.insert(key.to_string(), section);
+            }
+            config.encode_sections(&mut sections);
+            if let Some(section) = sections.remove(key) {
+                let _span = info_span!("section", name = key).entered();
+                map.serialize_entry(key, &section)?;
+                config.check_size(written.get())?;
+            }
+        }
+        SerializeMap::end(map)?;
+        Ok(stats)
+    }
+
+    fn stream_section(
+        &self,
+        world: &World,
+        entities: &[Entity],
+        stashed: &BTreeSet<String>,
+        name: &str,
+    ) -> Result<Option<Value>, serde_json::Error> {
+        let mut section = match self.get(name) {
+            Some(reg) => reg.serialize_kept(world, entities)?,
+            None => self.dynamic_section(world, entities, name)?,
+        };
+        if stashed.contains(name) {
+            let section = section.get_or_insert_with(|| Value::Array(Vec::new()));
+            write_stashed_section(world, entities, name, section);
+        }
+        Ok(section)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::EntityEncoding;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_streamed_save_matches_save_bytes() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .register_mapped::<Component2>();
+        let mut world = World::default();
+        for _ in 0..100 {
+            let entity = world.spawn((Component1, SerializeMe)).id();
+            world.spawn((Component2 { target: entity }, SerializeMe));
+        }
+
+        let config = SaveConfig::new();
+        let streamed = registry
+            .save_streaming::<SerializeMe, _>(&mut world, Vec::new(), &config)
+            .unwrap();
+        let bytes = registry
+            .save_bytes::<SerializeMe>(&mut world, &config)
+            .unwrap();
+        assert_eq!(streamed, bytes);
+
+        let config = SaveConfig::new()
+            .with_pretty(true)
+            .with_entity_encoding(EntityEncoding::String);
+        #[cfg(feature = "gzip")]
+        let config = config.with_compression(Compression::Gzip);
+        world.init_resource::<PersistenceMetrics>();
+        let streamed = registry
+            .save_streaming::<SerializeMe, _>(&mut world, Vec::new(), &config)
+            .unwrap();
+        assert_eq!(
+            world.resource::<PersistenceMetrics>().last_save_size,
+            streamed.len()
+        );
+        assert_eq!(
+            config.decode(&streamed).unwrap(),
+            config
+                .decode(
+                    &registry
+                        .save_bytes::<SerializeMe>(&mut world, &config)
+                        .unwrap()
+                )
+                .unwrap()
+        );
+
+        let too_small = SaveConfig::new().with_max_size(100);
+        assert!(matches!(
+            registry.save_streaming::<SerializeMe, _>(&mut world, Vec::new(), &too_small),
+            Err(SaveError::TooLarge { .. })
+        ));
+        let by_entity = SaveConfig::new().with_layout(SaveLayout::ByEntity);
+        assert!(registry
+            .save_streaming::<SerializeMe, _>(&mut world, Vec::new(), &by_entity)
+            .is_err());
+    }
+}
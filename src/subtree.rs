@@ -0,0 +1,196 @@
+use std::collections::BTreeSet;
+
+use bevy_ecs::prelude::*;
+use bevy_hierarchy::{BuildWorldChildren, Parent};
+use serde::de::Error;
+use serde_json::Value;
+
+use crate::delta::section_entries;
+use crate::entity_map::{get_or_insert, EntityMap};
+use crate::manifest::MANIFEST_KEY;
+use crate::registry::SaveRegistry;
+use crate::SaveDocument;
+
+/// The section recording the hierarchy of an exported fragment, as `[entity, parent]`
+/// pairs. Roots are the entries whose parent is `null`.
+pub const HIERARCHY_KEY: &str = "__hierarchy__";
+
+/// Builds the [`HIERARCHY_KEY`] section for `entities`, treating `roots` as parentless.
+pub(crate) fn hierarchy_section(
+    world: &World,
+    roots: &[Entity],
+    entities: &[Entity],
+) -> Result<Value, serde_json::Error> {
+    let hierarchy: Vec<(Entity, Option<Entity>)> = entities
+        .iter()
+        .map(|entity| {
+            let parent = (!roots.contains(entity))
+                .then(|| world.get::<Parent>(*entity).map(|parent| parent.get()))
+                .flatten();
+            (*entity, parent)
+        })
+        .collect();
+    serde_json::to_value(hierarchy)
+}
+
+/// Serializes `root` and all of its descendants into a self-contained document, e.g. to turn
+/// an in-game construction into a reusable template. The hierarchy is recorded alongside the
+/// registered components, so [`load_subtree`] can rebuild it.
+pub fn serialize_subtree(
+    world: &World,
+    registry: &SaveRegistry,
+    root: Entity,
+) -> Result<SaveDocument, serde_json::Error> {
+    registry.serialize_entities_with_descendants(world, &[root])
+}
+
+/// Spawns a copy of a subtree written by [`serialize_subtree`], adding `marker` to every
+/// entity of it, and returns the new root. References between entities of the subtree are
+/// remapped to the copies for components registered with
+/// [`SaveRegistry::register_mapped`]; references leaving the subtree are left dangling.
+/// The document is left intact, so it may be loaded any number of times.
+pub fn load_subtree<M: Component + Clone>(
+    world: &mut World,
+    registry: &SaveRegistry,
+    doc: &SaveDocument,
+    marker: M,
+) -> Result<Entity, serde_json::Error> {
+    if !doc.contains_key(HIERARCHY_KEY) {
+        return Err(serde_json::Error::custom(format!(
+            "document has no {HIERARCHY_KEY} section"
+        )));
+    }
+    match spawn_from_json(world, registry, doc, marker)?.as_slice() {
+        [root] => Ok(*root),
+        roots => Err(serde_json::Error::custom(format!(
+            "subtree document has {} roots",
+            roots.len()
+        ))),
+    }
+}
+
+/// The `[entity, parent]` pairs of a fragment: its [`HIERARCHY_KEY`] section, or every
+/// entity of it as a root if it has none.
+pub(crate) fn fragment_hierarchy(
+    fragment: &SaveDocument,
+) -> Result<Vec<(Entity, Option<Entity>)>, serde_json::Error> {
+    match fragment.get(HIERARCHY_KEY) {
+        Some(hierarchy) => serde_json::from_value(hierarchy.clone()),
+        None => {
+            let mut entities = BTreeSet::new();
+            for (name, section) in fragment.iter().filter(|(name, _)| *name != MANIFEST_KEY) {
+                entities.extend(
+                    section_entries(name, section)?
+                        .into_iter()
+                        .map(|(entity, _)| entity),
+                );
+            }
+            Ok(entities.into_iter().map(|entity| (entity, None)).collect())
+        }
+    }
+}
+
+/// Instantiates a fragment written by [`serialize_subtree`] or
+/// [`SaveRegistry::serialize_entities`] (and its `_with_descendants` variant), adding
+/// `marker` to every spawned entity, and returns the new root entities: the roots of the
+/// recorded hierarchy, or every entity of a fragment without one. References within the
+/// fragment are remapped as in [`load_subtree`].
+///
+/// The fragment is left intact, which makes this a lightweight data-driven prefab system:
+/// parse a template once and spawn it as often as needed.
+pub fn spawn_from_json<M: Component + Clone>(
+    world: &mut World,
+    registry: &SaveRegistry,
+    fragment: &SaveDocument,
+    marker: M,
+) -> Result<Vec<Entity>, serde_json::Error> {
+    let hierarchy = fragment_hierarchy(fragment)?;
+    let mut entity_map = EntityMap::new();
+    for (entity, _) in &hierarchy {
+        let new_entity = get_or_insert(world, &mut entity_map, *entity);
+        world.entity_mut(new_entity).insert(marker.clone());
+    }
+    let mut sections = fragment.clone();
+    sections.remove(HIERARCHY_KEY);
+    registry.deserialize(world, &mut entity_map, &mut sections, marker)?;
+
+    let mut roots = Vec::new();
+    for (entity, parent) in &hierarchy {
+        let new_entity = entity_map[entity];
+        match parent.and_then(|parent| entity_map.get(&parent)) {
+            Some(new_parent) => {
+                world.entity_mut(*new_parent).add_child(new_entity);
+            }
+            None => roots.push(new_entity),
+        }
+    }
+    Ok(roots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_hierarchy::Children;
+
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_subtree_roundtrip() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .register_mapped::<Component2>();
+        let mut world = World::default();
+        let outside = world.spawn(Component1).id();
+        let root = world.spawn(Component1).id();
+        let child = world.spawn(Component2 { target: root }).id();
+        let grandchild = world.spawn(Component2 { target: outside }).id();
+        world.entity_mut(outside).add_child(root);
+        world.entity_mut(root).add_child(child);
+        world.entity_mut(child).add_child(grandchild);
+
+        let doc = serialize_subtree(&world, &registry, root).unwrap();
+        let copies: Vec<Entity> = (0..2)
+            .map(|_| load_subtree(&mut world, &registry, &doc, SerializeMe).unwrap())
+            .collect();
+        assert_ne!(copies[0], copies[1]);
+        for new_root in copies {
+            assert!(world.get::<Parent>(new_root).is_none());
+            let new_child = world.get::<Children>(new_root).unwrap()[0];
+            assert_eq!(world.get::<Component2>(new_child).unwrap().target, new_root);
+            let new_grandchild = world.get::<Children>(new_child).unwrap()[0];
+            let dangling = world.get::<Component2>(new_grandchild).unwrap().target;
+            assert!(world.get_entity(dangling).is_none());
+            assert!(world.get::<SerializeMe>(new_grandchild).is_some());
+        }
+    }
+
+    #[test]
+    fn test_spawn_from_json() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .register_mapped::<Component2>();
+        let mut world = World::default();
+        let entity1 = world.spawn(Component1).id();
+        let entity2 = world.spawn(Component2 { target: entity1 }).id();
+        world.entity_mut(entity1).add_child(entity2);
+        let entity3 = world.spawn(Component1).id();
+
+        let flat = registry
+            .serialize_entities(&world, &[entity1, entity3])
+            .unwrap();
+        let roots = spawn_from_json(&mut world, &registry, &flat, SerializeMe).unwrap();
+        assert_eq!(roots.len(), 2);
+        assert!(world.get::<Children>(roots[0]).is_none());
+
+        let nested = registry
+            .serialize_entities_with_descendants(&world, &[entity1, entity3])
+            .unwrap();
+        let roots = spawn_from_json(&mut world, &registry, &nested, SerializeMe).unwrap();
+        assert_eq!(roots.len(), 2);
+        let new_child = world.get::<Children>(roots[0]).unwrap()[0];
+        assert_eq!(world.get::<Component2>(new_child).unwrap().target, roots[0]);
+        assert!(load_subtree(&mut world, &registry, &nested, SerializeMe).is_err());
+    }
+}
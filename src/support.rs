@@ -0,0 +1,210 @@
+//! Bug-report bundles: the current save, optionally [redacted](Redaction), with its
+//! [`SaveStats`], the registry's [`Manifest`] and version strings, packed into one tar
+//! archive for a support pipeline to ingest.
+//!
+//! The archive holds `save.json` (or `save.json.gz` when the config compresses),
+//! `stats.json`, `manifest.json` and `versions.json`.
+
+use std::collections::BTreeMap;
+use std::io;
+
+use bevy_ecs::prelude::*;
+use serde_json::{json, Value};
+
+use crate::config::{Compression, SaveConfig, SaveError};
+use crate::redact::Redaction;
+use crate::registry::SaveRegistry;
+use crate::stats::SaveStats;
+
+const BLOCK: usize = 512;
+
+/// What [`create_support_bundle`] puts into a bundle.
+#[derive(Clone, Debug, Default)]
+pub struct SupportBundleOptions {
+    game_version: String,
+    config: SaveConfig,
+    redaction: Option<Redaction>,
+}
+
+impl SupportBundleOptions {
+    pub fn new(game_version: &str) -> Self {
+        SupportBundleOptions {
+            game_version: game_version.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Sets the config the save in the bundle is encoded with.
+    pub fn with_config(mut self, config: SaveConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Redacts the save before it is bundled. Section stats are measured before redaction.
+    pub fn with_redaction(mut self, redaction: Redaction) -> Self {
+        self.redaction = Some(redaction);
+        self
+    }
+}
+
+/// Saves the entities marked with `M` and bundles the save with its stats, the registry's
+/// manifest and the game and crate versions, as a tar archive.
+pub fn create_support_bundle<M: Component>(
+    world: &mut World,
+    registry: &SaveRegistry,
+    options: &SupportBundleOptions,
+) -> Result<Vec<u8>, SaveError> {
+    let (mut doc, stats) = registry.serialize_with_stats::<M>(world)?;
+    if let Some(redaction) = &options.redaction {
+        redaction.apply(&mut doc);
+    }
+    let save_name = match options.config.compression() {
+        Compression::None => "save.json",
+        #[cfg(feature = "gzip")]
+        Compression::Gzip => "save.json.gz",
+    };
+    let versions = json!({
+        "game": options.game_version,
+        env!("CARGO_PKG_NAME"): env!("CARGO_PKG_VERSION"),
+    });
+
+    let mut archive = Vec::new();
+    append_entry(&mut archive, save_name, &options.config.encode(&doc)?)?;
+    append_entry(&mut archive, "stats.json", &stats_json(&stats))?;
+    append_entry(
+        &mut archive,
+        "manifest.json",
+        &serde_json::to_vec_pretty(&registry.manifest())?,
+    )?;
+    append_entry(
+        &mut archive,
+        "versions.json",
+        &serde_json::to_vec_pretty(&versions)?,
+    )?;
+    archive.resize(archive.len() + 2 * BLOCK, 0);
+    Ok(archive)
+}
+
+/// The files of a bundle written by [`create_support_bundle`], by name.
+pub fn read_support_bundle(bytes: &[u8]) -> Result<BTreeMap<String, Vec<u8>>, SaveError> {
+    let mut files = BTreeMap::new();
+    let mut rest = bytes;
+    while rest.len() >= BLOCK && rest[..BLOCK].iter().any(|byte| *byte != 0) {
+        let (header, data) = rest.split_at(BLOCK);
+        let name = std::str::from_utf8(&header[..100])
+            .map_err(|_| invalid("bundle entry name isn't UTF-8"))?
+            .trim_end_matches('\0');
+        let size = std::str::from_utf8(&header[124..135])
+            .ok()
+            .and_then(|size| usize::from_str_radix(size.trim_end_matches('\0'), 8).ok())
+            .ok_or_else(|| invalid("bundle entry size isn't octal"))?;
+        if data.len() < size {
+            return Err(invalid("truncated bundle entry"));
+        }
+        files.insert(name.to_string(), data[..size].to_vec());
+        rest = &data[(size.div_ceil(BLOCK) * BLOCK).min(data.len())..];
+    }
+    Ok(files)
+}
+
+fn invalid(message: &str) -> SaveError {
+    SaveError::Io(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+fn stats_json(stats: &SaveStats) -> Vec<u8> {
+    let sections: serde_json::Map<String, Value> = stats
+        .sections
+        .iter()
+        .map(|(name, section)| {
+            let section = json!({
+                "entities": section.entities,
+                "bytes": section.bytes,
+                "duration_us": section.duration.as_micros() as u64,
+            });
+            (name.clone(), section)
+        })
+        .collect();
+    let stats = json!({
+        "total_bytes": stats.total_bytes(),
+        "total_duration_us": stats.total_duration().as_micros() as u64,
+        "sections": sections,
+    });
+    // a `Value` always serializes
+    serde_json::to_vec_pretty(&stats).unwrap_or_default()
+}
+
+/// Appends a regular file to a ustar archive. Modification times are left at zero, so
+/// bundles of the same save are byte-for-byte equal.
+fn append_entry(archive: &mut Vec<u8>, name: &str, data: &[u8]) -> Result<(), SaveError> {
+    if name.len() > 100 {
+        return Err(invalid("bundle entry name too long"));
+    }
+    let mut header = [0u8; BLOCK];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    header[100..107].copy_from_slice(b"0000644");
+    header[108..115].copy_from_slice(b"0000000");
+    header[116..123].copy_from_slice(b"0000000");
+    header[124..135].copy_from_slice(format!("{:011o}", data.len()).as_bytes());
+    header[136..147].copy_from_slice(b"00000000000");
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    // the checksum is summed with its own field as spaces
+    header[148..156].fill(b' ');
+    let checksum: u32 = header.iter().map(|byte| u32::from(*byte)).sum();
+    header[148..155].copy_from_slice(format!("{checksum:06o}\0").as_bytes());
+    archive.extend_from_slice(&header);
+    archive.extend_from_slice(data);
+    archive.resize(archive.len().div_ceil(BLOCK) * BLOCK, 0);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::manifest::Manifest;
+    use crate::redact::RedactAction;
+    use crate::tests::{Component1, SerializeMe};
+
+    #[derive(Component, Serialize, Deserialize)]
+    struct Profile {
+        name: String,
+    }
+
+    #[test]
+    fn test_bundle_holds_redacted_save_and_metadata() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Profile>();
+        let mut world = World::default();
+        world.spawn((Component1, SerializeMe));
+        world.spawn((
+            Profile {
+                name: "Alice".to_string(),
+            },
+            SerializeMe,
+        ));
+        let options = SupportBundleOptions::new("1.4.0-beta")
+            .with_redaction(Redaction::new().with_field("Profile", "name", RedactAction::Zero));
+
+        let bundle = create_support_bundle::<SerializeMe>(&mut world, &registry, &options).unwrap();
+        assert_eq!(bundle.len() % BLOCK, 0);
+        let files = read_support_bundle(&bundle).unwrap();
+        let names: Vec<&str> = files.keys().map(String::as_str).collect();
+        assert_eq!(
+            names,
+            ["manifest.json", "save.json", "stats.json", "versions.json"]
+        );
+
+        let save = SaveConfig::new().decode(&files["save.json"]).unwrap();
+        assert_eq!(save["Profile"][0][1]["name"], "");
+        let stats: Value = serde_json::from_slice(&files["stats.json"]).unwrap();
+        assert_eq!(stats["sections"]["Component1"]["entities"], 1);
+        let manifest: Manifest = serde_json::from_slice(&files["manifest.json"]).unwrap();
+        assert_eq!(manifest, registry.manifest());
+        let versions: Value = serde_json::from_slice(&files["versions.json"]).unwrap();
+        assert_eq!(versions["game"], "1.4.0-beta");
+        assert_eq!(versions["bevy_serde_macros"], env!("CARGO_PKG_VERSION"));
+    }
+}
@@ -0,0 +1,211 @@
+//! Emergency saves on app suspension. Mobile platforms may kill a suspended process at any
+//! time without further notice, and Android gives an app a single frame after suspending
+//! it, so the save is written synchronously in the frame the event arrives.
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use bevy_app::prelude::*;
+use bevy_ecs::event::{Events, ManualEventReader};
+use bevy_ecs::prelude::*;
+use bevy_utils::tracing::error;
+use bevy_window::{ApplicationLifetime, WindowFocused};
+
+use crate::async_io::{IoCompleted, IoOperation};
+use crate::config::SaveError;
+use crate::plugin::PersistencePlugin;
+use crate::profiles::SaveRequest;
+use crate::registry::SaveRegistry;
+use crate::storage::SaveStorage;
+
+/// The state of [`SaveOnSuspendPlugin`]: where the emergency save goes, and with which
+/// [`SaveProfile`](crate::SaveProfile).
+#[derive(Resource)]
+pub struct SaveOnSuspend<M, S> {
+    registry: Arc<SaveRegistry>,
+    storage: S,
+    slot: String,
+    request: SaveRequest,
+    on_focus_lost: bool,
+    lifetime: ManualEventReader<ApplicationLifetime>,
+    focus: ManualEventReader<WindowFocused>,
+    _marker: PhantomData<fn(M)>,
+}
+
+impl<M, S> SaveOnSuspend<M, S> {
+    pub fn slot(&self) -> &str {
+        &self.slot
+    }
+
+    pub fn profile(&self) -> &str {
+        self.request.profile()
+    }
+
+    /// Whether losing window focus triggers the save as well as suspension.
+    pub fn on_focus_lost(&self) -> bool {
+        self.on_focus_lost
+    }
+}
+
+impl<M: Component, S: SaveStorage> SaveOnSuspend<M, S> {
+    /// Drains the lifecycle events, returning whether one of them calls for a save.
+    fn triggered(&mut self, world: &World) -> bool {
+        let suspended = world
+            .get_resource::<Events<ApplicationLifetime>>()
+            .is_some_and(|events| {
+                self.lifetime
+                    .read(events)
+                    .any(|event| *event == ApplicationLifetime::Suspended)
+            });
+        let unfocused = world
+            .get_resource::<Events<WindowFocused>>()
+            .is_some_and(|events| self.focus.read(events).any(|event| !event.focused));
+        suspended || (unfocused && self.on_focus_lost)
+    }
+
+    /// Saves the entities marked with `M` with the profile's components and config, and
+    /// writes them to the slot. The [`PersistenceLock`](crate::PersistenceLock) isn't
+    /// consulted: a save in flight may never get to finish once the app is suspended.
+    fn save(&mut self, world: &mut World) -> Result<(), SaveError> {
+        let profile = self.request.resolve(world)?;
+        let bytes = self
+            .registry
+            .save_sections::<M>(world, profile.config(), |name| profile.includes(name))?;
+        self.storage.write(&self.slot, &bytes)?;
+        Ok(())
+    }
+}
+
+/// Saves the entities marked with `M` to `slot` of `storage` with the [`Profiles`]
+/// entry `profile` when the app is suspended, and optionally when a window loses focus,
+/// which on mobile often comes first. The save runs in [`First`], in the frame the event
+/// arrives, and is reported with an [`IoCompleted`] event. Adds [`PersistencePlugin`] if it
+/// isn't yet.
+///
+/// [`Profiles`]: crate::Profiles
+pub struct SaveOnSuspendPlugin<M, S> {
+    registry: Arc<SaveRegistry>,
+    storage: S,
+    slot: String,
+    profile: String,
+    on_focus_lost: bool,
+    _marker: PhantomData<fn(M)>,
+}
+
+impl<M, S> SaveOnSuspendPlugin<M, S> {
+    pub fn new(registry: Arc<SaveRegistry>, storage: S, slot: &str, profile: &str) -> Self {
+        SaveOnSuspendPlugin {
+            registry,
+            storage,
+            slot: slot.to_string(),
+            profile: profile.to_string(),
+            on_focus_lost: false,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Also saves when a window loses focus. Off by default, as on desktop that happens on
+    /// every alt-tab.
+    pub fn with_focus_loss(mut self, on_focus_lost: bool) -> Self {
+        self.on_focus_lost = on_focus_lost;
+        self
+    }
+}
+
+impl<M, S> Plugin for SaveOnSuspendPlugin<M, S>
+where
+    M: Component,
+    S: SaveStorage + Clone + Send + Sync + 'static,
+{
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<PersistencePlugin>() {
+            app.add_plugins(PersistencePlugin);
+        }
+        app.add_event::<ApplicationLifetime>()
+            .add_event::<WindowFocused>()
+            .insert_resource(SaveOnSuspend::<M, S> {
+                registry: self.registry.clone(),
+                storage: self.storage.clone(),
+                slot: self.slot.clone(),
+                request: SaveRequest::new(&self.profile),
+                on_focus_lost: self.on_focus_lost,
+                lifetime: ManualEventReader::default(),
+                focus: ManualEventReader::default(),
+                _marker: PhantomData,
+            })
+            .add_systems(First, save_on_suspend::<M, S>);
+    }
+}
+
+fn save_on_suspend<M: Component, S: SaveStorage + Send + Sync + 'static>(world: &mut World) {
+    world.resource_scope(|world, mut suspend: Mut<SaveOnSuspend<M, S>>| {
+        if !suspend.triggered(world) {
+            return;
+        }
+        let result = suspend.save(world);
+        if let Err(err) = &result {
+            error!("emergency save to slot {} failed: {err}", suspend.slot);
+        }
+        if let Some(mut events) = world.get_resource_mut::<Events<IoCompleted>>() {
+            events.send(IoCompleted {
+                slot: suspend.slot.clone(),
+                operation: IoOperation::Save,
+                error: result.err().map(|err| err.to_string()),
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::SaveConfig;
+    use crate::profiles::{Profiles, SaveProfile};
+    use crate::storage::FileStorage;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_suspension_saves_the_profile() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>().register::<Component2>();
+        let dir = std::env::temp_dir().join(format!("bevy_serde_suspend_{}", std::process::id()));
+        let storage = FileStorage::new(&dir);
+        let mut app = App::new();
+        let mut profiles = Profiles::new();
+        profiles.insert(
+            "emergency",
+            SaveProfile::new(SaveConfig::new()).with_sections(&["Component1"]),
+        );
+        app.insert_resource(profiles)
+            .add_plugins(SaveOnSuspendPlugin::<SerializeMe, _>::new(
+                Arc::new(registry),
+                storage.clone(),
+                "quick",
+                "emergency",
+            ));
+        let entity = app.world.spawn((Component1, SerializeMe)).id();
+        app.world
+            .spawn((Component2 { target: entity }, SerializeMe));
+
+        app.world.send_event(WindowFocused {
+            window: Entity::PLACEHOLDER,
+            focused: false,
+        });
+        app.update();
+        assert_eq!(storage.read("quick").unwrap(), None);
+
+        app.world.send_event(ApplicationLifetime::Suspended);
+        app.update();
+        let bytes = storage.read("quick").unwrap().unwrap();
+        let doc = SaveConfig::new().decode(&bytes).unwrap();
+        assert!(doc.contains_key("Component1"));
+        assert!(!doc.contains_key("Component2"));
+        let events = app.world.resource::<Events<IoCompleted>>();
+        let mut reader = events.get_reader();
+        let sent: Vec<&IoCompleted> = reader.read(events).collect();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].error, None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+//! Helpers for checking in tests that a world survives a save and load unchanged.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use bevy_ecs::prelude::*;
+
+use crate::entity_map::EntityMap;
+use crate::load::saved_entities;
+use crate::registry::SaveRegistry;
+use crate::SaveDocument;
+
+/// Spawns every entity of `doc` into a fresh world under the id it was saved with, and
+/// returns the world with an identity entity map. Loading `doc` into it then reproduces
+/// the saved ids, references included, so that a re-save can be compared to `doc` as is.
+pub fn world_with_saved_ids(doc: &SaveDocument) -> Result<(World, EntityMap), serde_json::Error> {
+    let mut world = World::default();
+    let mut entity_map = EntityMap::new();
+    for entity in saved_entities(doc)? {
+        world.get_or_spawn(entity);
+        entity_map.insert(entity, entity);
+    }
+    Ok((world, entity_map))
+}
+
+/// Appends to `message` every section that differs between `expected` and `actual`, with
+/// the two values labelled `labels`. Returns whether any did.
+pub(crate) fn describe_differences(
+    message: &mut String,
+    expected: &SaveDocument,
+    actual: &SaveDocument,
+    labels: (&str, &str),
+) -> bool {
+    if expected == actual {
+        return false;
+    }
+    let names = expected
+        .keys()
+        .chain(actual.keys())
+        .collect::<BTreeSet<_>>();
+    for name in names {
+        let (before, after) = (expected.get(name), actual.get(name));
+        if before != after {
+            let _ = write!(
+                message,
+                "\n  section {name}:\n    {:<9} {}\n    {:<9} {}",
+                format!("{}:", labels.0),
+                before.map_or("<missing>".to_string(), ToString::to_string),
+                format!("{}:", labels.1),
+                after.map_or("<missing>".to_string(), ToString::to_string),
+            );
+        }
+    }
+    true
+}
+
+/// Panics with the sections that differ between two save documents.
+pub fn assert_documents_eq(expected: &SaveDocument, actual: &SaveDocument) {
+    let mut message = String::from("save documents differ after a roundtrip");
+    if describe_differences(&mut message, expected, actual, ("saved", "reloaded")) {
+        panic!("{message}");
+    }
+}
+
+/// Saves the entities marked with `M` through `registry`, loads them into a fresh world,
+/// saves again and asserts that both documents are identical. Catches components whose
+/// serde impls don't roundtrip, and mapped references that come back wrong.
+pub fn assert_registry_roundtrip<M: Component + Clone>(
+    world: &mut World,
+    registry: &SaveRegistry,
+    marker: M,
+) {
+    let saved = registry.serialize::<M>(world).unwrap();
+    let (mut fresh, mut entity_map) = world_with_saved_ids(&saved).unwrap();
+    registry
+        .deserialize(&mut fresh, &mut entity_map, &mut saved.clone(), marker)
+        .unwrap();
+    let reloaded = registry.serialize::<M>(&mut fresh).unwrap();
+    assert_documents_eq(&saved, &reloaded);
+}
+
+/// The macro counterpart of [`assert_registry_roundtrip`]: saves the listed component types
+/// of the entities marked with `$marker` with `serialize_individually!`, loads them into a
+/// fresh world with `deserialize_individually!`, saves again and asserts that both documents
+/// are identical. `$marker` must be a unit struct, as it is used both as a type and a value.
+///
+/// ```ignore
+/// assert_world_roundtrip!(&mut world, SerializeMe, Position, Health);
+/// ```
+#[macro_export]
+macro_rules! assert_world_roundtrip {
+  ($world:expr, $marker:path, $( $(#[$attr:meta])* $comp_type:ty $(as $section:literal)?),+ $(,)?) => {{
+      let world = $world;
+      let saved: $crate::SaveDocument =
+          $crate::serialize_individually!(world, $marker, $($(#[$attr])* $comp_type $(as $section)?),*,);
+      let (mut fresh, mut entity_map) =
+          $crate::testing::world_with_saved_ids(&saved).unwrap();
+      {
+          let fresh = &mut fresh;
+          $crate::deserialize_individually!(
+              fresh,
+              &mut entity_map,
+              &mut saved.clone(),
+              $marker,
+              $($(#[$attr])* $comp_type $(as $section)?),*,
+          );
+      }
+      let reloaded = {
+          let fresh = &mut fresh;
+          $crate::serialize_individually!(fresh, $marker, $($(#[$attr])* $comp_type $(as $section)?),*,)
+      };
+      $crate::testing::assert_documents_eq(&saved, &reloaded);
+  }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Component1, Component2, Component3, SerializeMe, TestEnum};
+
+    fn build_world() -> World {
+        let mut world = World::default();
+        world.spawn(Component1);
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        world.spawn((
+            Component2 { target: entity1 },
+            Component3 {
+                target: entity1,
+                test_enum: TestEnum::BTest(3),
+            },
+            SerializeMe,
+        ));
+        world
+    }
+
+    #[test]
+    fn test_roundtrip_helpers() {
+        let world = &mut build_world();
+        assert_world_roundtrip!(world, SerializeMe, Component1, Component2, Component3);
+
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .register_mapped::<Component2>()
+            .register::<Component3>();
+        assert_registry_roundtrip(&mut build_world(), &registry, SerializeMe);
+    }
+
+    #[test]
+    #[should_panic(expected = "section Component1")]
+    fn test_mismatch_names_section() {
+        let mut saved = SaveDocument::new();
+        saved.insert("Component1".to_string(), serde_json::json!([[0, null]]));
+        assert_documents_eq(&saved, &SaveDocument::new());
+    }
+}
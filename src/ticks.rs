@@ -0,0 +1,103 @@
+//! Control over how loaded components show up to change detection.
+
+use bevy_ecs::change_detection::MAX_CHANGE_AGE;
+use bevy_ecs::component::{ComponentId, Tick};
+use bevy_ecs::prelude::*;
+
+/// Whether components inserted by a load count as changed. Set with
+/// [`SaveRegistry::set_change_detection`](crate::SaveRegistry::set_change_detection).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LoadChangeDetection {
+    /// Loaded components are inserted like any other, so they match `Added` and `Changed`
+    /// filters on the next run of every system.
+    #[default]
+    Trigger,
+    /// Loaded components, and the load marker, are backdated so they don't match `Changed`
+    /// filters. bevy gives no way to backdate the added tick, so `Added` filters still
+    /// match them; systems can skip them with [`LastLoad`].
+    Suppress,
+}
+
+/// Inserted into the world by every load, so that reactive systems can tell a bulk
+/// restore apart from gameplay changes: either skip the frame of a load altogether with
+/// `Res<LastLoad>::is_changed`, or skip single components with
+/// [`was_loaded`](Self::was_loaded).
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct LastLoad {
+    /// The change tick the loaded components were inserted at.
+    pub tick: Tick,
+}
+
+impl LastLoad {
+    /// True if `component` was inserted by the last load and hasn't changed since.
+    pub fn was_loaded(&self, component: &impl DetectChanges) -> bool {
+        component.last_changed() == self.tick
+    }
+}
+
+/// Backdates the changed tick of the `component_id` components of `entities` as far as
+/// change detection allows.
+pub(crate) fn suppress_changes(
+    world: &mut World,
+    component_id: Option<ComponentId>,
+    entities: &[Entity],
+) {
+    let Some(component_id) = component_id else {
+        return;
+    };
+    let old = Tick::new(world.change_tick().get().wrapping_sub(MAX_CHANGE_AGE));
+    for entity in entities {
+        if let Some(mut comp) = world.get_mut_by_id(*entity, component_id) {
+            comp.set_last_changed(old);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity_map::EntityMap;
+    use bevy_ecs::system::RunSystemOnce;
+
+    use crate::registry::SaveRegistry;
+    use crate::tests::{Component1, SerializeMe};
+
+    fn changed_count(world: &mut World) -> usize {
+        world.run_system_once(|query: Query<(), Changed<Component1>>| query.iter().count())
+    }
+
+    #[test]
+    fn test_suppressed_loads_are_not_changed() {
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>();
+        let mut world = World::default();
+        world.spawn((Component1, SerializeMe));
+        let doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+
+        let mut loaded = World::default();
+        registry
+            .deserialize(
+                &mut loaded,
+                &mut EntityMap::new(),
+                &mut doc.clone(),
+                SerializeMe,
+            )
+            .unwrap();
+        assert_eq!(changed_count(&mut loaded), 1);
+        let last_load = *loaded.resource::<LastLoad>();
+        let mut query = loaded.query::<Ref<Component1>>();
+        assert!(last_load.was_loaded(&query.single(&loaded)));
+
+        registry.set_change_detection(LoadChangeDetection::Suppress);
+        let mut loaded = World::default();
+        registry
+            .deserialize(
+                &mut loaded,
+                &mut EntityMap::new(),
+                &mut doc.clone(),
+                SerializeMe,
+            )
+            .unwrap();
+        assert_eq!(changed_count(&mut loaded), 0);
+    }
+}
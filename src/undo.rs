@@ -0,0 +1,153 @@
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+
+use bevy_ecs::prelude::*;
+
+use crate::entity_map::EntityMap;
+use crate::registry::SaveRegistry;
+use crate::snapshot::{restore_in_place, take_snapshot, WorldSnapshot};
+
+/// Undo and redo history for the entities marked with `M`, e.g. the contents of a level
+/// being edited. Call [`checkpoint`](UndoStack::checkpoint) before each edit; undoing
+/// restores the world to the last checkpoint and redoing reapplies the undone edit.
+///
+/// States are kept as [`WorldSnapshot`]s and restored in place, as with
+/// [`RollbackBuffer`](crate::RollbackBuffer): entities deleted by an edit come back under new
+/// ids, which the stack remembers so that older states still resolve to them.
+#[derive(Resource)]
+pub struct UndoStack<M> {
+    limit: usize,
+    undo: VecDeque<WorldSnapshot>,
+    redo: Vec<WorldSnapshot>,
+    entity_map: EntityMap,
+    _marker: PhantomData<fn(M)>,
+}
+
+impl<M: Component + Clone> UndoStack<M> {
+    /// `limit` bounds the number of undo steps kept; the oldest are dropped beyond it.
+    pub fn new(limit: usize) -> Self {
+        assert!(limit > 0, "an undo stack needs room for at least one step");
+        UndoStack {
+            limit,
+            undo: VecDeque::with_capacity(limit),
+            redo: Vec::new(),
+            entity_map: EntityMap::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Records the current state as an undo step. Anything that could be redone is
+    /// discarded, as the edit that follows starts a new branch of history.
+    pub fn checkpoint(&mut self, world: &mut World, registry: &SaveRegistry) {
+        self.push_undo(take_snapshot::<M>(world, registry));
+        self.redo.clear();
+    }
+
+    fn push_undo(&mut self, snapshot: WorldSnapshot) {
+        if self.undo.len() == self.limit {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(snapshot);
+    }
+
+    /// Restores the state of the last checkpoint. Returns `false` if there is nothing to
+    /// undo.
+    pub fn undo(&mut self, world: &mut World, registry: &SaveRegistry, marker: M) -> bool {
+        let Some(snapshot) = self.undo.pop_back() else {
+            return false;
+        };
+        self.redo.push(take_snapshot::<M>(world, registry));
+        restore_in_place(
+            world,
+            registry,
+            &snapshot,
+            &mut self.entity_map,
+            marker,
+            |_| true,
+        );
+        true
+    }
+
+    /// Reapplies the last undone edit. Returns `false` if there is nothing to redo.
+    pub fn redo(&mut self, world: &mut World, registry: &SaveRegistry, marker: M) -> bool {
+        let Some(snapshot) = self.redo.pop() else {
+            return false;
+        };
+        self.push_undo(take_snapshot::<M>(world, registry));
+        restore_in_place(
+            world,
+            registry,
+            &snapshot,
+            &mut self.entity_map,
+            marker,
+            |_| true,
+        );
+        true
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.entity_map.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_undo_redo() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register_cloneable::<Component1>()
+            .register_cloneable::<Component2>();
+        let mut stack = UndoStack::<SerializeMe>::new(2);
+        let mut world = World::default();
+        let count = |world: &mut World| {
+            world
+                .query_filtered::<Entity, With<SerializeMe>>()
+                .iter(world)
+                .count()
+        };
+
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        stack.checkpoint(&mut world, &registry);
+        world.despawn(entity1);
+        stack.checkpoint(&mut world, &registry);
+        let entity2 = world
+            .spawn((Component2 { target: entity1 }, SerializeMe))
+            .id();
+        assert_eq!(count(&mut world), 1);
+
+        assert!(stack.undo(&mut world, &registry, SerializeMe));
+        assert_eq!(count(&mut world), 0);
+        assert!(stack.undo(&mut world, &registry, SerializeMe));
+        assert!(!stack.undo(&mut world, &registry, SerializeMe));
+        assert_eq!(count(&mut world), 1);
+        let restored = stack.entity_map[&entity1];
+        assert!(world.get::<Component1>(restored).is_some());
+
+        assert!(stack.redo(&mut world, &registry, SerializeMe));
+        assert!(stack.redo(&mut world, &registry, SerializeMe));
+        assert!(!stack.can_redo());
+        assert_eq!(count(&mut world), 1);
+        assert!(world
+            .get::<Component2>(stack.entity_map[&entity2])
+            .is_some());
+
+        // a new checkpoint drops the redo history
+        stack.undo(&mut world, &registry, SerializeMe);
+        stack.checkpoint(&mut world, &registry);
+        assert!(!stack.can_redo());
+    }
+}
@@ -0,0 +1,249 @@
+//! Loading components written by a newer build, whose enums may hold variants this build
+//! doesn't know. By default such an entry fails the load like any malformed one; a
+//! [`VariantFallback`] set with [`SaveRegistry::on_unknown_variant`] handles it instead.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use bevy_ecs::prelude::*;
+use serde_json::Value;
+
+use crate::entity_map::EntityMap;
+use crate::registry::{insert_components, LoadHookFn, SaveRegistry};
+use crate::SaveDocument;
+
+/// What to do with a saved `C` naming an enum variant this build doesn't know.
+pub enum VariantFallback<C> {
+    /// Leave the whole entity out of the load.
+    SkipEntity,
+    /// Restore the value `C` returned by the function instead, e.g. `Substitute(C::default)`.
+    Substitute(fn() -> C),
+    /// Keep the saved value as it is in the entity's [`StashedComponents`], so that saving
+    /// the entity again writes it back unchanged for the newer build to read.
+    Stash,
+}
+
+pub(crate) type SubstituteFn =
+    Box<dyn Fn(&mut World, &mut EntityMap, Entity, &[LoadHookFn]) -> Vec<Entity> + Send + Sync>;
+
+/// [`VariantFallback`] with the component type erased.
+pub(crate) enum UnknownVariantFallback {
+    SkipEntity,
+    Substitute(SubstituteFn),
+    Stash,
+}
+
+/// Saved components this build couldn't read, by section name, kept on their entity so
+/// that they are written back on the next save.
+#[derive(Component, Clone, Debug, Default, PartialEq)]
+pub struct StashedComponents(pub BTreeMap<String, Value>);
+
+/// Whether `err` is serde's complaint about an enum variant it doesn't know.
+pub(crate) fn is_unknown_variant(err: &serde_json::Error) -> bool {
+    err.is_data() && err.to_string().starts_with("unknown variant")
+}
+
+/// Adds the components stashed on `entities` to their sections of `data_map`, unless the
+/// entity has been given a component of that section since.
+pub(crate) fn write_stashed(world: &World, entities: &[Entity], data_map: &mut SaveDocument) {
+    for name in stashed_section_names(world, entities) {
+        let section = data_map
+            .entry(name.clone())
+            .or_insert_with(|| Value::Array(Vec::new()));
+        write_stashed_section(world, entities, &name, section);
+    }
+}
+
+/// The sections `entities` have stashed components of.
+pub(crate) fn stashed_section_names(world: &World, entities: &[Entity]) -> BTreeSet<String> {
+    entities
+        .iter()
+        .filter_map(|entity| world.get::<StashedComponents>(*entity))
+        .flat_map(|stashed| stashed.0.keys().cloned())
+        .collect()
+}
+
+/// [`write_stashed`] for the single section `name`.
+pub(crate) fn write_stashed_section(
+    world: &World,
+    entities: &[Entity],
+    name: &str,
+    section: &mut Value,
+) {
+    let Value::Array(entries) = section else {
+        return;
+    };
+    for entity in entities {
+        let Some(comp) = world
+            .get::<StashedComponents>(*entity)
+            .and_then(|stashed| stashed.0.get(name))
+        else {
+            continue;
+        };
+        let bits = entity.to_bits();
+        let position = entries
+            .iter()
+            .map(|entry| entry[0].as_u64().unwrap_or_default())
+            .position(|saved| saved >= bits);
+        match position {
+            Some(index) if entries[index][0].as_u64() == Some(bits) => {}
+            Some(index) => entries.insert(index, serde_json::json!([bits, comp])),
+            None => entries.push(serde_json::json!([bits, comp])),
+        }
+    }
+}
+
+impl SaveRegistry {
+    /// Handles saved `C`s naming an enum variant this build doesn't know with `fallback`,
+    /// rather than failing the load. The entries handled are listed in
+    /// [`LoadReport::unknown_variants`](crate::LoadReport::unknown_variants).
+    ///
+    /// # Panics
+    /// If `C` hasn't been registered.
+    pub fn on_unknown_variant<C: Component>(&mut self, fallback: VariantFallback<C>) -> &mut Self {
+        let Some(reg) = self.get_by_type_mut::<C>() else {
+            panic!(
+                "{} must be registered before setting its unknown variant fallback",
+                std::any::type_name::<C>()
+            );
+        };
+        reg.unknown_variant = Some(match fallback {
+            VariantFallback::SkipEntity => UnknownVariantFallback::SkipEntity,
+            VariantFallback::Substitute(substitute) => UnknownVariantFallback::Substitute(
+                Box::new(move |world, entity_map, entity, load_hooks| {
+                    insert_components(world, entity_map, vec![(entity, substitute())], load_hooks)
+                }),
+            ),
+            VariantFallback::Stash => UnknownVariantFallback::Stash,
+        });
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::load::LoadMode;
+    use crate::tests::{Component1, SerializeMe};
+
+    #[derive(Component, Serialize, Deserialize, Default, PartialEq, Debug)]
+    enum Weather {
+        #[default]
+        Clear,
+        Rain,
+    }
+
+    #[derive(Component, Serialize, Deserialize, PartialEq, Debug)]
+    enum Mood {
+        Calm,
+    }
+
+    /// A save from a newer build, in which `Weather` and `Mood` gained a `Fog` and an
+    /// `Angry` variant.
+    fn newer_save(registry: &SaveRegistry) -> SaveDocument {
+        let mut world = World::default();
+        world.spawn((Weather::Rain, Mood::Calm, Component1, SerializeMe));
+        world.spawn((Weather::Clear, Mood::Calm, Component1, SerializeMe));
+        let mut doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        doc.get_mut("Weather").unwrap()[1][1] = "Fog".into();
+        doc.get_mut("Mood").unwrap()[0][1] = "Angry".into();
+        doc
+    }
+
+    fn registry() -> SaveRegistry {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Weather>()
+            .register::<Mood>()
+            .register::<Component1>();
+        registry
+    }
+
+    #[test]
+    fn test_unknown_variant_fallbacks() {
+        let mut registry = registry();
+        let mut world = World::default();
+        assert!(registry
+            .load(
+                &mut world,
+                &mut newer_save(&registry),
+                LoadMode::Merge,
+                SerializeMe
+            )
+            .is_err());
+
+        registry
+            .on_unknown_variant(VariantFallback::Substitute(Weather::default))
+            .on_unknown_variant::<Mood>(VariantFallback::SkipEntity);
+        let mut world = World::default();
+        let report = registry
+            .load(
+                &mut world,
+                &mut newer_save(&registry),
+                LoadMode::Merge,
+                SerializeMe,
+            )
+            .unwrap();
+        assert_eq!(report.unknown_variants.len(), 2);
+        assert_eq!(report.entity_map.len(), 1);
+        let weather: Vec<&Weather> = world.query::<&Weather>().iter(&world).collect();
+        assert_eq!(weather, vec![&Weather::Clear]);
+        assert_eq!(world.query::<&Component1>().iter(&world).count(), 1);
+    }
+
+    #[test]
+    fn test_skipped_entity_reused_by_sync_load_survives() {
+        let mut registry = registry();
+        registry.on_unknown_variant::<Mood>(VariantFallback::SkipEntity);
+        let mut world = World::default();
+        world.spawn((Weather::Rain, Mood::Calm, Component1, SerializeMe));
+        world.spawn((Weather::Clear, Mood::Calm, Component1, SerializeMe));
+        let mut doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        let angry: Entity = serde_json::from_value(doc["Mood"][0][0].clone()).unwrap();
+        doc.get_mut("Mood").unwrap()[0][1] = "Angry".into();
+
+        let report = registry
+            .load(&mut world, &mut doc, LoadMode::Sync, SerializeMe)
+            .unwrap();
+        assert_eq!(report.reused, 2);
+        assert_eq!(report.created, 0);
+        assert_eq!(report.restored["Mood"], 1);
+        assert!(world.get_entity(angry).is_some());
+        assert!(world.get::<Weather>(angry).is_none());
+        assert_eq!(world.query::<&Mood>().iter(&world).count(), 1);
+    }
+
+    #[test]
+    fn test_stashed_variant_is_saved_again() {
+        let mut registry = registry();
+        registry
+            .on_unknown_variant::<Weather>(VariantFallback::Stash)
+            .on_unknown_variant::<Mood>(VariantFallback::Stash);
+        let saved = newer_save(&registry);
+        let mut world = World::default();
+        let report = registry
+            .load(&mut world, &mut saved.clone(), LoadMode::Merge, SerializeMe)
+            .unwrap();
+        assert_eq!(report.entity_map.len(), 2);
+        assert_eq!(world.query::<&StashedComponents>().iter(&world).count(), 2);
+
+        let resaved = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        let sections = |doc: &SaveDocument| {
+            ["Weather", "Mood"].map(|name| {
+                doc[name]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|entry| entry[1].clone())
+                    .collect::<Vec<_>>()
+            })
+        };
+        let mut expected = sections(&saved);
+        let mut actual = sections(&resaved);
+        for section in expected.iter_mut().chain(actual.iter_mut()) {
+            section.sort_by_key(ToString::to_string);
+        }
+        assert_eq!(actual, expected);
+    }
+}
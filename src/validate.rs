@@ -0,0 +1,151 @@
+use std::fmt;
+
+use bevy_ecs::prelude::*;
+
+use crate::registry::SaveRegistry;
+
+pub(crate) type ValidateFn = fn(&World, Entity) -> Option<Result<(), String>>;
+
+/// A check run on every loaded `Self` once the whole save is in, e.g. that a health value
+/// is within range or that a referenced entity exists. Failures are collected in
+/// [`LoadReport::validation_errors`](crate::LoadReport::validation_errors). Enable it with
+/// [`SaveRegistry::add_validation`].
+pub trait ValidateOnLoad: Component {
+    fn validate(&self, entity: Entity, world: &World) -> Result<(), String>;
+}
+
+/// A loaded component that failed its [`ValidateOnLoad`] check.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationError {
+    pub entity: Entity,
+    /// The section name of the component.
+    pub component: String,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid {} on {:?}: {}",
+            self.component, self.entity, self.message
+        )
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+fn validate_component<C: ValidateOnLoad>(
+    world: &World,
+    entity: Entity,
+) -> Option<Result<(), String>> {
+    world
+        .get::<C>(entity)
+        .map(|comp| comp.validate(entity, world))
+}
+
+impl SaveRegistry {
+    /// Checks every `C` loaded by [`load`](Self::load) with [`ValidateOnLoad`].
+    ///
+    /// # Panics
+    /// If `C` hasn't been registered.
+    pub fn add_validation<C: ValidateOnLoad>(&mut self) -> &mut Self {
+        let Some(reg) = self.get_by_type_mut::<C>() else {
+            panic!(
+                "{} must be registered before adding validation",
+                std::any::type_name::<C>()
+            );
+        };
+        reg.validate = Some(validate_component::<C>);
+        self
+    }
+
+    /// Runs the [`ValidateOnLoad`] checks of every registered component on `entities`.
+    pub fn validate(&self, world: &World, entities: &[Entity]) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+        for reg in self.iter() {
+            let Some(validate) = reg.validate else {
+                continue;
+            };
+            for entity in entities {
+                if let Some(Err(message)) = validate(world, *entity) {
+                    errors.push(ValidationError {
+                        entity: *entity,
+                        component: reg.name().to_string(),
+                        message,
+                    });
+                }
+            }
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    use crate::load::LoadMode;
+    use crate::tests::{Component2, SerializeMe};
+
+    #[derive(Component, Serialize, Deserialize)]
+    struct Health(i32);
+
+    impl ValidateOnLoad for Health {
+        fn validate(&self, _entity: Entity, _world: &World) -> Result<(), String> {
+            if (0..=100).contains(&self.0) {
+                Ok(())
+            } else {
+                Err(format!("{} is out of range", self.0))
+            }
+        }
+    }
+
+    impl ValidateOnLoad for Component2 {
+        fn validate(&self, _entity: Entity, world: &World) -> Result<(), String> {
+            match world.get_entity(self.target) {
+                Some(_) => Ok(()),
+                None => Err("target doesn't exist".to_string()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_failures_are_reported() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Health>()
+            .register_mapped::<Component2>()
+            .add_validation::<Health>()
+            .add_validation::<Component2>();
+        let mut world = World::default();
+        let healthy = world.spawn((Health(50), SerializeMe)).id();
+        world.spawn((Health(500), SerializeMe));
+        world.spawn((Component2 { target: healthy }, SerializeMe));
+        let mut doc = registry.serialize::<SerializeMe>(&mut world).unwrap();
+        let section = doc.get_mut("Component2").unwrap();
+        section[0][1]["target"] = serde_json::json!(Entity::from_raw(99));
+
+        let report = registry
+            .load(
+                &mut World::default(),
+                &mut doc,
+                LoadMode::Merge,
+                SerializeMe,
+            )
+            .unwrap();
+        let messages: Vec<String> = report
+            .validation_errors
+            .iter()
+            .map(|error| format!("{}: {}", error.component, error.message))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Health: 500 is out of range",
+                "Component2: target doesn't exist"
+            ]
+        );
+    }
+}
@@ -0,0 +1,358 @@
+//! Save slots in the browser's IndexedDB or `localStorage`, for wasm builds without a
+//! filesystem.
+
+use std::future::Future;
+use std::io;
+
+use js_sys::{Array, Function, Promise, Uint8Array};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    IdbDatabase, IdbFactory, IdbObjectStore, IdbRequest, IdbTransaction, IdbTransactionMode,
+};
+
+use crate::async_io::{AsyncSaveStorage, MaybeSend};
+use crate::storage::SaveStorage;
+
+fn js_error(err: wasm_bindgen::JsValue) -> io::Error {
+    io::Error::other(format!("{err:?}"))
+}
+
+/// Slots stored as `localStorage` items named `{prefix}{slot}`. `localStorage` only holds
+/// strings, and browsers cap it at around 5MB per origin, so this suits small, uncompressed
+/// JSON saves; binary saves such as gzipped ones are rejected with
+/// [`io::ErrorKind::InvalidData`], and writes beyond the quota fail.
+#[derive(Clone, Debug)]
+pub struct LocalStorage {
+    storage: web_sys::Storage,
+    prefix: String,
+}
+
+impl LocalStorage {
+    /// The `localStorage` of the current window. Fails outside a window, e.g. in workers,
+    /// or if the browser denies access.
+    pub fn new(prefix: &str) -> io::Result<Self> {
+        let window = web_sys::window()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "no window"))?;
+        let storage = window
+            .local_storage()
+            .map_err(js_error)?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "no localStorage"))?;
+        Ok(LocalStorage {
+            storage,
+            prefix: prefix.to_string(),
+        })
+    }
+
+    fn key(&self, slot: &str) -> String {
+        format!("{}{slot}", self.prefix)
+    }
+}
+
+impl SaveStorage for LocalStorage {
+    fn read(&self, slot: &str) -> io::Result<Option<Vec<u8>>> {
+        let item = self.storage.get_item(&self.key(slot)).map_err(js_error)?;
+        Ok(item.map(String::into_bytes))
+    }
+
+    fn write(&mut self, slot: &str, bytes: &[u8]) -> io::Result<()> {
+        let text = std::str::from_utf8(bytes).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "localStorage only holds text saves",
+            )
+        })?;
+        self.storage
+            .set_item(&self.key(slot), text)
+            .map_err(js_error)
+    }
+
+    fn remove(&mut self, slot: &str) -> io::Result<()> {
+        self.storage.remove_item(&self.key(slot)).map_err(js_error)
+    }
+
+    fn slots(&self) -> io::Result<Vec<String>> {
+        let len = self.storage.length().map_err(js_error)?;
+        let mut slots = Vec::new();
+        for index in 0..len {
+            if let Some(key) = self.storage.key(index).map_err(js_error)? {
+                if let Some(slot) = key.strip_prefix(&self.prefix) {
+                    slots.push(slot.to_string());
+                }
+            }
+        }
+        Ok(slots)
+    }
+}
+
+/// The object store holding the slots in the databases of [`IndexedDbStorage`].
+const STORE: &str = "slots";
+
+/// Resolves to `request`'s result once it succeeds.
+fn request_done(request: &IdbRequest) -> JsFuture {
+    let promise = Promise::new(&mut |resolve: Function, reject: Function| {
+        let succeeded = request.clone();
+        let on_success = Closure::once_into_js(move || {
+            let result = succeeded.result().unwrap_or(JsValue::UNDEFINED);
+            let _ = resolve.call1(&JsValue::UNDEFINED, &result);
+        });
+        let failed = request.clone();
+        let on_error = Closure::once_into_js(move || {
+            let error = failed.error().ok().flatten();
+            let _ = reject.call1(&JsValue::UNDEFINED, &error.into());
+        });
+        request.set_onsuccess(Some(on_success.unchecked_ref()));
+        request.set_onerror(Some(on_error.unchecked_ref()));
+    });
+    JsFuture::from(promise)
+}
+
+/// Resolves once `transaction` is committed.
+fn transaction_done(transaction: &IdbTransaction) -> JsFuture {
+    let promise = Promise::new(&mut |resolve: Function, reject: Function| {
+        let on_complete = Closure::once_into_js(move || {
+            let _ = resolve.call0(&JsValue::UNDEFINED);
+        });
+        // an aborted transaction fires abort, and error first if a request failed
+        let failed = transaction.clone();
+        let on_abort = Closure::once_into_js(move || {
+            let error = failed.error();
+            let _ = reject.call1(&JsValue::UNDEFINED, &error.into());
+        });
+        transaction.set_oncomplete(Some(on_complete.unchecked_ref()));
+        transaction.set_onabort(Some(on_abort.unchecked_ref()));
+    });
+    JsFuture::from(promise)
+}
+
+/// Slots stored as byte arrays in the IndexedDB database `name`. Unlike `localStorage`,
+/// IndexedDB holds binary saves, such as gzipped ones, and browsers let it grow far beyond
+/// a few megabytes, but it can only be waited on asynchronously, so it's an
+/// [`AsyncSaveStorage`] for [`save_slot_async`](crate::SaveRegistry::save_slot_async) and
+/// [`load_slot_async`](crate::SaveRegistry::load_slot_async) rather than a [`SaveStorage`].
+#[derive(Clone, Debug)]
+pub struct IndexedDbStorage {
+    factory: IdbFactory,
+    name: String,
+}
+
+impl IndexedDbStorage {
+    /// The database `name` of the current window or worker, created on first use. Fails if
+    /// the browser doesn't offer IndexedDB.
+    pub fn new(name: &str) -> io::Result<Self> {
+        let factory = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("indexedDB"))
+            .ok()
+            .and_then(|factory| factory.dyn_into::<IdbFactory>().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "no IndexedDB"))?;
+        Ok(IndexedDbStorage {
+            factory,
+            name: name.to_string(),
+        })
+    }
+
+    async fn open(&self) -> io::Result<IdbDatabase> {
+        let request = self
+            .factory
+            .open_with_u32(&self.name, 1)
+            .map_err(js_error)?;
+        let upgraded = request.clone();
+        let on_upgrade_needed = Closure::once_into_js(move || {
+            if let Ok(db) = upgraded.result().map(JsCast::unchecked_into::<IdbDatabase>) {
+                let _ = db.create_object_store(STORE);
+            }
+        });
+        request.set_onupgradeneeded(Some(on_upgrade_needed.unchecked_ref()));
+        let db = request_done(&request).await.map_err(js_error)?;
+        db.dyn_into().map_err(js_error)
+    }
+
+    /// The slots' object store, in a transaction that commits once the requests made on the
+    /// store before the next await are done.
+    async fn store(
+        &self,
+        mode: IdbTransactionMode,
+    ) -> io::Result<(IdbTransaction, IdbObjectStore)> {
+        let db = self.open().await?;
+        let transaction = db
+            .transaction_with_str_and_mode(STORE, mode)
+            .map_err(js_error)?;
+        let store = transaction.object_store(STORE).map_err(js_error)?;
+        Ok((transaction, store))
+    }
+}
+
+impl AsyncSaveStorage for IndexedDbStorage {
+    fn read_async(
+        &self,
+        slot: &str,
+    ) -> impl Future<Output = io::Result<Option<Vec<u8>>>> + MaybeSend {
+        let key = JsValue::from_str(slot);
+        async move {
+            let (_, store) = self.store(IdbTransactionMode::Readonly).await?;
+            let request = store.get(&key).map_err(js_error)?;
+            let value = request_done(&request).await.map_err(js_error)?;
+            Ok((!value.is_undefined()).then(|| Uint8Array::new(&value).to_vec()))
+        }
+    }
+
+    fn write_async(
+        &mut self,
+        slot: &str,
+        bytes: &[u8],
+    ) -> impl Future<Output = io::Result<()>> + MaybeSend {
+        let (key, value) = (JsValue::from_str(slot), Uint8Array::from(bytes));
+        async move {
+            let (transaction, store) = self.store(IdbTransactionMode::Readwrite).await?;
+            store.put_with_key(&value, &key).map_err(js_error)?;
+            transaction_done(&transaction).await.map_err(js_error)?;
+            Ok(())
+        }
+    }
+
+    fn remove_async(&mut self, slot: &str) -> impl Future<Output = io::Result<()>> + MaybeSend {
+        let key = JsValue::from_str(slot);
+        async move {
+            let (transaction, store) = self.store(IdbTransactionMode::Readwrite).await?;
+            store.delete(&key).map_err(js_error)?;
+            transaction_done(&transaction).await.map_err(js_error)?;
+            Ok(())
+        }
+    }
+
+    async fn slots_async(&self) -> io::Result<Vec<String>> {
+        let (_, store) = self.store(IdbTransactionMode::Readonly).await?;
+        let request = store.get_all_keys().map_err(js_error)?;
+        let keys = request_done(&request).await.map_err(js_error)?;
+        Ok(Array::from(&keys)
+            .iter()
+            .filter_map(|key| key.as_string())
+            .collect())
+    }
+}
+
+/// IndexedDB where the browser offers it, and `localStorage`, with its limit to small text
+/// saves, where it doesn't.
+#[derive(Clone, Debug)]
+pub enum BrowserStorage {
+    IndexedDb(IndexedDbStorage),
+    Local(LocalStorage),
+}
+
+impl BrowserStorage {
+    /// The IndexedDB database `name`, or else the `localStorage` items prefixed `{name}/`.
+    pub fn new(name: &str) -> io::Result<Self> {
+        match IndexedDbStorage::new(name) {
+            Ok(storage) => Ok(BrowserStorage::IndexedDb(storage)),
+            Err(_) => LocalStorage::new(&format!("{name}/")).map(BrowserStorage::Local),
+        }
+    }
+}
+
+impl AsyncSaveStorage for BrowserStorage {
+    async fn read_async(&self, slot: &str) -> io::Result<Option<Vec<u8>>> {
+        match self {
+            BrowserStorage::IndexedDb(storage) => storage.read_async(slot).await,
+            BrowserStorage::Local(storage) => storage.read(slot),
+        }
+    }
+
+    async fn write_async(&mut self, slot: &str, bytes: &[u8]) -> io::Result<()> {
+        match self {
+            BrowserStorage::IndexedDb(storage) => storage.write_async(slot, bytes).await,
+            BrowserStorage::Local(storage) => storage.write(slot, bytes),
+        }
+    }
+
+    async fn remove_async(&mut self, slot: &str) -> io::Result<()> {
+        match self {
+            BrowserStorage::IndexedDb(storage) => storage.remove_async(slot).await,
+            BrowserStorage::Local(storage) => storage.remove(slot),
+        }
+    }
+
+    async fn slots_async(&self) -> io::Result<Vec<String>> {
+        match self {
+            BrowserStorage::IndexedDb(storage) => storage.slots_async().await,
+            BrowserStorage::Local(storage) => storage.slots(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ecs::prelude::*;
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+    use crate::config::SaveConfig;
+    use crate::registry::SaveRegistry;
+    use crate::tests::{Component1, SerializeMe};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    /// Lets the browser run its pending events, such as IndexedDB's, for a few milliseconds.
+    async fn yield_to_browser() {
+        let set_timeout: Function = js_sys::Reflect::get(&js_sys::global(), &"setTimeout".into())
+            .unwrap()
+            .unchecked_into();
+        let timer = Promise::new(&mut |resolve, _| {
+            set_timeout
+                .call2(&JsValue::UNDEFINED, &resolve, &JsValue::from(5))
+                .unwrap();
+        });
+        JsFuture::from(timer).await.unwrap();
+    }
+
+    #[wasm_bindgen_test]
+    async fn test_indexed_db_holds_binary_slots() {
+        let mut storage = IndexedDbStorage::new("bevy_serde_test_slots").unwrap();
+        let bytes = [0x1f, 0x8b, 0, 255];
+        storage.write_async("one", &bytes).await.unwrap();
+        assert_eq!(
+            storage.read_async("one").await.unwrap(),
+            Some(bytes.to_vec())
+        );
+        assert!(storage
+            .slots_async()
+            .await
+            .unwrap()
+            .contains(&"one".to_string()));
+        storage.remove_async("one").await.unwrap();
+        assert_eq!(storage.read_async("one").await.unwrap(), None);
+    }
+
+    #[wasm_bindgen_test]
+    async fn test_async_slots_in_the_browser() {
+        let storage = BrowserStorage::new("bevy_serde_test_async").unwrap();
+        assert!(matches!(storage, BrowserStorage::IndexedDb(_)));
+        let mut registry = SaveRegistry::new();
+        registry.register::<Component1>();
+        let mut world = World::default();
+        world.spawn_batch((0..10).map(|_| (Component1, SerializeMe)));
+
+        let config = SaveConfig::new();
+        let mut save = registry
+            .save_slot_async::<SerializeMe, _>(&mut world, &storage, "one", &config)
+            .unwrap();
+        let saved = loop {
+            if let Some(result) = save.poll(&mut world) {
+                break result;
+            }
+            yield_to_browser().await;
+        };
+        saved.unwrap();
+
+        let mut loaded = World::default();
+        let mut load = registry
+            .load_slot_async(&mut loaded, &storage, "one", &config)
+            .unwrap();
+        let report = loop {
+            if let Some(result) = load.poll(&mut loaded, &registry, SerializeMe) {
+                break result;
+            }
+            yield_to_browser().await;
+        };
+        assert_eq!(report.unwrap().entity_map.len(), 10);
+    }
+}
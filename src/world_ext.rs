@@ -0,0 +1,83 @@
+use std::io::{Read, Write};
+
+use bevy_ecs::prelude::*;
+
+use crate::config::{SaveConfig, SaveError};
+use crate::load::LoadReport;
+use crate::registry::SaveRegistry;
+
+/// One-line saving and loading on a [`World`], for games that don't need the macros or
+/// any of the registry's finer controls. Saves are written with the default
+/// [`SaveConfig`], and loaded in its default [`LoadMode`](crate::LoadMode).
+pub trait WorldSaveExt {
+    /// Writes every registered component of the entities marked with `M` to `writer`.
+    fn save_marked<M: Component>(
+        &mut self,
+        registry: &SaveRegistry,
+        writer: impl Write,
+    ) -> Result<(), SaveError>;
+
+    /// Reads a save written by [`save_marked`](Self::save_marked) from `reader`, marking
+    /// every loaded entity with `M::default()`.
+    fn load_marked<M: Component + Clone + Default>(
+        &mut self,
+        registry: &SaveRegistry,
+        reader: impl Read,
+    ) -> Result<LoadReport, SaveError>;
+}
+
+impl WorldSaveExt for World {
+    fn save_marked<M: Component>(
+        &mut self,
+        registry: &SaveRegistry,
+        mut writer: impl Write,
+    ) -> Result<(), SaveError> {
+        let bytes = registry.save_bytes::<M>(self, &SaveConfig::default())?;
+        writer.write_all(&bytes)?;
+        Ok(())
+    }
+
+    fn load_marked<M: Component + Clone + Default>(
+        &mut self,
+        registry: &SaveRegistry,
+        mut reader: impl Read,
+    ) -> Result<LoadReport, SaveError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        registry.load_bytes(self, &bytes, &SaveConfig::default(), M::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Component1, Component2, SerializeMe};
+
+    #[test]
+    fn test_save_and_load_marked() {
+        let mut registry = SaveRegistry::new();
+        registry
+            .register::<Component1>()
+            .register_mapped::<Component2>();
+        let mut world = World::default();
+        let entity1 = world.spawn((Component1, SerializeMe)).id();
+        let entity2 = world
+            .spawn((Component2 { target: entity1 }, SerializeMe))
+            .id();
+
+        let mut save = Vec::new();
+        world
+            .save_marked::<SerializeMe>(&registry, &mut save)
+            .unwrap();
+        let mut loaded = World::default();
+        let report = loaded
+            .load_marked::<SerializeMe>(&registry, save.as_slice())
+            .unwrap();
+        let new_entity2 = report.entity_map[&entity2];
+        assert_eq!(
+            loaded.get::<Component2>(new_entity2).unwrap().target,
+            report.entity_map[&entity1]
+        );
+        assert!(loaded.get::<SerializeMe>(new_entity2).is_some());
+    }
+}